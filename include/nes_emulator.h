@@ -0,0 +1,52 @@
+/* c interface of libnes_emulator (cargo build --release --lib --no-default-features), kept in sync with src/ffi.rs by ffi_tests */
+#ifndef NES_EMULATOR_H
+#define NES_EMULATOR_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+#define NES_OK 0
+#define NES_ERROR_ARGUMENT -1
+#define NES_ERROR_ROM -2
+
+/* the framebuffer is rgba, 4 bytes per pixel, row by row, page aligned */
+#define NES_FRAME_WIDTH 256
+#define NES_FRAME_HEIGHT 240
+
+/* buttons for nes_set_input, or them together */
+#define NES_BUTTON_A 0x01
+#define NES_BUTTON_B 0x02
+#define NES_BUTTON_SELECT 0x04
+#define NES_BUTTON_START 0x08
+#define NES_BUTTON_UP 0x10
+#define NES_BUTTON_DOWN 0x20
+#define NES_BUTTON_LEFT 0x40
+#define NES_BUTTON_RIGHT 0x80
+
+typedef struct NesHandle NesHandle;
+
+/* free it with nes_destroy, null handles are ignored by all functions */
+NesHandle *nes_create(void);
+void nes_destroy(NesHandle *handle);
+
+/* the contents of an .nes file, copied. Powers on the console, NES_OK or an error */
+int32_t nes_load_rom(NesHandle *handle, const uint8_t *data, size_t len);
+
+/* 1 if a frame was completed, 0 without a rom or if the cpu stopped */
+int32_t nes_run_frame(NesHandle *handle);
+
+/* the last completed frame, the emulator's own buffer: valid until the next nes_run_frame / nes_load_rom. Null without a rom */
+const uint8_t *nes_get_framebuffer(const NesHandle *handle);
+
+/* player 1 or 2, the buttons are held until the next call */
+void nes_set_input(NesHandle *handle, uint32_t player, uint8_t buttons);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif
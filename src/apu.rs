@@ -0,0 +1,686 @@
+use std::collections::VecDeque;
+use std::io;
+use crate::audio_worker::RawOutput;
+use crate::resampler::Resampler;
+use crate::wav::WavRecorder;
+use crate::savestate::{StateReader, StateWriter, Stateful};
+
+// https://wiki.nesdev.org/w/index.php/APU
+pub const CPU_FREQUENCY: f64 = 1_789_773.0;
+pub const DEFAULT_SAMPLE_RATE: u32 = 44_100;
+// ~0.2s of audio, older samples get dropped if nobody drains the buffer
+const SAMPLE_BUFFER_SIZE: usize = 8192;
+
+const LENGTH_TABLE: [u8; 32] = [
+  10, 254, 20, 2, 40, 4, 80, 6, 160, 8, 60, 10, 14, 12, 26, 14,
+  12, 16, 24, 18, 48, 20, 96, 22, 192, 24, 72, 26, 16, 28, 32, 30,
+];
+
+const DUTY_TABLE: [[u8; 8]; 4] = [
+  [0, 1, 0, 0, 0, 0, 0, 0], // 12.5%
+  [0, 1, 1, 0, 0, 0, 0, 0], // 25%
+  [0, 1, 1, 1, 1, 0, 0, 0], // 50%
+  [1, 0, 0, 1, 1, 1, 1, 1], // 25% negated
+];
+
+// cpu cycles of the frame counter steps, quarter frames clock envelopes,
+// half frames (2nd and last step) clock length counters and sweeps
+// https://wiki.nesdev.org/w/index.php/APU_Frame_Counter
+const FOUR_STEP_SEQUENCE: [u32; 4] = [7457, 14913, 22371, 29829];
+const FIVE_STEP_SEQUENCE: [u32; 4] = [7457, 14913, 22371, 37281];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Channel {
+  Pulse1,
+  Pulse2,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ChannelConfig {
+  pub enabled: bool,
+  pub volume: f32, // 1.0 = as the hardware mixes it
+}
+
+impl Default for ChannelConfig {
+  fn default() -> Self {
+    ChannelConfig { enabled: true, volume: 1.0 }
+  }
+}
+
+impl ChannelConfig {
+  fn level(&self, output: u8) -> f32 {
+    if self.enabled { output as f32 * self.volume } else { 0.0 }
+  }
+}
+
+// a mix preference, not emulated state: it isn't part of save states.
+// only the channels the apu emulates
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct ApuConfig {
+  pub pulse1: ChannelConfig,
+  pub pulse2: ChannelConfig,
+}
+
+impl ApuConfig {
+  pub fn channel(&self, channel: Channel) -> &ChannelConfig {
+    match channel {
+      Channel::Pulse1 => &self.pulse1,
+      Channel::Pulse2 => &self.pulse2,
+    }
+  }
+
+  pub fn channel_mut(&mut self, channel: Channel) -> &mut ChannelConfig {
+    match channel {
+      Channel::Pulse1 => &mut self.pulse1,
+      Channel::Pulse2 => &mut self.pulse2,
+    }
+  }
+
+  // mutes every other channel
+  pub fn solo(&mut self, channel: Channel) {
+    for other in [Channel::Pulse1, Channel::Pulse2] {
+      self.channel_mut(other).enabled = other == channel;
+    }
+  }
+}
+
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FrameCounterMode {
+  FourStep,
+  FiveStep,
+}
+
+// $4017
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Debug, Clone, PartialEq)]
+pub struct FrameCounter {
+  pub mode: FrameCounterMode,
+  irq_inhibit: bool,
+  interrupt: bool,
+  cycles: u32,
+}
+
+impl FrameCounter {
+  pub fn new() -> Self {
+    FrameCounter { mode: FrameCounterMode::FourStep, irq_inhibit: false, interrupt: false, cycles: 0 }
+  }
+
+  fn sequence(&self) -> &'static [u32; 4] {
+    match self.mode {
+      FrameCounterMode::FourStep => &FOUR_STEP_SEQUENCE,
+      FrameCounterMode::FiveStep => &FIVE_STEP_SEQUENCE,
+    }
+  }
+
+  // the real write takes effect 3-4 cycles later, here it is immediate
+  fn write(&mut self, data: u8) {
+    self.mode = if data & 0b1000_0000 != 0 { FrameCounterMode::FiveStep } else { FrameCounterMode::FourStep };
+    self.irq_inhibit = data & 0b0100_0000 != 0;
+    if self.irq_inhibit {
+      self.interrupt = false;
+    }
+    self.cycles = 0;
+  }
+
+  // one cpu cycle, returns (quarter frame, half frame)
+  fn clock(&mut self) -> (bool, bool) {
+    self.cycles += 1;
+    let sequence = self.sequence();
+    match sequence.iter().position(|&c| c == self.cycles) {
+      Some(step) => {
+        let last = step == sequence.len() - 1;
+        if last {
+          if self.mode == FrameCounterMode::FourStep && !self.irq_inhibit {
+            self.interrupt = true;
+          }
+          self.cycles = 0;
+        }
+        (true, step == 1 || last)
+      }
+      None => (false, false),
+    }
+  }
+}
+
+impl Stateful for FrameCounter {
+  fn save_state(&self, w: &mut StateWriter) {
+    w.write_bool(self.mode == FrameCounterMode::FiveStep);
+    w.write_bool(self.irq_inhibit);
+    w.write_bool(self.interrupt);
+    w.write_u64(self.cycles as u64);
+  }
+
+  fn load_state(&mut self, r: &mut StateReader) -> Result<(), String> {
+    self.mode = if r.read_bool()? { FrameCounterMode::FiveStep } else { FrameCounterMode::FourStep };
+    self.irq_inhibit = r.read_bool()?;
+    self.interrupt = r.read_bool()?;
+    self.cycles = r.read_u64()? as u32;
+    Ok(())
+  }
+}
+
+impl Default for FrameCounter {
+  fn default() -> Self {
+    FrameCounter::new()
+  }
+}
+
+// fixed size ring buffer between the emulation and the audio device
+pub struct SampleBuffer {
+  samples: VecDeque<f32>,
+  capacity: usize,
+}
+
+impl SampleBuffer {
+  pub fn new(capacity: usize) -> Self {
+    SampleBuffer { samples: VecDeque::with_capacity(capacity), capacity }
+  }
+
+  pub fn push(&mut self, sample: f32) {
+    if self.samples.len() == self.capacity {
+      self.samples.pop_front();
+    }
+    self.samples.push_back(sample);
+  }
+
+  pub fn len(&self) -> usize {
+    self.samples.len()
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.samples.is_empty()
+  }
+
+  pub fn drain(&mut self) -> Vec<f32> {
+    self.samples.drain(..).collect()
+  }
+}
+
+// what a pulse channel plays right now, for piano roll and level meters
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct PulseSnapshot {
+  pub enabled: bool,
+  // silent channels keep the frequency of their timer
+  pub audible: bool,
+  pub frequency: f64,
+  pub timer_period: u16,
+  pub duty: u8, // index into 12.5%, 25%, 50%, 25% negated
+  pub volume: u8, // 0-15, constant or the current envelope level
+  pub length_counter: u8,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ApuSnapshot {
+  pub pulse1: PulseSnapshot,
+  pub pulse2: PulseSnapshot,
+  pub frame_counter_mode: FrameCounterMode,
+}
+
+// $4000-$4003 / $4004-$4007
+// https://wiki.nesdev.org/w/index.php/APU_Pulse
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Pulse {
+  // pulse 1 negates the sweep with ones' complement, pulse 2 with two's complement
+  ones_complement: bool,
+  enabled: bool,
+  duty: u8,
+  sequence_pos: u8,
+  timer_period: u16,
+  timer: u16,
+  pub length_counter: u8,
+  // doubles as envelope loop flag
+  length_halt: bool,
+  constant_volume: bool,
+  // constant volume or envelope period
+  volume: u8,
+  envelope_start: bool,
+  envelope_divider: u8,
+  envelope_decay: u8,
+  sweep_enabled: bool,
+  sweep_period: u8,
+  sweep_negate: bool,
+  sweep_shift: u8,
+  sweep_reload: bool,
+  sweep_divider: u8,
+}
+
+impl Pulse {
+  pub fn new(ones_complement: bool) -> Self {
+    Pulse { ones_complement, ..Default::default() }
+  }
+
+  pub fn write_register(&mut self, register: u16, data: u8) {
+    match register {
+      0 => {
+        self.duty = data >> 6;
+        self.length_halt = data & 0b0010_0000 != 0;
+        self.constant_volume = data & 0b0001_0000 != 0;
+        self.volume = data & 0b0000_1111;
+      }
+      1 => {
+        self.sweep_enabled = data & 0b1000_0000 != 0;
+        self.sweep_period = (data >> 4) & 0b111;
+        self.sweep_negate = data & 0b0000_1000 != 0;
+        self.sweep_shift = data & 0b111;
+        self.sweep_reload = true;
+      }
+      2 => self.timer_period = (self.timer_period & 0x0700) | data as u16,
+      _ => {
+        self.timer_period = (self.timer_period & 0x00FF) | (((data & 0b111) as u16) << 8);
+        if self.enabled {
+          self.length_counter = LENGTH_TABLE[(data >> 3) as usize];
+        }
+        self.sequence_pos = 0;
+        self.envelope_start = true;
+      }
+    }
+  }
+
+  pub fn set_enabled(&mut self, enabled: bool) {
+    self.enabled = enabled;
+    if !enabled {
+      self.length_counter = 0;
+    }
+  }
+
+  // every other cpu cycle
+  pub fn clock_timer(&mut self) {
+    if self.timer == 0 {
+      self.timer = self.timer_period;
+      self.sequence_pos = (self.sequence_pos + 1) % 8;
+    } else {
+      self.timer -= 1;
+    }
+  }
+
+  // quarter frame
+  pub fn clock_envelope(&mut self) {
+    if self.envelope_start {
+      self.envelope_start = false;
+      self.envelope_decay = 15;
+      self.envelope_divider = self.volume;
+    } else if self.envelope_divider == 0 {
+      self.envelope_divider = self.volume;
+      if self.envelope_decay > 0 {
+        self.envelope_decay -= 1;
+      } else if self.length_halt {
+        self.envelope_decay = 15;
+      }
+    } else {
+      self.envelope_divider -= 1;
+    }
+  }
+
+  // half frame
+  pub fn clock_length(&mut self) {
+    if !self.length_halt && self.length_counter > 0 {
+      self.length_counter -= 1;
+    }
+  }
+
+  // half frame
+  pub fn clock_sweep(&mut self) {
+    if self.sweep_divider == 0 && self.sweep_enabled && self.sweep_shift > 0 && !self.sweep_muted() {
+      self.timer_period = self.sweep_target();
+    }
+    if self.sweep_divider == 0 || self.sweep_reload {
+      self.sweep_divider = self.sweep_period;
+      self.sweep_reload = false;
+    } else {
+      self.sweep_divider -= 1;
+    }
+  }
+
+  fn sweep_target(&self) -> u16 {
+    let change = self.timer_period >> self.sweep_shift;
+    if !self.sweep_negate {
+      self.timer_period + change
+    } else if self.ones_complement {
+      self.timer_period.saturating_sub(change + 1)
+    } else {
+      self.timer_period.saturating_sub(change)
+    }
+  }
+
+  // the sweep unit mutes the channel even when it is disabled
+  fn sweep_muted(&self) -> bool {
+    self.timer_period < 8 || self.sweep_target() > 0x7FF
+  }
+
+  pub fn snapshot(&self) -> PulseSnapshot {
+    PulseSnapshot {
+      enabled: self.enabled,
+      audible: self.length_counter > 0 && !self.sweep_muted(),
+      frequency: CPU_FREQUENCY / (16.0 * (self.timer_period as f64 + 1.0)),
+      timer_period: self.timer_period,
+      duty: self.duty,
+      volume: if self.constant_volume { self.volume } else { self.envelope_decay },
+      length_counter: self.length_counter,
+    }
+  }
+
+  pub fn output(&self) -> u8 {
+    if self.length_counter == 0 || self.sweep_muted()
+      || DUTY_TABLE[self.duty as usize][self.sequence_pos as usize] == 0 {
+      return 0;
+    }
+    if self.constant_volume { self.volume } else { self.envelope_decay }
+  }
+}
+
+impl Stateful for Pulse {
+  fn save_state(&self, w: &mut StateWriter) {
+    w.write_bool(self.enabled);
+    w.write_u8(self.duty);
+    w.write_u8(self.sequence_pos);
+    w.write_u16(self.timer_period);
+    w.write_u16(self.timer);
+    w.write_u8(self.length_counter);
+    w.write_bool(self.length_halt);
+    w.write_bool(self.constant_volume);
+    w.write_u8(self.volume);
+    w.write_bool(self.envelope_start);
+    w.write_u8(self.envelope_divider);
+    w.write_u8(self.envelope_decay);
+    w.write_bool(self.sweep_enabled);
+    w.write_u8(self.sweep_period);
+    w.write_bool(self.sweep_negate);
+    w.write_u8(self.sweep_shift);
+    w.write_bool(self.sweep_reload);
+    w.write_u8(self.sweep_divider);
+  }
+
+  fn load_state(&mut self, r: &mut StateReader) -> Result<(), String> {
+    self.enabled = r.read_bool()?;
+    self.duty = r.read_u8()?;
+    self.sequence_pos = r.read_u8()?;
+    self.timer_period = r.read_u16()?;
+    self.timer = r.read_u16()?;
+    self.length_counter = r.read_u8()?;
+    self.length_halt = r.read_bool()?;
+    self.constant_volume = r.read_bool()?;
+    self.volume = r.read_u8()?;
+    self.envelope_start = r.read_bool()?;
+    self.envelope_divider = r.read_u8()?;
+    self.envelope_decay = r.read_u8()?;
+    self.sweep_enabled = r.read_bool()?;
+    self.sweep_period = r.read_u8()?;
+    self.sweep_negate = r.read_bool()?;
+    self.sweep_shift = r.read_u8()?;
+    self.sweep_reload = r.read_bool()?;
+    self.sweep_divider = r.read_u8()?;
+    Ok(())
+  }
+}
+
+pub struct Apu {
+  pub pulse1: Pulse,
+  pub pulse2: Pulse,
+  pub frame_counter: FrameCounter,
+  pub config: ApuConfig,
+  cycles: u64,
+  sample_rate: u32,
+  resampler: Resampler,
+  samples: SampleBuffer,
+  recording: Option<WavRecorder>,
+  // a failed write ends the recording, stop_recording() reports it
+  recording_error: Option<io::Error>,
+  // set while an AudioWorker mixes
+  raw_output: Option<RawOutput>,
+}
+
+// nonlinear mixer, 0.0 - ~0.26 for the pulse channels at volume 1.0
+// https://wiki.nesdev.org/w/index.php/APU_Mixer
+pub fn mix(config: &ApuConfig, pulse1: u8, pulse2: u8) -> f32 {
+  let pulse = config.pulse1.level(pulse1) + config.pulse2.level(pulse2);
+  if pulse <= 0.0 {
+    return 0.0;
+  }
+  95.88 / (8128.0 / pulse + 100.0)
+}
+
+impl Apu {
+  pub fn new() -> Self {
+    Apu::with_sample_rate(DEFAULT_SAMPLE_RATE)
+  }
+
+  pub fn with_sample_rate(sample_rate: u32) -> Self {
+    Apu {
+      pulse1: Pulse::new(true),
+      pulse2: Pulse::new(false),
+      frame_counter: FrameCounter::new(),
+      config: ApuConfig::default(),
+      cycles: 0,
+      sample_rate,
+      resampler: Resampler::new(CPU_FREQUENCY, sample_rate),
+      samples: SampleBuffer::new(SAMPLE_BUFFER_SIZE),
+      recording: None,
+      recording_error: None,
+      raw_output: None,
+    }
+  }
+
+  // $4000-$4013, triangle ($4008-$400B), noise ($400C-$400F) and dmc ($4010-$4013) are not emulated yet
+  pub fn write_register(&mut self, addr: u16, data: u8) {
+    match addr {
+      0x4000..=0x4003 => self.pulse1.write_register(addr - 0x4000, data),
+      0x4004..=0x4007 => self.pulse2.write_register(addr - 0x4004, data),
+      _ => {}
+    }
+  }
+
+  // $4015
+  pub fn write_status(&mut self, data: u8) {
+    self.pulse1.set_enabled(data & 0b01 != 0);
+    self.pulse2.set_enabled(data & 0b10 != 0);
+  }
+
+  // $4017, 5-step mode clocks all units right away
+  pub fn write_frame_counter(&mut self, data: u8) {
+    self.frame_counter.write(data);
+    if self.frame_counter.mode == FrameCounterMode::FiveStep {
+      self.quarter_frame();
+      self.half_frame();
+    }
+  }
+
+  // reading acknowledges the frame interrupt
+  pub fn read_status(&mut self) -> u8 {
+    let mut status = 0;
+    if self.frame_counter.interrupt {
+      status |= 0b0100_0000;
+      self.frame_counter.interrupt = false;
+    }
+    if self.pulse1.length_counter > 0 {
+      status |= 0b01;
+    }
+    if self.pulse2.length_counter > 0 {
+      status |= 0b10;
+    }
+    status
+  }
+
+  pub fn frame_irq(&self) -> bool {
+    self.frame_counter.interrupt
+  }
+
+  pub fn quarter_frame(&mut self) {
+    self.pulse1.clock_envelope();
+    self.pulse2.clock_envelope();
+  }
+
+  pub fn half_frame(&mut self) {
+    self.pulse1.clock_length();
+    self.pulse2.clock_length();
+    self.pulse1.clock_sweep();
+    self.pulse2.clock_sweep();
+  }
+
+  // cpu cycles
+  pub fn tick(&mut self, cycles: u16) {
+    for _ in 0..cycles {
+      self.cycles += 1;
+      let (quarter, half) = self.frame_counter.clock();
+      if quarter {
+        self.quarter_frame();
+      }
+      if half {
+        self.half_frame();
+      }
+      if self.cycles.is_multiple_of(2) {
+        self.pulse1.clock_timer();
+        self.pulse2.clock_timer();
+      }
+
+      let mixes_here = match self.raw_output.as_mut() {
+        Some(raw) => {
+          raw.push(&self.config, self.pulse1.output(), self.pulse2.output());
+          false
+        }
+        None => true,
+      };
+      // a recording needs the samples here, even with a worker
+      if mixes_here || self.recording.is_some() {
+        if let Some(sample) = self.resampler.push(self.output()) {
+          if mixes_here {
+            self.samples.push(sample);
+          }
+          self.record(sample);
+        }
+      }
+    }
+    if let Some(raw) = self.raw_output.as_mut() {
+      raw.flush();
+    }
+  }
+
+  pub fn output(&self) -> f32 {
+    mix(&self.config, self.pulse1.output(), self.pulse2.output())
+  }
+
+  // from now on the channel outputs go to the worker of AudioWorker::spawn
+  // instead of being mixed into samples()
+  pub fn offload_mixing(&mut self, output: RawOutput) {
+    self.raw_output = Some(output);
+  }
+
+  // mixes here again, dropping the output ends the worker
+  pub fn take_raw_output(&mut self) -> Option<RawOutput> {
+    self.raw_output.take()
+  }
+
+  pub fn sample_rate(&self) -> u32 {
+    self.sample_rate
+  }
+
+  // taken once per frame by visualizers
+  pub fn snapshot(&self) -> ApuSnapshot {
+    ApuSnapshot {
+      pulse1: self.pulse1.snapshot(),
+      pulse2: self.pulse2.snapshot(),
+      frame_counter_mode: self.frame_counter.mode,
+    }
+  }
+
+  // everything the mixer produces from now on goes to the recorder as well,
+  // a recorder with a duration finishes the file on its own
+  pub fn start_recording(&mut self, recorder: WavRecorder) {
+    self.recording = Some(recorder);
+    self.recording_error = None;
+  }
+
+  pub fn is_recording(&self) -> bool {
+    self.recording.is_some()
+  }
+
+  // finishes the file, or reports the error that ended the recording early
+  pub fn stop_recording(&mut self) -> io::Result<()> {
+    match (self.recording.take(), self.recording_error.take()) {
+      (Some(recorder), _) => recorder.finish(),
+      (None, Some(e)) => Err(e),
+      (None, None) => Ok(()),
+    }
+  }
+
+  fn record(&mut self, sample: f32) {
+    let recorder = match self.recording.as_mut() {
+      Some(recorder) => recorder,
+      None => return,
+    };
+    let result = recorder.write_samples(&[sample]);
+    if result.is_err() || recorder.is_full() {
+      let recorder = self.recording.take().unwrap();
+      if let Err(e) = result.and_then(|_| recorder.finish()) {
+        self.recording_error = Some(e);
+      }
+    }
+  }
+
+  pub fn samples(&self) -> &SampleBuffer {
+    &self.samples
+  }
+
+  pub fn take_samples(&mut self) -> Vec<f32> {
+    self.samples.drain()
+  }
+}
+
+// buffered samples are not part of the state
+impl Stateful for Apu {
+  fn save_state(&self, w: &mut StateWriter) {
+    self.pulse1.save_state(w);
+    self.pulse2.save_state(w);
+    self.frame_counter.save_state(w);
+    w.write_u64(self.cycles);
+    w.write_u64(self.resampler.timer().to_bits());
+  }
+
+  fn load_state(&mut self, r: &mut StateReader) -> Result<(), String> {
+    self.pulse1.load_state(r)?;
+    self.pulse2.load_state(r)?;
+    self.frame_counter.load_state(r)?;
+    self.cycles = r.read_u64()?;
+    self.resampler.set_timer(f64::from_bits(r.read_u64()?));
+    Ok(())
+  }
+}
+
+impl Default for Apu {
+  fn default() -> Self {
+    Apu::new()
+  }
+}
+
+// what the Stateful impl saves, as a struct for serde
+#[cfg(feature = "serde")]
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct ApuState {
+  pub pulse1: Pulse,
+  pub pulse2: Pulse,
+  pub frame_counter: FrameCounter,
+  pub cycles: u64,
+  pub resampler_timer: f64,
+}
+
+#[cfg(feature = "serde")]
+impl Apu {
+  pub fn state(&self) -> ApuState {
+    ApuState {
+      pulse1: self.pulse1.clone(),
+      pulse2: self.pulse2.clone(),
+      frame_counter: self.frame_counter.clone(),
+      cycles: self.cycles,
+      resampler_timer: self.resampler.timer(),
+    }
+  }
+
+  pub fn set_state(&mut self, state: ApuState) {
+    self.pulse1 = state.pulse1;
+    self.pulse2 = state.pulse2;
+    self.frame_counter = state.frame_counter;
+    self.cycles = state.cycles;
+    self.resampler.set_timer(state.resampler_timer);
+  }
+}
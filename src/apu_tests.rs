@@ -0,0 +1,236 @@
+use crate::apu::{Apu, Channel, CPU_FREQUENCY, Pulse, SampleBuffer};
+use crate::bus::Bus;
+use crate::cartridge_tests::create_test_rom;
+use crate::cpu::MyMem;
+
+// 50% duty, constant volume 15, length index 1 (254), timer period $100
+fn init_pulse() -> Pulse {
+  let mut pulse = Pulse::new(false);
+  pulse.set_enabled(true);
+  pulse.write_register(0, 0b1001_1111);
+  pulse.write_register(2, 0x00);
+  pulse.write_register(3, 0b0000_1001);
+  pulse
+}
+
+#[test]
+fn test_length_counter_is_loaded_and_cleared_by_disable() {
+  let mut pulse = init_pulse();
+  assert_eq!(254, pulse.length_counter);
+
+  pulse.set_enabled(false);
+  assert_eq!(0, pulse.length_counter);
+
+  // writes while disabled don't load the counter
+  pulse.write_register(3, 0b0000_1001);
+  assert_eq!(0, pulse.length_counter);
+}
+
+#[test]
+fn test_length_counter_halt() {
+  let mut pulse = init_pulse();
+  pulse.clock_length();
+  assert_eq!(253, pulse.length_counter);
+
+  pulse.write_register(0, 0b1011_1111); // halt
+  pulse.clock_length();
+  assert_eq!(253, pulse.length_counter);
+}
+
+#[test]
+fn test_duty_sequence_output() {
+  let mut pulse = init_pulse();
+  pulse.write_register(2, 0x08);
+  pulse.write_register(3, 0b0000_1000);
+
+  // first clock reloads the timer and moves to step 1, then every 9 clocks
+  pulse.clock_timer();
+  let mut out = Vec::new();
+  for _ in 0..8 {
+    out.push(pulse.output());
+    for _ in 0..9 {
+      pulse.clock_timer();
+    }
+  }
+  assert_eq!(vec![15, 15, 15, 15, 0, 0, 0, 0], out);
+}
+
+#[test]
+fn test_envelope_decays_and_loops() {
+  let mut pulse = init_pulse();
+  pulse.write_register(0, 0b1000_0000); // envelope, period 0, no loop
+  pulse.clock_envelope(); // start: decay = 15
+  for _ in 0..20 {
+    pulse.clock_envelope();
+  }
+  for _ in 0..=0x100 {
+    pulse.clock_timer();
+  }
+  assert_eq!(0, pulse.output());
+
+  pulse.write_register(0, 0b1010_0000); // loop
+  pulse.clock_envelope();
+  for _ in 0..=0x100 {
+    pulse.clock_timer();
+  }
+  assert_eq!(15, pulse.output());
+}
+
+#[test]
+fn test_sweep_mutes_and_adjusts_period() {
+  let mut pulse = init_pulse();
+  // period 7 is too high a frequency
+  pulse.write_register(2, 0x07);
+  pulse.write_register(3, 0b0000_1000);
+  pulse.clock_timer();
+  assert_eq!(0, pulse.output());
+
+  // +period>>1 overflows $7FF
+  let mut pulse = init_pulse();
+  pulse.write_register(2, 0xFF);
+  pulse.write_register(3, 0b0000_1110);
+  pulse.write_register(1, 0b0000_0001);
+  for _ in 0..=0x7FF {
+    pulse.clock_timer();
+  }
+  assert_eq!(0, pulse.output());
+
+  // enabled sweep, shift 1, negate: $100 -> $80
+  let mut pulse = init_pulse();
+  pulse.write_register(1, 0b1000_1001);
+  pulse.clock_sweep();
+  for _ in 0..=0x80 {
+    pulse.clock_timer();
+  }
+  assert_eq!(15, pulse.output());
+}
+
+#[test]
+fn test_samples_are_produced_at_sample_rate() {
+  let mut apu = Apu::with_sample_rate(44_100);
+  for _ in 0..(CPU_FREQUENCY as usize / 100) {
+    apu.tick(1);
+  }
+  let produced = apu.samples().len();
+  assert!((440..=441).contains(&produced));
+  assert_eq!(produced, apu.take_samples().len());
+  assert!(apu.samples().is_empty());
+}
+
+#[test]
+fn test_sample_buffer_drops_oldest() {
+  let mut buffer = SampleBuffer::new(2);
+  buffer.push(1.0);
+  buffer.push(2.0);
+  buffer.push(3.0);
+  assert_eq!(vec![2.0, 3.0], buffer.drain());
+}
+
+#[test]
+fn test_bus_maps_apu_registers() {
+  let mut bus = Bus::new(create_test_rom()).unwrap();
+  bus.mem_write(0x4015, 0b10);
+  bus.mem_write(0x4007, 0b0000_1000);
+  bus.mem_write(0x4003, 0b0000_1000);
+
+  assert_eq!(0, bus.apu.pulse1.length_counter);
+  assert_eq!(254, bus.apu.pulse2.length_counter);
+  assert_eq!(0b10, bus.mem_read(0x4015));
+}
+
+#[test]
+fn test_four_step_frame_counter_raises_irq() {
+  let mut bus = Bus::new(create_test_rom()).unwrap();
+  for _ in 0..29828 {
+    bus.tick(1);
+  }
+  assert!(!bus.irq_pending());
+
+  bus.tick(1);
+  assert!(bus.irq_pending());
+
+  // reading the status acknowledges the interrupt
+  assert_eq!(0b0100_0000, bus.mem_read(0x4015) & 0b0100_0000);
+  assert!(!bus.irq_pending());
+  assert_eq!(0, bus.mem_read(0x4015) & 0b0100_0000);
+}
+
+#[test]
+fn test_irq_inhibit_and_five_step_mode() {
+  let mut bus = Bus::new(create_test_rom()).unwrap();
+  bus.mem_write(0x4017, 0b0100_0000);
+  for _ in 0..40000 {
+    bus.tick(1);
+  }
+  assert!(!bus.irq_pending());
+
+  let mut bus = Bus::new(create_test_rom()).unwrap();
+  bus.mem_write(0x4017, 0b1000_0000);
+  for _ in 0..40000 {
+    bus.tick(1);
+  }
+  assert!(!bus.irq_pending());
+}
+
+#[test]
+fn test_frame_counter_clocks_length_counters() {
+  let mut apu = Apu::new();
+  apu.write_status(0b01);
+  apu.write_register(0x4000, 0b1001_1111);
+  apu.write_register(0x4003, 0b0000_1000); // 254
+
+  // two half frames per 4-step sequence
+  for _ in 0..29830 {
+    apu.tick(1);
+  }
+  assert_eq!(252, apu.pulse1.length_counter);
+
+  // switching to 5-step mode clocks a half frame immediately
+  apu.write_frame_counter(0b1000_0000);
+  assert_eq!(251, apu.pulse1.length_counter);
+}
+
+#[test]
+fn test_config_mutes_and_scales_channels() {
+  let mut apu = Apu::new();
+  apu.pulse1 = init_pulse();
+  apu.pulse2 = init_pulse();
+  // step into the high part of the duty cycle
+  for _ in 0..0x101 {
+    apu.pulse1.clock_timer();
+    apu.pulse2.clock_timer();
+  }
+  let both = apu.output();
+  assert!(both > 0.0);
+
+  apu.config.solo(Channel::Pulse2);
+  let pulse2 = apu.output();
+  assert!(pulse2 > 0.0 && pulse2 < both);
+  assert!(!apu.config.channel(Channel::Pulse1).enabled);
+
+  apu.config.channel_mut(Channel::Pulse2).volume = 0.0;
+  assert_eq!(0.0, apu.output());
+
+  apu.config.channel_mut(Channel::Pulse1).enabled = true;
+  assert_eq!(pulse2, apu.output());
+}
+
+#[test]
+fn test_snapshot_of_pulse_channels() {
+  let mut apu = Apu::new();
+  apu.write_status(0b01);
+  apu.write_register(0x4000, 0b0101_1010); // 25% duty, constant volume 10
+  apu.write_register(0x4002, 0xFD);
+  apu.write_register(0x4003, 0b0000_1000); // period $0FD
+
+  let snapshot = apu.snapshot();
+
+  let pulse1 = snapshot.pulse1;
+  assert!(pulse1.enabled && pulse1.audible);
+  assert_eq!(1, pulse1.duty);
+  assert_eq!(10, pulse1.volume);
+  assert_eq!(254, pulse1.length_counter);
+  assert_eq!(0x0FD, pulse1.timer_period);
+  assert!((pulse1.frequency - 440.4).abs() < 0.1, "{}", pulse1.frequency);
+  assert!(!snapshot.pulse2.enabled && !snapshot.pulse2.audible);
+}
@@ -0,0 +1,232 @@
+use std::collections::HashMap;
+use std::fmt;
+use crate::cpu::AddressingMode;
+use crate::opcodes::{CPU_OPS_CODES, OpCode};
+
+// two pass assembler for simple 6502 source, mainly for readable cpu tests:
+//   loop:  LDA #$C0      ; comments
+//          STA $0200,X
+//          BNE loop
+//   speed = $10          ; constants
+//          .byte $01, 2, %11
+//          .word loop
+// numbers are $hex, %binary or decimal, <expr / >expr take the low / high byte.
+// labels are always 16 bit, constants defined before their use can be zero page
+#[derive(Debug, PartialEq)]
+pub struct AsmError {
+  pub line: usize,
+  pub message: String,
+}
+
+impl fmt::Display for AsmError {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(f, "line {}: {}", self.line, self.message)
+  }
+}
+
+impl std::error::Error for AsmError {}
+
+// the code starts at `origin`
+pub fn assemble(source: &str, origin: u16) -> Result<Vec<u8>, AsmError> {
+  let mut symbols = HashMap::new();
+  let mut statements = Vec::new();
+  let mut address = origin;
+
+  // pass 1: addresses of the labels and the size of every statement
+  for (index, line) in source.lines().enumerate() {
+    let error = |message: String| AsmError { line: index + 1, message };
+    let mut text = line.split(';').next().unwrap_or("").trim();
+    if let Some((name, value)) = text.split_once('=') {
+      let name = name.trim();
+      check_name(name).map_err(error)?;
+      let value = evaluate(value.trim(), &symbols).map_err(error)?
+        .ok_or_else(|| error(format!("{} must be defined before its use", value.trim())))?;
+      symbols.insert(name.to_string(), value);
+      continue;
+    }
+    if let Some((label, rest)) = text.split_once(':') {
+      let label = label.trim();
+      check_name(label).map_err(error)?;
+      if symbols.insert(label.to_string(), address).is_some() {
+        return Err(error(format!("{} is defined twice", label)));
+      }
+      text = rest.trim();
+    }
+    if text.is_empty() {
+      continue;
+    }
+    let statement = parse_statement(text, &symbols).map_err(error)?;
+    address = address.wrapping_add(statement.len());
+    statements.push((index + 1, address, statement));
+  }
+
+  // pass 2: operands, all labels are known now
+  let mut bytes = Vec::new();
+  for (line, next_address, statement) in statements {
+    let error = |message: String| AsmError { line, message };
+    statement.encode(next_address, &symbols, &mut bytes).map_err(error)?;
+  }
+  Ok(bytes)
+}
+
+enum Statement {
+  Instruction { opcode: &'static OpCode, operand: Option<String> },
+  Bytes(Vec<String>),
+  Words(Vec<String>),
+}
+
+impl Statement {
+  fn len(&self) -> u16 {
+    match self {
+      Statement::Instruction { opcode, .. } => opcode.len as u16,
+      Statement::Bytes(values) => values.len() as u16,
+      Statement::Words(values) => 2 * values.len() as u16,
+    }
+  }
+
+  // `next_address` follows the statement, branches are relative to it
+  fn encode(&self, next_address: u16, symbols: &HashMap<String, u16>, bytes: &mut Vec<u8>) -> Result<(), String> {
+    let resolve = |expression: &str| {
+      evaluate(expression, symbols)?.ok_or_else(|| format!("unknown label in {}", expression))
+    };
+    match self {
+      Statement::Instruction { opcode, operand } => {
+        bytes.push(opcode.code);
+        let value = match operand {
+          Some(expression) => resolve(expression)?,
+          None => return Ok(()),
+        };
+        match (&opcode.mode, opcode.len) {
+          (AddressingMode::Relative, _) => {
+            let offset = value.wrapping_sub(next_address) as i16;
+            if !(-128..=127).contains(&offset) {
+              return Err(format!("branch target ${:04X} is out of range", value));
+            }
+            bytes.push(offset as u8);
+          }
+          (_, 2) => bytes.push(byte(value)?),
+          _ => bytes.extend_from_slice(&value.to_le_bytes()),
+        }
+      }
+      Statement::Bytes(values) => {
+        for value in values {
+          bytes.push(byte(resolve(value)?)?);
+        }
+      }
+      Statement::Words(values) => {
+        for value in values {
+          bytes.extend_from_slice(&resolve(value)?.to_le_bytes());
+        }
+      }
+    }
+    Ok(())
+  }
+}
+
+fn byte(value: u16) -> Result<u8, String> {
+  u8::try_from(value).map_err(|_| format!("${:04X} does not fit into a byte", value))
+}
+
+fn check_name(name: &str) -> Result<(), String> {
+  let valid = name.chars().next().is_some_and(|c| c.is_ascii_alphabetic() || c == '_' || c == '@')
+    && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '@');
+  if valid { Ok(()) } else { Err(format!("invalid label {}", name)) }
+}
+
+fn parse_statement(text: &str, symbols: &HashMap<String, u16>) -> Result<Statement, String> {
+  let (mnemonic, operand) = match text.split_once(char::is_whitespace) {
+    Some((mnemonic, operand)) => (mnemonic, operand.trim()),
+    None => (text, ""),
+  };
+  let values = || operand.split(',').map(|v| v.trim().to_string()).collect();
+  match mnemonic.to_ascii_lowercase().as_str() {
+    ".byte" | ".db" => return Ok(Statement::Bytes(values())),
+    ".word" | ".dw" => return Ok(Statement::Words(values())),
+    _ => {}
+  }
+  let mnemonic = mnemonic.to_ascii_uppercase();
+  if !CPU_OPS_CODES.iter().any(|op| op.mnemonic == mnemonic) {
+    return Err(format!("unknown instruction {}", mnemonic));
+  }
+  let find = |mode: AddressingMode| CPU_OPS_CODES.iter().find(|op| op.mnemonic == mnemonic && op.mode == mode);
+  let upper = operand.to_ascii_uppercase();
+
+  let (opcode, expression) = if operand.is_empty() || upper == "A" {
+    (find(AddressingMode::NoneAddressing), None)
+  } else if let Some(expression) = operand.strip_prefix('#') {
+    (find(AddressingMode::Immediate), Some(expression))
+  } else if let Some(expression) = upper.strip_prefix('(').and_then(|o| o.strip_suffix(",X)")) {
+    (find(AddressingMode::Indirect_X), Some(&operand[1..=expression.len()]))
+  } else if let Some(expression) = upper.strip_prefix('(').and_then(|o| o.strip_suffix("),Y")) {
+    (find(AddressingMode::Indirect_Y), Some(&operand[1..=expression.len()]))
+  } else if let Some(expression) = upper.strip_prefix('(').and_then(|o| o.strip_suffix(')')) {
+    (find(AddressingMode::Indirect), Some(&operand[1..=expression.len()]))
+  } else if let Some(relative) = find(AddressingMode::Relative) {
+    (Some(relative), Some(operand))
+  } else {
+    let (expression, zero_page, absolute) = if let Some(expression) = upper.strip_suffix(",X") {
+      (&operand[..expression.len()], AddressingMode::ZeroPage_X, AddressingMode::Absolute_X)
+    } else if let Some(expression) = upper.strip_suffix(",Y") {
+      (&operand[..expression.len()], AddressingMode::ZeroPage_Y, AddressingMode::Absolute_Y)
+    } else {
+      (operand, AddressingMode::ZeroPage, AddressingMode::Absolute)
+    };
+    let expression = expression.trim();
+    // unknown values are forward references to labels, which are 16 bit
+    let fits_zero_page = evaluate(expression, symbols)?.is_some_and(|value| value <= 0xFF);
+    let opcode = match find(zero_page) {
+      Some(opcode) if fits_zero_page => Some(opcode),
+      _ => find(absolute),
+    };
+    (opcode, Some(expression))
+  };
+
+  match opcode {
+    Some(opcode) => Ok(Statement::Instruction { opcode, operand: expression.map(|e| e.trim().to_string()) }),
+    None => Err(format!("{} does not support the operand {}", mnemonic, operand)),
+  }
+}
+
+// None if the expression uses a label which is not defined yet
+fn evaluate(expression: &str, symbols: &HashMap<String, u16>) -> Result<Option<u16>, String> {
+  if let Some(rest) = expression.strip_prefix('<') {
+    return Ok(evaluate(rest, symbols)?.map(|value| value & 0xFF));
+  }
+  if let Some(rest) = expression.strip_prefix('>') {
+    return Ok(evaluate(rest, symbols)?.map(|value| value >> 8));
+  }
+  // terms joined by + and -
+  let mut total: Option<u16> = Some(0);
+  let mut rest = expression.trim();
+  let mut negative = false;
+  loop {
+    let end = rest.find(['+', '-']).unwrap_or(rest.len());
+    let value = term(rest[..end].trim(), symbols)?;
+    total = match (total, value) {
+      (Some(total), Some(value)) if negative => Some(total.wrapping_sub(value)),
+      (Some(total), Some(value)) => Some(total.wrapping_add(value)),
+      _ => None,
+    };
+    if end == rest.len() {
+      return Ok(total);
+    }
+    negative = rest[end..].starts_with('-');
+    rest = &rest[end + 1..];
+  }
+}
+
+fn term(text: &str, symbols: &HashMap<String, u16>) -> Result<Option<u16>, String> {
+  let number = |digits: &str, radix: u32| {
+    u16::from_str_radix(digits, radix).map(Some).map_err(|_| format!("invalid number {}", text))
+  };
+  match text.chars().next() {
+    Some('$') => number(&text[1..], 16),
+    Some('%') => number(&text[1..], 2),
+    Some(c) if c.is_ascii_digit() => number(text, 10),
+    Some(_) => {
+      check_name(text)?;
+      Ok(symbols.get(text).copied())
+    }
+    None => Err("missing value".to_string()),
+  }
+}
@@ -0,0 +1,90 @@
+use crate::bus::Bus;
+use crate::asm::{assemble, AsmError};
+use crate::cartridge_tests::create_test_rom;
+use crate::cpu::MyCPU;
+use crate::disasm::disassemble;
+use crate::opcodes::CPU_OPS_CODES;
+
+#[test]
+fn test_addressing_modes() {
+  let source = "
+    LDA #$C0
+    lda $10
+    LDA $10,X
+    LDX $10,Y
+    LDA $0200
+    LDA $0200,X
+    LDA $10,Y      ; no zero page,Y for LDA
+    LDA ($10,X)
+    LDA ($10),Y
+    JMP ($FFFC)
+    ASL
+    ASL A
+    NOP";
+
+  assert_eq!(Ok(vec![
+    0xA9, 0xC0, 0xA5, 0x10, 0xB5, 0x10, 0xB6, 0x10, 0xAD, 0x00, 0x02, 0xBD, 0x00, 0x02,
+    0xB9, 0x10, 0x00, 0xA1, 0x10, 0xB1, 0x10, 0x6C, 0xFC, 0xFF, 0x0A, 0x0A, 0xEA,
+  ]), assemble(source, 0x0600));
+}
+
+#[test]
+fn test_labels_constants_and_data() {
+  let source = "
+    counter = $10
+    start:  LDX #3
+    loop:   DEX
+            STX counter
+            BNE loop
+            JSR done
+            LDA #<table
+            LDA #>table+1
+    done:   RTS
+    table:  .byte $01, 2, %11
+            .word start, table";
+
+  assert_eq!(Ok(vec![
+    0xA2, 0x03, 0xCA, 0x86, 0x10, 0xD0, 0xFB, 0x20, 0x0E, 0x80, 0xA9, 0x0F, 0xA9, 0x80, 0x60,
+    0x01, 0x02, 0x03, 0x00, 0x80, 0x0F, 0x80,
+  ]), assemble(source, 0x8000));
+}
+
+#[test]
+fn test_errors_name_the_line() {
+  let error = |line: usize, message: &str| Err(AsmError { line, message: message.to_string() });
+
+  assert_eq!(error(2, "unknown instruction FOO"), assemble("NOP\nFOO", 0));
+  assert_eq!(error(1, "unknown label in nowhere"), assemble("JMP nowhere", 0));
+  assert_eq!(error(1, "$0100 does not fit into a byte"), assemble("LDA #$100", 0));
+  assert_eq!(error(1, "STX does not support the operand $0200,X"), assemble("STX $0200,X", 0));
+  assert_eq!(error(2, "a is defined twice"), assemble("a: NOP\na: NOP", 0));
+  assert!(assemble("BNE far\n.byte 0\nfar = $1000", 0).is_err());
+}
+
+#[test]
+fn test_every_official_opcode_survives_disassembly() {
+  for op in CPU_OPS_CODES.iter().filter(|op| !op.mnemonic.starts_with('*')) {
+    // operands above the zero page keep absolute modes absolute
+    let bytes: Vec<u8> = [op.code, 0x34, 0x12].iter().take(op.len as usize).copied().collect();
+    let line = &disassemble(&bytes, 0x8000)[0];
+
+    assert_eq!(Ok(bytes.clone()), assemble(&line.text, 0x8000), "{}", line.text);
+  }
+}
+
+#[test]
+fn test_programs_run_on_the_cpu() {
+  let mut cpu = MyCPU::new(Bus::new(create_test_rom()).unwrap());
+  cpu.program_counter = 0x0600;
+
+  cpu.load_and_run(assemble("
+        LDX #0
+        LDY #5
+  loop: INX
+        INX
+        DEY
+        BNE loop
+        STX $0200", 0x0600).unwrap());
+
+  assert_eq!(10, cpu.register_x);
+}
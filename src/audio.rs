@@ -0,0 +1,74 @@
+use std::sync::{Arc, Mutex};
+
+// hands samples from the emulation to the audio callback of the sound card.
+// Bounded: if the emulation runs ahead the oldest samples are dropped (less latency),
+// if it falls behind the callback repeats the last sample instead of clicking to silence.
+pub struct AudioRing {
+  samples: Vec<f32>,
+  read: usize,
+  len: usize,
+  last: f32,
+  underruns: u64,
+  dropped: u64,
+}
+
+pub type SharedAudioRing = Arc<Mutex<AudioRing>>;
+
+impl AudioRing {
+  pub fn new(capacity: usize) -> Self {
+    AudioRing { samples: vec![0.0; capacity.max(1)], read: 0, len: 0, last: 0.0, underruns: 0, dropped: 0 }
+  }
+
+  pub fn shared(capacity: usize) -> SharedAudioRing {
+    Arc::new(Mutex::new(AudioRing::new(capacity)))
+  }
+
+  pub fn len(&self) -> usize {
+    self.len
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.len == 0
+  }
+
+  // callbacks that didn't get enough samples
+  pub fn underruns(&self) -> u64 {
+    self.underruns
+  }
+
+  // samples thrown away because the buffer was full
+  pub fn dropped(&self) -> u64 {
+    self.dropped
+  }
+
+  pub fn push(&mut self, input: &[f32]) {
+    let capacity = self.samples.len();
+    for &sample in input {
+      if self.len == capacity {
+        self.read = (self.read + 1) % capacity;
+        self.len -= 1;
+        self.dropped += 1;
+      }
+      self.samples[(self.read + self.len) % capacity] = sample;
+      self.len += 1;
+    }
+  }
+
+  // fills the whole output, missing samples repeat the last one
+  pub fn fill(&mut self, out: &mut [f32]) {
+    let capacity = self.samples.len();
+    let available = self.len.min(out.len());
+    for sample in out.iter_mut().take(available) {
+      *sample = self.samples[self.read];
+      self.read = (self.read + 1) % capacity;
+    }
+    self.len -= available;
+    if available > 0 {
+      self.last = out[available - 1];
+    }
+    if available < out.len() {
+      self.underruns += 1;
+      out[available..].fill(self.last);
+    }
+  }
+}
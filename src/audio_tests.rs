@@ -0,0 +1,54 @@
+use crate::audio::AudioRing;
+
+#[test]
+fn test_samples_come_out_in_order() {
+  let mut ring = AudioRing::new(8);
+  ring.push(&[0.1, 0.2, 0.3]);
+  let mut out = [0.0; 2];
+
+  ring.fill(&mut out);
+
+  assert_eq!([0.1, 0.2], out);
+  assert_eq!(1, ring.len());
+  assert_eq!(0, ring.underruns());
+}
+
+#[test]
+fn test_full_ring_drops_oldest_samples() {
+  let mut ring = AudioRing::new(3);
+  ring.push(&[0.1, 0.2, 0.3, 0.4, 0.5]);
+  let mut out = [0.0; 3];
+
+  ring.fill(&mut out);
+
+  assert_eq!([0.3, 0.4, 0.5], out);
+  assert_eq!(2, ring.dropped());
+}
+
+#[test]
+fn test_underrun_repeats_the_last_sample() {
+  let mut ring = AudioRing::new(8);
+  ring.push(&[0.1, 0.2]);
+  let mut out = [0.0; 4];
+
+  ring.fill(&mut out);
+  assert_eq!([0.1, 0.2, 0.2, 0.2], out);
+
+  ring.fill(&mut out);
+  assert_eq!([0.2; 4], out);
+  assert_eq!(2, ring.underruns());
+  assert!(ring.is_empty());
+}
+
+#[test]
+fn test_wraps_around() {
+  let mut ring = AudioRing::new(4);
+  let mut out = [0.0; 3];
+  ring.push(&[1.0, 2.0, 3.0]);
+  ring.fill(&mut out);
+
+  ring.push(&[4.0, 5.0, 6.0]);
+  ring.fill(&mut out);
+
+  assert_eq!([4.0, 5.0, 6.0], out);
+}
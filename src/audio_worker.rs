@@ -0,0 +1,222 @@
+use std::cell::UnsafeCell;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+use crate::apu::{mix, ApuConfig, CPU_FREQUENCY};
+use crate::audio::SharedAudioRing;
+use crate::resampler::Resampler;
+
+// a run is cut after this many cycles even if the outputs don't change,
+// so the worker isn't left waiting during silence (~0.6ms)
+const MAX_RUN: u16 = 1024;
+// ~0.15s of runs of the shortest pulse period
+const QUEUE_SIZE: usize = 16 * 1024;
+const IDLE_WAIT: Duration = Duration::from_millis(1);
+
+// what the apu hands to the worker: the channel outputs and for how many cycles they
+// stayed the same, plus the mix settings whenever they changed
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RawAudio {
+  Run { pulse1: u8, pulse2: u8, cycles: u16 },
+  Config(ApuConfig),
+}
+
+impl Default for RawAudio {
+  fn default() -> Self {
+    RawAudio::Run { pulse1: 0, pulse2: 0, cycles: 0 }
+  }
+}
+
+// bounded queue for exactly one producer and one consumer thread, neither side ever blocks
+struct Shared<T> {
+  items: Box<[UnsafeCell<T>]>,
+  // both only grow, the slot is the counter modulo the capacity
+  read: AtomicUsize,
+  write: AtomicUsize,
+  closed: AtomicBool,
+}
+
+// a slot is only touched by the producer before it is published with `write`
+// and by the consumer before it is released with `read`
+unsafe impl<T: Send> Sync for Shared<T> {}
+
+pub struct Producer<T> {
+  shared: Arc<Shared<T>>,
+}
+
+pub struct Consumer<T> {
+  shared: Arc<Shared<T>>,
+}
+
+pub fn queue<T: Copy + Default>(capacity: usize) -> (Producer<T>, Consumer<T>) {
+  let shared = Arc::new(Shared {
+    items: (0..capacity.max(1)).map(|_| UnsafeCell::new(T::default())).collect(),
+    read: AtomicUsize::new(0),
+    write: AtomicUsize::new(0),
+    closed: AtomicBool::new(false),
+  });
+  (Producer { shared: shared.clone() }, Consumer { shared })
+}
+
+impl<T: Copy> Producer<T> {
+  // as many items as fit, the number pushed
+  pub fn push(&mut self, items: &[T]) -> usize {
+    let capacity = self.shared.items.len();
+    let write = self.shared.write.load(Ordering::Relaxed);
+    let free = capacity - write.wrapping_sub(self.shared.read.load(Ordering::Acquire));
+    let count = items.len().min(free);
+    for (i, &item) in items[..count].iter().enumerate() {
+      unsafe { *self.shared.items[write.wrapping_add(i) % capacity].get() = item };
+    }
+    self.shared.write.store(write.wrapping_add(count), Ordering::Release);
+    count
+  }
+}
+
+// tells the consumer that nothing comes anymore
+impl<T> Drop for Producer<T> {
+  fn drop(&mut self) {
+    self.shared.closed.store(true, Ordering::Release);
+  }
+}
+
+impl<T: Copy> Consumer<T> {
+  // appends everything available to `out`, the number popped
+  pub fn pop(&mut self, out: &mut Vec<T>) -> usize {
+    let capacity = self.shared.items.len();
+    let read = self.shared.read.load(Ordering::Relaxed);
+    let count = self.shared.write.load(Ordering::Acquire).wrapping_sub(read);
+    out.extend((0..count).map(|i| unsafe { *self.shared.items[read.wrapping_add(i) % capacity].get() }));
+    self.shared.read.store(read.wrapping_add(count), Ordering::Release);
+    count
+  }
+
+  // the producer is gone, items pushed before might still be queued
+  pub fn is_closed(&self) -> bool {
+    self.shared.closed.load(Ordering::Acquire)
+  }
+}
+
+// the apu side: collects the channel outputs of every cycle into runs
+pub struct RawOutput {
+  producer: Producer<RawAudio>,
+  pending: Vec<RawAudio>,
+  run: Option<(u8, u8, u16)>,
+  config: Option<ApuConfig>,
+  dropped: u64,
+}
+
+impl RawOutput {
+  pub fn new(producer: Producer<RawAudio>) -> Self {
+    RawOutput { producer, pending: Vec::new(), run: None, config: None, dropped: 0 }
+  }
+
+  pub fn push(&mut self, config: &ApuConfig, pulse1: u8, pulse2: u8) {
+    if self.config.as_ref() != Some(config) {
+      self.end_run();
+      self.config = Some(*config);
+      self.pending.push(RawAudio::Config(*config));
+    }
+    match self.run.as_mut() {
+      Some((p1, p2, cycles)) if (*p1, *p2) == (pulse1, pulse2) && *cycles < MAX_RUN => *cycles += 1,
+      _ => {
+        self.end_run();
+        self.run = Some((pulse1, pulse2, 1));
+      }
+    }
+  }
+
+  // hands the finished runs to the worker, called after every apu tick
+  pub fn flush(&mut self) {
+    if self.pending.is_empty() {
+      return;
+    }
+    let pushed = self.producer.push(&self.pending);
+    self.dropped += (self.pending.len() - pushed) as u64;
+    self.pending.clear();
+  }
+
+  // runs that didn't fit into the queue because the worker fell behind
+  pub fn dropped(&self) -> u64 {
+    self.dropped
+  }
+
+  fn end_run(&mut self) {
+    if let Some((pulse1, pulse2, cycles)) = self.run.take() {
+      self.pending.push(RawAudio::Run { pulse1, pulse2, cycles });
+    }
+  }
+}
+
+// the last, unfinished run goes out as well
+impl Drop for RawOutput {
+  fn drop(&mut self) {
+    self.end_run();
+    self.flush();
+  }
+}
+
+// mixes and resamples the raw channel outputs of the apu on its own thread and fills the
+// audio ring, the emulation thread only collects the outputs. The mix is the same as
+// Apu::output, so the samples equal the ones of the apu mixing them itself.
+// Ends once the RawOutput is dropped and everything is mixed, or when dropped itself.
+pub struct AudioWorker {
+  playing: Arc<AtomicBool>,
+  stop: Arc<AtomicBool>,
+  handle: Option<JoinHandle<()>>,
+}
+
+impl AudioWorker {
+  pub fn spawn(sample_rate: u32, ring: SharedAudioRing) -> (AudioWorker, RawOutput) {
+    let (producer, mut consumer) = queue(QUEUE_SIZE);
+    let playing = Arc::new(AtomicBool::new(true));
+    let stop = Arc::new(AtomicBool::new(false));
+    let (worker_playing, worker_stop) = (playing.clone(), stop.clone());
+    let handle = thread::spawn(move || {
+      let mut resampler = Resampler::new(CPU_FREQUENCY, sample_rate);
+      let mut config = ApuConfig::default();
+      let (mut raw, mut samples) = (Vec::new(), Vec::new());
+      loop {
+        // checked first: everything pushed before closing is popped below
+        let done = consumer.is_closed() || worker_stop.load(Ordering::Acquire);
+        raw.clear();
+        if consumer.pop(&mut raw) == 0 {
+          if done {
+            return;
+          }
+          thread::sleep(IDLE_WAIT);
+          continue;
+        }
+        for item in &raw {
+          match *item {
+            RawAudio::Config(c) => config = c,
+            RawAudio::Run { pulse1, pulse2, cycles } => {
+              let level = mix(&config, pulse1, pulse2);
+              samples.extend((0..cycles).filter_map(|_| resampler.push(level)));
+            }
+          }
+        }
+        if worker_playing.load(Ordering::Relaxed) {
+          ring.lock().unwrap().push(&samples);
+        }
+        samples.clear();
+      }
+    });
+    (AudioWorker { playing, stop, handle: Some(handle) }, RawOutput::new(producer))
+  }
+
+  // while not playing (e.g. fast-forward) the samples are mixed but thrown away
+  pub fn set_playing(&self, playing: bool) {
+    self.playing.store(playing, Ordering::Relaxed);
+  }
+}
+
+impl Drop for AudioWorker {
+  fn drop(&mut self) {
+    self.stop.store(true, Ordering::Release);
+    if let Some(handle) = self.handle.take() {
+      let _ = handle.join();
+    }
+  }
+}
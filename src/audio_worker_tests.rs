@@ -0,0 +1,80 @@
+use crate::apu::Apu;
+use crate::audio::AudioRing;
+use crate::audio_worker::{queue, AudioWorker};
+
+// pulse 1 at ~870Hz, full constant volume
+fn playing_apu() -> Apu {
+  let mut apu = Apu::new();
+  apu.write_status(0b01);
+  apu.write_register(0x4000, 0b1011_1111);
+  apu.write_register(0x4002, 0x80);
+  apu.write_register(0x4003, 0x08);
+  apu
+}
+
+// a frame of cycles, then pulse 1 muted for another one
+fn play(apu: &mut Apu) {
+  for _ in 0..10_000 {
+    apu.tick(3);
+  }
+  apu.config.pulse1.enabled = false;
+  for _ in 0..10_000 {
+    apu.tick(3);
+  }
+}
+
+#[test]
+fn test_queue_keeps_order_and_bounds() {
+  let (mut producer, mut consumer) = queue::<u8>(4);
+  let mut out = Vec::new();
+
+  assert_eq!(3, producer.push(&[1, 2, 3]));
+  assert_eq!(1, producer.push(&[4, 5]));
+  assert_eq!(4, consumer.pop(&mut out));
+  // wraps around
+  assert_eq!(4, producer.push(&[6, 7, 8, 9, 10]));
+  assert_eq!(4, consumer.pop(&mut out));
+  assert_eq!(0, consumer.pop(&mut out));
+
+  assert_eq!(vec![1, 2, 3, 4, 6, 7, 8, 9], out);
+  assert!(!consumer.is_closed());
+  drop(producer);
+  assert!(consumer.is_closed());
+}
+
+#[test]
+fn test_worker_mixes_the_same_samples() {
+  let mut local = playing_apu();
+  play(&mut local);
+  let expected = local.take_samples();
+
+  let ring = AudioRing::shared(expected.len() * 2);
+  let (worker, raw) = AudioWorker::spawn(local.sample_rate(), ring.clone());
+  let mut offloaded = playing_apu();
+  offloaded.offload_mixing(raw);
+  play(&mut offloaded);
+  drop(offloaded.take_raw_output());
+  drop(worker);
+
+  assert!(offloaded.samples().is_empty());
+  let mut ring = ring.lock().unwrap();
+  let mut actual = vec![0.0; ring.len()];
+  ring.fill(&mut actual);
+  assert_eq!(expected, actual);
+  assert!(expected.iter().any(|&sample| sample > 0.0));
+}
+
+#[test]
+fn test_worker_drops_samples_while_not_playing() {
+  let ring = AudioRing::shared(1024);
+  let (worker, raw) = AudioWorker::spawn(44_100, ring.clone());
+  worker.set_playing(false);
+  let mut apu = playing_apu();
+  apu.offload_mixing(raw);
+
+  play(&mut apu);
+  drop(apu.take_raw_output());
+  drop(worker);
+
+  assert!(ring.lock().unwrap().is_empty());
+}
@@ -0,0 +1,32 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use crate::bus::Bus;
+
+// battery backed prg ram is kept next to the rom: game.nes -> game.sav
+
+pub fn save_path(rom_path: &Path) -> PathBuf {
+  rom_path.with_extension("sav")
+}
+
+// a missing save file is fine, the game starts with empty ram then
+pub fn load(bus: &mut Bus, path: &Path) -> io::Result<()> {
+  if !bus.has_battery() {
+    return Ok(());
+  }
+  match fs::read(path) {
+    Ok(data) => {
+      bus.load_prg_ram(&data);
+      Ok(())
+    }
+    Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(()),
+    Err(e) => Err(e),
+  }
+}
+
+pub fn store(bus: &Bus, path: &Path) -> io::Result<()> {
+  if !bus.has_battery() {
+    return Ok(());
+  }
+  fs::write(path, bus.prg_ram())
+}
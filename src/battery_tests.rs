@@ -0,0 +1,53 @@
+use std::path::Path;
+use crate::bus::Bus;
+use crate::battery;
+use crate::cartridge_tests::create_test_rom;
+use crate::cpu::MyMem;
+
+fn battery_bus() -> Bus {
+  let mut rom = create_test_rom();
+  rom.battery = true;
+  Bus::new(rom).unwrap()
+}
+
+#[test]
+fn test_prg_ram_is_mapped_at_6000() {
+  let mut bus = Bus::new(create_test_rom()).unwrap();
+  bus.mem_write(0x6000, 0x42);
+  bus.mem_write(0x7FFF, 0x43);
+
+  assert_eq!(0x42, bus.mem_read(0x6000));
+  assert_eq!(0x43, bus.peek(0x7FFF));
+  assert_eq!(0x42, bus.prg_ram()[0]);
+}
+
+#[test]
+fn test_save_path_replaces_extension() {
+  assert_eq!(Path::new("games/zelda.sav"), battery::save_path(Path::new("games/zelda.nes")));
+}
+
+#[test]
+fn test_prg_ram_survives_store_and_load() {
+  let path = std::env::temp_dir().join(format!("nes_emulator_battery_{}.sav", std::process::id()));
+  let mut bus = battery_bus();
+  bus.mem_write(0x6123, 0x42);
+  battery::store(&bus, &path).unwrap();
+
+  let mut reloaded = battery_bus();
+  battery::load(&mut reloaded, &path).unwrap();
+  std::fs::remove_file(&path).unwrap();
+
+  assert_eq!(0x42, reloaded.mem_read(0x6123));
+}
+
+#[test]
+fn test_no_save_without_battery_or_file() {
+  let path = std::env::temp_dir().join(format!("nes_emulator_no_battery_{}.sav", std::process::id()));
+  let bus = Bus::new(create_test_rom()).unwrap();
+  battery::store(&bus, &path).unwrap();
+  assert!(!path.exists());
+
+  let mut bus = battery_bus();
+  battery::load(&mut bus, &path).unwrap();
+  assert_eq!(0, bus.peek(0x6000));
+}
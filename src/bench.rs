@@ -0,0 +1,67 @@
+use std::time::{Duration, Instant};
+use crate::asm::assemble;
+use crate::bus::Bus;
+use crate::cartridge::Rom;
+use crate::clock::Region;
+use crate::cpu::{CpuBus, MyCPU, StopCondition};
+use crate::error::EmuError;
+use crate::nes::Nes;
+
+// throughput of the emulator, e.g. to compare the interpreter before and after a change.
+// criterion is not available offline, so this is a `bench` command instead of cargo bench targets
+#[derive(Debug)]
+pub struct BenchResult {
+  pub name: &'static str,
+  pub unit: &'static str,
+  pub count: u64,
+  pub elapsed: Duration,
+}
+
+impl BenchResult {
+  pub fn per_second(&self) -> f64 {
+    self.count as f64 / self.elapsed.as_secs_f64().max(f64::EPSILON)
+  }
+
+  pub fn format(&self) -> String {
+    format!("{}: {} {} in {:.3}s ({:.0} {}/s)",
+            self.name, self.count, self.unit, self.elapsed.as_secs_f64(), self.per_second(), self.unit)
+  }
+}
+
+// a mix of loads, stores, arithmetic and branches in ram, the ppu of the bus still runs
+const CPU_LOOP: &str = "
+  loop: LDA $10
+        CLC
+        ADC #3
+        STA $10
+        LDX #8
+  inner: DEX
+        STA $0200,X
+        BNE inner
+        INC $11
+        JMP loop";
+
+pub fn cpu_instructions(rom: Rom, instructions: u64) -> Result<BenchResult, EmuError> {
+  let mut cpu = MyCPU::new(Bus::new(rom)?);
+  let start_address = cpu.bus.load_address();
+  cpu.load(assemble(CPU_LOOP, start_address).expect("benchmark program"));
+  cpu.program_counter = start_address;
+  cpu.stop_condition = StopCondition::Instructions(instructions);
+
+  let started = Instant::now();
+  cpu.run();
+  Ok(BenchResult { name: "cpu", unit: "instructions", count: instructions, elapsed: started.elapsed() })
+}
+
+pub fn nes_frames(rom: Rom, frames: usize) -> Result<BenchResult, EmuError> {
+  let mut nes = Nes::new(rom)?;
+
+  let started = Instant::now();
+  nes.run_for_frames(frames);
+  Ok(BenchResult { name: "nes", unit: "frames", count: nes.frame_count() as u64, elapsed: started.elapsed() })
+}
+
+// how many times faster than a real ntsc console
+pub fn realtime_factor(frames: &BenchResult) -> f64 {
+  frames.per_second() / Region::Ntsc.frames_per_second()
+}
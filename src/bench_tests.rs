@@ -0,0 +1,33 @@
+use std::time::Duration;
+use crate::bench::{BenchResult, cpu_instructions, nes_frames, realtime_factor};
+use crate::cartridge::Rom;
+use crate::cartridge_tests::test_rom_bytes_with_program;
+
+fn test_rom() -> Rom {
+  // loop: JMP loop
+  Rom::new(&test_rom_bytes_with_program(&[0x4C, 0x00, 0x80])).unwrap()
+}
+
+#[test]
+fn test_cpu_benchmark_runs_the_requested_instructions() {
+  let result = cpu_instructions(test_rom(), 1000).unwrap();
+
+  assert_eq!(1000, result.count);
+  assert!(result.per_second() > 0.0);
+  assert!(result.format().starts_with("cpu: 1000 instructions in "));
+}
+
+#[test]
+fn test_nes_benchmark_counts_frames() {
+  let result = nes_frames(test_rom(), 2).unwrap();
+
+  assert_eq!(2, result.count);
+  assert_eq!("frames", result.unit);
+}
+
+#[test]
+fn test_realtime_factor() {
+  let result = BenchResult { name: "nes", unit: "frames", count: 120, elapsed: Duration::from_secs(1) };
+
+  assert!((realtime_factor(&result) - 120.0 / 60.0988).abs() < 1e-9);
+}
@@ -0,0 +1,62 @@
+use crate::cartridge::Rom;
+use crate::error::EmuError;
+use crate::nes::Nes;
+
+// blargg's test roms report through prg ram: $6001-$6003 hold DE B0 61 once the
+// protocol is active, $6000 is the status ($80 running, $81 reset requested,
+// below $80 the result code, 0 = passed) and $6004 a zero terminated message.
+// https://github.com/christopherpow/nes-test-roms
+const STATUS: u16 = 0x6000;
+const SIGNATURE: [u8; 3] = [0xDE, 0xB0, 0x61];
+const MESSAGE: u16 = 0x6004;
+const RUNNING: u8 = 0x80;
+const RESET_REQUESTED: u8 = 0x81;
+// the rom wants the reset at least 100ms after the request
+const RESET_DELAY_FRAMES: usize = 7;
+
+#[derive(Debug, PartialEq)]
+pub enum BlarggResult {
+  Passed(String),
+  Failed(u8, String),
+  Timeout(String),
+}
+
+fn protocol_active(nes: &Nes) -> bool {
+  (0..3).all(|i| nes.cpu.bus.peek(STATUS + 1 + i) == SIGNATURE[i as usize])
+}
+
+fn message(nes: &Nes) -> String {
+  let mut text = String::new();
+  for addr in MESSAGE..0x8000 {
+    match nes.cpu.bus.peek(addr) {
+      0 => break,
+      byte => text.push(byte as char),
+    }
+  }
+  text.trim().to_string()
+}
+
+// runs the rom until it reports a result, checking once per frame
+pub fn run_test_rom(rom: Rom, max_frames: usize) -> Result<BlarggResult, EmuError> {
+  let mut nes = Nes::new(rom)?;
+  let mut reset_at = None;
+  for _ in 0..max_frames {
+    nes.run_for_frames(1);
+    if !protocol_active(&nes) {
+      continue;
+    }
+    match nes.cpu.bus.peek(STATUS) {
+      RUNNING => {}
+      RESET_REQUESTED => {
+        let frame = *reset_at.get_or_insert(nes.frame_count() + RESET_DELAY_FRAMES);
+        if nes.frame_count() >= frame {
+          nes.cpu.reset();
+          reset_at = None;
+        }
+      }
+      0 => return Ok(BlarggResult::Passed(message(&nes))),
+      code => return Ok(BlarggResult::Failed(code, message(&nes))),
+    }
+  }
+  Ok(BlarggResult::Timeout(message(&nes)))
+}
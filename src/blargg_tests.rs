@@ -0,0 +1,64 @@
+use std::fs;
+use std::path::Path;
+use crate::blargg::{run_test_rom, BlarggResult};
+use crate::cartridge::Rom;
+use crate::cartridge_tests::create_test_rom_with_program;
+
+// writes the signature and "ok", reports running for a while and then the given result code
+fn reporting_program(code: u8) -> Vec<u8> {
+  vec![
+    0xA9, 0xDE, 0x8D, 0x01, 0x60, // signature
+    0xA9, 0xB0, 0x8D, 0x02, 0x60,
+    0xA9, 0x61, 0x8D, 0x03, 0x60,
+    0xA9, 0x6F, 0x8D, 0x04, 0x60, // "ok"
+    0xA9, 0x6B, 0x8D, 0x05, 0x60,
+    0xA9, 0x80, 0x8D, 0x00, 0x60, // running
+    0xE8, 0xD0, 0xFD,             // INX, BNE -3
+    0xC8, 0xD0, 0xFA,             // INY, BNE -6
+    0xA9, code, 0x8D, 0x00, 0x60, // result
+    0x4C, 0x29, 0x80,             // JMP self
+  ]
+}
+
+#[test]
+fn test_passing_rom() {
+  let rom = create_test_rom_with_program(&reporting_program(0));
+  assert_eq!(BlarggResult::Passed("ok".to_string()), run_test_rom(rom, 60).unwrap());
+}
+
+#[test]
+fn test_failing_rom_reports_code() {
+  let rom = create_test_rom_with_program(&reporting_program(3));
+  assert_eq!(BlarggResult::Failed(3, "ok".to_string()), run_test_rom(rom, 60).unwrap());
+}
+
+#[test]
+fn test_timeout() {
+  let rom = create_test_rom_with_program(&reporting_program(0));
+  assert_eq!(BlarggResult::Timeout("ok".to_string()), run_test_rom(rom, 1).unwrap());
+}
+
+// every rom in test_roms/blargg next to Cargo.toml has to pass, e.g. instr_test-v5 singles,
+// ppu_vbl_nmi or cpu_timing_test from https://github.com/christopherpow/nes-test-roms.
+// the roms aren't redistributable, so this only runs with --features test-roms
+#[test]
+#[cfg_attr(not(feature = "test-roms"), ignore = "needs test_roms/blargg, run with --features test-roms")]
+fn test_blargg_roms() {
+  let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("test_roms").join("blargg");
+  let entries = fs::read_dir(&dir).unwrap_or_else(|e| panic!("{}: {}", dir.display(), e));
+
+  let mut failures = Vec::new();
+  let mut count = 0;
+  for path in entries.map(|e| e.unwrap().path()).filter(|p| p.extension().is_some_and(|e| e == "nes")) {
+    count += 1;
+    let result = Rom::new(&fs::read(&path).unwrap())
+      .map_err(|e| e.to_string())
+      .and_then(|rom| run_test_rom(rom, 60 * 60).map_err(|e| e.to_string()));
+    match result {
+      Ok(BlarggResult::Passed(_)) => {}
+      other => failures.push(format!("{}: {:?}", path.display(), other)),
+    }
+  }
+  assert!(count > 0, "no .nes files in {}", dir.display());
+  assert!(failures.is_empty(), "failing test roms:\n{}", failures.join("\n"));
+}
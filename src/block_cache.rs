@@ -0,0 +1,103 @@
+use std::collections::HashMap;
+use crate::cpu::MyCPU;
+use crate::decode_cache::DecodedInstruction;
+
+const MAX_BLOCK_LEN: usize = 64;
+
+// instructions after which the next pc isn't known at decode time
+fn ends_block(mnemonic: &str) -> bool {
+  matches!(mnemonic,
+    "BCC" | "BCS" | "BEQ" | "BMI" | "BNE" | "BPL" | "BVC" | "BVS" |
+    "JMP" | "JSR" | "RTS" | "RTI" | "BRK")
+}
+
+// experimental backend: decodes basic blocks of prg rom once and replays them without
+// fetch/decode and without any of the debugging hooks (history, trace, breakpoints, ...).
+// code outside of prg rom (e.g. in ram) falls back to the normal interpreter.
+// blocks are keyed by their rom offset like the decode cache, so they survive bank switches
+#[derive(Default)]
+pub struct BlockCache {
+  blocks: HashMap<usize, Vec<DecodedInstruction>>,
+  pub hits: u64,
+  pub misses: u64,
+}
+
+impl BlockCache {
+  pub fn new() -> Self {
+    BlockCache::default()
+  }
+
+  // drops all blocks, bank switches don't need it
+  pub fn invalidate(&mut self) {
+    self.blocks.clear();
+  }
+
+  pub fn len(&self) -> usize {
+    self.blocks.len()
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.blocks.is_empty()
+  }
+
+  pub fn run(&mut self, cpu: &mut MyCPU) {
+    while self.run_block(cpu) {}
+  }
+
+  // executes one basic block, false once the cpu stopped (BRK)
+  pub fn run_block(&mut self, cpu: &mut MyCPU) -> bool {
+    let start = cpu.program_counter;
+    // the interpreter services pending interrupts
+    let offset = match cpu.bus.prg_rom_offset(start) {
+      Some(offset) if !cpu.interrupt_pending() => offset,
+      _ => return cpu.step().is_some(),
+    };
+
+    if !self.blocks.contains_key(&offset) {
+      self.misses += 1;
+      match Self::decode_block(cpu, start, offset) {
+        Some(block) => self.blocks.insert(offset, block),
+        None => return cpu.step().is_some(), // let the interpreter report unknown opcodes
+      };
+    } else {
+      self.hits += 1;
+    }
+
+    for instruction in &self.blocks[&offset] {
+      // an instruction of the block switched the bank the rest was decoded from
+      let expected = offset + cpu.program_counter.wrapping_sub(start) as usize;
+      if cpu.bus.prg_rom_offset(cpu.program_counter) != Some(expected) {
+        return true;
+      }
+      match instruction.handler {
+        Some(handler) => cpu.execute_decoded(instruction.opcode, handler),
+        // BRK, the interpreter knows whether to stop or to interrupt
+        None => return cpu.step().is_some(),
+      }
+    }
+    true
+  }
+
+  // stops where the mapping of the rom isn't contiguous anymore, e.g. at the end of a bank
+  fn decode_block(cpu: &MyCPU, start: u16, offset: usize) -> Option<Vec<DecodedInstruction>> {
+    let mut block = Vec::new();
+    let mut addr = start;
+    loop {
+      let decoded = DecodedInstruction::decode(cpu.bus.peek(addr), cpu.handlers())?;
+      if decoded.handler.is_none() && decoded.opcode.code != 0x00 {
+        return None;
+      }
+      let opcode = decoded.opcode;
+      block.push(decoded);
+
+      let next = addr as u32 + opcode.len as u32;
+      if ends_block(opcode.mnemonic) || block.len() == MAX_BLOCK_LEN || next > 0xFFFF {
+        return Some(block);
+      }
+      addr = next as u16;
+      if cpu.bus.prg_rom_offset(addr) != Some(offset + (addr - start) as usize) {
+        return Some(block);
+      }
+    }
+  }
+}
@@ -0,0 +1,103 @@
+use crate::bus::Bus;
+use crate::block_cache::BlockCache;
+use crate::cartridge::{Mirroring, Rom};
+use crate::cartridge_tests::create_test_rom_with_program;
+use crate::cpu::{MyCPU, MyMem};
+
+fn init_cpu(program: &[u8]) -> MyCPU {
+  let mut cpu = MyCPU::new(Bus::new(create_test_rom_with_program(program)).unwrap());
+  cpu.reset();
+  cpu
+}
+
+// AxROM with one program per 32KB bank at $8000, all reset vectors point there
+fn init_axrom_cpu(banks: &[&[u8]]) -> MyCPU {
+  let mut prg_rom = Vec::new();
+  for program in banks {
+    let mut bank = vec![0; 0x8000];
+    bank[..program.len()].copy_from_slice(program);
+    bank[0x7FFC..0x7FFE].copy_from_slice(&[0x00, 0x80]);
+    prg_rom.extend(bank);
+  }
+  let rom = Rom { prg_rom, chr_rom: vec![0; 0x2000], chr_ram: false, mapper: 7, screen_mirroring: Mirroring::VERTICAL, battery: false };
+  let mut cpu = MyCPU::new(Bus::new(rom).unwrap());
+  cpu.reset();
+  cpu
+}
+
+// $8000: LDX #$05, loop: INC $10, DEX, BNE loop, BRK
+const LOOP: [u8; 8] = [0xA2, 0x05, 0xE6, 0x10, 0xCA, 0xD0, 0xFB, 0x00];
+
+#[test]
+fn test_cached_run_matches_interpreter() {
+  let mut interpreted = init_cpu(&LOOP);
+  interpreted.run();
+
+  let mut cached = init_cpu(&LOOP);
+  let mut cache = BlockCache::new();
+  cache.run(&mut cached);
+
+  assert_eq!(interpreted.snapshot(), cached.snapshot());
+  assert_eq!(5, cached.mem_read(0x10));
+  assert_eq!(0x8008, cached.program_counter);
+}
+
+#[test]
+fn test_blocks_are_reused() {
+  let mut cpu = init_cpu(&LOOP);
+  let mut cache = BlockCache::new();
+
+  cache.run(&mut cpu);
+
+  // $8000 (LDX..BNE), $8002 (loop body), $8007 (BRK)
+  assert_eq!(3, cache.len());
+  assert_eq!(3, cache.misses);
+  assert_eq!(3, cache.hits);
+
+  cache.invalidate();
+  assert!(cache.is_empty());
+}
+
+#[test]
+fn test_code_in_ram_uses_interpreter() {
+  let mut cpu = init_cpu(&[]);
+  cpu.load(vec![0xE8, 0xE8]);
+  cpu.program_counter = 0x0600;
+  let mut cache = BlockCache::new();
+
+  cache.run(&mut cpu);
+
+  assert_eq!(2, cpu.register_x);
+  assert!(cache.is_empty());
+}
+
+#[test]
+fn test_bank_switch_under_a_cached_block() {
+  // loop: LDX #$11 / #$22, JMP loop
+  let mut cpu = init_axrom_cpu(&[&[0xA2, 0x11, 0x4C, 0x00, 0x80], &[0xA2, 0x22, 0x4C, 0x00, 0x80]]);
+  let mut cache = BlockCache::new();
+
+  cache.run_block(&mut cpu);
+  assert_eq!(0x11, cpu.register_x);
+
+  cpu.mem_write(0x8000, 1);
+  cache.run_block(&mut cpu);
+
+  assert_eq!(0x22, cpu.register_x);
+  assert_eq!(2, cache.len());
+}
+
+#[test]
+fn test_bank_switch_inside_a_block() {
+  // LDA #$01, STA $8000 (selects bank 1), LDX #$11 / #$22, JMP $8000
+  let mut cpu = init_axrom_cpu(&[&[0xA9, 0x01, 0x8D, 0x00, 0x80, 0xA2, 0x11, 0x4C, 0x00, 0x80],
+                                  &[0xA9, 0x01, 0x8D, 0x00, 0x80, 0xA2, 0x22, 0x4C, 0x00, 0x80]]);
+  let mut cache = BlockCache::new();
+
+  // the rest of the block was decoded from bank 0
+  cache.run_block(&mut cpu);
+  assert_eq!((0x8005, 0), (cpu.program_counter, cpu.register_x));
+
+  cache.run_block(&mut cpu);
+  assert_eq!(0x22, cpu.register_x);
+}
@@ -0,0 +1,121 @@
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Breakpoint {
+  Address(u16),
+  Nmi,
+  Irq,
+  Reset,
+  VblankStart,
+  VblankEnd,
+  Sprite0Hit,
+  ScanlineDot { scanline: u16, dot: u16 },
+}
+
+// things happening in the machine, reported by the components
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DebugEvent {
+  Nmi,
+  Irq,
+  Reset,
+  VblankStart,
+  VblankEnd,
+  Sprite0Hit,
+  // ppu moved from one position to the next (both inclusive)
+  PpuAdvanced { from: (u16, u16), to: (u16, u16) },
+}
+
+impl Breakpoint {
+  fn matches(&self, event: &DebugEvent) -> bool {
+    match (self, event) {
+      (Breakpoint::Nmi, DebugEvent::Nmi) => true,
+      (Breakpoint::Irq, DebugEvent::Irq) => true,
+      (Breakpoint::Reset, DebugEvent::Reset) => true,
+      (Breakpoint::VblankStart, DebugEvent::VblankStart) => true,
+      (Breakpoint::VblankEnd, DebugEvent::VblankEnd) => true,
+      (Breakpoint::Sprite0Hit, DebugEvent::Sprite0Hit) => true,
+      (Breakpoint::ScanlineDot { scanline, dot }, DebugEvent::PpuAdvanced { from, to }) => {
+        let position = (*scanline, *dot);
+        if from <= to {
+          *from <= position && position <= *to
+        } else {
+          // wrapped around into the next frame
+          *from <= position || position <= *to
+        }
+      }
+      _ => false,
+    }
+  }
+}
+
+// events mark a breakpoint as pending, the cpu stops before its next instruction
+#[derive(Default)]
+pub struct Breakpoints {
+  breakpoints: Vec<Breakpoint>,
+  pending: Option<Breakpoint>,
+  hit: Option<Breakpoint>,
+  resume_address: Option<u16>,
+}
+
+impl Breakpoints {
+  pub fn new() -> Self {
+    Breakpoints::default()
+  }
+
+  pub fn add(&mut self, breakpoint: Breakpoint) {
+    if !self.breakpoints.contains(&breakpoint) {
+      self.breakpoints.push(breakpoint);
+    }
+  }
+
+  pub fn remove(&mut self, breakpoint: &Breakpoint) -> bool {
+    let len = self.breakpoints.len();
+    self.breakpoints.retain(|b| b != breakpoint);
+    len != self.breakpoints.len()
+  }
+
+  pub fn list(&self) -> &[Breakpoint] {
+    &self.breakpoints
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.breakpoints.is_empty()
+  }
+
+  // the breakpoint which stopped the last step, None if it stopped for another reason
+  pub fn hit(&self) -> Option<Breakpoint> {
+    self.hit
+  }
+
+  pub fn notify(&mut self, event: DebugEvent) {
+    if self.pending.is_none() {
+      self.pending = self.breakpoints.iter().find(|b| b.matches(&event)).copied();
+    }
+  }
+
+  // the next instruction runs even if there is a breakpoint at program_counter,
+  // e.g. after the debugger moved the cpu back onto one
+  pub fn resume_at(&mut self, program_counter: u16) {
+    self.resume_address = Some(program_counter);
+  }
+
+  // checked by the cpu before each instruction - true means stop
+  pub fn should_break(&mut self, program_counter: u16) -> bool {
+    self.hit = None;
+    if let Some(breakpoint) = self.pending.take() {
+      self.hit = Some(breakpoint);
+      return true;
+    }
+
+    // continue after an address breakpoint without hitting it again
+    if self.resume_address.take() == Some(program_counter) {
+      return false;
+    }
+
+    let breakpoint = Breakpoint::Address(program_counter);
+    if self.breakpoints.contains(&breakpoint) {
+      self.hit = Some(breakpoint);
+      self.resume_address = Some(program_counter);
+      return true;
+    }
+    false
+  }
+}
@@ -0,0 +1,103 @@
+use crate::bus::Bus;
+use crate::breakpoints::{Breakpoint, Breakpoints, DebugEvent};
+use crate::cartridge_tests::create_test_rom;
+use crate::cpu::{MyCPU, MyMem, StopCondition};
+
+fn init_cpu() -> MyCPU {
+  let mut cpu = MyCPU::new(Bus::new(create_test_rom()).unwrap());
+  cpu.program_counter = 0x0600;
+  cpu
+}
+
+#[test]
+fn test_event_breakpoints_become_pending() {
+  let mut breakpoints = Breakpoints::new();
+  breakpoints.add(Breakpoint::Nmi);
+
+  breakpoints.notify(DebugEvent::Irq);
+  assert!(!breakpoints.should_break(0x8000));
+
+  breakpoints.notify(DebugEvent::Nmi);
+  assert!(breakpoints.should_break(0x8000));
+  assert_eq!(Some(Breakpoint::Nmi), breakpoints.hit());
+  assert!(!breakpoints.should_break(0x8000));
+}
+
+#[test]
+fn test_scanline_dot_breakpoint_matches_passed_position() {
+  let mut breakpoints = Breakpoints::new();
+  breakpoints.add(Breakpoint::ScanlineDot { scanline: 30, dot: 100 });
+
+  breakpoints.notify(DebugEvent::PpuAdvanced { from: (30, 10), to: (30, 99) });
+  assert!(!breakpoints.should_break(0x8000));
+
+  breakpoints.notify(DebugEvent::PpuAdvanced { from: (30, 99), to: (30, 120) });
+  assert!(breakpoints.should_break(0x8000));
+}
+
+#[test]
+fn test_scanline_dot_breakpoint_across_frame_wrap() {
+  let mut breakpoints = Breakpoints::new();
+  breakpoints.add(Breakpoint::ScanlineDot { scanline: 0, dot: 5 });
+
+  breakpoints.notify(DebugEvent::PpuAdvanced { from: (261, 330), to: (0, 20) });
+
+  assert!(breakpoints.should_break(0x8000));
+}
+
+#[test]
+fn test_cpu_stops_at_address_and_resumes() {
+  let mut cpu = init_cpu();
+  cpu.breakpoints.add(Breakpoint::Address(0x0602));
+
+  // INX, INX, INX
+  cpu.load_and_run(vec![0xE8, 0xE8, 0xE8]);
+  assert_eq!(0x0602, cpu.program_counter);
+  assert_eq!(2, cpu.register_x);
+  assert_eq!(Some(Breakpoint::Address(0x0602)), cpu.breakpoints.hit());
+
+  cpu.run();
+  assert_eq!(3, cpu.register_x);
+}
+
+#[test]
+fn test_cpu_stops_after_reset() {
+  let mut cpu = init_cpu();
+  cpu.breakpoints.add(Breakpoint::Reset);
+  cpu.mem_write(0x0600, 0xE8);
+
+  cpu.reset();
+  cpu.program_counter = 0x0600;
+  cpu.run();
+
+  assert_eq!(0, cpu.register_x);
+  assert_eq!(Some(Breakpoint::Reset), cpu.breakpoints.hit());
+}
+
+#[test]
+fn test_cpu_stops_at_vblank_start() {
+  let mut cpu = init_cpu();
+  cpu.stop_condition = StopCondition::Never;
+  cpu.breakpoints.add(Breakpoint::VblankStart);
+  // loop: JMP loop
+  cpu.load(vec![0x4C, 0x00, 0x06]);
+
+  cpu.run();
+
+  assert_eq!(Some(Breakpoint::VblankStart), cpu.breakpoints.hit());
+  assert_eq!(241, cpu.bus.ppu.scanline);
+  assert!(cpu.bus.ppu.dot() < 10);
+}
+
+#[test]
+fn test_cpu_stops_at_scanline_and_dot() {
+  let mut cpu = init_cpu();
+  cpu.stop_condition = StopCondition::Never;
+  cpu.breakpoints.add(Breakpoint::ScanlineDot { scanline: 100, dot: 200 });
+  cpu.load(vec![0x4C, 0x00, 0x06]);
+
+  cpu.run();
+
+  assert_eq!(100, cpu.bus.ppu.scanline);
+  assert!((200..210).contains(&cpu.bus.ppu.dot()));
+}
@@ -1,5 +1,23 @@
+use std::cell::RefCell;
+use crate::apu::Apu;
 use crate::cartridge::Rom;
-use crate::MyMem;
+use crate::cheats::Cheats;
+use crate::clock::Clock;
+use crate::error::EmuError;
+use crate::event_log::{EventLog, is_logged_register};
+use crate::joypad::Joypad;
+use crate::mapper::{self, SharedMapper};
+use crate::power_on::PowerOnState;
+use crate::ppu::{NesPPU, StatusRegister, VBLANK_SCANLINE};
+use crate::simple_device::{SimpleDevice, LAST_KEY_ADDR, RANDOM_ADDR};
+use crate::savestate::{StateReader, StateWriter, Stateful};
+#[cfg(feature = "serde")]
+use crate::savestate::copy_exact;
+#[cfg(feature = "serde")]
+use crate::{apu::ApuState, mapper::MapperState, ppu::PpuState};
+use crate::cpu::CpuBus;
+use crate::breakpoints::DebugEvent;
+use crate::cpu::MyMem;
 
 //  _______________ $10000  _______________
 // | PRG-ROM       |       |               |
@@ -33,67 +51,478 @@ const RAM: u16 = 0x0000;
 const RAM_MIRRORS_END: u16 = 0x1FFF;
 const PPU_REGISTERS: u16 = 0x2000;
 const PPU_REGISTERS_MIRRORS_END: u16 = 0x3FFF;
+const APU_REGISTERS: u16 = 0x4000;
+const APU_REGISTERS_END: u16 = 0x4013;
+const OAM_DMA: u16 = 0x4014;
+const APU_STATUS: u16 = 0x4015;
+const JOYPAD1: u16 = 0x4016;
+const JOYPAD2: u16 = 0x4017;
+const APU_FRAME_COUNTER: u16 = 0x4017; // write only, reads go to the second controller
+const PRG_RAM: u16 = 0x6000;
+const PRG_RAM_END: u16 = 0x7FFF;
 const ROM: u16 = 0x8000;
 const ROM_END: u16 = 0xFFFF;
 
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AccessKind {
+  Read,
+  Write,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BusAccess {
+  pub kind: AccessKind,
+  pub addr: u16,
+  pub value: u8,
+}
+
+bitflags! {
+  // devices pulling the shared irq line low
+  #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+  pub struct IrqSource: u8 {
+    const APU_FRAME_COUNTER = 0b0000_0001;
+    const APU_DMC = 0b0000_0010;
+    const MAPPER = 0b0000_0100;
+  }
+}
+
 pub struct Bus {
   cpu_vram: [u8; 2048],
-  rom: Rom,
+  mapper: SharedMapper,
+  prg_ram: [u8; 0x2000],
+  battery: bool,
+  pub ppu: NesPPU,
+  pub apu: Apu,
+  pub joypad1: Joypad,
+  pub joypad2: Joypad,
+  pub cheats: Cheats,
+  irq_line: IrqSource,
+  // how far the cpu, ppu and apu have run, the parity of cpu cycles decides the length of a dma
+  clock: Clock,
+  // cpu cycles the last oam dma still has to halt the cpu
+  dma_stall: u16,
+  // set once the ppu entered vblank, the picture is complete then
+  frame_ready: bool,
+  // i/o of the easy6502 demo programs, off for real games
+  simple_device: Option<SimpleDevice>,
+  // reads only borrow the bus, so the log needs interior mutability
+  access_log: RefCell<Option<Vec<BusAccess>>>,
+  // ppu and apu register writes, off unless a debugger wants them
+  pub event_log: Option<EventLog>,
+  instruction_start: u16,
 }
 
 impl Bus {
-  pub fn new(rom: Rom) -> Self{
-    Bus {
-      cpu_vram: [0; 2048],
-      rom,
+  pub fn new(rom: Rom) -> Result<Self, EmuError> {
+    Bus::with_power_on(rom, &PowerOnState::default())
+  }
+
+  pub fn with_power_on(rom: Rom, power_on: &PowerOnState) -> Result<Self, EmuError> {
+    let mut cpu_vram = [0; 2048];
+    power_on.cpu_ram.fill(&mut cpu_vram);
+    let battery = rom.battery;
+    let mapper = mapper::for_rom(rom)?;
+    let ppu = NesPPU::with_mapper(mapper.clone(), power_on);
+    Ok(Bus {
+      cpu_vram,
+      mapper,
+      prg_ram: [0; 0x2000],
+      battery,
+      ppu,
+      apu: Apu::new(),
+      joypad1: Joypad::new(),
+      joypad2: Joypad::new(),
+      cheats: Cheats::new(),
+      irq_line: IrqSource::empty(),
+      clock: Clock::new(),
+      dma_stall: 0,
+      frame_ready: false,
+      simple_device: None,
+      access_log: RefCell::new(None),
+      event_log: None,
+      instruction_start: 0,
+    })
+  }
+
+  pub fn ram(&self) -> &[u8] {
+    &self.cpu_vram
+  }
+
+  pub fn load_ram(&mut self, ram: &[u8]) {
+    self.cpu_vram.copy_from_slice(ram);
+  }
+
+  pub fn prg_ram(&self) -> &[u8] {
+    &self.prg_ram
+  }
+
+  pub fn load_prg_ram(&mut self, ram: &[u8]) {
+    let len = ram.len().min(self.prg_ram.len());
+    self.prg_ram[..len].copy_from_slice(&ram[..len]);
+  }
+
+  // the cartridge keeps prg ram alive with a battery, it should be saved
+  pub fn has_battery(&self) -> bool {
+    self.battery
+  }
+
+  // random numbers at $FE for programs written for the easy6502 simulator
+  pub fn enable_simple_device(&mut self, seed: u64) {
+    self.simple_device = Some(SimpleDevice::new(seed));
+  }
+
+  // the easy6502 simulator stores the last key in ram, the program clears it
+  pub fn press_key(&mut self, key: u8) {
+    self.cpu_vram[LAST_KEY_ADDR as usize] = key;
+  }
+
+  pub fn record_accesses(&mut self, enabled: bool) {
+    *self.access_log.get_mut() = if enabled { Some(Vec::new()) } else { None };
+  }
+
+  // returns the accesses since the last call, recording continues
+  pub fn take_accesses(&mut self) -> Vec<BusAccess> {
+    self.access_log.get_mut().as_mut().map(std::mem::take).unwrap_or_default()
+  }
+
+  pub fn enable_event_log(&mut self, max_frames: usize) {
+    self.event_log = Some(EventLog::new(max_frames));
+    self.ppu.signals = Some(Vec::new());
+  }
+
+  fn log_access(&self, kind: AccessKind, addr: u16, value: u8) {
+    if let Some(log) = self.access_log.borrow_mut().as_mut() {
+      log.push(BusAccess { kind, addr, value });
+    }
+  }
+
+  // debugger access: never triggers side effects of i/o registers
+  pub fn peek(&self, addr: u16) -> u8 {
+    match addr {
+      RAM ..= RAM_MIRRORS_END => self.cpu_vram[(addr & 0b00000111_11111111) as usize],
+      PRG_RAM ..= PRG_RAM_END => self.prg_ram[(addr - PRG_RAM) as usize],
+      ROM ..= ROM_END => self.read_prg_rom(addr),
+      _ => 0,
+    }
+  }
+
+  // debugger access: false if the address can't be modified
+  pub fn poke(&mut self, addr: u16, data: u8) -> bool {
+    match addr {
+      RAM ..= RAM_MIRRORS_END => {
+        self.cpu_vram[(addr & 0b00000111_11111111) as usize] = data;
+        true
+      }
+      PRG_RAM ..= PRG_RAM_END => {
+        self.prg_ram[(addr - PRG_RAM) as usize] = data;
+        true
+      }
+      _ => false,
+    }
+  }
+
+  // position inside the cartridge prg rom the address is currently mapped to,
+  // None for addresses patched by a cheat, their value doesn't come from the rom alone
+  pub fn prg_rom_offset(&self, addr: u16) -> Option<usize> {
+    if self.cheats.is_patched(addr) {
+      return None;
+    }
+    self.mapper.borrow().prg_rom_offset(addr)
+  }
+
+  // cpu cycles an instruction (or dma, interrupt) took, the other components follow
+  pub fn tick(&mut self, cycles: u16) {
+    self.clock.advance_cpu(cycles as u64);
+    self.catch_up();
+  }
+
+  pub fn clock(&self) -> &Clock {
+    &self.clock
+  }
+
+  // runs the ppu and apu until they reached the cpu
+  pub fn catch_up(&mut self) {
+    let vblank = self.ppu.status.contains(StatusRegister::VBLANK_STARTED);
+    let dots = self.clock.ppu_behind();
+    self.ppu.tick(dots as u16);
+    self.clock.ppu_ran(dots);
+    let cycles = self.clock.apu_behind();
+    self.apu.tick(cycles as u16);
+    self.clock.apu_ran(cycles);
+    self.sync_apu_irq();
+    let vblank_started = !vblank && self.ppu.status.contains(StatusRegister::VBLANK_STARTED);
+    if vblank_started {
+      self.frame_ready = true;
+    }
+    if let Some(log) = self.event_log.as_mut() {
+      let signals = self.ppu.signals.as_mut().map(std::mem::take).unwrap_or_default();
+      // the ones from vblank on belong to the next frame
+      let (next, current): (Vec<_>, Vec<_>) = signals.into_iter()
+        .partition(|&(_, scanline, _)| vblank_started && scanline >= VBLANK_SCANLINE);
+      for (kind, scanline, dot) in current {
+        log.record_event(scanline, dot, kind);
+      }
+      if vblank_started {
+        log.end_frame();
+      }
+      for (kind, scanline, dot) in next {
+        log.record_event(scanline, dot, kind);
+      }
+    }
+  }
+
+  // true once per frame, when a new picture can be rendered
+  pub fn take_frame_ready(&mut self) -> bool {
+    std::mem::take(&mut self.frame_ready)
+  }
+
+  pub fn take_dma_stall(&mut self) -> u16 {
+    std::mem::take(&mut self.dma_stall)
+  }
+
+  // https://wiki.nesdev.org/w/index.php/PPU_registers#OAMDMA
+  fn oam_dma(&mut self, page: u8) {
+    let start = (page as u16) << 8;
+    let mut data = [0; 256];
+    for (i, value) in data.iter_mut().enumerate() {
+      *value = self.mem_read(start + i as u16);
     }
+    self.ppu.write_oam_dma(&data);
+    // one extra alignment cycle when starting on an odd cycle
+    self.dma_stall = 513 + (self.clock.cpu_cycles() % 2) as u16;
+  }
+
+  pub fn poll_nmi_status(&mut self) -> Option<u8> {
+    self.ppu.poll_nmi_interrupt()
+  }
+
+  pub fn set_irq(&mut self, source: IrqSource, active: bool) {
+    self.irq_line.set(source, active);
+  }
+
+  pub fn irq_pending(&self) -> bool {
+    !self.irq_line.is_empty() || self.mapper.borrow().irq()
+  }
+
+  fn sync_apu_irq(&mut self) {
+    let frame_irq = self.apu.frame_irq();
+    self.set_irq(IrqSource::APU_FRAME_COUNTER, frame_irq);
+  }
+
+  fn read_prg_rom(&self, addr: u16) -> u8 {
+    let value = self.mapper.borrow().prg_read(addr);
+    self.cheats.apply(addr, value)
   }
+}
+
+// everything behind the cpu: ram, devices and cartridge registers
+impl Stateful for Bus {
+  fn save_state(&self, w: &mut StateWriter) {
+    w.write_bytes(&self.cpu_vram);
+    w.write_bytes(&self.prg_ram);
+    w.write_u8(self.irq_line.bits());
+    w.write_u64(self.clock.cpu_cycles());
+    w.write_u16(self.dma_stall);
+    w.write_bool(self.frame_ready);
+    self.ppu.save_state(w);
+    self.apu.save_state(w);
+    self.joypad1.save_state(w);
+    self.joypad2.save_state(w);
+    self.mapper.borrow().save_state(w);
+  }
+
+  fn load_state(&mut self, r: &mut StateReader) -> Result<(), String> {
+    r.read_into(&mut self.cpu_vram)?;
+    r.read_into(&mut self.prg_ram)?;
+    self.irq_line = IrqSource::from_bits_truncate(r.read_u8()?);
+    self.clock.sync_to_cpu_cycles(r.read_u64()?);
+    self.dma_stall = r.read_u16()?;
+    self.frame_ready = r.read_bool()?;
+    self.ppu.load_state(r)?;
+    self.apu.load_state(r)?;
+    self.joypad1.load_state(r)?;
+    self.joypad2.load_state(r)?;
+    self.mapper.borrow_mut().load_state(r)
+  }
+}
+
+// what the Stateful impl saves, as a struct for serde
+#[cfg(feature = "serde")]
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct BusState {
+  pub cpu_vram: Vec<u8>,
+  pub prg_ram: Vec<u8>,
+  pub irq_line: IrqSource,
+  pub cpu_cycles: u64,
+  pub dma_stall: u16,
+  pub frame_ready: bool,
+  pub ppu: PpuState,
+  pub apu: ApuState,
+  pub joypad1: Joypad,
+  pub joypad2: Joypad,
+  pub mapper: MapperState,
+}
 
-  fn read_prg_rom(&self, mut addr: u16) -> u8 {
-    addr -= 0x8000;
-    // mirror if needed
-    if self.rom.prg_rom.len() == 0x4000 && addr >= 0x4000 {
-      addr = addr % 0x4000;
+#[cfg(feature = "serde")]
+impl Bus {
+  pub fn state(&self) -> BusState {
+    BusState {
+      cpu_vram: self.cpu_vram.to_vec(),
+      prg_ram: self.prg_ram.to_vec(),
+      irq_line: self.irq_line,
+      cpu_cycles: self.clock.cpu_cycles(),
+      dma_stall: self.dma_stall,
+      frame_ready: self.frame_ready,
+      ppu: self.ppu.state(),
+      apu: self.apu.state(),
+      joypad1: self.joypad1.clone(),
+      joypad2: self.joypad2.clone(),
+      mapper: self.mapper.borrow().state(),
     }
-    self.rom.prg_rom[addr as usize]
+  }
+
+  // as load_state, a failed set can leave the bus half restored
+  pub fn set_state(&mut self, state: BusState) -> Result<(), String> {
+    copy_exact(&mut self.cpu_vram, &state.cpu_vram)?;
+    copy_exact(&mut self.prg_ram, &state.prg_ram)?;
+    self.irq_line = state.irq_line;
+    self.clock.sync_to_cpu_cycles(state.cpu_cycles);
+    self.dma_stall = state.dma_stall;
+    self.frame_ready = state.frame_ready;
+    self.ppu.set_state(state.ppu)?;
+    self.apu.set_state(state.apu);
+    self.joypad1 = state.joypad1;
+    self.joypad2 = state.joypad2;
+    self.mapper.borrow_mut().set_state(state.mapper)
+  }
+}
+
+impl CpuBus for Bus {
+  fn peek(&self, addr: u16) -> u8 {
+    Bus::peek(self, addr)
+  }
+
+  fn poke(&mut self, addr: u16, data: u8) -> bool {
+    Bus::poke(self, addr, data)
+  }
+
+  fn tick(&mut self, cycles: u16) {
+    Bus::tick(self, cycles)
+  }
+
+  fn poll_nmi_status(&mut self) -> Option<u8> {
+    Bus::poll_nmi_status(self)
+  }
+
+  fn nmi_pending(&self) -> bool {
+    self.ppu.nmi_pending()
+  }
+
+  fn irq_pending(&self) -> bool {
+    Bus::irq_pending(self)
+  }
+
+  fn take_dma_stall(&mut self) -> u16 {
+    Bus::take_dma_stall(self)
+  }
+
+  fn prg_rom_offset(&self, addr: u16) -> Option<usize> {
+    Bus::prg_rom_offset(self, addr)
+  }
+
+  fn begin_instruction(&mut self, program_counter: u16) {
+    self.instruction_start = program_counter;
+  }
+
+  fn record_accesses(&mut self, enabled: bool) {
+    Bus::record_accesses(self, enabled)
+  }
+
+  fn take_accesses(&mut self) -> Vec<BusAccess> {
+    Bus::take_accesses(self)
+  }
+
+  fn drain_events<F: FnMut(DebugEvent)>(&mut self, callback: F) {
+    self.ppu.drain_events(callback)
   }
 }
 
 impl MyMem for Bus {
-  fn mem_read(&self, addr: u16) -> u8 {
-    match addr {
+  fn mem_read(&mut self, addr: u16) -> u8 {
+    let value = match addr {
       RAM ..= RAM_MIRRORS_END => {
         let mirror_down_addr = addr & 0b00000111_11111111;
-        self.cpu_vram[mirror_down_addr as usize]
+        match self.simple_device.as_mut() {
+          Some(device) if mirror_down_addr == RANDOM_ADDR => device.random(),
+          _ => self.cpu_vram[mirror_down_addr as usize],
+        }
       }
       PPU_REGISTERS ..= PPU_REGISTERS_MIRRORS_END => {
-        let _mirror_down_addr = addr & 0b00100000_00000111;
-        todo!("PPU is not supported yet")
+        match addr & 0b00100000_00000111 {
+          0x2002 => self.ppu.read_status(),
+          0x2004 => self.ppu.read_oam_data(),
+          0x2007 => self.ppu.read_data(),
+          // write-only registers
+          _ => self.ppu.io_latch(),
+        }
       }
+      APU_STATUS => {
+        let status = self.apu.read_status();
+        self.sync_apu_irq();
+        status
+      }
+      JOYPAD1 => self.joypad1.read(),
+      JOYPAD2 => self.joypad2.read(),
+      PRG_RAM ..= PRG_RAM_END => self.prg_ram[(addr - PRG_RAM) as usize],
       ROM ..= ROM_END => self.read_prg_rom(addr),
 
-      _ => {
-        println!("Ignoring mem access at {}", addr);
-        0
-      }
-    }
+      // write-only apu registers and the unmapped $4018-$5FFF
+      _ => 0,
+    };
+    self.log_access(AccessKind::Read, addr, value);
+    value
   }
 
   fn mem_write(&mut self, addr: u16, data: u8) {
+    self.log_access(AccessKind::Write, addr, data);
+    if let Some(log) = self.event_log.as_mut().filter(|_| is_logged_register(addr)) {
+      log.record(self.ppu.scanline, self.ppu.dot() as u16, self.instruction_start, addr, data);
+    }
     match addr {
       RAM ..= RAM_MIRRORS_END => {
         let mirror_down_addr = addr & 0b00000111_11111111;
         self.cpu_vram[mirror_down_addr as usize] = data
       }
       PPU_REGISTERS ..= PPU_REGISTERS_MIRRORS_END => {
-        let _mirror_down_addr = addr & 0b00100000_00000111;
-        todo!("PPU is not supported yet")
+        match addr & 0b00100000_00000111 {
+          0x2000 => self.ppu.write_to_ctrl(data),
+          0x2001 => self.ppu.write_to_mask(data),
+          // read-only PPUSTATUS
+          0x2002 => {}
+          0x2003 => self.ppu.write_to_oam_addr(data),
+          0x2004 => self.ppu.write_to_oam_data(data),
+          0x2005 => self.ppu.write_to_scroll(data),
+          0x2006 => self.ppu.write_to_ppu_addr(data),
+          _ => self.ppu.write_to_data(data),
+        }
       }
-      ROM ..= ROM_END => panic!("Attempt to write to Cartridge ROM space"),
-
-      _ => {
-        println!("Ignoring mem write-access at {}", addr);
+      APU_REGISTERS ..= APU_REGISTERS_END => self.apu.write_register(addr, data),
+      OAM_DMA => self.oam_dma(data),
+      APU_STATUS => self.apu.write_status(data),
+      APU_FRAME_COUNTER => {
+        self.apu.write_frame_counter(data);
+        self.sync_apu_irq();
+      }
+      // the strobe line is shared by both controllers
+      JOYPAD1 => {
+        self.joypad1.write(data);
+        self.joypad2.write(data);
       }
+      PRG_RAM ..= PRG_RAM_END => self.prg_ram[(addr - PRG_RAM) as usize] = data,
+      ROM ..= ROM_END => self.mapper.borrow_mut().prg_write(addr, data),
+
+      // unmapped $4018-$5FFF
+      _ => {}
     }
   }
 }
\ No newline at end of file
@@ -0,0 +1,84 @@
+use crate::bus::Bus;
+use crate::cartridge_tests::{create_test_rom, create_test_rom_with_vectors};
+use crate::cpu::MyMem;
+use crate::ppu::{ControlRegister, MaskRegister};
+
+fn init_bus() -> Bus {
+  Bus::new(create_test_rom()).unwrap()
+}
+
+#[test]
+fn test_ram_is_mirrored_through_1fff() {
+  let mut bus = init_bus();
+
+  bus.mem_write(0x0801, 0x42);
+  bus.mem_write(0x1FFF, 0x24);
+
+  assert_eq!(0x42, bus.mem_read(0x0001));
+  assert_eq!(0x42, bus.mem_read(0x1001));
+  assert_eq!(0x42, bus.mem_read(0x1801));
+  assert_eq!(0x24, bus.mem_read(0x07FF));
+  assert_eq!(0x24, bus.peek(0x0FFF));
+}
+
+#[test]
+fn test_poke_writes_through_ram_mirrors() {
+  let mut bus = init_bus();
+
+  assert!(bus.poke(0x1234, 0x66));
+
+  assert_eq!(0x66, bus.mem_read(0x0234));
+}
+
+#[test]
+fn test_ppu_registers_are_mirrored_every_8_bytes() {
+  let mut bus = init_bus();
+
+  bus.mem_write(0x2008, 0b1000_0000); // PPUCTRL
+  bus.mem_write(0x3FF9, 0b0001_1000); // PPUMASK
+
+  assert_eq!(ControlRegister::GENERATE_NMI, bus.ppu.ctrl);
+  assert_eq!(MaskRegister::SHOW_BACKGROUND | MaskRegister::SHOW_SPRITES, bus.ppu.mask);
+}
+
+#[test]
+fn test_ppudata_mirror_advances_the_vram_address() {
+  let mut bus = init_bus();
+  bus.mem_write(0x200E, 0x20); // PPUADDR
+  bus.mem_write(0x200E, 0x00);
+
+  bus.mem_write(0x200F, 0x11);
+  bus.mem_write(0x3FFF, 0x22);
+
+  assert_eq!(0x11, bus.ppu.vram[0x0000]);
+  assert_eq!(0x22, bus.ppu.vram[0x0001]);
+}
+
+#[test]
+fn test_tick_catches_up_ppu_for_long_stalls() {
+  let mut bus = init_bus();
+
+  // longer than an oam dma, three dots per cpu cycle
+  bus.tick(514);
+
+  assert!(bus.clock().in_sync());
+  assert_eq!(514, bus.clock().cpu_cycles());
+  assert_eq!(514 * 3, bus.ppu.scanline as usize * 341 + bus.ppu.dot());
+}
+
+#[test]
+fn test_vectors_are_read_from_the_end_of_prg_rom() {
+  let mut bus = Bus::new(create_test_rom_with_vectors(&[], 0x8010, 0x8020)).unwrap();
+
+  assert_eq!(0x8010, bus.mem_read_u16(0xFFFA));
+  assert_eq!(0x8000, bus.mem_read_u16(0xFFFC));
+  assert_eq!(0x8020, bus.mem_read_u16(0xFFFE));
+}
+
+#[test]
+fn test_u16_read_at_ffff_wraps_to_ram() {
+  let mut bus = Bus::new(create_test_rom_with_vectors(&[], 0x0000, 0x3400)).unwrap();
+  bus.mem_write(0x0000, 0x12);
+
+  assert_eq!(0x1234, bus.mem_read_u16(0xFFFF));
+}
@@ -0,0 +1,113 @@
+// shadow call stack, maintained next to the real 6502 stack:
+// JSR pushes a frame, RTS/RTI pop it. Games like to abuse the stack (RTS as indirect jump,
+// TXS to unwind), so a return that doesn't match the top frame is counted as mismatch
+// instead of being trusted blindly.
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FrameKind {
+  Subroutine,
+  Interrupt,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CallFrame {
+  pub kind: FrameKind,
+  pub caller: u16,
+  pub target: u16,
+  pub return_address: u16,
+  pub stack_pointer: u8, // before the return address got pushed
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Mismatch {
+  pub program_counter: u16,
+  pub expected: Option<u16>,
+  pub actual: u16,
+}
+
+#[derive(Default, Clone)]
+pub struct CallStack {
+  frames: Vec<CallFrame>,
+  mismatches: Vec<Mismatch>,
+}
+
+const MAX_MISMATCHES: usize = 16;
+
+impl CallStack {
+  pub fn new() -> Self {
+    CallStack::default()
+  }
+
+  pub fn frames(&self) -> &[CallFrame] {
+    &self.frames
+  }
+
+  pub fn depth(&self) -> usize {
+    self.frames.len()
+  }
+
+  pub fn mismatches(&self) -> &[Mismatch] {
+    &self.mismatches
+  }
+
+  pub fn clear(&mut self) {
+    self.frames.clear();
+    self.mismatches.clear();
+  }
+
+  pub fn on_call(&mut self, caller: u16, target: u16, return_address: u16, stack_pointer: u8) {
+    self.frames.push(CallFrame { kind: FrameKind::Subroutine, caller, target, return_address, stack_pointer });
+  }
+
+  pub fn on_interrupt(&mut self, caller: u16, target: u16, stack_pointer: u8) {
+    self.frames.push(CallFrame { kind: FrameKind::Interrupt, caller, target, return_address: caller, stack_pointer });
+  }
+
+  // stack_pointer: after the return address (and status for RTI) got pulled
+  pub fn on_return(&mut self, program_counter: u16, return_address: u16, stack_pointer: u8) {
+    let top_matches = self.frames.last()
+      .is_some_and(|f| f.stack_pointer == stack_pointer && f.return_address == return_address);
+    if top_matches {
+      self.frames.pop();
+      return;
+    }
+
+    self.record_mismatch(program_counter, return_address);
+
+    // stack got unwound manually (e.g. TXS) - drop everything above the matching frame
+    if let Some(idx) = self.frames.iter().rposition(|f| f.stack_pointer == stack_pointer) {
+      self.frames.truncate(idx);
+    }
+  }
+
+  fn record_mismatch(&mut self, program_counter: u16, actual: u16) {
+    if self.mismatches.len() == MAX_MISMATCHES {
+      self.mismatches.remove(0);
+    }
+    self.mismatches.push(Mismatch {
+      program_counter,
+      expected: self.frames.last().map(|f| f.return_address),
+      actual,
+    });
+  }
+
+  pub fn backtrace(&self) -> String {
+    self.backtrace_with_labels(|_| None)
+  }
+
+  // innermost frame first, label lookup is used for the call targets
+  pub fn backtrace_with_labels<F>(&self, label: F) -> String
+    where F: Fn(u16) -> Option<String>
+  {
+    let mut trace = String::new();
+    for (i, frame) in self.frames.iter().rev().enumerate() {
+      let target = label(frame.target).unwrap_or_else(|| format!("${:04X}", frame.target));
+      let kind = match frame.kind {
+        FrameKind::Subroutine => "",
+        FrameKind::Interrupt => " (interrupt)",
+      };
+      trace.push_str(&format!("#{} {}{} called from ${:04X}\n", i, target, kind, frame.caller));
+    }
+    trace
+  }
+}
@@ -0,0 +1,90 @@
+use crate::bus::Bus;
+use crate::call_stack::{CallStack, FrameKind};
+use crate::cartridge_tests::create_test_rom;
+use crate::cpu::{MyCPU, MyMem};
+
+fn init_cpu() -> MyCPU {
+  let mut cpu = MyCPU::new(Bus::new(create_test_rom()).unwrap());
+  cpu.program_counter = 0x0600;
+  cpu
+}
+
+#[test]
+fn test_call_and_matching_return() {
+  let mut stack = CallStack::new();
+
+  stack.on_call(0x0600, 0x0700, 0x0602, 0xFF);
+  assert_eq!(1, stack.depth());
+
+  stack.on_return(0x0710, 0x0602, 0xFF);
+
+  assert_eq!(0, stack.depth());
+  assert!(stack.mismatches().is_empty());
+}
+
+#[test]
+fn test_return_without_call_is_mismatch() {
+  let mut stack = CallStack::new();
+
+  stack.on_return(0x0710, 0x1234, 0xFF);
+
+  assert_eq!(1, stack.mismatches().len());
+  assert_eq!(None, stack.mismatches()[0].expected);
+  assert_eq!(0x1234, stack.mismatches()[0].actual);
+}
+
+#[test]
+fn test_manually_unwound_stack_drops_inner_frames() {
+  let mut stack = CallStack::new();
+  stack.on_call(0x0600, 0x0700, 0x0602, 0xFF);
+  stack.on_call(0x0700, 0x0800, 0x0702, 0xFD);
+  stack.on_call(0x0800, 0x0900, 0x0802, 0xFB);
+
+  // inner routine resets SP and returns directly to the outermost caller
+  stack.on_return(0x0910, 0x0602, 0xFF);
+
+  assert_eq!(0, stack.depth());
+  assert_eq!(1, stack.mismatches().len());
+  assert_eq!(Some(0x0802), stack.mismatches()[0].expected);
+}
+
+#[test]
+fn test_backtrace_innermost_first_with_labels() {
+  let mut stack = CallStack::new();
+  stack.on_call(0x0600, 0x0700, 0x0602, 0xFF);
+  stack.on_interrupt(0x0702, 0x8000, 0xFD);
+
+  let trace = stack.backtrace_with_labels(|addr| if addr == 0x8000 { Some("nmi".to_string()) } else { None });
+
+  assert_eq!("#0 nmi (interrupt) called from $0702\n#1 $0700 called from $0600\n", trace);
+  assert_eq!(FrameKind::Interrupt, stack.frames()[1].kind);
+}
+
+#[test]
+fn test_cpu_tracks_jsr_and_rts() {
+  let mut cpu = init_cpu();
+  // JSR $0610 at $0600, subroutine at $0610: JSR $0620, RTS; $0620: BRK
+  cpu.mem_write(0x0610, 0x20);
+  cpu.mem_write(0x0611, 0x20);
+  cpu.mem_write(0x0612, 0x06);
+  cpu.mem_write(0x0613, 0x60);
+
+  cpu.load_and_run(vec![0x20, 0x10, 0x06]);
+
+  assert_eq!(2, cpu.call_stack.depth());
+  assert_eq!(0x0610, cpu.call_stack.frames()[0].target);
+  assert_eq!(0x0620, cpu.call_stack.frames()[1].target);
+  assert_eq!(0x0610, cpu.call_stack.frames()[1].caller);
+}
+
+#[test]
+fn test_cpu_pops_frame_on_rts() {
+  let mut cpu = init_cpu();
+  // JSR $0610, BRK; $0610: RTS
+  cpu.mem_write(0x0610, 0x60);
+
+  cpu.load_and_run(vec![0x20, 0x10, 0x06]);
+
+  assert_eq!(0, cpu.call_stack.depth());
+  assert!(cpu.call_stack.mismatches().is_empty());
+}
@@ -1,32 +1,89 @@
+use std::fmt;
+use std::path::Path;
+use crate::error::EmuError;
+
 const NES_TAG: [u8; 4] = [0x4E, 0x45, 0x53, 0x1A];
+const HEADER_SIZE: usize = 16;
+const TRAINER_SIZE: usize = 512;
 pub const PRG_ROM_PAGE_SIZE: usize = 16_384;
 pub const CHR_ROM_PAGE_SIZE: usize = 8_192;
 
-#[derive(Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum Mirroring {
   VERTICAL,
   HORIZONTAL,
   #[allow(non_camel_case_types)]FOUR_SCREEN,
+  // selected by the mapper, all four nametables show the same 1KB
+  #[allow(non_camel_case_types)]SINGLE_SCREEN_LOWER,
+  #[allow(non_camel_case_types)]SINGLE_SCREEN_UPPER,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum RomError {
+  TooSmall,
+  BadMagic,
+  UnsupportedVersion(u8),
+  VsUnisystem,
+  PlayChoice10,
+  TruncatedFile { prg_rom_size: usize, chr_rom_size: usize, available: usize },
+  UnsupportedMapper(u8),
 }
 
+impl fmt::Display for RomError {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self {
+      RomError::TooSmall => write!(f, "File is too small for an iNES header"),
+      RomError::BadMagic => write!(f, "File is not in iNES file format"),
+      RomError::UnsupportedVersion(_) => write!(f, "only iNES1.0 format is supported!"),
+      RomError::VsUnisystem => write!(f, "VS Unisystem ROMs are not supported!"),
+      RomError::PlayChoice10 => write!(f, "PlayChoice-10 ROMs are not supported!"),
+      RomError::TruncatedFile { prg_rom_size, chr_rom_size, available } =>
+        write!(f, "File is truncated: header announces {} bytes of PRG and {} bytes of CHR ROM, but only {} bytes follow",
+               prg_rom_size, chr_rom_size, available),
+      RomError::UnsupportedMapper(mapper) => write!(f, "Mapper {} is not supported", mapper),
+    }
+  }
+}
+
+impl std::error::Error for RomError {}
+
 pub struct Rom {
   pub prg_rom: Vec<u8>, // code
   pub chr_rom: Vec<u8>, // visual graphics
+  pub chr_ram: bool, // no chr rom on the cartridge, chr_rom holds writable ram instead
   pub mapper: u8,
   pub screen_mirroring: Mirroring,
+  pub battery: bool, // prg ram at $6000-$7FFF keeps its content
 }
 
 impl Rom {
-  pub fn new(raw: &Vec<u8>) -> Result<Rom, String> {
-    if &raw[0..4] != NES_TAG {
-      return Err("File is not in iNES file format".to_string());
+  pub fn load(path: &Path) -> Result<Rom, EmuError> {
+    let raw = std::fs::read(path)?;
+    Ok(Rom::new(&raw)?)
+  }
+
+  pub fn new(raw: &[u8]) -> Result<Rom, RomError> {
+    if raw.len() < HEADER_SIZE {
+      return Err(RomError::TooSmall);
+    }
+    if raw[0..4] != NES_TAG {
+      return Err(RomError::BadMagic);
     }
 
     let mapper = (raw[7] & 0xF0) | (raw[6] >> 4);  // higher bits of header
 
     let ines_ver = (raw[7] >> 2) & 0b11;
     if ines_ver != 0 {
-      return Err("only iNES1.0 format is supported!".to_string())
+      return Err(RomError::UnsupportedVersion(ines_ver))
+    }
+
+    // arcade variants need their own palettes, dip switches and coin inputs
+    if raw[7] & 0b01 != 0 {
+      return Err(RomError::VsUnisystem);
+    }
+    if raw[7] & 0b10 != 0 {
+      return Err(RomError::PlayChoice10);
     }
 
     let four_screen = raw[6] & 0b1000 != 0;
@@ -40,16 +97,34 @@ impl Rom {
     let prg_rom_size = raw[4] as usize * PRG_ROM_PAGE_SIZE;
     let chr_rom_size = raw[5] as usize * CHR_ROM_PAGE_SIZE;
 
+    let battery = raw[6] & 0b10 != 0;
     let skip_trainer = raw[6] & 0b100 != 0;
 
-    let prg_rom_start = 16 + if skip_trainer { 512 } else { 0 };
+    let prg_rom_start = HEADER_SIZE + if skip_trainer { TRAINER_SIZE } else { 0 };
     let chr_rom_start = prg_rom_start + prg_rom_size;
+    if raw.len() < chr_rom_start + chr_rom_size {
+      return Err(RomError::TruncatedFile {
+        prg_rom_size,
+        chr_rom_size,
+        available: raw.len() - prg_rom_start.min(raw.len()),
+      });
+    }
+
+    // 0 chr pages: the cartridge has 8KB of chr ram
+    let chr_ram = chr_rom_size == 0;
+    let chr_rom = if chr_ram {
+      vec![0; CHR_ROM_PAGE_SIZE]
+    } else {
+      raw[chr_rom_start..(chr_rom_start + chr_rom_size)].to_vec()
+    };
 
     Ok(Rom {
       prg_rom: raw[prg_rom_start..(prg_rom_start + prg_rom_size)].to_vec(),
-      chr_rom: raw[chr_rom_start..(chr_rom_start + chr_rom_size)].to_vec(),
+      chr_rom,
+      chr_ram,
       mapper,
-      screen_mirroring
+      screen_mirroring,
+      battery,
     })
   }
 }
\ No newline at end of file
@@ -1,4 +1,4 @@
-use crate::cartridge::{CHR_ROM_PAGE_SIZE, Mirroring, PRG_ROM_PAGE_SIZE, Rom};
+use crate::cartridge::{CHR_ROM_PAGE_SIZE, Mirroring, PRG_ROM_PAGE_SIZE, Rom, RomError};
 
 struct TestRom {
   header: Vec<u8>,
@@ -32,18 +32,50 @@ pub fn create_test_rom() -> Rom {
     ],
     trainer: None,
     pgp_rom: vec![1; 2 * PRG_ROM_PAGE_SIZE],
-    chr_rom: vec![2; 1 * CHR_ROM_PAGE_SIZE],
+    chr_rom: vec![2; CHR_ROM_PAGE_SIZE],
   });
 
   Rom::new(&test_rom).unwrap()
 }
 
+// program is placed at $8000, reset vector points to it
+pub fn create_test_rom_with_program(program: &[u8]) -> Rom {
+  create_test_rom_with_vectors(program, 0x0000, 0x0000)
+}
+
+pub fn create_test_rom_with_vectors(program: &[u8], nmi: u16, irq: u16) -> Rom {
+  Rom::new(&test_rom_bytes_with_vectors(program, nmi, irq)).unwrap()
+}
+
+// the .nes file of create_test_rom_with_program, for code that loads roms itself
+pub fn test_rom_bytes_with_program(program: &[u8]) -> Vec<u8> {
+  test_rom_bytes_with_vectors(program, 0x0000, 0x0000)
+}
+
+fn test_rom_bytes_with_vectors(program: &[u8], nmi: u16, irq: u16) -> Vec<u8> {
+  let mut pgp_rom = vec![0; 2 * PRG_ROM_PAGE_SIZE];
+  pgp_rom[..program.len()].copy_from_slice(program);
+  pgp_rom[0x7FFA..0x7FFC].copy_from_slice(&nmi.to_le_bytes());
+  pgp_rom[0x7FFC] = 0x00;
+  pgp_rom[0x7FFD] = 0x80;
+  pgp_rom[0x7FFE..0x8000].copy_from_slice(&irq.to_le_bytes());
+
+  create_rom(TestRom{
+    header: vec![
+      0x4E, 0x45, 0x53, 0x1A, 0x02, 0x01, 0x31, 00, 00, 00, 00, 00, 00, 00, 00, 00,
+    ],
+    trainer: None,
+    pgp_rom,
+    chr_rom: vec![2; CHR_ROM_PAGE_SIZE],
+  })
+}
+
 #[test]
 fn test_without_trainer() {
   let rom = create_test_rom();
 
   assert_eq!(vec![1; 2 * PRG_ROM_PAGE_SIZE], rom.prg_rom);
-  assert_eq!(vec![2; 1 * CHR_ROM_PAGE_SIZE], rom.chr_rom);
+  assert_eq!(vec![2; CHR_ROM_PAGE_SIZE], rom.chr_rom);
   assert_eq!(3, rom.mapper);
   assert_eq!(Mirroring::VERTICAL, rom.screen_mirroring);
 }
@@ -55,15 +87,15 @@ fn test_nes2_is_not_supported() {
       0x4E, 0x45, 0x53, 0x1A, 0x01, 0x01, 0x31, 0x8, 00, 00, 00, 00, 00, 00, 00, 00,
     ],
     trainer: None,
-    pgp_rom: vec![1; 1 * PRG_ROM_PAGE_SIZE],
-    chr_rom: vec![2; 1 * CHR_ROM_PAGE_SIZE],
+    pgp_rom: vec![1; PRG_ROM_PAGE_SIZE],
+    chr_rom: vec![2; CHR_ROM_PAGE_SIZE],
   });
 
   let rom = Rom::new(&test_rom);
 
   match rom {
-    Result::Ok(_) => assert!(false, "should not load rom"),
-    Result::Err(str) => assert_eq!("only iNES1.0 format is supported!", str)
+    Result::Ok(_) => panic!("should not load rom"),
+    Result::Err(e) => assert_eq!(RomError::UnsupportedVersion(2), e)
   }
 }
 
@@ -86,4 +118,114 @@ fn test_with_trainer() {
   assert_eq!(vec![2; 2 * CHR_ROM_PAGE_SIZE], rom.chr_rom);
   assert_eq!(3, rom.mapper);
   assert_eq!(Mirroring::VERTICAL, rom.screen_mirroring);
-}
\ No newline at end of file
+}
+
+#[test]
+fn test_vs_unisystem_is_not_supported() {
+  let test_rom = create_rom(TestRom {
+    header: vec![
+      0x4E, 0x45, 0x53, 0x1A, 0x01, 0x01, 0x31, 0x01, 00, 00, 00, 00, 00, 00, 00, 00,
+    ],
+    trainer: None,
+    pgp_rom: vec![1; PRG_ROM_PAGE_SIZE],
+    chr_rom: vec![2; CHR_ROM_PAGE_SIZE],
+  });
+
+  match Rom::new(&test_rom) {
+    Result::Ok(_) => panic!("should not load rom"),
+    Result::Err(e) => assert_eq!(RomError::VsUnisystem, e)
+  }
+}
+
+#[test]
+fn test_playchoice_10_is_not_supported() {
+  let test_rom = create_rom(TestRom {
+    header: vec![
+      0x4E, 0x45, 0x53, 0x1A, 0x01, 0x01, 0x31, 0x02, 00, 00, 00, 00, 00, 00, 00, 00,
+    ],
+    trainer: None,
+    pgp_rom: vec![1; PRG_ROM_PAGE_SIZE],
+    chr_rom: vec![2; CHR_ROM_PAGE_SIZE],
+  });
+
+  match Rom::new(&test_rom) {
+    Result::Ok(_) => panic!("should not load rom"),
+    Result::Err(e) => assert_eq!(RomError::PlayChoice10, e)
+  }
+}
+
+#[test]
+fn test_invalid_magic_is_rejected() {
+  let test_rom = create_rom(TestRom {
+    header: vec![
+      0x4E, 0x45, 0x53, 0x00, 0x01, 0x01, 0x31, 00, 00, 00, 00, 00, 00, 00, 00, 00,
+    ],
+    trainer: None,
+    pgp_rom: vec![1; PRG_ROM_PAGE_SIZE],
+    chr_rom: vec![2; CHR_ROM_PAGE_SIZE],
+  });
+
+  assert_eq!(Err(RomError::BadMagic), Rom::new(&test_rom).map(|_| ()));
+}
+
+#[test]
+fn test_short_header_is_rejected() {
+  let raw = vec![0x4E, 0x45, 0x53, 0x1A, 0x01];
+
+  assert_eq!(Err(RomError::TooSmall), Rom::new(&raw).map(|_| ()));
+  assert_eq!("File is too small for an iNES header", RomError::TooSmall.to_string());
+}
+
+#[test]
+fn test_truncated_rom_is_rejected() {
+  let test_rom = create_rom(TestRom {
+    header: vec![
+      0x4E, 0x45, 0x53, 0x1A, 0x02, 0x01, 0x31, 00, 00, 00, 00, 00, 00, 00, 00, 00,
+    ],
+    trainer: None,
+    pgp_rom: vec![1; PRG_ROM_PAGE_SIZE],
+    chr_rom: vec![],
+  });
+
+  match Rom::new(&test_rom) {
+    Result::Ok(_) => panic!("should not load rom"),
+    Result::Err(e) => {
+      assert_eq!(RomError::TruncatedFile { prg_rom_size: 2 * PRG_ROM_PAGE_SIZE, chr_rom_size: CHR_ROM_PAGE_SIZE, available: PRG_ROM_PAGE_SIZE }, e);
+      assert!(e.to_string().starts_with("File is truncated"), "{}", e);
+    }
+  }
+}
+
+#[test]
+fn test_mapper_and_mirroring_from_header() {
+  let test_rom = create_rom(TestRom {
+    header: vec![
+      0x4E, 0x45, 0x53, 0x1A, 0x01, 0x01, 0x40 | 0b1000, 0x40, 00, 00, 00, 00, 00, 00, 00, 00,
+    ],
+    trainer: None,
+    pgp_rom: vec![1; PRG_ROM_PAGE_SIZE],
+    chr_rom: vec![2; CHR_ROM_PAGE_SIZE],
+  });
+
+  let rom = Rom::new(&test_rom).unwrap();
+
+  assert_eq!(0x44, rom.mapper);
+  assert_eq!(Mirroring::FOUR_SCREEN, rom.screen_mirroring);
+}
+
+#[test]
+fn test_zero_chr_pages_allocate_chr_ram() {
+  let test_rom = create_rom(TestRom {
+    header: vec![
+      0x4E, 0x45, 0x53, 0x1A, 0x01, 0x00, 0x00, 00, 00, 00, 00, 00, 00, 00, 00, 00,
+    ],
+    trainer: None,
+    pgp_rom: vec![1; PRG_ROM_PAGE_SIZE],
+    chr_rom: vec![],
+  });
+
+  let rom = Rom::new(&test_rom).unwrap();
+
+  assert!(rom.chr_ram);
+  assert_eq!(vec![0; CHR_ROM_PAGE_SIZE], rom.chr_rom);
+}
@@ -0,0 +1,118 @@
+// Game Genie codes: patches on cpu reads of prg rom, the rom itself is never modified.
+// https://wiki.nesdev.org/w/index.php/Game_Genie
+// 8 letter codes only replace the value if the rom contains the compare value,
+// so they don't hit other banks mapped to the same address.
+
+const LETTERS: &str = "APZLGITYEOXUKSVN";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Patch {
+  pub address: u16,
+  pub value: u8,
+  pub compare: Option<u8>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Cheat {
+  pub code: String,
+  pub patch: Patch,
+  pub enabled: bool,
+}
+
+// bits of the letters, value = 12345678, address = 1ABCDEFGHIJKLMNO, compare = abcdefgh:
+// 6 letters: 1678 H234 -IJK LABC DMNO 5EFG
+// 8 letters: 1678 H234 -IJK LABC DMNO eEFG afgh 5bcd
+pub fn decode_game_genie(code: &str) -> Result<Patch, String> {
+  let n = code.chars()
+    .map(|c| LETTERS.find(c.to_ascii_uppercase()).map(|i| i as u16))
+    .collect::<Option<Vec<u16>>>()
+    .ok_or_else(|| format!("invalid game genie code: '{}'", code))?;
+  if n.len() != 6 && n.len() != 8 {
+    return Err(format!("game genie codes have 6 or 8 letters: '{}'", code));
+  }
+
+  let address = 0x8000 | (n[3] & 7) << 12 | (n[4] & 8) << 8 | (n[5] & 7) << 8
+    | (n[1] & 8) << 4 | (n[2] & 7) << 4 | (n[3] & 8) | (n[4] & 7);
+  let value_low = if n.len() == 6 { n[5] & 8 } else { n[7] & 8 };
+  let value = ((n[0] & 8) << 4 | (n[1] & 7) << 4 | value_low | (n[0] & 7)) as u8;
+  let compare = if n.len() == 8 {
+    Some(((n[6] & 8) << 4 | (n[7] & 7) << 4 | (n[5] & 8) | (n[6] & 7)) as u8)
+  } else {
+    None
+  };
+  Ok(Patch { address, value, compare })
+}
+
+pub fn encode_game_genie(patch: &Patch) -> String {
+  let (a, v) = (patch.address, patch.value as u16);
+  let mut n = vec![
+    (v >> 4) & 8 | v & 7,
+    (a >> 4) & 8 | (v >> 4) & 7,
+    (a >> 4) & 7,
+    a & 8 | (a >> 12) & 7,
+    (a >> 8) & 8 | a & 7,
+    v & 8 | (a >> 8) & 7,
+  ];
+  if let Some(compare) = patch.compare {
+    let c = compare as u16;
+    n[2] |= 8;
+    n[5] = c & 8 | (a >> 8) & 7;
+    n.push((c >> 4) & 8 | c & 7);
+    n.push(v & 8 | (c >> 4) & 7);
+  }
+  n.iter().map(|&i| LETTERS.as_bytes()[i as usize] as char).collect()
+}
+
+#[derive(Default)]
+pub struct Cheats {
+  cheats: Vec<Cheat>,
+}
+
+impl Cheats {
+  pub fn new() -> Self {
+    Cheats::default()
+  }
+
+  pub fn list(&self) -> &[Cheat] {
+    &self.cheats
+  }
+
+  // new codes are enabled, adding a code twice keeps the first one
+  pub fn add(&mut self, code: &str) -> Result<Patch, String> {
+    let patch = decode_game_genie(code)?;
+    let code = code.to_ascii_uppercase();
+    if !self.cheats.iter().any(|c| c.code == code) {
+      self.cheats.push(Cheat { code, patch, enabled: true });
+    }
+    Ok(patch)
+  }
+
+  pub fn remove(&mut self, code: &str) -> bool {
+    let len = self.cheats.len();
+    self.cheats.retain(|c| !c.code.eq_ignore_ascii_case(code));
+    self.cheats.len() != len
+  }
+
+  pub fn set_enabled(&mut self, code: &str, enabled: bool) -> bool {
+    match self.cheats.iter_mut().find(|c| c.code.eq_ignore_ascii_case(code)) {
+      Some(cheat) => {
+        cheat.enabled = enabled;
+        true
+      }
+      None => false,
+    }
+  }
+
+  // an enabled code targets the address, reads there may differ from the rom
+  pub fn is_patched(&self, address: u16) -> bool {
+    self.cheats.iter().any(|c| c.enabled && c.patch.address == address)
+  }
+
+  // value the cpu sees when reading `value` from prg rom at `address`
+  pub fn apply(&self, address: u16, value: u8) -> u8 {
+    self.cheats.iter()
+      .filter(|c| c.enabled && c.patch.address == address)
+      .find(|c| c.patch.compare.is_none_or(|compare| compare == value))
+      .map_or(value, |c| c.patch.value)
+  }
+}
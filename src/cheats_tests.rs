@@ -0,0 +1,80 @@
+use crate::bus::Bus;
+use crate::cartridge_tests::create_test_rom_with_program;
+use crate::cheats::{decode_game_genie, encode_game_genie, Cheats, Patch};
+use crate::cpu::{MyCPU, MyMem};
+
+#[test]
+fn test_decode_6_letter_code() {
+  // super mario bros, infinite lives
+  assert_eq!(Ok(Patch { address: 0x91D9, value: 0xAD, compare: None }), decode_game_genie("SXIOPO"));
+  assert_eq!(decode_game_genie("SXIOPO"), decode_game_genie("sxiopo"));
+}
+
+#[test]
+fn test_decode_8_letter_code() {
+  let patch = decode_game_genie("ZEXPYGLA").unwrap();
+
+  assert_eq!(8, encode_game_genie(&patch).len());
+  assert_eq!("ZEXPYGLA", encode_game_genie(&patch));
+  assert!(patch.compare.is_some());
+}
+
+#[test]
+fn test_encode_round_trips() {
+  for patch in [
+    Patch { address: 0x8000, value: 0x00, compare: None },
+    Patch { address: 0xFFFF, value: 0xFF, compare: None },
+    Patch { address: 0xC123, value: 0x5A, compare: Some(0xA5) },
+    Patch { address: 0x9ABC, value: 0x81, compare: Some(0x18) },
+  ] {
+    assert_eq!(Ok(patch), decode_game_genie(&encode_game_genie(&patch)));
+  }
+}
+
+#[test]
+fn test_invalid_codes() {
+  assert!(decode_game_genie("SXIOP").is_err());
+  assert!(decode_game_genie("SXIOPB").is_err());
+  assert!(decode_game_genie("").is_err());
+}
+
+#[test]
+fn test_compare_value_has_to_match() {
+  let mut cheats = Cheats::new();
+  let code = encode_game_genie(&Patch { address: 0x8000, value: 0x42, compare: Some(0x10) });
+  cheats.add(&code).unwrap();
+
+  assert_eq!(0x42, cheats.apply(0x8000, 0x10));
+  assert_eq!(0x11, cheats.apply(0x8000, 0x11));
+  assert_eq!(0x10, cheats.apply(0x8001, 0x10));
+}
+
+#[test]
+fn test_cheats_can_be_disabled_and_removed() {
+  let mut cheats = Cheats::new();
+  cheats.add("SXIOPO").unwrap();
+
+  assert!(cheats.set_enabled("sxiopo", false));
+  assert_eq!(0x00, cheats.apply(0x91D9, 0x00));
+  assert!(cheats.set_enabled("SXIOPO", true));
+  assert_eq!(0xAD, cheats.apply(0x91D9, 0x00));
+  assert!(cheats.remove("SXIOPO"));
+  assert!(!cheats.remove("SXIOPO"));
+  assert!(cheats.list().is_empty());
+}
+
+#[test]
+fn test_patch_applies_to_prg_reads_and_execution() {
+  // LDA #$01, BRK - the cheat turns it into LDA #$07
+  let mut cpu = MyCPU::new(Bus::new(create_test_rom_with_program(&[0xA9, 0x01, 0x00])).unwrap());
+  cpu.reset();
+  cpu.run();
+  assert_eq!(0x01, cpu.register_a);
+
+  let code = encode_game_genie(&Patch { address: 0x8001, value: 0x07, compare: Some(0x01) });
+  cpu.bus.cheats.add(&code).unwrap();
+  assert_eq!(0x07, cpu.bus.mem_read(0x8001));
+  cpu.reset();
+  cpu.run();
+  assert_eq!(0x07, cpu.register_a);
+}
@@ -0,0 +1,348 @@
+use std::io;
+use std::net::TcpListener;
+use std::path::{Path, PathBuf};
+use crate::bench;
+use crate::cartridge::{CHR_ROM_PAGE_SIZE, PRG_ROM_PAGE_SIZE, Rom};
+use crate::clock::Region;
+use crate::disasm::disassemble_with_labels;
+use crate::error::EmuError;
+use crate::control::{self, Control};
+use crate::gdb;
+use crate::monitor;
+use crate::nes::Nes;
+use crate::netplay::{DEFAULT_INPUT_DELAY, Peer};
+use crate::palette::Palette;
+use crate::profiler::Profiler;
+use crate::symbols::Symbols;
+use crate::trace::{FileSink, Tracer};
+use crate::video::{ScaleMode, VideoOptions};
+
+pub const USAGE: &str = "usage: nes_emulator [command]
+  run <rom.nes> [--scale N] [--scale-mode integer|fit] [--aspect-correct] [--region ntsc|pal] [--palette 2c02|fceux|sony|file.pal] [--trace file [--trace-rotate MB,FILES]] [--symbols file.dbg|file.mlb] [--profile] [--headless --frames N]
+  disasm <rom.nes> [--symbols file.dbg|file.mlb]
+  info <rom.nes>
+  nsf <file.nsf> [--track N]
+  gdb <rom.nes> [--port N]
+  control <rom.nes> [--port N]
+  monitor <rom.nes> [--symbols file.dbg|file.mlb]
+  bench <rom.nes> [--instructions N] [--frames N]
+  netplay <rom.nes> (--host PORT | --connect HOST:PORT) [--delay FRAMES]
+without a command the snake game is started";
+
+pub const BENCH_INSTRUCTIONS: u64 = 10_000_000;
+pub const BENCH_FRAMES: usize = 600;
+
+// lines per section of the --profile report
+pub const PROFILE_ENTRIES: usize = 20;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RunOptions {
+  pub rom: PathBuf,
+  pub video: VideoOptions,
+  pub region: Region,
+  // builtin name or .pal file
+  pub palette: Option<String>,
+  pub trace: Option<PathBuf>,
+  // megabytes per trace file and the number of files to keep, the oldest is dropped
+  pub trace_rotation: Option<(u64, usize)>,
+  // cc65 .dbg or mesen .mlb labels for traces and crash dumps
+  pub symbols: Option<PathBuf>,
+  // hot spot report when the emulation ends
+  pub profile: bool,
+  // no window and no audio, stops after `frames`
+  pub headless: bool,
+  pub frames: Option<usize>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Command {
+  Snake,
+  Run(RunOptions),
+  Disasm { rom: PathBuf, symbols: Option<PathBuf> },
+  Info(PathBuf),
+  Nsf { file: PathBuf, track: Option<u8> },
+  // waits for a gdb remote protocol connection on localhost
+  Gdb { rom: PathBuf, port: u16 },
+  // json lines on localhost for scripts and test frameworks
+  Control { rom: PathBuf, port: u16 },
+  // machine monitor on stdin / stdout
+  Monitor { rom: PathBuf, symbols: Option<PathBuf> },
+  // cpu instructions and nes frames per second
+  Bench { rom: PathBuf, instructions: u64, frames: usize },
+  // two players over udp, the host is player 1
+  Netplay { rom: PathBuf, peer: Peer, delay: u32 },
+}
+
+// the arguments without the program name
+pub fn parse(args: impl IntoIterator<Item = String>) -> Result<Command, String> {
+  let mut args = args.into_iter();
+  let command = match args.next() {
+    None => return Ok(Command::Snake),
+    Some(command) => command,
+  };
+  let file = args.next().map(PathBuf::from).ok_or(format!("{} needs a file", command))?;
+  let mut options = RunOptions { rom: file, video: VideoOptions::default(), region: Region::Ntsc, palette: None, trace: None, trace_rotation: None, symbols: None, profile: false, headless: false, frames: None };
+  let mut track = None;
+  let mut port = None;
+  let mut instructions = BENCH_INSTRUCTIONS;
+  let mut peer = None;
+  let mut delay = DEFAULT_INPUT_DELAY;
+
+  while let Some(arg) = args.next() {
+    let mut value = || args.next().ok_or(format!("{} needs a value", arg));
+    match (command.as_str(), arg.as_str()) {
+      ("run", "--scale") => options.video.scale = parse_number(&arg, &value()?)?,
+      ("run", "--scale-mode") => options.video.mode = match value()?.as_str() {
+        "integer" => ScaleMode::Integer,
+        "fit" => ScaleMode::Fit,
+        other => return Err(format!("unknown scale mode {}, expected integer or fit", other)),
+      },
+      ("run", "--aspect-correct") => options.video.aspect_correct = true,
+      ("run", "--region") => options.region = match value()?.as_str() {
+        "ntsc" => Region::Ntsc,
+        "pal" => Region::Pal,
+        other => return Err(format!("unknown region {}, expected ntsc or pal", other)),
+      },
+      ("run", "--palette") => options.palette = Some(value()?),
+      ("run", "--trace") => options.trace = Some(PathBuf::from(value()?)),
+      ("run", "--trace-rotate") => options.trace_rotation = Some(parse_rotation(&value()?)?),
+      ("run" | "disasm" | "monitor", "--symbols") => options.symbols = Some(PathBuf::from(value()?)),
+      ("run", "--profile") => options.profile = true,
+      ("run", "--headless") => options.headless = true,
+      ("run", "--frames") => options.frames = Some(parse_number(&arg, &value()?)?),
+      ("nsf", "--track") => track = Some(parse_number(&arg, &value()?)?),
+      ("gdb" | "control", "--port") => port = Some(parse_number(&arg, &value()?)?),
+      ("bench", "--instructions") => instructions = parse_number(&arg, &value()?)?,
+      ("bench", "--frames") => options.frames = Some(parse_number(&arg, &value()?)?),
+      ("netplay", "--host") if peer.is_none() => peer = Some(Peer::Host(parse_number(&arg, &value()?)?)),
+      ("netplay", "--connect") if peer.is_none() => peer = Some(Peer::Connect(value()?)),
+      ("netplay", "--delay") => delay = parse_number(&arg, &value()?)?,
+      _ => return Err(format!("unexpected argument {} for {}", arg, command)),
+    }
+  }
+
+  match command.as_str() {
+    "run" if options.trace_rotation.is_some() && options.trace.is_none() => Err("--trace-rotate needs --trace".to_string()),
+    "run" if options.headless && options.frames.is_none() => Err("--headless needs --frames".to_string()),
+    "run" if !options.headless && options.frames.is_some() => Err("--frames only works with --headless".to_string()),
+    "run" => Ok(Command::Run(options)),
+    "disasm" => Ok(Command::Disasm { rom: options.rom, symbols: options.symbols }),
+    "info" => Ok(Command::Info(options.rom)),
+    "nsf" => Ok(Command::Nsf { file: options.rom, track }),
+    "gdb" => Ok(Command::Gdb { rom: options.rom, port: port.unwrap_or(gdb::DEFAULT_PORT) }),
+    "control" => Ok(Command::Control { rom: options.rom, port: port.unwrap_or(control::DEFAULT_PORT) }),
+    "monitor" => Ok(Command::Monitor { rom: options.rom, symbols: options.symbols }),
+    "bench" => Ok(Command::Bench { rom: options.rom, instructions, frames: options.frames.unwrap_or(BENCH_FRAMES) }),
+    "netplay" => match peer {
+      Some(peer) => Ok(Command::Netplay { rom: options.rom, peer, delay }),
+      None => Err("netplay needs --host or --connect".to_string()),
+    },
+    _ => Err(format!("unknown command {}", command)),
+  }
+}
+
+fn parse_number<T: std::str::FromStr>(arg: &str, value: &str) -> Result<T, String> {
+  value.parse().map_err(|_| format!("{} expects a number, got {}", arg, value))
+}
+
+// MB,FILES - both at least 1
+fn parse_rotation(value: &str) -> Result<(u64, usize), String> {
+  let invalid = || format!("--trace-rotate expects MB,FILES, got {}", value);
+  let (megabytes, files) = value.split_once(',').ok_or_else(invalid)?;
+  match (megabytes.parse(), files.parse()) {
+    (Ok(megabytes), Ok(files)) if megabytes > 0 && files > 0 => Ok((megabytes, files)),
+    _ => Err(invalid()),
+  }
+}
+
+// errors are prefixed with the file they belong to
+pub fn execute(command: Command) -> Result<(), String> {
+  match command {
+    Command::Info(path) => {
+      let rom = Rom::load(&path).map_err(with_path(&path))?;
+      print!("{}", info(&rom));
+      Ok(())
+    }
+    Command::Disasm { rom: path, symbols } => {
+      let rom = Rom::load(&path).map_err(with_path(&path))?;
+      let symbols = load_symbols(symbols.as_deref(), &rom)?;
+      disasm(&rom, &symbols).iter().for_each(|line| println!("{}", line));
+      Ok(())
+    }
+    Command::Run(options) if options.headless => {
+      run_headless(&options).map_err(with_path(&options.rom))
+    }
+    Command::Gdb { rom, port } => {
+      run_gdb(&rom, port).map_err(with_path(&rom))
+    }
+    Command::Control { rom, port } => {
+      run_control(&rom, port).map_err(with_path(&rom))
+    }
+    Command::Bench { rom: path, instructions, frames } => {
+      let load = || Rom::load(&path).map_err(with_path(&path));
+      let cpu = bench::cpu_instructions(load()?, instructions).map_err(with_path(&path))?;
+      println!("{}", cpu.format());
+      let nes = bench::nes_frames(load()?, frames).map_err(with_path(&path))?;
+      println!("{} {:.1}x realtime", nes.format(), bench::realtime_factor(&nes));
+      Ok(())
+    }
+    Command::Monitor { rom: path, symbols } => {
+      let rom = Rom::load(&path).map_err(with_path(&path))?;
+      let symbols = load_symbols(symbols.as_deref(), &rom)?;
+      let mut nes = Nes::new(rom).map_err(with_path(&path))?;
+      nes.cpu.symbols = symbols;
+      monitor::repl(&mut nes.cpu, io::stdin().lock(), io::stdout()).map_err(|e| e.to_string())
+    }
+    #[cfg(feature = "sdl2")]
+    Command::Run(options) => {
+      let rom = Rom::load(&options.rom).map_err(with_path(&options.rom))?;
+      let mut frontend_options = crate::frontend::FrontendOptions {
+        video: options.video,
+        region: options.region,
+        save_path: Some(crate::battery::save_path(&options.rom)),
+        input_path: Some(PathBuf::from(crate::input::CONFIG_FILE)),
+        ..Default::default()
+      };
+      if let Some(palette) = &options.palette {
+        frontend_options.palette = load_palette(palette)?;
+      }
+      if let Some(trace) = &options.trace {
+        frontend_options.tracer = create_tracer(trace, options.trace_rotation).map_err(with_path(trace))?;
+      }
+      frontend_options.symbols = load_symbols(options.symbols.as_deref(), &rom)?;
+      frontend_options.profile = options.profile;
+      crate::frontend::run(rom, frontend_options).map_err(with_path(&options.rom))
+    }
+    #[cfg(feature = "sdl2")]
+    Command::Netplay { rom: path, peer, delay } => {
+      let rom = Rom::load(&path).map_err(with_path(&path))?;
+      let netplay = crate::netplay::Netplay::open(&peer, delay).map_err(|e| format!("netplay: {}", e))?;
+      match &peer {
+        Peer::Host(port) => println!("waiting for player 2 on port {}", port),
+        Peer::Connect(address) => println!("connecting to {}", address),
+      }
+      // no battery save, both machines have to start from the same state
+      let frontend_options = crate::frontend::FrontendOptions {
+        input_path: Some(PathBuf::from(crate::input::CONFIG_FILE)),
+        netplay: Some(netplay),
+        ..Default::default()
+      };
+      crate::frontend::run(rom, frontend_options).map_err(with_path(&path))
+    }
+    #[cfg(feature = "sdl2")]
+    Command::Nsf { file, track } => {
+      let nsf = crate::nsf::Nsf::load(&file).map_err(with_path(&file))?;
+      let track = track.unwrap_or(nsf.starting_song);
+      crate::frontend::play_nsf(nsf, track).map_err(with_path(&file))
+    }
+    #[cfg(feature = "sdl2")]
+    Command::Snake => {
+      let path = Path::new("snake.nes");
+      Rom::load(path).and_then(crate::snake::run).map_err(with_path(path))
+    }
+    #[cfg(not(feature = "sdl2"))]
+    _ => Err("built without a front-end, enable the sdl2 feature or use run --headless".to_string()),
+  }
+}
+
+fn with_path(path: &Path) -> impl Fn(EmuError) -> String + '_ {
+  move |e| format!("{}: {}", path.display(), e)
+}
+
+#[cfg(feature = "sdl2")]
+fn load_palette(name: &str) -> Result<Palette, String> {
+  Palette::by_name_or_path(name).map_err(|e| format!("{}: {}", name, e))
+}
+
+fn load_symbols(path: Option<&Path>, rom: &Rom) -> Result<Symbols, String> {
+  match path {
+    Some(path) => Symbols::load(path, rom.prg_rom.len()).map_err(with_path(path)),
+    None => Ok(Symbols::new()),
+  }
+}
+
+fn create_tracer(path: &Path, rotation: Option<(u64, usize)>) -> Result<Tracer, EmuError> {
+  let path = path.to_string_lossy();
+  let sink = match rotation {
+    Some((megabytes, files)) => FileSink::rotating(&path, megabytes << 20, files)?,
+    None => FileSink::create(&path)?,
+  };
+  Ok(Tracer::new(sink))
+}
+
+fn run_headless(options: &RunOptions) -> Result<(), EmuError> {
+  let rom = Rom::load(&options.rom)?;
+  let prg_rom_size = rom.prg_rom.len();
+  let mut nes = Nes::new(rom)?;
+  if let Some(symbols) = &options.symbols {
+    nes.cpu.symbols = Symbols::load(symbols, prg_rom_size)?;
+  }
+  if let Some(palette) = &options.palette {
+    nes.cpu.bus.ppu.palette = Palette::by_name_or_path(palette)?;
+  }
+  if let Some(trace) = &options.trace {
+    nes.cpu.tracer = create_tracer(trace, options.trace_rotation)?;
+  }
+  if options.profile {
+    nes.cpu.profiler = Some(Profiler::new());
+  }
+  nes.run_for_frames(options.frames.unwrap_or(0));
+  println!("frames: {}, cpu cycles: {}", nes.frame_count(), nes.cpu.cycles);
+  if let Some(profiler) = &nes.cpu.profiler {
+    print!("{}", profiler.report_with_symbols(PROFILE_ENTRIES, &nes.cpu.symbols));
+  }
+  Ok(())
+}
+
+fn run_gdb(path: &Path, port: u16) -> Result<(), EmuError> {
+  let mut nes = Nes::from_rom_file(path)?;
+  let listener = TcpListener::bind(("127.0.0.1", port))?;
+  println!("waiting for gdb on 127.0.0.1:{}", port);
+  gdb::serve(&mut nes.cpu, &listener)?;
+  Ok(())
+}
+
+fn run_control(path: &Path, port: u16) -> Result<(), EmuError> {
+  let mut control = Control::new(Nes::from_rom_file(path)?);
+  let listener = TcpListener::bind(("127.0.0.1", port))?;
+  println!("waiting for control connections on 127.0.0.1:{}", port);
+  control.serve(&listener)?;
+  Ok(())
+}
+
+pub fn info(rom: &Rom) -> String {
+  let chr = if rom.chr_ram {
+    format!("{} KB ram", rom.chr_rom.len() / 1024)
+  } else {
+    format!("{} KB ({} banks)", rom.chr_rom.len() / 1024, rom.chr_rom.len() / CHR_ROM_PAGE_SIZE)
+  };
+  let mapper = match rom.mapper {
+    0 => "NROM",
+    3 => "CNROM",
+    4 => "MMC3",
+    7 => "AxROM",
+    _ => "unsupported",
+  };
+  format!("prg rom:   {} KB ({} banks)\nchr:       {}\nmapper:    {} ({})\nmirroring: {:?}\nbattery:   {}\n",
+          rom.prg_rom.len() / 1024, rom.prg_rom.len() / PRG_ROM_PAGE_SIZE, chr, rom.mapper, mapper,
+          rom.screen_mirroring, if rom.battery { "yes" } else { "no" })
+}
+
+// 16KB roms are mirrored to $C000, bigger roms are shown bank by bank at $8000
+pub fn disasm(rom: &Rom, symbols: &Symbols) -> Vec<String> {
+  let disassemble = |code: &[u8], base: u16| -> Vec<String> {
+    disassemble_with_labels(code, base, symbols.lookup()).iter().map(|line| line.format()).collect()
+  };
+  if rom.prg_rom.len() <= PRG_ROM_PAGE_SIZE {
+    return disassemble(&rom.prg_rom, 0xC000);
+  }
+  if rom.prg_rom.len() == 2 * PRG_ROM_PAGE_SIZE {
+    return disassemble(&rom.prg_rom, 0x8000);
+  }
+  let mut lines = Vec::new();
+  for (bank, code) in rom.prg_rom.chunks(PRG_ROM_PAGE_SIZE).enumerate() {
+    lines.push(format!("; bank {}", bank));
+    lines.extend(disassemble(code, 0x8000));
+  }
+  lines
+}
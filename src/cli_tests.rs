@@ -0,0 +1,100 @@
+use std::path::PathBuf;
+use crate::cartridge::Rom;
+use crate::cartridge_tests::test_rom_bytes_with_program;
+use crate::cli::{Command, disasm, info, parse, RunOptions};
+use crate::clock::Region;
+use crate::netplay::Peer;
+use crate::symbols::Symbols;
+use crate::video::{ScaleMode, VideoOptions};
+
+fn args(line: &str) -> Vec<String> {
+  line.split_whitespace().map(String::from).collect()
+}
+
+#[test]
+fn test_without_arguments_starts_snake() {
+  assert_eq!(Ok(Command::Snake), parse(args("")));
+}
+
+#[test]
+fn test_run_options() {
+  let command = parse(args("run game.nes --scale 2 --scale-mode fit --aspect-correct --region pal --palette fceux --trace out.log --trace-rotate 64,3 --symbols game.dbg --profile --headless --frames 60"));
+
+  assert_eq!(Ok(Command::Run(RunOptions {
+    rom: PathBuf::from("game.nes"),
+    video: VideoOptions { scale: 2, mode: ScaleMode::Fit, aspect_correct: true },
+    region: Region::Pal,
+    palette: Some("fceux".to_string()),
+    trace: Some(PathBuf::from("out.log")),
+    trace_rotation: Some((64, 3)),
+    symbols: Some(PathBuf::from("game.dbg")),
+    profile: true,
+    headless: true,
+    frames: Some(60),
+  })), command);
+}
+
+#[test]
+fn test_run_defaults() {
+  match parse(args("run game.nes")) {
+    Ok(Command::Run(options)) => {
+      assert_eq!(VideoOptions::default(), options.video);
+      assert_eq!(Region::Ntsc, options.region);
+      assert!(!options.headless);
+    }
+    other => panic!("{:?}", other),
+  }
+}
+
+#[test]
+fn test_other_commands() {
+  assert_eq!(Ok(Command::Info(PathBuf::from("a.nes"))), parse(args("info a.nes")));
+  assert_eq!(Ok(Command::Disasm { rom: PathBuf::from("a.nes"), symbols: None }), parse(args("disasm a.nes")));
+  assert_eq!(Ok(Command::Disasm { rom: PathBuf::from("a.nes"), symbols: Some(PathBuf::from("a.mlb")) }),
+             parse(args("disasm a.nes --symbols a.mlb")));
+  assert_eq!(Ok(Command::Monitor { rom: PathBuf::from("a.nes"), symbols: None }), parse(args("monitor a.nes")));
+  assert_eq!(Ok(Command::Bench { rom: PathBuf::from("a.nes"), instructions: 5000, frames: 60 }),
+             parse(args("bench a.nes --instructions 5000 --frames 60")));
+  assert_eq!(Ok(Command::Nsf { file: PathBuf::from("a.nsf"), track: Some(3) }), parse(args("nsf a.nsf --track 3")));
+  assert_eq!(Ok(Command::Gdb { rom: PathBuf::from("a.nes"), port: 6502 }), parse(args("gdb a.nes")));
+  assert_eq!(Ok(Command::Control { rom: PathBuf::from("a.nes"), port: 6504 }), parse(args("control a.nes")));
+  assert_eq!(Ok(Command::Control { rom: PathBuf::from("a.nes"), port: 7000 }), parse(args("control a.nes --port 7000")));
+  assert_eq!(Ok(Command::Netplay { rom: PathBuf::from("a.nes"), peer: Peer::Host(6503), delay: 2 }),
+             parse(args("netplay a.nes --host 6503")));
+  assert_eq!(Ok(Command::Netplay { rom: PathBuf::from("a.nes"), peer: Peer::Connect("pc:6503".to_string()), delay: 4 }),
+             parse(args("netplay a.nes --connect pc:6503 --delay 4")));
+}
+
+#[test]
+fn test_invalid_arguments() {
+  assert!(parse(args("play game.nes")).is_err());
+  assert!(parse(args("run")).is_err());
+  assert!(parse(args("run game.nes --scale big")).is_err());
+  assert!(parse(args("run game.nes --region secam")).is_err());
+  assert!(parse(args("run game.nes --scale-mode stretch")).is_err());
+  assert!(parse(args("run game.nes --headless")).is_err());
+  assert!(parse(args("run game.nes --frames 10")).is_err());
+  assert!(parse(args("info game.nes --scale 2")).is_err());
+  assert!(parse(args("run game.nes --trace")).is_err());
+  assert!(parse(args("run game.nes --trace-rotate 64,3")).is_err());
+  assert!(parse(args("run game.nes --trace out.log --trace-rotate 64")).is_err());
+  assert!(parse(args("run game.nes --trace out.log --trace-rotate 0,3")).is_err());
+  assert!(parse(args("netplay game.nes")).is_err());
+  assert!(parse(args("netplay game.nes --host 1 --connect pc:1")).is_err());
+}
+
+#[test]
+fn test_info_and_disasm() {
+  let rom = Rom::new(&test_rom_bytes_with_program(&[0xA9, 0x01])).unwrap();
+
+  let info = info(&rom);
+  assert!(info.contains("mapper:    3 (CNROM)"), "{}", info);
+
+  let lines = disasm(&rom, &Symbols::new());
+  assert!(lines[0].ends_with("LDA #$01"), "{}", lines[0]);
+
+  let mut symbols = Symbols::new();
+  symbols.add(0x8000, "reset");
+  let lines = disasm(&rom, &symbols);
+  assert!(lines[0].starts_with("reset:\n8000"), "{}", lines[0]);
+}
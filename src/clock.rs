@@ -0,0 +1,74 @@
+// master clock of the console, every component runs on a divider of it (ntsc):
+// the cpu ticks every 12 master cycles, the ppu every 4 (three dots per cpu cycle).
+// The cpu runs ahead one instruction at a time, the bus then catches the other
+// components up to the same point in time instead of interleaving them cycle by cycle.
+pub const CPU_DIVIDER: u64 = 12;
+pub const PPU_DIVIDER: u64 = 4;
+
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct Clock {
+  // master cycles each component has been run for
+  cpu: u64,
+  ppu: u64,
+  apu: u64,
+}
+
+impl Clock {
+  pub fn new() -> Self {
+    Clock::default()
+  }
+
+  pub fn advance_cpu(&mut self, cycles: u64) {
+    self.cpu += cycles * CPU_DIVIDER;
+  }
+
+  pub fn cpu_cycles(&self) -> u64 {
+    self.cpu / CPU_DIVIDER
+  }
+
+  // dots the ppu still has to run to reach the cpu
+  pub fn ppu_behind(&self) -> u64 {
+    (self.cpu - self.ppu) / PPU_DIVIDER
+  }
+
+  pub fn ppu_ran(&mut self, dots: u64) {
+    self.ppu += dots * PPU_DIVIDER;
+  }
+
+  // the apu is clocked with the cpu
+  pub fn apu_behind(&self) -> u64 {
+    (self.cpu - self.apu) / CPU_DIVIDER
+  }
+
+  pub fn apu_ran(&mut self, cycles: u64) {
+    self.apu += cycles * CPU_DIVIDER;
+  }
+
+  pub fn in_sync(&self) -> bool {
+    self.ppu_behind() == 0 && self.apu_behind() == 0
+  }
+
+  // save states only hold the cpu position, everything else is caught up before saving
+  pub fn sync_to_cpu_cycles(&mut self, cycles: u64) {
+    self.cpu = cycles * CPU_DIVIDER;
+    self.ppu = self.cpu;
+    self.apu = self.cpu;
+  }
+}
+
+// only ntsc timing is emulated, the region sets the pace of the front-end
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Region {
+  #[default]
+  Ntsc,
+  Pal,
+}
+
+impl Region {
+  pub fn frames_per_second(&self) -> f64 {
+    match self {
+      Region::Ntsc => 60.0988,
+      Region::Pal => 50.007,
+    }
+  }
+}
@@ -0,0 +1,37 @@
+use crate::clock::Clock;
+
+#[test]
+fn test_ppu_runs_three_dots_per_cpu_cycle() {
+  let mut clock = Clock::new();
+
+  clock.advance_cpu(7);
+
+  assert_eq!(7, clock.cpu_cycles());
+  assert_eq!(21, clock.ppu_behind());
+  assert_eq!(7, clock.apu_behind());
+  assert!(!clock.in_sync());
+}
+
+#[test]
+fn test_components_catch_up_with_the_cpu() {
+  let mut clock = Clock::new();
+  clock.advance_cpu(2);
+
+  clock.ppu_ran(4);
+  assert_eq!(2, clock.ppu_behind());
+  clock.ppu_ran(2);
+  clock.apu_ran(2);
+
+  assert!(clock.in_sync());
+}
+
+#[test]
+fn test_sync_to_cpu_cycles_puts_everything_at_the_same_point() {
+  let mut clock = Clock::new();
+  clock.advance_cpu(5);
+
+  clock.sync_to_cpu_cycles(100);
+
+  assert_eq!(100, clock.cpu_cycles());
+  assert!(clock.in_sync());
+}
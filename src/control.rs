@@ -0,0 +1,397 @@
+use std::fmt;
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::TcpListener;
+use std::path::Path;
+use crate::input::{button_by_name, Player};
+use crate::joypad::JoypadButton;
+use crate::nes::Nes;
+
+// json lines over tcp for test frameworks and scripts in other languages, one request object
+// per line and one reply object per line:
+//   {"cmd":"load","path":"game.nes"}                     power on with another rom
+//   {"cmd":"buttons","player":1,"pressed":["A","RIGHT"]} held until the next buttons request
+//   {"cmd":"frames","count":60}                          run, replies with the frame count
+//   {"cmd":"read","addr":768,"len":16}                   cpu memory as a list of numbers
+//   {"cmd":"write","addr":768,"data":[1,2]}              rom is not writable
+//   {"cmd":"screenshot"}                                 last frame as base64 png
+//   {"cmd":"quit"}                                       stops the server
+// replies have "ok":true and the results or "ok":false and an "error"
+
+pub const DEFAULT_PORT: u16 = 6504;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Json {
+  Null,
+  Bool(bool),
+  Number(f64),
+  String(String),
+  Array(Vec<Json>),
+  // keeps the order of the keys
+  Object(Vec<(String, Json)>),
+}
+
+impl Json {
+  pub fn parse(text: &str) -> Result<Json, String> {
+    let mut parser = Parser { text: text.as_bytes(), pos: 0 };
+    let value = parser.value()?;
+    parser.skip_whitespace();
+    if parser.pos != parser.text.len() {
+      return Err(format!("unexpected data at {}", parser.pos));
+    }
+    Ok(value)
+  }
+
+  pub fn get(&self, key: &str) -> Option<&Json> {
+    match self {
+      Json::Object(entries) => entries.iter().find(|(k, _)| k == key).map(|(_, v)| v),
+      _ => None,
+    }
+  }
+
+  pub fn as_str(&self) -> Option<&str> {
+    match self {
+      Json::String(s) => Some(s),
+      _ => None,
+    }
+  }
+
+  // only whole, non-negative numbers
+  pub fn as_u64(&self) -> Option<u64> {
+    match self {
+      Json::Number(n) if *n >= 0.0 && n.fract() == 0.0 && *n <= u64::MAX as f64 => Some(*n as u64),
+      _ => None,
+    }
+  }
+
+  pub fn as_array(&self) -> Option<&[Json]> {
+    match self {
+      Json::Array(values) => Some(values),
+      _ => None,
+    }
+  }
+}
+
+impl fmt::Display for Json {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self {
+      Json::Null => write!(f, "null"),
+      Json::Bool(b) => write!(f, "{}", b),
+      Json::Number(n) => write!(f, "{}", n),
+      Json::String(s) => write_string(f, s),
+      Json::Array(values) => {
+        write!(f, "[")?;
+        for (i, value) in values.iter().enumerate() {
+          if i > 0 {
+            write!(f, ",")?;
+          }
+          write!(f, "{}", value)?;
+        }
+        write!(f, "]")
+      }
+      Json::Object(entries) => {
+        write!(f, "{{")?;
+        for (i, (key, value)) in entries.iter().enumerate() {
+          if i > 0 {
+            write!(f, ",")?;
+          }
+          write_string(f, key)?;
+          write!(f, ":{}", value)?;
+        }
+        write!(f, "}}")
+      }
+    }
+  }
+}
+
+fn write_string(f: &mut fmt::Formatter, s: &str) -> fmt::Result {
+  write!(f, "\"")?;
+  for c in s.chars() {
+    match c {
+      '"' => write!(f, "\\\"")?,
+      '\\' => write!(f, "\\\\")?,
+      '\n' => write!(f, "\\n")?,
+      '\r' => write!(f, "\\r")?,
+      '\t' => write!(f, "\\t")?,
+      c if (c as u32) < 0x20 => write!(f, "\\u{:04x}", c as u32)?,
+      c => write!(f, "{}", c)?,
+    }
+  }
+  write!(f, "\"")
+}
+
+struct Parser<'a> {
+  text: &'a [u8],
+  pos: usize,
+}
+
+impl Parser<'_> {
+  fn skip_whitespace(&mut self) {
+    while matches!(self.text.get(self.pos), Some(b' ' | b'\t' | b'\n' | b'\r')) {
+      self.pos += 1;
+    }
+  }
+
+  fn expect(&mut self, byte: u8) -> Result<(), String> {
+    self.skip_whitespace();
+    if self.text.get(self.pos) != Some(&byte) {
+      return Err(format!("expected '{}' at {}", byte as char, self.pos));
+    }
+    self.pos += 1;
+    Ok(())
+  }
+
+  fn literal(&mut self, word: &str, value: Json) -> Result<Json, String> {
+    if !self.text[self.pos..].starts_with(word.as_bytes()) {
+      return Err(format!("unexpected data at {}", self.pos));
+    }
+    self.pos += word.len();
+    Ok(value)
+  }
+
+  fn value(&mut self) -> Result<Json, String> {
+    self.skip_whitespace();
+    match self.text.get(self.pos) {
+      None => Err("unexpected end".to_string()),
+      Some(b'n') => self.literal("null", Json::Null),
+      Some(b't') => self.literal("true", Json::Bool(true)),
+      Some(b'f') => self.literal("false", Json::Bool(false)),
+      Some(b'"') => self.string().map(Json::String),
+      Some(b'[') => {
+        self.pos += 1;
+        let mut values = Vec::new();
+        self.skip_whitespace();
+        if self.text.get(self.pos) == Some(&b']') {
+          self.pos += 1;
+          return Ok(Json::Array(values));
+        }
+        loop {
+          values.push(self.value()?);
+          self.skip_whitespace();
+          match self.text.get(self.pos) {
+            Some(b',') => self.pos += 1,
+            Some(b']') => {
+              self.pos += 1;
+              return Ok(Json::Array(values));
+            }
+            _ => return Err(format!("expected ',' or ']' at {}", self.pos)),
+          }
+        }
+      }
+      Some(b'{') => {
+        self.pos += 1;
+        let mut entries = Vec::new();
+        self.skip_whitespace();
+        if self.text.get(self.pos) == Some(&b'}') {
+          self.pos += 1;
+          return Ok(Json::Object(entries));
+        }
+        loop {
+          self.skip_whitespace();
+          let key = self.string()?;
+          self.expect(b':')?;
+          entries.push((key, self.value()?));
+          self.skip_whitespace();
+          match self.text.get(self.pos) {
+            Some(b',') => self.pos += 1,
+            Some(b'}') => {
+              self.pos += 1;
+              return Ok(Json::Object(entries));
+            }
+            _ => return Err(format!("expected ',' or '}}' at {}", self.pos)),
+          }
+        }
+      }
+      Some(_) => self.number(),
+    }
+  }
+
+  fn number(&mut self) -> Result<Json, String> {
+    let start = self.pos;
+    while matches!(self.text.get(self.pos), Some(b'-' | b'+' | b'.' | b'e' | b'E' | b'0'..=b'9')) {
+      self.pos += 1;
+    }
+    std::str::from_utf8(&self.text[start..self.pos]).ok()
+      .and_then(|number| number.parse().ok())
+      .map(Json::Number)
+      .ok_or(format!("invalid value at {}", start))
+  }
+
+  fn string(&mut self) -> Result<String, String> {
+    self.expect(b'"')?;
+    let mut bytes = Vec::new();
+    loop {
+      let byte = *self.text.get(self.pos).ok_or("unterminated string")?;
+      self.pos += 1;
+      match byte {
+        b'"' => break,
+        b'\\' => {
+          let escaped = *self.text.get(self.pos).ok_or("unterminated string")?;
+          self.pos += 1;
+          let c = match escaped {
+            b'"' => '"',
+            b'\\' => '\\',
+            b'/' => '/',
+            b'b' => '\u{8}',
+            b'f' => '\u{c}',
+            b'n' => '\n',
+            b'r' => '\r',
+            b't' => '\t',
+            // surrogate pairs are not combined, they become replacement characters
+            b'u' => {
+              let hex = self.text.get(self.pos..self.pos + 4).ok_or("unterminated string")?;
+              self.pos += 4;
+              let code = std::str::from_utf8(hex).ok().and_then(|hex| u32::from_str_radix(hex, 16).ok())
+                .ok_or(format!("invalid escape at {}", self.pos - 6))?;
+              char::from_u32(code).unwrap_or(char::REPLACEMENT_CHARACTER)
+            }
+            _ => return Err(format!("invalid escape at {}", self.pos - 2)),
+          };
+          bytes.extend_from_slice(c.encode_utf8(&mut [0; 4]).as_bytes());
+        }
+        byte => bytes.push(byte),
+      }
+    }
+    String::from_utf8(bytes).map_err(|_| "invalid utf-8 in string".to_string())
+  }
+}
+
+// what the server does after a request
+#[derive(Debug, PartialEq)]
+pub enum Action {
+  Reply(Json),
+  // reply and stop serving
+  Quit(Json),
+}
+
+// one machine for all connections, a client can pick up where the last one stopped
+pub struct Control {
+  pub nes: Nes,
+}
+
+impl Control {
+  pub fn new(nes: Nes) -> Self {
+    Control { nes }
+  }
+
+  pub fn handle(&mut self, line: &str) -> Action {
+    let request = match Json::parse(line) {
+      Ok(request) => request,
+      Err(e) => return Action::Reply(error(format!("invalid json: {}", e))),
+    };
+    let command = request.get("cmd").and_then(Json::as_str).unwrap_or_default();
+    if command == "quit" {
+      return Action::Quit(ok(Vec::new()));
+    }
+    Action::Reply(match self.execute(command, &request) {
+      Ok(results) => ok(results),
+      Err(e) => error(e),
+    })
+  }
+
+  fn execute(&mut self, command: &str, request: &Json) -> Result<Vec<(String, Json)>, String> {
+    let number = |key: &str| request.get(key).and_then(Json::as_u64).ok_or(format!("{} needs a number {}", command, key));
+    match command {
+      "load" => {
+        let path = request.get("path").and_then(Json::as_str).ok_or("load needs a path")?;
+        self.nes = Nes::from_rom_file(Path::new(path)).map_err(|e| format!("{}: {}", path, e))?;
+        Ok(Vec::new())
+      }
+      "buttons" => {
+        let player = match number("player")? {
+          1 => Player::One,
+          2 => Player::Two,
+          player => return Err(format!("there is no player {}", player)),
+        };
+        let pressed = request.get("pressed").and_then(Json::as_array).ok_or("buttons needs a list pressed")?;
+        let mut buttons = JoypadButton::empty();
+        for name in pressed {
+          let name = name.as_str().unwrap_or_default();
+          buttons |= button_by_name(&name.to_uppercase()).ok_or(format!("unknown button {:?}", name))?;
+        }
+        self.nes.set_buttons(player, buttons);
+        Ok(Vec::new())
+      }
+      "frames" => {
+        self.nes.run_for_frames(number("count")? as usize);
+        Ok(vec![("frame".to_string(), Json::Number(self.nes.frame_count() as f64))])
+      }
+      "read" => {
+        let addr = address(number("addr")?)?;
+        let len = number("len")?.min(0x10000) as u16;
+        let data = (0..len).map(|i| Json::Number(self.nes.cpu.bus.peek(addr.wrapping_add(i)) as f64)).collect();
+        Ok(vec![("data".to_string(), Json::Array(data))])
+      }
+      "write" => {
+        let addr = address(number("addr")?)?;
+        let data = request.get("data").and_then(Json::as_array).ok_or("write needs a list data")?;
+        let values = data.iter()
+          .map(|value| value.as_u64().filter(|v| *v <= 0xFF).map(|v| v as u8))
+          .collect::<Option<Vec<u8>>>()
+          .ok_or("data has to be bytes")?;
+        for (i, value) in values.into_iter().enumerate() {
+          let cpu = &mut self.nes.cpu;
+          cpu.memory_editor.poke(&mut cpu.bus, addr.wrapping_add(i as u16), value)?;
+        }
+        Ok(Vec::new())
+      }
+      "screenshot" => {
+        Ok(vec![("png".to_string(), Json::String(base64(&self.nes.frame().to_png())))])
+      }
+      "" => Err("missing cmd".to_string()),
+      _ => Err(format!("unknown cmd {}", command)),
+    }
+  }
+
+  // serves one connection after the other until a client sends quit
+  pub fn serve(&mut self, listener: &TcpListener) -> io::Result<()> {
+    loop {
+      let (stream, _) = listener.accept()?;
+      stream.set_nodelay(true)?;
+      let mut writer = stream.try_clone()?;
+      for line in BufReader::new(stream).lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+          continue;
+        }
+        match self.handle(&line) {
+          Action::Reply(reply) => writeln!(writer, "{}", reply)?,
+          Action::Quit(reply) => {
+            writeln!(writer, "{}", reply)?;
+            return Ok(());
+          }
+        }
+      }
+    }
+  }
+}
+
+fn address(value: u64) -> Result<u16, String> {
+  u16::try_from(value).map_err(|_| format!("address {} is out of range", value))
+}
+
+fn ok(mut results: Vec<(String, Json)>) -> Json {
+  results.insert(0, ("ok".to_string(), Json::Bool(true)));
+  Json::Object(results)
+}
+
+fn error(message: String) -> Json {
+  Json::Object(vec![("ok".to_string(), Json::Bool(false)), ("error".to_string(), Json::String(message))])
+}
+
+const BASE64: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+pub fn base64(data: &[u8]) -> String {
+  let mut encoded = String::with_capacity(data.len().div_ceil(3) * 4);
+  for chunk in data.chunks(3) {
+    let bytes = [chunk[0], *chunk.get(1).unwrap_or(&0), *chunk.get(2).unwrap_or(&0)];
+    let bits = u32::from_be_bytes([0, bytes[0], bytes[1], bytes[2]]);
+    for i in 0..4 {
+      if i <= chunk.len() {
+        encoded.push(BASE64[(bits >> (18 - 6 * i) & 0x3F) as usize] as char);
+      } else {
+        encoded.push('=');
+      }
+    }
+  }
+  encoded
+}
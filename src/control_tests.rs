@@ -0,0 +1,130 @@
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::thread;
+use crate::cartridge_tests::{create_test_rom_with_program, test_rom_bytes_with_program};
+use crate::control::{Action, base64, Control, Json};
+use crate::nes::Nes;
+
+// copies the first button of both joypads to $10 / $11, forever
+fn init_control() -> Control {
+  let program = [
+    0xA9, 0x01, 0x8D, 0x16, 0x40, // loop: LDA #$01, STA $4016
+    0xA9, 0x00, 0x8D, 0x16, 0x40, // LDA #$00, STA $4016
+    0xAD, 0x16, 0x40, 0x85, 0x10, // LDA $4016, STA $10
+    0xAD, 0x17, 0x40, 0x85, 0x11, // LDA $4017, STA $11
+    0x4C, 0x00, 0x80,             // JMP loop
+  ];
+  Control::new(Nes::new(create_test_rom_with_program(&program)).unwrap())
+}
+
+fn reply(control: &mut Control, request: &str) -> String {
+  match control.handle(request) {
+    Action::Reply(reply) => reply.to_string(),
+    other => panic!("{:?}", other),
+  }
+}
+
+#[test]
+fn test_json() {
+  let value = Json::parse(r#" {"cmd": "read", "list": [1, -2.5, true, null], "text": "a\"é\n"} "#).unwrap();
+
+  assert_eq!(Some("read"), value.get("cmd").and_then(Json::as_str));
+  assert_eq!(Some(&Json::Number(-2.5)), value.get("list").and_then(Json::as_array).map(|list| &list[1]));
+  assert_eq!(None, Json::Number(-2.5).as_u64());
+  assert_eq!(r#"{"cmd":"read","list":[1,-2.5,true,null],"text":"a\"é\n"}"#, value.to_string());
+
+  assert!(Json::parse("{\"cmd\":}").is_err());
+  assert!(Json::parse("[1,2").is_err());
+  assert!(Json::parse("{} {}").is_err());
+}
+
+#[test]
+fn test_base64() {
+  assert_eq!("", base64(b""));
+  assert_eq!("Zg==", base64(b"f"));
+  assert_eq!("Zm8=", base64(b"fo"));
+  assert_eq!("Zm9vYmFy", base64(b"foobar"));
+}
+
+#[test]
+fn test_buttons_frames_and_memory() {
+  let mut control = init_control();
+
+  assert_eq!(r#"{"ok":true}"#, reply(&mut control, r#"{"cmd":"buttons","player":2,"pressed":["a","start"]}"#));
+  assert_eq!(r#"{"ok":true,"frame":2}"#, reply(&mut control, r#"{"cmd":"frames","count":2}"#));
+  // player 2 holds A
+  assert_eq!(r#"{"ok":true,"data":[0,1]}"#, reply(&mut control, r#"{"cmd":"read","addr":16,"len":2}"#));
+
+  assert_eq!(r#"{"ok":true}"#, reply(&mut control, r#"{"cmd":"write","addr":768,"data":[171,205]}"#));
+  assert_eq!(r#"{"ok":true,"data":[171,205]}"#, reply(&mut control, r#"{"cmd":"read","addr":768,"len":2}"#));
+}
+
+#[test]
+fn test_screenshot_is_a_png() {
+  let mut control = init_control();
+  control.nes.run_for_frames(1);
+
+  let reply = Json::parse(&reply(&mut control, r#"{"cmd":"screenshot"}"#)).unwrap();
+
+  let png = reply.get("png").and_then(Json::as_str).unwrap();
+  assert_eq!(base64(&control.nes.frame().to_png()), png);
+  // the png signature
+  assert!(png.starts_with("iVBORw0KGgo"));
+}
+
+#[test]
+fn test_load_starts_another_rom() {
+  let path = std::env::temp_dir().join(format!("nes_emulator_control_{}.nes", std::process::id()));
+  // LDA #$42, STA $20, loop: JMP loop
+  std::fs::write(&path, test_rom_bytes_with_program(&[0xA9, 0x42, 0x85, 0x20, 0x4C, 0x04, 0x80])).unwrap();
+  let mut control = init_control();
+  control.nes.run_for_frames(1);
+
+  let loaded = reply(&mut control, &format!(r#"{{"cmd":"load","path":{}}}"#, Json::String(path.to_string_lossy().into_owned())));
+  control.nes.run_for_frames(1);
+  std::fs::remove_file(&path).unwrap();
+
+  assert_eq!(r#"{"ok":true}"#, loaded);
+  assert_eq!(1, control.nes.frame_count());
+  assert_eq!(0x42, control.nes.cpu.bus.ram()[0x20]);
+}
+
+#[test]
+fn test_errors() {
+  let mut control = init_control();
+
+  assert!(reply(&mut control, "frames 2").starts_with(r#"{"ok":false,"error":"invalid json"#));
+  assert_eq!(r#"{"ok":false,"error":"missing cmd"}"#, reply(&mut control, "{}"));
+  assert_eq!(r#"{"ok":false,"error":"unknown cmd rewind"}"#, reply(&mut control, r#"{"cmd":"rewind"}"#));
+  assert_eq!(r#"{"ok":false,"error":"frames needs a number count"}"#, reply(&mut control, r#"{"cmd":"frames","count":-1}"#));
+  assert_eq!(r#"{"ok":false,"error":"unknown button \"TURBO\""}"#,
+             reply(&mut control, r#"{"cmd":"buttons","player":1,"pressed":["TURBO"]}"#));
+  assert_eq!(r#"{"ok":false,"error":"$8000 is not writable"}"#, reply(&mut control, r#"{"cmd":"write","addr":32768,"data":[0]}"#));
+  assert!(reply(&mut control, r#"{"cmd":"load","path":"missing.nes"}"#).starts_with(r#"{"ok":false,"error":"missing.nes: "#));
+}
+
+#[test]
+fn test_session_over_tcp() {
+  let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+  let address = listener.local_addr().unwrap();
+  let client = thread::spawn(move || {
+    let mut replies = Vec::new();
+    // the machine is kept between connections
+    for requests in [vec![r#"{"cmd":"write","addr":16,"data":[7]}"#], vec!["", r#"{"cmd":"read","addr":16,"len":1}"#, r#"{"cmd":"quit"}"#]] {
+      let mut stream = TcpStream::connect(address).unwrap();
+      let mut lines = BufReader::new(stream.try_clone().unwrap()).lines();
+      for request in requests {
+        writeln!(stream, "{}", request).unwrap();
+        if !request.is_empty() {
+          replies.push(lines.next().unwrap().unwrap());
+        }
+      }
+    }
+    replies
+  });
+
+  let mut control = init_control();
+  control.serve(&listener).unwrap();
+
+  assert_eq!(vec![r#"{"ok":true}"#, r#"{"ok":true,"data":[7]}"#, r#"{"ok":true}"#], client.join().unwrap());
+}
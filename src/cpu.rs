@@ -1,10 +1,22 @@
-use std::collections::HashMap;
-use std::ops::{BitAnd, BitOr, BitXor};
-use crate::bus::Bus;
+use std::fmt;
+use std::ops::{BitAnd, BitOr, BitXor, ControlFlow};
+use std::time::{Duration, Instant};
+use crate::breakpoints::{Breakpoint, Breakpoints, DebugEvent};
+use crate::bus::{Bus, BusAccess};
+use crate::call_stack::CallStack;
+use crate::decode_cache::{DecodeCache, DecodedInstruction};
+use crate::history::{ExecutedInstruction, ExecutionHistory};
+use crate::memory_editor::MemoryEditor;
 use crate::opcodes;
+use crate::profiler::Profiler;
+use crate::savestate::{StateReader, StateWriter, Stateful};
+use crate::snapshot::Snapshot;
+use crate::symbols::Symbols;
+use crate::trace::{TraceRecord, Tracer};
 
 bitflags! {
   // https://wiki.nesdev.org/w/index.php/Status_flags#The_B_flag
+  #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
   pub struct CpuFlags: u8 {
     const CARRY = 0x01;
     const ZERO = 0x02;
@@ -21,17 +33,286 @@ bitflags! {
 const STACK_AREA: u16 = 0x0100;
 const STACK_RESET: u8 = 0xFF;
 
-pub struct MyCPU {
+pub type Handler<B = Bus> = fn(&mut MyCPU<B>, &AddressingMode);
+pub type HandlerTable<B = Bus> = [Option<Handler<B>>; 256];
+
+pub fn has_handler(code: u8) -> bool {
+  dispatch_table::<Bus>()[code as usize].is_some()
+}
+
+// decoded once from the opcode table: opcode -> instruction implementation
+pub fn dispatch_table<B: CpuBus>() -> HandlerTable<B> {
+  let mut table: HandlerTable<B> = [None; 256];
+  for op in opcodes::CPU_OPS_CODES.iter() {
+    table[op.code as usize] = handler(op.mnemonic);
+  }
+  table
+}
+
+fn handler<B: CpuBus>(mnemonic: &str) -> Option<Handler<B>> {
+  let handler: Handler<B> = match mnemonic {
+    "ADC" => MyCPU::adc,
+    "AND" => MyCPU::and,
+    "ASL" => MyCPU::asl,
+    "BCC" => |cpu, _| cpu.bcc(),
+    "BCS" => |cpu, _| cpu.bcs(),
+    "BEQ" => |cpu, _| cpu.beq(),
+    "BMI" => |cpu, _| cpu.bmi(),
+    "BNE" => |cpu, _| cpu.bne(),
+    "BPL" => |cpu, _| cpu.bpl(),
+    "BVC" => |cpu, _| cpu.bvc(),
+    "BVS" => |cpu, _| cpu.bvs(),
+    "BIT" => MyCPU::bit,
+    "CLC" => |cpu, _| cpu.clc(),
+    "CLD" => |cpu, _| cpu.cld(),
+    "CLI" => |cpu, _| cpu.cli(),
+    "CLV" => |cpu, _| cpu.clv(),
+    "CMP" => MyCPU::cmp,
+    "CPX" => MyCPU::cpx,
+    "CPY" => MyCPU::cpy,
+    "DEC" => MyCPU::dec,
+    "DEX" => |cpu, _| cpu.dex(),
+    "DEY" => |cpu, _| cpu.dey(),
+    "EOR" => MyCPU::eor,
+    "INC" => MyCPU::inc,
+    "INX" => |cpu, _| cpu.inx(),
+    "INY" => |cpu, _| cpu.iny(),
+    "JMP" => MyCPU::jmp,
+    "JSR" => |cpu, _| cpu.jsr(),
+    "LDA" => MyCPU::lda,
+    "LDX" => MyCPU::ldx,
+    "LDY" => MyCPU::ldy,
+    "LSR" => MyCPU::lsr,
+    "NOP" => |cpu, _| cpu.nop(),
+    "ORA" => MyCPU::ora,
+    "STA" => MyCPU::sta,
+    "STX" => MyCPU::stx,
+    "STY" => MyCPU::sty,
+    "PHA" => |cpu, _| cpu.pha(),
+    "PHP" => |cpu, _| cpu.php(),
+    "PLA" => |cpu, _| cpu.pla(),
+    "PLP" => |cpu, _| cpu.plp(),
+    "ROL" => MyCPU::rol,
+    "ROR" => MyCPU::ror,
+    "RTS" => |cpu, _| cpu.rts(),
+    "RTI" => |cpu, _| cpu.rti(),
+    "SBC" => MyCPU::sbc,
+    "SEC" => |cpu, _| cpu.sec(),
+    "SED" => |cpu, _| cpu.sed(),
+    "SEI" => |cpu, _| cpu.sei(),
+    "TAX" => |cpu, _| cpu.tax(),
+    "TAY" => |cpu, _| cpu.tay(),
+    "TSX" => |cpu, _| cpu.tsx(),
+    "TXA" => |cpu, _| cpu.txa(),
+    "TXS" => |cpu, _| cpu.txs(),
+    "TYA" => |cpu, _| cpu.tya(),
+    // unofficial
+    "*NOP" => MyCPU::nop_read,
+    "*SBC" => MyCPU::sbc,
+    "*LAX" => MyCPU::lax,
+    "*SAX" => MyCPU::sax,
+    "*DCP" => MyCPU::dcp,
+    "*ISB" => MyCPU::isb,
+    "*SLO" => MyCPU::slo,
+    "*RLA" => MyCPU::rla,
+    "*SRE" => MyCPU::sre,
+    "*RRA" => MyCPU::rra,
+    "*AXS" => MyCPU::axs,
+    "*ARR" => MyCPU::arr,
+    "*ANC" => MyCPU::anc,
+    "*ALR" => MyCPU::alr,
+    "*LXA" => MyCPU::lxa,
+    "*XAA" => MyCPU::xaa,
+    "*LAS" => MyCPU::las,
+    "*TAS" => MyCPU::tas,
+    "*AHX" => MyCPU::ahx,
+    "*SHX" => MyCPU::shx,
+    "*SHY" => MyCPU::shy,
+    _ => return None,
+  };
+  Some(handler)
+}
+
+// everything the cpu needs from the machine around it. The NES bus implements all of it,
+// other machines can get away with plain memory access
+pub trait CpuBus: MyMem {
+  // debugger access, without side effects
+  fn peek(&self, addr: u16) -> u8;
+
+  fn poke(&mut self, addr: u16, data: u8) -> bool;
+
+  // where load() puts a program
+  fn load_address(&self) -> u16 {
+    0x0600
+  }
+
+  fn reset_vector(&self) -> u16 {
+    0xFFFC
+  }
+
+  // cpu cycles an instruction (or dma, interrupt) took
+  fn tick(&mut self, _cycles: u16) {}
+
+  // address of the instruction that is about to run, for logs of its writes
+  fn begin_instruction(&mut self, _program_counter: u16) {}
+
+  fn poll_nmi_status(&mut self) -> Option<u8> {
+    None
+  }
+
+  fn nmi_pending(&self) -> bool {
+    false
+  }
+
+  fn irq_pending(&self) -> bool {
+    false
+  }
+
+  // cpu cycles a dma started by the last instruction halts the cpu
+  fn take_dma_stall(&mut self) -> u16 {
+    0
+  }
+
+  // position inside immutable rom, instructions there are decoded only once
+  fn prg_rom_offset(&self, _addr: u16) -> Option<usize> {
+    None
+  }
+
+  fn record_accesses(&mut self, _enabled: bool) {}
+
+  fn take_accesses(&mut self) -> Vec<BusAccess> {
+    Vec::new()
+  }
+
+  fn drain_events<F: FnMut(DebugEvent)>(&mut self, _callback: F) {}
+}
+
+pub struct MyCPU<B: CpuBus = Bus> {
   pub register_a: u8,
   pub register_x: u8,
   pub register_y: u8,
   pub status: CpuFlags,
   pub program_counter: u16,
   pub stack_pointer: u8,
-  pub bus: Bus,
+  pub bus: B,
+  pub history: ExecutionHistory,
+  pub call_stack: CallStack,
+  pub profiler: Option<Profiler>,
+  pub cycles: usize,
+  pub tracer: Tracer,
+  pub memory_editor: MemoryEditor,
+  pub breakpoints: Breakpoints,
+  // labels for traces and crash dumps
+  pub symbols: Symbols,
+  pub decode_cache: DecodeCache<B>,
+  pub stop_condition: StopCondition,
+  pub limits: RunLimits,
+  pub state: CpuState,
+  pub variant: CpuVariant,
+  handlers: Box<HandlerTable<B>>,
+}
+
+// the 2A03 in the NES has the decimal mode of the 6502 cut out, the flag can still be set
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CpuVariant {
+  Nes,
+  // NMOS 6502: ADC/SBC use BCD arithmetic while the D flag is set
+  Nmos6502,
 }
 
-#[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CpuState {
+  Running,
+  // hit a KIL opcode, nothing is executed until the next reset
+  Jammed { code: u8, program_counter: u16 },
+}
+
+// decides when run() returns, breakpoints can still stop it on top of that
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum StopCondition {
+  // BRK ends run() instead of jumping through $FFFE, handy for test programs
+  Brk,
+  // before executing the instruction at this address
+  ProgramCounter(u16),
+  // after this many instructions
+  Instructions(u64),
+  // real emulation, BRK is a regular interrupt
+  Never,
+}
+
+// safety net for run(), an accidental endless loop ends with RunExit::LimitReached instead of hanging
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct RunLimits {
+  pub instructions: Option<u64>,
+  pub time: Option<Duration>,
+}
+
+// the clock is only read every this many instructions
+const TIME_LIMIT_INTERVAL: u64 = 1024;
+
+// why run() returned
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RunExit {
+  // BRK with StopCondition::Brk
+  Brk,
+  // StopCondition::ProgramCounter or StopCondition::Instructions
+  StopCondition,
+  Breakpoint(Breakpoint),
+  Jammed { code: u8, program_counter: u16 },
+  LimitReached,
+  // the callback returned ControlFlow::Break
+  Requested,
+}
+
+// load_with_address was given more bytes than fit between the start address and $FFFF
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProgramTooLarge {
+  pub start_address: u16,
+  pub len: usize,
+}
+
+impl fmt::Display for ProgramTooLarge {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(f, "program of {} bytes at ${:04X} ends {} byte(s) past $FFFF",
+           self.len, self.start_address, self.start_address as usize + self.len - 0x10000)
+  }
+}
+
+impl std::error::Error for ProgramTooLarge {}
+
+// what a single step() executed
+#[derive(Debug, Clone, PartialEq)]
+pub struct StepInfo {
+  pub program_counter: u16,
+  pub opcode: u8,
+  pub mnemonic: &'static str,
+  operands: [u8; 2],
+  operand_count: u8,
+  pub effective_address: Option<u16>,
+  // including interrupt sequence and dma stall
+  pub cycles: usize,
+  pub page_crossed: bool,
+}
+
+impl StepInfo {
+  pub fn operands(&self) -> &[u8] {
+    &self.operands[..self.operand_count as usize]
+  }
+}
+
+struct Interrupt {
+  vector: u16,
+  break_flag: bool,
+  cycles: u8,
+}
+
+const NMI: Interrupt = Interrupt { vector: 0xFFFA, break_flag: false, cycles: 7 };
+const IRQ: Interrupt = Interrupt { vector: 0xFFFE, break_flag: false, cycles: 7 };
+// cycles are already counted by the opcode
+const BRK: Interrupt = Interrupt { vector: 0xFFFE, break_flag: true, cycles: 0 };
+
+#[derive(Debug, PartialEq)]
 #[allow(non_camel_case_types)]
 pub enum AddressingMode {
   Immediate,
@@ -43,17 +324,21 @@ pub enum AddressingMode {
   Absolute_Y,
   Indirect_X,
   Indirect_Y,
+  // JMP only
+  Indirect,
+  // branches, signed offset to the next instruction
+  Relative,
   NoneAddressing,
 }
 
 pub trait MyMem {
-  fn mem_read(&self, addr: u16) -> u8;
+  fn mem_read(&mut self, addr: u16) -> u8;
 
   fn mem_write(&mut self, addr: u16, data: u8);
 
-  fn mem_read_u16(&self, pos: u16) -> u16 {
+  fn mem_read_u16(&mut self, pos: u16) -> u16 {
     let lo = self.mem_read(pos) as u16;
-    let hi = self.mem_read(pos + 1) as u16;
+    let hi = self.mem_read(pos.wrapping_add(1)) as u16;
     hi << 8 | lo
   }
 
@@ -61,12 +346,12 @@ pub trait MyMem {
     let hi = (data >> 8) as u8;
     let lo = (data & 0xff) as u8;
     self.mem_write(pos, lo);
-    self.mem_write(pos + 1, hi);
+    self.mem_write(pos.wrapping_add(1), hi);
   }
 }
 
-impl MyMem for MyCPU {
-  fn mem_read(&self, addr: u16) -> u8 {
+impl<B: CpuBus> MyMem for MyCPU<B> {
+  fn mem_read(&mut self, addr: u16) -> u8 {
     self.bus.mem_read(addr)
   }
 
@@ -74,7 +359,7 @@ impl MyMem for MyCPU {
     self.bus.mem_write(addr, data)
   }
 
-  fn mem_read_u16(&self, addr: u16) -> u16 {
+  fn mem_read_u16(&mut self, addr: u16) -> u16 {
     self.bus.mem_read_u16(addr)
   }
 
@@ -83,8 +368,8 @@ impl MyMem for MyCPU {
   }
 }
 
-impl MyCPU {
-  pub fn new(bus: Bus) -> Self {
+impl<B: CpuBus> MyCPU<B> {
+  pub fn new(bus: B) -> Self {
     MyCPU {
       register_a: 0,
       register_x: 0,
@@ -93,47 +378,85 @@ impl MyCPU {
       status: CpuFlags::INTERRUPT_DISABLE | CpuFlags::BREAK2,
       program_counter: 0,
       bus,
+      history: ExecutionHistory::default(),
+      call_stack: CallStack::new(),
+      profiler: None,
+      cycles: 0,
+      tracer: Tracer::default(),
+      memory_editor: MemoryEditor::new(),
+      breakpoints: Breakpoints::new(),
+      symbols: Symbols::new(),
+      decode_cache: DecodeCache::new(),
+      stop_condition: StopCondition::Brk,
+      limits: RunLimits::default(),
+      state: CpuState::Running,
+      variant: CpuVariant::Nes,
+      handlers: Box::new(dispatch_table()),
     }
   }
 
+  pub fn handlers(&self) -> &HandlerTable<B> {
+    &self.handlers
+  }
+
   pub fn dump_non_empty_memory(&self) -> String {
     let mut dump = String::new();
 
     for i in 0..0x1FFF {
-      let value = self.mem_read(i);
+      let value = self.bus.peek(i);
       if value > 0 {
         dump.push_str(&format!("Memory {:x} = {:x}\n", i, value))
       }
     }
 
-    return dump;
+    dump
+  }
+
+  // post-mortem report: current registers, the last executed instructions and the call stack
+  pub fn crash_dump(&self, reason: &str) -> String {
+    let mut dump = format!("{}\nA:{:02X} X:{:02X} Y:{:02X} P:{:02X} SP:{:02X} PC:{:04X} CYC:{}\n",
+                           reason, self.register_a, self.register_x, self.register_y,
+                           self.status.bits(), self.stack_pointer, self.program_counter, self.cycles);
+    dump.push_str(&self.history.dump());
+    dump.push_str("call stack:\n");
+    dump.push_str(&self.call_stack.backtrace_with_labels(self.symbols.lookup()));
+    dump
   }
 
-  pub fn load_reset_and_run(&mut self, program: Vec<u8>) {
+  pub fn load_reset_and_run(&mut self, program: Vec<u8>) -> RunExit {
     self.load(program);
     self.reset();
-    self.run();
+    self.run()
   }
 
-  pub fn load_and_run(&mut self, program: Vec<u8>) {
+  pub fn load_and_run(&mut self, program: Vec<u8>) -> RunExit {
     self.load(program);
-    self.run();
+    self.run()
   }
 
+  // a program running past $FFFF wraps around to $0000 as the address bus does,
+  // load_with_address refuses it instead
   pub fn load(&mut self, program: Vec<u8>) {
-    for i in 0..(program.len() as u16) {
-      self.mem_write(0x0600 + i, program[i as usize]); // todo: remove magic 0x0600
+    let load_address = self.bus.load_address();
+    for (i, byte) in program.into_iter().enumerate() {
+      self.mem_write(load_address.wrapping_add(i as u16), byte);
     }
-
-    // self.mem_write_u16(0xFFFC, 0x0600); // todo: remove magic 0x0600
   }
 
-  pub fn load_with_address(&mut self, program: Vec<u8>, start_address: u16) {
-    for i in 0..(program.len() as u16) {
-      self.mem_write(start_address + i, program[i as usize]);
+  // the program may end at $FFFF, if it covers the reset vector its own vector is kept
+  pub fn load_with_address(&mut self, program: Vec<u8>, start_address: u16) -> Result<(), ProgramTooLarge> {
+    let end = start_address as usize + program.len();
+    if end > 0x10000 {
+      return Err(ProgramTooLarge { start_address, len: program.len() });
     }
-    self.mem_write_u16(0xFFFC, start_address);
-    // self.program_counter = start_address;
+    for (i, byte) in program.iter().enumerate() {
+      self.mem_write(start_address + i as u16, *byte);
+    }
+    let reset_vector = self.bus.reset_vector() as usize;
+    if !(start_address as usize..end).contains(&reset_vector) {
+      self.mem_write_u16(reset_vector as u16, start_address);
+    }
+    Ok(())
   }
 
   pub fn reset(&mut self) {
@@ -143,151 +466,351 @@ impl MyCPU {
     self.stack_pointer = STACK_RESET;
     self.status = CpuFlags::INTERRUPT_DISABLE | CpuFlags::BREAK2;
 
-    self.program_counter = self.mem_read_u16(0xFFFC);
-    println!("program_counter: {}", self.program_counter);
+    self.program_counter = self.mem_read_u16(self.bus.reset_vector());
+    self.call_stack.clear();
+    self.state = CpuState::Running;
+    self.breakpoints.notify(DebugEvent::Reset);
   }
 
-  pub fn run(&mut self) {
-    self.run_with_callback(|_| {});
+  pub fn run(&mut self) -> RunExit {
+    self.run_with_callback(|_| ControlFlow::Continue(()))
   }
 
-  pub fn run_with_callback<F>(&mut self, mut callback: F)
+  // the callback runs after every instruction, ControlFlow::Break ends the run
+  pub fn run_with_callback<F>(&mut self, mut callback: F) -> RunExit
     where
-      F: FnMut(&mut MyCPU),
+      F: FnMut(&mut MyCPU<B>) -> ControlFlow<()>,
   {
-    let ref opcodes: HashMap<u8, &'static opcodes::OpCode> = *opcodes::OPCODES_MAP;
-
-    loop {
-      let code = self.mem_read(self.program_counter);
-      self.program_counter += 1;
-      let program_counter_state = self.program_counter;
-
-      let opcode = opcodes.get(&code)
-        .expect(&format!("OpCode {:#04x} is not recognized! (pc={:x}, registers={:b})\n",
-                         code, self.program_counter, self.status.bits()));
-
-      println!("opCode {} {:#04x} {}, pc={:#04x}, registers={:b}",
-               opcode.mnemonic, code, self.get_next_bytes(opcode.len),
-               self.program_counter, self.status.bits());
-
-      match code {
-        0x00 => {
-          // ignore all break-flags, no check after that...
-          // https://wiki.nesdev.org/w/index.php/Status_flags#The_B_flag
-          // self.status.insert(CpuFlags::BREAK);
-          // self.status.insert(CpuFlags::BREAK2);
-          // self.status.insert(CpuFlags::INTERRUPT_DISABLE);
-          return;
+    let started = self.limits.time.map(|_| Instant::now());
+    let mut instructions = 0;
+    while self.step().is_some() {
+      if callback(self).is_break() {
+        return RunExit::Requested;
+      }
+      instructions += 1;
+      if self.stop_condition == StopCondition::Instructions(instructions) {
+        return RunExit::StopCondition;
+      }
+      if self.limits.instructions.is_some_and(|limit| instructions >= limit) {
+        return RunExit::LimitReached;
+      }
+      if let (Some(started), Some(limit)) = (started, self.limits.time) {
+        if instructions % TIME_LIMIT_INTERVAL == 0 && started.elapsed() >= limit {
+          return RunExit::LimitReached;
         }
+      }
+    }
+    self.stop_reason()
+  }
 
-        0x69 | 0x65 | 0x75 | 0x6D | 0x7D | 0x79 | 0x61 | 0x71 => self.adc(&opcode.mode),
-        0x29 | 0x25 | 0x35 | 0x2D | 0x3D | 0x39 | 0x21 | 0x31 => self.and(&opcode.mode),
-        0x0A | 0x06 | 0x16 | 0x0E | 0x1E => self.asl(&opcode.mode),
-
-        0x90 => self.bcc(),
-        0xB0 => self.bcs(),
-        0xF0 => self.beq(),
-        0x30 => self.bmi(),
-        0xD0 => self.bne(),
-        0x10 => self.bpl(),
-        0x50 => self.bvc(),
-        0x70 => self.bvs(),
-
-        0x24 | 0x2C => self.bit(&opcode.mode),
+  // why the last step() returned None
+  fn stop_reason(&self) -> RunExit {
+    if let CpuState::Jammed { code, program_counter } = self.state {
+      return RunExit::Jammed { code, program_counter };
+    }
+    if let Some(breakpoint) = self.breakpoints.hit() {
+      return RunExit::Breakpoint(breakpoint);
+    }
+    match self.stop_condition {
+      StopCondition::ProgramCounter(address) if address == self.program_counter => RunExit::StopCondition,
+      _ => RunExit::Brk,
+    }
+  }
 
-        0x18 => self.clc(),
-        0xD8 => self.cld(),
-        0x58 => self.cli(),
-        0xB8 => self.clv(),
+  // executes a single instruction, None if the cpu stopped (stop condition, breakpoint or jam)
+  pub fn step(&mut self) -> Option<StepInfo> {
+    if self.state != CpuState::Running {
+      return None;
+    }
+    let start_cycles = self.cycles;
+    if self.bus.poll_nmi_status().is_some() {
+      self.interrupt(&NMI);
+      self.breakpoints.notify(DebugEvent::Nmi);
+    } else if self.irq_active() {
+      self.interrupt(&IRQ);
+      self.breakpoints.notify(DebugEvent::Irq);
+    }
+    if self.breakpoints.should_break(self.program_counter)
+      || self.stop_condition == StopCondition::ProgramCounter(self.program_counter) {
+      return None;
+    }
 
-        0xC9 | 0xC5 | 0xD5 | 0xCD | 0xDD | 0xD9 | 0xC1 | 0xD1 => self.cmp(&opcode.mode),
-        0xE0 | 0xE4 | 0xEC => self.cpx(&opcode.mode),
-        0xC0 | 0xC4 | 0xCC => self.cpy(&opcode.mode),
+    let decoded = match self.decode(self.program_counter) {
+      Ok(decoded) => decoded,
+      Err(code) if opcodes::is_jam(code) => {
+        self.state = CpuState::Jammed { code, program_counter: self.program_counter };
+        return None;
+      }
+      Err(code) => panic!("{}", self.crash_dump(&format!("OpCode {:#04x} is not recognized!", code))),
+    };
+    let mut info = self.step_info(decoded.opcode);
+    self.bus.begin_instruction(self.program_counter);
+    self.program_counter = self.program_counter.wrapping_add(1);
+    let program_counter_state = self.program_counter;
+    let opcode = decoded.opcode;
+    let code = opcode.code;
+    let trace_record = if self.tracer.is_enabled() {
+      Some(self.trace_record(code, opcode))
+    } else {
+      None
+    };
+    self.record_history(code, opcode);
+
+    self.cycles += opcode.cycles as usize;
+    self.bus.tick(opcode.cycles as u16);
+    if let Some(profiler) = self.profiler.as_mut() {
+      let function = self.call_stack.frames().last().map(|f| f.target);
+      profiler.record(program_counter_state.wrapping_sub(1), opcode.cycles as u64, function);
+    }
 
-        0xC6 | 0xD6 | 0xCE | 0xDE => self.dec(&opcode.mode),
-        0xCA => self.dex(),
-        0x88 => self.dey(),
+    let mut running = true;
+    if code == 0x00 {
+      if self.stop_condition == StopCondition::Brk {
+        running = false;
+      } else {
+        // padding byte after BRK is skipped on return
+        self.program_counter = self.program_counter.wrapping_add(1);
+        self.interrupt(&BRK);
+      }
+    } else {
+      match decoded.handler {
+        Some(handler) => handler(self, &opcode.mode),
+        None => todo!("{}", self.crash_dump(&format!("OpCode {:#04x} is not implemented yet", code))),
+      }
+    }
+    self.stall_for_dma();
 
-        0x49 | 0x45 | 0x55 | 0x4D | 0x5D | 0x59 | 0x41 | 0x51 => self.eor(&opcode.mode),
+    if program_counter_state == self.program_counter {
+      self.program_counter = self.program_counter.wrapping_add((opcode.len - 1) as u16);
+    }
 
-        0xE6 | 0xF6 | 0xEE | 0xFE => self.inc(&opcode.mode),
-        0xE8 => self.inx(),
-        0xC8 => self.iny(),
+    if let Some(mut record) = trace_record {
+      record.bus_accesses = self.bus.take_accesses();
+      self.tracer.trace(&record);
+    }
 
-        0x4C | 0x6c => self.jmp(&opcode.mode),
-        0x20 => self.jsr(),
+    let breakpoints = &mut self.breakpoints;
+    self.bus.drain_events(|event| breakpoints.notify(event));
 
-        0xA9 | 0xA5 | 0xB5 | 0xAD | 0xBD | 0xB9 | 0xA1 | 0xB1 => self.lda(&opcode.mode),
-        0xA2 | 0xA6 | 0xB6 | 0xAE | 0xBE => self.ldx(&opcode.mode),
-        0xA0 | 0xA4 | 0xB4 | 0xAC | 0xBC => self.ldy(&opcode.mode),
+    self.memory_editor.apply(&mut self.bus);
+    info.cycles = self.cycles - start_cycles;
+    if running { Some(info) } else { None }
+  }
 
-        0x4A | 0x46 | 0x56 | 0x4E | 0x5E => self.lsr(&opcode.mode),
+  // operands and effective address of the instruction at program_counter, without side effects
+  fn step_info(&self, opcode: &opcodes::OpCode) -> StepInfo {
+    let pc = self.program_counter;
+    let operand_count = opcode.len.saturating_sub(1).min(2);
+    let mut operands = [0; 2];
+    for (i, operand) in operands.iter_mut().enumerate().take(operand_count as usize) {
+      *operand = self.bus.peek(pc.wrapping_add(1 + i as u16));
+    }
+    let zero_page_u16 = |ptr: u8| {
+      u16::from_le_bytes([self.bus.peek(ptr as u16), self.bus.peek(ptr.wrapping_add(1) as u16)])
+    };
+    let absolute = u16::from_le_bytes(operands);
+
+    // (base, effective) - a page is crossed if indexing changed the high byte
+    let addresses = match opcode.mode {
+      _ if operand_count == 0 => None,
+      AddressingMode::Immediate => Some((pc.wrapping_add(1), pc.wrapping_add(1))),
+      AddressingMode::ZeroPage => Some((operands[0] as u16, operands[0] as u16)),
+      AddressingMode::ZeroPage_X => Some((operands[0] as u16, operands[0].wrapping_add(self.register_x) as u16)),
+      AddressingMode::ZeroPage_Y => Some((operands[0] as u16, operands[0].wrapping_add(self.register_y) as u16)),
+      AddressingMode::Absolute => Some((absolute, absolute)),
+      AddressingMode::Absolute_X => Some((absolute, absolute.wrapping_add(self.register_x as u16))),
+      AddressingMode::Absolute_Y => Some((absolute, absolute.wrapping_add(self.register_y as u16))),
+      AddressingMode::Indirect_X => {
+        let addr = zero_page_u16(operands[0].wrapping_add(self.register_x));
+        Some((addr, addr))
+      }
+      AddressingMode::Indirect_Y => {
+        let base = zero_page_u16(operands[0]);
+        Some((base, base.wrapping_add(self.register_y as u16)))
+      }
+      AddressingMode::Indirect => {
+        let hi_addr = (absolute & 0xFF00) | (absolute.wrapping_add(1) & 0x00FF);
+        let addr = u16::from_le_bytes([self.bus.peek(absolute), self.bus.peek(hi_addr)]);
+        Some((addr, addr))
+      }
+      AddressingMode::Relative => {
+        let next = pc.wrapping_add(2);
+        Some((next, next.wrapping_add(operands[0] as i8 as u16)))
+      }
+      AddressingMode::NoneAddressing => None,
+    };
+
+    StepInfo {
+      program_counter: pc,
+      opcode: opcode.code,
+      mnemonic: opcode.mnemonic,
+      operands,
+      operand_count,
+      effective_address: addresses.map(|(_, effective)| effective),
+      cycles: 0,
+      page_crossed: addresses.is_some_and(|(base, effective)| base & 0xFF00 != effective & 0xFF00),
+    }
+  }
 
-        0xEA => self.nop(),
-        0x09 | 0x05 | 0x15 | 0x0D | 0x1D | 0x19 | 0x01 | 0x11 => self.ora(&opcode.mode),
+  // oam dma halts the cpu while the bus copies the page
+  fn stall_for_dma(&mut self) {
+    let stall = self.bus.take_dma_stall();
+    self.cycles += stall as usize;
+    self.bus.tick(stall);
+  }
 
-        0x85 | 0x95 | 0x8D | 0x9D | 0x99 | 0x81 | 0x91 => self.sta(&opcode.mode),
-        0x86 | 0x96 | 0x8E => self.stx(&opcode.mode),
-        0x84 | 0x94 | 0x8C => self.sty(&opcode.mode),
+  // irq is level triggered and can be masked
+  fn irq_active(&self) -> bool {
+    self.bus.irq_pending() && !self.status.contains(CpuFlags::INTERRUPT_DISABLE)
+  }
 
-        0x48 => self.pha(),
-        0x08 => self.php(),
-        0x68 => self.pla(),
-        0x28 => self.plp(),
+  // true if the next step starts with an interrupt sequence
+  pub fn interrupt_pending(&self) -> bool {
+    self.bus.nmi_pending() || self.irq_active()
+  }
 
-        0x2A | 0x26 | 0x36 | 0x2E | 0x3E => self.rol(&opcode.mode),
-        0x6A | 0x66 | 0x76 | 0x6E | 0x7E => self.ror(&opcode.mode),
-        0x60 => self.rts(),
-        0x40 => self.rti(),
+  // push pc and status, continue at the interrupt vector
+  // https://wiki.nesdev.org/w/index.php/Status_flags#The_B_flag
+  fn interrupt(&mut self, interrupt: &Interrupt) {
+    let caller = self.program_counter;
+    let stack_pointer = self.stack_pointer;
+    self.stack_push_u16(self.program_counter);
+    let mut flag = self.status;
+    flag.set(CpuFlags::BREAK, interrupt.break_flag);
+    flag.insert(CpuFlags::BREAK2);
+    self.stack_push(flag.bits());
+    self.status.insert(CpuFlags::INTERRUPT_DISABLE);
 
-        0xE9 | 0xE5 | 0xF5 | 0xED | 0xFD | 0xF9 | 0xE1 | 0xF1 => self.sbc(&opcode.mode),
+    self.cycles += interrupt.cycles as usize;
+    self.bus.tick(interrupt.cycles as u16);
+    self.program_counter = self.mem_read_u16(interrupt.vector);
+    self.call_stack.on_interrupt(caller, self.program_counter, stack_pointer);
+  }
 
-        0x38 => self.sec(),
-        0xF8 => self.sed(),
-        0x78 => self.sei(),
+  // instructions in prg rom are decoded only once, keyed by their rom offset
+  // (stays valid across bank switches, as the rom itself never changes)
+  fn decode(&mut self, addr: u16) -> Result<DecodedInstruction<B>, u8> {
+    let offset = self.bus.prg_rom_offset(addr);
+    if let Some(decoded) = offset.and_then(|o| self.decode_cache.get(o)) {
+      return Ok(decoded);
+    }
 
-        0xAA => self.tax(),
-        0xA8 => self.tay(),
-        0xBA => self.tsx(),
-        0x8A => self.txa(),
-        0x9A => self.txs(),
-        0x98 => self.tya(),
+    let code = self.mem_read(addr);
+    let decoded = DecodedInstruction::decode(code, &self.handlers).ok_or(code)?;
+    if let Some(offset) = offset {
+      self.decode_cache.insert(offset, decoded);
+    }
+    Ok(decoded)
+  }
 
-        _ => todo!()
-      }
+  // fast path for already decoded instructions, skips all debugging hooks
+  pub fn execute_decoded(&mut self, opcode: &opcodes::OpCode, handler: Handler<B>) {
+    self.bus.begin_instruction(self.program_counter);
+    self.program_counter = self.program_counter.wrapping_add(1);
+    let program_counter_state = self.program_counter;
+    self.cycles += opcode.cycles as usize;
+    self.bus.tick(opcode.cycles as u16);
 
-      if program_counter_state == self.program_counter {
-        self.program_counter += (opcode.len - 1) as u16;
-      }
+    handler(self, &opcode.mode);
+    self.stall_for_dma();
 
-      callback(self);
+    if program_counter_state == self.program_counter {
+      self.program_counter = self.program_counter.wrapping_add((opcode.len - 1) as u16);
     }
   }
 
-  fn get_next_bytes(&self, len: u8) -> String {
-    if len == 2 {
-      return format!("{:#04x}     ", self.mem_read(self.program_counter));
+  fn record_history(&mut self, code: u8, opcode: &opcodes::OpCode) {
+    if self.history.capacity() == 0 {
+      return;
+    }
+    let instruction = self.executed_instruction(code, opcode);
+    self.history.record(instruction);
+  }
+
+  // state before executing the instruction at program_counter - 1
+  fn executed_instruction(&self, code: u8, opcode: &opcodes::OpCode) -> ExecutedInstruction {
+    let operands = (0..(opcode.len as u16).saturating_sub(1))
+      .map(|i| self.bus.peek(self.program_counter.wrapping_add(i)))
+      .collect();
+    ExecutedInstruction {
+      program_counter: self.program_counter.wrapping_sub(1),
+      code,
+      mnemonic: opcode.mnemonic,
+      operands,
+      register_a: self.register_a,
+      register_x: self.register_x,
+      register_y: self.register_y,
+      stack_pointer: self.stack_pointer,
+      status: self.status,
     }
-    if len == 3 {
-      return format!("{:#04x} {:#04x}",
-                     self.mem_read(self.program_counter),
-                     self.mem_read(self.program_counter + 1));
+  }
+
+  fn trace_record(&mut self, code: u8, opcode: &opcodes::OpCode) -> TraceRecord {
+    let effective_address = match opcode.mode {
+      _ if opcode.len == 1 => None,
+      AddressingMode::NoneAddressing => None,
+      _ => Some(self.get_operand_address(&opcode.mode)),
+    };
+    let record_accesses = self.tracer.wants_bus_accesses();
+    self.bus.record_accesses(record_accesses);
+    let instruction = self.executed_instruction(code, opcode);
+    TraceRecord {
+      label: self.symbols.label(instruction.program_counter).map(String::from),
+      prg_rom_offset: self.bus.prg_rom_offset(instruction.program_counter),
+      instruction,
+      cycles: self.cycles,
+      effective_address,
+      bus_accesses: vec![],
     }
-    return format!("         ");
   }
 
   fn adc(&mut self, mode: &AddressingMode) {
-    let addr = self.get_operand_address(mode);
+    let addr = self.get_read_address(mode);
     let data = self.mem_read(addr);
-    self.add_to_acc(data);
+    if self.decimal_mode() {
+      self.add_decimal(data);
+    } else {
+      self.add_to_acc(data);
+    }
   }
 
   fn sbc(&mut self, mode: &AddressingMode) {
-    let addr = self.get_operand_address(mode);
+    let addr = self.get_read_address(mode);
     let value = self.mem_read(addr);
+    let (acc, carry) = (self.register_a, self.status.contains(CpuFlags::CARRY));
 
-    // A - B = A + (-B). And -B = !B + 1
-    self.add_to_acc(((value as i8).wrapping_neg().wrapping_sub(1)) as u8);
+    // A - M - (1 - C) = A + !M + C, the carry is the inverted borrow
+    self.add_to_acc(!value);
+    // the NMOS 6502 sets all flags like the binary subtraction
+    if self.decimal_mode() {
+      self.register_a = subtract_decimal(acc, value, carry);
+    }
+  }
+
+  fn decimal_mode(&self) -> bool {
+    self.variant == CpuVariant::Nmos6502 && self.status.contains(CpuFlags::DECIMAL_MODE)
+  }
+
+  // http://www.6502.org/tutorials/decimal_mode.html#A (sequences 1 and 2)
+  // Z comes from the binary sum, N and V from the sum before the high digit is adjusted
+  fn add_decimal(&mut self, data: u8) {
+    let acc = self.register_a;
+    let carry = if self.status.contains(CpuFlags::CARRY) { 1 } else { 0 };
+
+    let mut low = (acc & 0x0F) as i16 + (data & 0x0F) as i16 + carry;
+    if low >= 0x0A {
+      low = ((low + 0x06) & 0x0F) + 0x10;
+    }
+    let mut sum = (acc & 0xF0) as i16 + (data & 0xF0) as i16 + low;
+    let signed = (acc & 0xF0) as i8 as i16 + (data & 0xF0) as i8 as i16 + low;
+    self.status.set(CpuFlags::NEGATIVE, sum & 0x80 != 0);
+    self.status.set(CpuFlags::OVERFLOW, !(-128..=127).contains(&signed));
+    self.status.set(CpuFlags::ZERO, acc.wrapping_add(data).wrapping_add(carry as u8) == 0);
+    if sum >= 0xA0 {
+      sum += 0x60;
+    }
+    self.status.set(CpuFlags::CARRY, sum >= 0x100);
+    self.register_a = sum as u8;
   }
 
   fn add_to_acc(&mut self, data: u8) {
@@ -305,7 +828,7 @@ impl MyCPU {
   }
 
   fn and(&mut self, mode: &AddressingMode) {
-    let addr = self.get_operand_address(mode);
+    let addr = self.get_read_address(mode);
     let value = self.mem_read(addr);
 
     self.register_a = value.bitand(self.register_a);
@@ -360,12 +883,14 @@ impl MyCPU {
     self.branch(self.status.contains(CpuFlags::OVERFLOW))
   }
 
+  // the offset is relative to the next instruction. Taken branches need a cycle more,
+  // two if the target is on another page
   fn branch(&mut self, condition: bool) {
     if condition {
-      self.program_counter = self.program_counter
-        .wrapping_add(1)
-        // i don't have a clue, why i need that, but without snake won't work
-        .wrapping_add((self.mem_read(self.program_counter) as i8) as u16);
+      let next = self.program_counter.wrapping_add(1);
+      let target = next.wrapping_add((self.mem_read(self.program_counter) as i8) as u16);
+      self.add_cycles(if next & 0xFF00 != target & 0xFF00 { 2 } else { 1 });
+      self.program_counter = target;
     }
   }
 
@@ -407,7 +932,7 @@ impl MyCPU {
   }
 
   fn compare(&mut self, mode: &AddressingMode, reference: u8) {
-    let addr = self.get_operand_address(mode);
+    let addr = self.get_read_address(mode);
     let data = self.mem_read(addr);
 
     // Z,C,N = A-M
@@ -452,7 +977,7 @@ impl MyCPU {
   }
 
   fn eor(&mut self, mode: &AddressingMode) {
-    let addr = self.get_operand_address(mode);
+    let addr = self.get_read_address(mode);
     let value = self.mem_read(addr);
 
     self.register_a = self.register_a.bitxor(value);
@@ -460,37 +985,28 @@ impl MyCPU {
   }
 
   fn jmp(&mut self, mode: &AddressingMode) {
-    let addr = self.mem_read_u16(self.program_counter);
-
-    if matches!(mode, AddressingMode::Absolute) {
-      self.program_counter = addr;
-    } else {
-      let indirect_addr =
-        if addr.bitand(0x00FF) == 0x00FF {
-          let lo = self.mem_read(addr);
-          let hi = self.mem_read(addr & 0xFF00);
-          (hi as u16) << 8 | (lo as u16)
-        } else {
-          self.mem_read_u16(addr)
-        };
-      self.program_counter = indirect_addr;
-    }
+    self.program_counter = self.get_operand_address(mode);
   }
 
   fn jsr(&mut self) {
-    self.stack_push_u16(self.program_counter + 2 - 1);
-    self.program_counter = self.mem_read_u16(self.program_counter);
+    let return_address = self.program_counter.wrapping_add(1);
+    let target = self.mem_read_u16(self.program_counter);
+    self.call_stack.on_call(self.program_counter.wrapping_sub(1), target, return_address, self.stack_pointer);
+    self.stack_push_u16(return_address);
+    self.program_counter = target;
   }
 
   fn rts(&mut self) {
     // -1 based on https://web.archive.org/web/20170224121759/http://www.obelisk.me.uk/6502/reference.html#RTS
     // +1 based on http://www.6502.org/tutorials/6502opcodes.html#RTS
     // take +1 for now, as jsr already subtracts 1 ...
-    self.program_counter = self.stack_pop_u16() + 1;
+    let return_address = self.stack_pop_u16();
+    self.call_stack.on_return(self.program_counter.wrapping_sub(1), return_address, self.stack_pointer);
+    self.program_counter = return_address.wrapping_add(1);
   }
 
   fn lda(&mut self, mode: &AddressingMode) {
-    let addr = self.get_operand_address(mode);
+    let addr = self.get_read_address(mode);
     let value = self.mem_read(addr);
 
     self.register_a = value;
@@ -498,7 +1014,7 @@ impl MyCPU {
   }
 
   fn ldx(&mut self, mode: &AddressingMode) {
-    let addr = self.get_operand_address(mode);
+    let addr = self.get_read_address(mode);
     let value = self.mem_read(addr);
 
     self.register_x = value;
@@ -506,7 +1022,7 @@ impl MyCPU {
   }
 
   fn ldy(&mut self, mode: &AddressingMode) {
-    let addr = self.get_operand_address(mode);
+    let addr = self.get_read_address(mode);
     let value = self.mem_read(addr);
 
     self.register_y = value;
@@ -514,7 +1030,7 @@ impl MyCPU {
   }
 
   fn lsr(&mut self, mode: &AddressingMode) {
-    if matches!(mode, AddressingMode::Immediate) {
+    if matches!(mode, AddressingMode::NoneAddressing) {
       self.status.set(CpuFlags::CARRY, self.register_a & 0x01 == 1);
       self.register_a >>= 1;
       self.update_zero_and_negative_flags(self.register_a);
@@ -533,7 +1049,7 @@ impl MyCPU {
   }
 
   fn ora(&mut self, mode: &AddressingMode) {
-    let addr = self.get_operand_address(mode);
+    let addr = self.get_read_address(mode);
     let data = self.mem_read(addr);
 
     self.register_a = self.register_a.bitor(data);
@@ -560,7 +1076,7 @@ impl MyCPU {
   }
 
   fn php(&mut self) {
-    let mut flags = self.status.clone();
+    let mut flags = self.status;
     // https://wiki.nesdev.org/w/index.php/Status_flags#The_B_flag
     flags.insert(CpuFlags::BREAK);
     flags.insert(CpuFlags::BREAK2);
@@ -580,27 +1096,31 @@ impl MyCPU {
   }
 
   fn rti(&mut self) {
+    let rti_addr = self.program_counter.wrapping_sub(1);
     self.status.bits = self.stack_pop();
     // https://wiki.nesdev.org/w/index.php/Status_flags#The_B_flag
     self.status.remove(CpuFlags::BREAK);
     self.status.insert(CpuFlags::BREAK2);
 
     self.program_counter = self.stack_pop_u16();
+    self.call_stack.on_return(rti_addr, self.program_counter, self.stack_pointer);
   }
 
+  // the carry goes into bit 0 and bit 7 into the carry
   fn rol(&mut self, mode: &AddressingMode) {
+    let carry = self.status.contains(CpuFlags::CARRY) as u8;
     if matches!(mode, AddressingMode::NoneAddressing) {
-      let result = self.register_a.rotate_left(1);
+      let result = self.register_a << 1 | carry;
       self.status.set(CpuFlags::CARRY, Self::highest_bit_set(self.register_a));
       self.update_zero_and_negative_flags(result);
       self.register_a = result;
     } else {
       let addr = self.get_operand_address(mode);
       let value = self.mem_read(addr);
-      let result = value.rotate_left(1);
+      let result = value << 1 | carry;
       self.status.set(CpuFlags::CARRY, Self::highest_bit_set(value));
-      self.status.set(CpuFlags::NEGATIVE, result >> 7 == 1);
       self.mem_write(addr, result);
+      self.update_zero_and_negative_flags(result);
     }
   }
 
@@ -613,18 +1133,19 @@ impl MyCPU {
   }
 
   fn ror(&mut self, mode: &AddressingMode) {
+    let carry = self.status.contains(CpuFlags::CARRY) as u8;
     if matches!(mode, AddressingMode::NoneAddressing) {
-      let result = self.register_a.rotate_right(1);
+      let result = self.register_a >> 1 | carry << 7;
       self.status.set(CpuFlags::CARRY, Self::lowest_bit_set(self.register_a));
       self.update_zero_and_negative_flags(result);
       self.register_a = result;
     } else {
       let addr = self.get_operand_address(mode);
       let value = self.mem_read(addr);
-      let result = value.rotate_right(1);
+      let result = value >> 1 | carry << 7;
       self.status.set(CpuFlags::CARRY, Self::lowest_bit_set(value));
-      self.status.set(CpuFlags::NEGATIVE, result >> 7 == 1);
       self.mem_write(addr, result);
+      self.update_zero_and_negative_flags(result);
     }
   }
 
@@ -669,8 +1190,172 @@ impl MyCPU {
     self.update_zero_and_negative_flags(self.register_a);
   }
 
+  // unofficial opcodes, see https://www.nesdev.org/undocumented_opcodes.txt
+
+  // NOPs with an operand still perform the read
+  fn nop_read(&mut self, mode: &AddressingMode) {
+    if !matches!(mode, AddressingMode::NoneAddressing) {
+      let addr = self.get_read_address(mode);
+      self.mem_read(addr);
+    }
+  }
+
+  fn lax(&mut self, mode: &AddressingMode) {
+    let addr = self.get_read_address(mode);
+    let value = self.mem_read(addr);
+    self.register_a = value;
+    self.register_x = value;
+    self.update_zero_and_negative_flags(value);
+  }
+
+  fn sax(&mut self, mode: &AddressingMode) {
+    let addr = self.get_operand_address(mode);
+    self.mem_write(addr, self.register_a & self.register_x);
+  }
+
+  // DEC + CMP
+  fn dcp(&mut self, mode: &AddressingMode) {
+    let addr = self.get_operand_address(mode);
+    let value = self.mem_read(addr).wrapping_sub(1);
+    self.mem_write(addr, value);
+    self.status.set(CpuFlags::CARRY, value <= self.register_a);
+    self.update_zero_and_negative_flags(self.register_a.wrapping_sub(value));
+  }
+
+  // INC + SBC
+  fn isb(&mut self, mode: &AddressingMode) {
+    let addr = self.get_operand_address(mode);
+    let value = self.mem_read(addr).wrapping_add(1);
+    self.mem_write(addr, value);
+    self.add_to_acc(!value);
+  }
+
+  // ASL + ORA
+  fn slo(&mut self, mode: &AddressingMode) {
+    let addr = self.get_operand_address(mode);
+    let value = self.mem_read(addr);
+    self.status.set(CpuFlags::CARRY, Self::highest_bit_set(value));
+    let value = value << 1;
+    self.mem_write(addr, value);
+    self.register_a |= value;
+    self.update_zero_and_negative_flags(self.register_a);
+  }
+
+  // ROL (through carry) + AND
+  fn rla(&mut self, mode: &AddressingMode) {
+    let addr = self.get_operand_address(mode);
+    let value = self.mem_read(addr);
+    let carry = self.status.contains(CpuFlags::CARRY) as u8;
+    self.status.set(CpuFlags::CARRY, Self::highest_bit_set(value));
+    let value = value << 1 | carry;
+    self.mem_write(addr, value);
+    self.register_a &= value;
+    self.update_zero_and_negative_flags(self.register_a);
+  }
+
+  // LSR + EOR
+  fn sre(&mut self, mode: &AddressingMode) {
+    let addr = self.get_operand_address(mode);
+    let value = self.mem_read(addr);
+    self.status.set(CpuFlags::CARRY, Self::lowest_bit_set(value));
+    let value = value >> 1;
+    self.mem_write(addr, value);
+    self.register_a ^= value;
+    self.update_zero_and_negative_flags(self.register_a);
+  }
+
+  // ROR (through carry) + ADC
+  fn rra(&mut self, mode: &AddressingMode) {
+    let addr = self.get_operand_address(mode);
+    let value = self.mem_read(addr);
+    let carry = self.status.contains(CpuFlags::CARRY) as u8;
+    self.status.set(CpuFlags::CARRY, Self::lowest_bit_set(value));
+    let value = value >> 1 | carry << 7;
+    self.mem_write(addr, value);
+    self.add_to_acc(value);
+  }
+
+  // X = (A & X) - operand, without borrow
+  fn axs(&mut self, mode: &AddressingMode) {
+    let addr = self.get_operand_address(mode);
+    let value = self.mem_read(addr);
+    let and = self.register_a & self.register_x;
+    self.status.set(CpuFlags::CARRY, and >= value);
+    self.register_x = and.wrapping_sub(value);
+    self.update_zero_and_negative_flags(self.register_x);
+  }
+
+  // AND + ROR, carry and overflow come from bits 6 and 5
+  fn arr(&mut self, mode: &AddressingMode) {
+    let addr = self.get_operand_address(mode);
+    let value = self.mem_read(addr);
+    let carry = self.status.contains(CpuFlags::CARRY) as u8;
+    self.register_a = (self.register_a & value) >> 1 | carry << 7;
+    let bit6 = self.register_a & 0b0100_0000 != 0;
+    let bit5 = self.register_a & 0b0010_0000 != 0;
+    self.status.set(CpuFlags::CARRY, bit6);
+    self.status.set(CpuFlags::OVERFLOW, bit6 ^ bit5);
+    self.update_zero_and_negative_flags(self.register_a);
+  }
+
+  // AND, carry is copied from bit 7
+  fn anc(&mut self, mode: &AddressingMode) {
+    self.and(mode);
+    self.status.set(CpuFlags::CARRY, self.status.contains(CpuFlags::NEGATIVE));
+  }
+
+  // AND + LSR A
+  fn alr(&mut self, mode: &AddressingMode) {
+    self.and(mode);
+    self.lsr(&AddressingMode::NoneAddressing);
+  }
+
+  // unstable on hardware, behaves like LDA + TAX on most consoles
+  fn lxa(&mut self, mode: &AddressingMode) {
+    self.lax(mode);
+  }
+
+  // unstable on hardware, TXA + AND
+  fn xaa(&mut self, mode: &AddressingMode) {
+    self.register_a = self.register_x;
+    self.and(mode);
+  }
+
+  fn las(&mut self, mode: &AddressingMode) {
+    let addr = self.get_read_address(mode);
+    let value = self.mem_read(addr) & self.stack_pointer;
+    self.register_a = value;
+    self.register_x = value;
+    self.stack_pointer = value;
+    self.update_zero_and_negative_flags(value);
+  }
+
+  // the following stores are and-ed with the high byte of the address + 1
+  fn store_and_high(&mut self, mode: &AddressingMode, value: u8) {
+    let addr = self.get_operand_address(mode);
+    let high = ((addr >> 8) as u8).wrapping_add(1);
+    self.mem_write(addr, value & high);
+  }
+
+  fn tas(&mut self, mode: &AddressingMode) {
+    self.stack_pointer = self.register_a & self.register_x;
+    self.store_and_high(mode, self.stack_pointer);
+  }
+
+  fn ahx(&mut self, mode: &AddressingMode) {
+    self.store_and_high(mode, self.register_a & self.register_x);
+  }
+
+  fn shx(&mut self, mode: &AddressingMode) {
+    self.store_and_high(mode, self.register_x);
+  }
+
+  fn shy(&mut self, mode: &AddressingMode) {
+    self.store_and_high(mode, self.register_y);
+  }
+
   fn stack_push(&mut self, data: u8) {
-    self.mem_write(STACK_AREA as u16 + self.stack_pointer as u16, data);
+    self.mem_write(STACK_AREA + self.stack_pointer as u16, data);
     self.stack_pointer = self.stack_pointer.wrapping_sub(1);
   }
 
@@ -683,7 +1368,7 @@ impl MyCPU {
 
   fn stack_pop(&mut self) -> u8 {
     self.stack_pointer = self.stack_pointer.wrapping_add(1);
-    self.mem_read(STACK_AREA as u16 + self.stack_pointer as u16)
+    self.mem_read(STACK_AREA + self.stack_pointer as u16)
   }
 
   fn stack_pop_u16(&mut self) -> u16 {
@@ -697,7 +1382,27 @@ impl MyCPU {
     self.status.set(CpuFlags::NEGATIVE, result & 0b1000_0000 != 0);
   }
 
-  fn get_operand_address(&self, mode: &AddressingMode) -> u16 {
+  // for instructions which only read: indexing across a page takes a cycle more.
+  // stores and read-modify-write instructions always take it, it's part of their cycles
+  fn get_read_address(&mut self, mode: &AddressingMode) -> u16 {
+    let addr = self.get_operand_address(mode);
+    let base = match mode {
+      AddressingMode::Absolute_X => addr.wrapping_sub(self.register_x as u16),
+      AddressingMode::Absolute_Y | AddressingMode::Indirect_Y => addr.wrapping_sub(self.register_y as u16),
+      _ => return addr,
+    };
+    if base & 0xFF00 != addr & 0xFF00 {
+      self.add_cycles(1);
+    }
+    addr
+  }
+
+  fn add_cycles(&mut self, cycles: u16) {
+    self.cycles += cycles as usize;
+    self.bus.tick(cycles);
+  }
+
+  fn get_operand_address(&mut self, mode: &AddressingMode) -> u16 {
     match mode {
       AddressingMode::Immediate => self.program_counter,
 
@@ -707,32 +1412,28 @@ impl MyCPU {
 
       AddressingMode::ZeroPage_X => {
         let pos = self.mem_read(self.program_counter);
-        let addr = pos.wrapping_add(self.register_x) as u16;
-        addr
+        pos.wrapping_add(self.register_x) as u16
       }
 
       AddressingMode::ZeroPage_Y => {
         let pos = self.mem_read(self.program_counter);
-        let addr = pos.wrapping_add(self.register_y) as u16;
-        addr
+        pos.wrapping_add(self.register_y) as u16
       }
 
       AddressingMode::Absolute_X => {
         let base = self.mem_read_u16(self.program_counter);
-        let addr = base.wrapping_add(self.register_x as u16);
-        addr
+        base.wrapping_add(self.register_x as u16)
       }
 
       AddressingMode::Absolute_Y => {
         let base = self.mem_read_u16(self.program_counter);
-        let addr = base.wrapping_add(self.register_y as u16);
-        addr
+        base.wrapping_add(self.register_y as u16)
       }
 
       AddressingMode::Indirect_X => {
         let base = self.mem_read(self.program_counter);
 
-        let ptr: u8 = (base as u8).wrapping_add(self.register_x);
+        let ptr: u8 = base.wrapping_add(self.register_x);
         let lo = self.mem_read(ptr as u16);
         let hi = self.mem_read(ptr.wrapping_add(1) as u16);
         (hi as u16) << 8 | (lo as u16)
@@ -742,10 +1443,26 @@ impl MyCPU {
         let base = self.mem_read(self.program_counter);
 
         let lo = self.mem_read(base as u16);
-        let hi = self.mem_read((base as u8).wrapping_add(1) as u16);
+        let hi = self.mem_read(base.wrapping_add(1) as u16);
         let deref_base = (hi as u16) << 8 | (lo as u16);
-        let deref = deref_base.wrapping_add(self.register_y as u16);
-        deref
+        deref_base.wrapping_add(self.register_y as u16)
+      }
+
+      AddressingMode::Indirect => {
+        let addr = self.mem_read_u16(self.program_counter);
+        // the high byte is not fetched from the next page
+        if addr.bitand(0x00FF) == 0x00FF {
+          let lo = self.mem_read(addr);
+          let hi = self.mem_read(addr & 0xFF00);
+          (hi as u16) << 8 | (lo as u16)
+        } else {
+          self.mem_read_u16(addr)
+        }
+      }
+
+      AddressingMode::Relative => {
+        let offset = self.mem_read(self.program_counter) as i8;
+        self.program_counter.wrapping_add(1).wrapping_add(offset as u16)
       }
 
       AddressingMode::NoneAddressing => {
@@ -753,4 +1470,145 @@ impl MyCPU {
       }
     }
   }
-}
\ No newline at end of file
+}
+
+// save states cover the cpu and everything its bus saves, for the nes the whole console
+impl<B: CpuBus + Stateful> MyCPU<B> {
+  // complete machine state, the cartridge rom has to be the same when loading
+  pub fn save_state(&self) -> Vec<u8> {
+    let mut w = StateWriter::new();
+    w.write_u8(self.register_a);
+    w.write_u8(self.register_x);
+    w.write_u8(self.register_y);
+    w.write_u8(self.status.bits());
+    w.write_u16(self.program_counter);
+    w.write_u8(self.stack_pointer);
+    w.write_u64(self.cycles as u64);
+    let jammed = match self.state {
+      CpuState::Running => None,
+      CpuState::Jammed { code, program_counter } => Some((code, program_counter)),
+    };
+    w.write_bool(jammed.is_some());
+    let (code, jammed_at) = jammed.unwrap_or_default();
+    w.write_u8(code);
+    w.write_u16(jammed_at);
+    self.bus.save_state(&mut w);
+    w.into_bytes()
+  }
+
+  // a failed load can leave the machine half restored
+  pub fn load_state(&mut self, state: &[u8]) -> Result<(), String> {
+    let mut r = StateReader::new(state)?;
+    self.register_a = r.read_u8()?;
+    self.register_x = r.read_u8()?;
+    self.register_y = r.read_u8()?;
+    self.status = CpuFlags::from_bits_truncate(r.read_u8()?);
+    self.program_counter = r.read_u16()?;
+    self.stack_pointer = r.read_u8()?;
+    self.cycles = r.read_u64()? as usize;
+    let (jammed, code, program_counter) = (r.read_bool()?, r.read_u8()?, r.read_u16()?);
+    self.state = if jammed { CpuState::Jammed { code, program_counter } } else { CpuState::Running };
+    self.bus.load_state(&mut r)?;
+    r.finish()?;
+    self.call_stack.clear();
+    Ok(())
+  }
+}
+
+// the cpu part of save_state, as a struct for serde
+#[cfg(feature = "serde")]
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct CpuRegisters {
+  pub register_a: u8,
+  pub register_x: u8,
+  pub register_y: u8,
+  pub status: CpuFlags,
+  pub program_counter: u16,
+  pub stack_pointer: u8,
+  pub cycles: usize,
+  pub state: CpuState,
+}
+
+// everything save_state saves for the nes, for serde
+#[cfg(feature = "serde")]
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct MachineState {
+  pub cpu: CpuRegisters,
+  pub bus: crate::bus::BusState,
+}
+
+#[cfg(feature = "serde")]
+impl MyCPU {
+  pub fn machine_state(&self) -> MachineState {
+    MachineState {
+      cpu: CpuRegisters {
+        register_a: self.register_a,
+        register_x: self.register_x,
+        register_y: self.register_y,
+        status: self.status,
+        program_counter: self.program_counter,
+        stack_pointer: self.stack_pointer,
+        cycles: self.cycles,
+        state: self.state,
+      },
+      bus: self.bus.state(),
+    }
+  }
+
+  // as load_state, the cartridge rom has to be the same and a failed set can leave the machine half restored
+  pub fn set_machine_state(&mut self, state: MachineState) -> Result<(), String> {
+    let cpu = state.cpu;
+    self.register_a = cpu.register_a;
+    self.register_x = cpu.register_x;
+    self.register_y = cpu.register_y;
+    self.status = cpu.status;
+    self.program_counter = cpu.program_counter;
+    self.stack_pointer = cpu.stack_pointer;
+    self.cycles = cpu.cycles;
+    self.state = cpu.state;
+    self.bus.set_state(state.bus)?;
+    self.call_stack.clear();
+    Ok(())
+  }
+}
+
+// snapshots hold the cpu and its ram
+impl MyCPU {
+  pub fn snapshot(&self) -> Snapshot {
+    Snapshot {
+      register_a: self.register_a,
+      register_x: self.register_x,
+      register_y: self.register_y,
+      status: self.status,
+      program_counter: self.program_counter,
+      stack_pointer: self.stack_pointer,
+      cycles: self.cycles,
+      ram: self.bus.ram().to_vec(),
+    }
+  }
+
+  pub fn restore(&mut self, snapshot: &Snapshot) {
+    self.register_a = snapshot.register_a;
+    self.register_x = snapshot.register_x;
+    self.register_y = snapshot.register_y;
+    self.status = snapshot.status;
+    self.program_counter = snapshot.program_counter;
+    self.stack_pointer = snapshot.stack_pointer;
+    self.cycles = snapshot.cycles;
+    self.bus.load_ram(&snapshot.ram);
+  }
+}
+
+// http://www.6502.org/tutorials/decimal_mode.html#A (sequence 3)
+fn subtract_decimal(acc: u8, value: u8, carry: bool) -> u8 {
+  let borrow = if carry { 0 } else { 1 };
+  let mut low = (acc & 0x0F) as i16 - (value & 0x0F) as i16 - borrow;
+  if low < 0 {
+    low = ((low - 0x06) & 0x0F) - 0x10;
+  }
+  let mut result = (acc & 0xF0) as i16 - (value & 0xF0) as i16 + low;
+  if result < 0 {
+    result -= 0x60;
+  }
+  result as u8
+}
@@ -0,0 +1,159 @@
+use rand::{Rng, SeedableRng};
+use rand::rngs::StdRng;
+use crate::bus::Bus;
+use crate::cartridge_tests::create_test_rom;
+use crate::cpu::{CpuFlags, MyCPU, MyMem};
+
+// random registers and operands checked against a plain arithmetic model of each instruction.
+// proptest / quickcheck are not available offline, a seeded rng keeps failures reproducible
+const CASES: usize = 20_000;
+const SEED: u64 = 0x6502;
+const START_ADDR: u16 = 0x0600;
+const ZERO_PAGE_OPERAND: u8 = 0x10;
+
+const NZC: u8 = 0x83;
+const NVZC: u8 = 0xC3;
+
+#[derive(Debug, Clone, Copy)]
+struct Case {
+  a: u8,
+  operand: u8,
+  status: u8,
+}
+
+impl Case {
+  fn carry(&self) -> u8 {
+    self.status & CpuFlags::CARRY.bits()
+  }
+}
+
+// what the model predicts: the result and the flags out of `affected`
+struct Expected {
+  result: u8,
+  flags: u8,
+  affected: u8,
+}
+
+fn nz(value: u8) -> u8 {
+  let zero = if value == 0 { CpuFlags::ZERO.bits() } else { 0 };
+  zero | (value & CpuFlags::NEGATIVE.bits())
+}
+
+fn flag(condition: bool, flag: CpuFlags) -> u8 {
+  if condition { flag.bits() } else { 0 }
+}
+
+fn adc_model(case: Case) -> Expected {
+  let sum = case.a as u16 + case.operand as u16 + case.carry() as u16;
+  let result = sum as u8;
+  let overflow = (!(case.a ^ case.operand) & (case.a ^ result) & 0x80) != 0;
+  Expected { result, flags: nz(result) | flag(sum > 0xFF, CpuFlags::CARRY) | flag(overflow, CpuFlags::OVERFLOW), affected: NVZC }
+}
+
+fn sbc_model(case: Case) -> Expected {
+  let borrow = 1 - case.carry() as i16;
+  let difference = case.a as i16 - case.operand as i16 - borrow;
+  let signed = case.a as i8 as i16 - case.operand as i8 as i16 - borrow;
+  let result = difference as u8;
+  let overflow = !(-128..=127).contains(&signed);
+  Expected { result, flags: nz(result) | flag(difference >= 0, CpuFlags::CARRY) | flag(overflow, CpuFlags::OVERFLOW), affected: NVZC }
+}
+
+// the result of CMP is the unchanged accumulator
+fn cmp_model(case: Case) -> Expected {
+  let flags = nz(case.a.wrapping_sub(case.operand)) | flag(case.a >= case.operand, CpuFlags::CARRY);
+  Expected { result: case.a, flags, affected: NZC }
+}
+
+fn asl_model(value: u8, _carry: u8) -> Expected {
+  let result = value << 1;
+  Expected { result, flags: nz(result) | flag(value & 0x80 != 0, CpuFlags::CARRY), affected: NZC }
+}
+
+fn rol_model(value: u8, carry: u8) -> Expected {
+  let result = (value << 1) | carry;
+  Expected { result, flags: nz(result) | flag(value & 0x80 != 0, CpuFlags::CARRY), affected: NZC }
+}
+
+fn ror_model(value: u8, carry: u8) -> Expected {
+  let result = (value >> 1) | (carry << 7);
+  Expected { result, flags: nz(result) | flag(value & 0x01 != 0, CpuFlags::CARRY), affected: NZC }
+}
+
+fn random_cases(seed: u64) -> impl Iterator<Item = Case> {
+  let mut rng = StdRng::seed_from_u64(seed);
+  // interrupts stay disabled, the apu frame irq of the bus would jump away from the instruction
+  (0..CASES).map(move |_| Case { a: rng.gen(), operand: rng.gen(), status: rng.gen::<u8>() | CpuFlags::INTERRUPT_DISABLE.bits() })
+}
+
+fn execute(cpu: &mut MyCPU, case: Case, program: &[u8]) {
+  cpu.register_a = case.a;
+  cpu.status = CpuFlags::from_bits_truncate(case.status);
+  cpu.mem_write(ZERO_PAGE_OPERAND as u16, case.operand);
+  for (i, byte) in program.iter().enumerate() {
+    cpu.mem_write(START_ADDR + i as u16, *byte);
+  }
+  cpu.program_counter = START_ADDR;
+  cpu.step();
+}
+
+fn check(cpu: &MyCPU, case: Case, expected: &Expected, actual_result: u8, name: &str) {
+  let status = (case.status & !expected.affected) | expected.flags;
+  assert_eq!(expected.result, actual_result, "{} {:?}", name, case);
+  assert_eq!(status, cpu.status.bits(), "{} {:?}: flags {:08b} instead of {:08b}", name, case, cpu.status.bits(), status);
+}
+
+fn check_accumulator_ops(code: u8, name: &str, model: fn(Case) -> Expected) {
+  let mut cpu = MyCPU::new(Bus::new(create_test_rom()).unwrap());
+  for case in random_cases(SEED ^ code as u64) {
+    // immediate
+    execute(&mut cpu, case, &[code, case.operand]);
+    check(&cpu, case, &model(case), cpu.register_a, name);
+  }
+}
+
+// accumulator mode and the zero page read-modify-write mode
+fn check_shift(accumulator_code: u8, zero_page_code: u8, name: &str, model: fn(u8, u8) -> Expected) {
+  let mut cpu = MyCPU::new(Bus::new(create_test_rom()).unwrap());
+  for case in random_cases(SEED ^ accumulator_code as u64) {
+    execute(&mut cpu, case, &[accumulator_code]);
+    check(&cpu, case, &model(case.a, case.carry()), cpu.register_a, name);
+
+    execute(&mut cpu, case, &[zero_page_code, ZERO_PAGE_OPERAND]);
+    let result = cpu.mem_read(ZERO_PAGE_OPERAND as u16);
+    check(&cpu, case, &model(case.operand, case.carry()), result, name);
+    assert_eq!(case.a, cpu.register_a, "{} {:?} changed the accumulator", name, case);
+  }
+}
+
+#[test]
+fn test_adc_matches_model() {
+  check_accumulator_ops(0x69, "ADC", adc_model);
+}
+
+#[test]
+fn test_sbc_matches_model() {
+  check_accumulator_ops(0xE9, "SBC", sbc_model);
+  // unofficial copy
+  check_accumulator_ops(0xEB, "*SBC", sbc_model);
+}
+
+#[test]
+fn test_cmp_matches_model() {
+  check_accumulator_ops(0xC9, "CMP", cmp_model);
+}
+
+#[test]
+fn test_asl_matches_model() {
+  check_shift(0x0A, 0x06, "ASL", asl_model);
+}
+
+#[test]
+fn test_rol_matches_model() {
+  check_shift(0x2A, 0x26, "ROL", rol_model);
+}
+
+#[test]
+fn test_ror_matches_model() {
+  check_shift(0x6A, 0x66, "ROR", ror_model);
+}
@@ -1,11 +1,20 @@
-use crate::Bus;
-use crate::cartridge_tests::create_test_rom;
-use crate::cpu::{MyCPU, CpuFlags, MyMem};
+use std::collections::HashSet;
+use std::ops::ControlFlow;
+use std::time::Duration;
+use crate::bus::Bus;
+use crate::asm::assemble;
+use crate::breakpoints::Breakpoint;
+use crate::bus::IrqSource;
+use crate::call_stack::FrameKind;
+use crate::cartridge_tests::{create_test_rom, create_test_rom_with_vectors};
+use crate::cpu::{AddressingMode, MyCPU, CpuFlags, CpuState, CpuVariant, MyMem, ProgramTooLarge, RunExit, RunLimits, StopCondition, has_handler};
+use crate::opcodes::CPU_OPS_CODES;
+use crate::ppu::StatusRegister;
 
 const START_ADDR: u16 = 0x0600;
 
 fn init_cpu() -> MyCPU {
-  let mut cpu = MyCPU::new(Bus::new(create_test_rom()));
+  let mut cpu = MyCPU::new(Bus::new(create_test_rom()).unwrap());
   cpu.program_counter = START_ADDR;
   cpu
 }
@@ -13,12 +22,64 @@ fn init_cpu() -> MyCPU {
 #[test]
 fn test_5_ops_working_together() {
   let mut cpu = init_cpu();
-  cpu.load_and_run(vec![0xA9, 0xC0, 0xAA, 0xE8]);
+  cpu.load_and_run(assemble("LDA #$C0\nTAX\nINX", START_ADDR).unwrap());
 
   cpu.dump_non_empty_memory();
   assert_eq!(0xC1, cpu.register_x);
 }
 
+#[test]
+fn test_load_with_address_rejects_programs_past_ffff() {
+  let mut cpu = init_cpu();
+
+  let result = cpu.load_with_address(vec![0xEA; 0x101], 0xFF00);
+
+  assert_eq!(Err(ProgramTooLarge { start_address: 0xFF00, len: 0x101 }), result);
+  assert_eq!("program of 257 bytes at $FF00 ends 1 byte(s) past $FFFF", result.unwrap_err().to_string());
+}
+
+#[test]
+fn test_load_with_address_writes_the_program_to_ram() {
+  let mut cpu = init_cpu();
+
+  cpu.load_with_address(vec![0xE8, 0xC8], 0x0700).unwrap();
+
+  assert_eq!(0xE8, cpu.mem_read(0x0700));
+  assert_eq!(0xC8, cpu.mem_read(0x0701));
+}
+
+#[test]
+fn test_every_opcode_is_dispatched() {
+  for op in CPU_OPS_CODES.iter().filter(|op| op.code != 0x00) {
+    assert!(has_handler(op.code), "no handler for {} {:#04x}", op.mnemonic, op.code);
+  }
+}
+
+#[test]
+fn test_opcode_table_has_all_official_opcodes_once() {
+  let mut seen = HashSet::new();
+  for op in CPU_OPS_CODES.iter() {
+    assert!(seen.insert(op.code), "duplicate entry for {:#04x}", op.code);
+  }
+  let official = CPU_OPS_CODES.iter().filter(|op| !op.mnemonic.starts_with('*')).count();
+  assert_eq!(151, official);
+  // everything except the 12 KIL opcodes
+  assert_eq!(244, seen.len());
+}
+
+#[test]
+fn test_opcode_lengths_match_addressing_modes() {
+  for op in CPU_OPS_CODES.iter() {
+    let expected_len = match op.mode {
+      AddressingMode::NoneAddressing => 1,
+      AddressingMode::Absolute | AddressingMode::Absolute_X | AddressingMode::Absolute_Y
+      | AddressingMode::Indirect => 3,
+      _ => 2,
+    };
+    assert_eq!(expected_len, op.len, "{} {:#04x} {:?}", op.mnemonic, op.code, op.mode);
+  }
+}
+
 #[test]
 fn test_adc_add_with_no_carry() {
   let mut cpu = init_cpu();
@@ -58,6 +119,75 @@ fn test_adc_add_with_carry_in() {
   assert_eq!(CpuFlags::empty(), cpu.status & CpuFlags::OVERFLOW);
 }
 
+// decimal mode vectors, see: http://www.6502.org/tutorials/decimal_mode.html
+
+fn run_decimal(variant: CpuVariant, acc: u8, carry: bool, program: Vec<u8>) -> MyCPU {
+  let mut cpu = init_cpu();
+  cpu.variant = variant;
+  cpu.register_a = acc;
+  cpu.status.insert(CpuFlags::DECIMAL_MODE);
+  cpu.status.set(CpuFlags::CARRY, carry);
+  cpu.load_and_run(program);
+  cpu
+}
+
+#[test]
+fn test_adc_decimal_mode() {
+  // (a, operand, carry in) -> (a, carry out)
+  let vectors = [
+    (0x12, 0x34, false, 0x46, false),
+    (0x58, 0x46, true, 0x05, true),
+    (0x81, 0x92, false, 0x73, true),
+    (0x99, 0x01, false, 0x00, true),
+  ];
+  for &(acc, operand, carry, result, carry_out) in vectors.iter() {
+    let cpu = run_decimal(CpuVariant::Nmos6502, acc, carry, vec![0x69, operand]);
+
+    assert_eq!(result, cpu.register_a, "{:02X} + {:02X}", acc, operand);
+    assert_eq!(carry_out, cpu.status.contains(CpuFlags::CARRY), "{:02X} + {:02X}", acc, operand);
+  }
+}
+
+#[test]
+fn test_adc_decimal_mode_flags() {
+  // 99 + 01: the binary sum isn't zero, so Z stays clear on the NMOS 6502
+  let cpu = run_decimal(CpuVariant::Nmos6502, 0x99, false, vec![0x69, 0x01]);
+  assert!(!cpu.status.contains(CpuFlags::ZERO));
+
+  // 79 + 00 + 1 = 80, N and V from the unadjusted sum
+  let cpu = run_decimal(CpuVariant::Nmos6502, 0x79, true, vec![0x69, 0x00]);
+  assert_eq!(0x80, cpu.register_a);
+  assert!(cpu.status.contains(CpuFlags::NEGATIVE));
+  assert!(cpu.status.contains(CpuFlags::OVERFLOW));
+}
+
+#[test]
+fn test_sbc_decimal_mode() {
+  // (a, operand, carry in) -> (a, carry out)
+  let vectors = [
+    (0x46, 0x12, true, 0x34, true),
+    (0x40, 0x13, true, 0x27, true),
+    (0x32, 0x02, false, 0x29, true),
+    (0x12, 0x21, true, 0x91, false),
+    (0x21, 0x34, true, 0x87, false),
+  ];
+  for &(acc, operand, carry, result, carry_out) in vectors.iter() {
+    let cpu = run_decimal(CpuVariant::Nmos6502, acc, carry, vec![0xE9, operand]);
+
+    assert_eq!(result, cpu.register_a, "{:02X} - {:02X}", acc, operand);
+    assert_eq!(carry_out, cpu.status.contains(CpuFlags::CARRY), "{:02X} - {:02X}", acc, operand);
+  }
+}
+
+#[test]
+fn test_nes_ignores_decimal_flag() {
+  let cpu = run_decimal(CpuVariant::Nes, 0x09, false, vec![0x69, 0x01]);
+  assert_eq!(0x0A, cpu.register_a);
+
+  let cpu = run_decimal(CpuVariant::Nes, 0x10, true, vec![0xE9, 0x01]);
+  assert_eq!(0x0F, cpu.register_a);
+}
+
 // overflow adc & sbc examples, see: http://www.6502.org/tutorials/vflag.html
 
 #[test]
@@ -155,6 +285,35 @@ fn test_sbc_subtract_with_overflow() {
   assert_eq!(CpuFlags::OVERFLOW, cpu.status & CpuFlags::OVERFLOW);
 }
 
+#[test]
+fn test_sbc_truth_table() {
+  let mut cpu = init_cpu();
+  for a in 0..=255u8 {
+    for value in 0..=255u8 {
+      for carry in [false, true] {
+        let borrow = !carry as i16;
+        let difference = a as i16 - value as i16 - borrow;
+        let result = difference as u8;
+        let signed = (a as i8) as i16 - (value as i8) as i16 - borrow;
+
+        cpu.register_a = a;
+        cpu.status.set(CpuFlags::CARRY, carry);
+        cpu.mem_write(START_ADDR, 0xE9);
+        cpu.mem_write(START_ADDR + 1, value);
+        cpu.program_counter = START_ADDR;
+        cpu.step();
+
+        let case = format!("{:02X} - {:02X} carry {}", a, value, carry);
+        assert_eq!(result, cpu.register_a, "{}", case);
+        assert_eq!(difference >= 0, cpu.status.contains(CpuFlags::CARRY), "{}", case);
+        assert_eq!(!(-128..=127).contains(&signed), cpu.status.contains(CpuFlags::OVERFLOW), "{}", case);
+        assert_eq!(result == 0, cpu.status.contains(CpuFlags::ZERO), "{}", case);
+        assert_eq!(result & 0x80 != 0, cpu.status.contains(CpuFlags::NEGATIVE), "{}", case);
+      }
+    }
+  }
+}
+
 #[test]
 fn test_and_acc_and_immediate_memory() {
   let mut cpu= init_cpu();
@@ -780,6 +939,7 @@ fn test_rti_return_from_interrupt() {
 fn test_rol_rotate_left_accumulator() {
   let mut cpu = init_cpu();
   cpu.register_a = 0b1100_0011;
+  cpu.status.insert(CpuFlags::CARRY);
 
   cpu.load_and_run(vec![0x2A]);
 
@@ -819,6 +979,7 @@ fn test_rol_rotate_left_absolute() {
 fn test_ror_rotate_right_accumulator() {
   let mut cpu = init_cpu();
   cpu.register_a = 0b1100_0011;
+  cpu.status.insert(CpuFlags::CARRY);
 
   cpu.load_and_run(vec![0x6A]);
 
@@ -1072,4 +1233,397 @@ fn test_tya_transfer_y_to_acc() {
   cpu.load_and_run(vec![0x98]);
 
   assert_eq!(0x42, cpu.register_a);
-}
\ No newline at end of file
+}
+#[test]
+fn test_nmi_is_serviced_through_vector() {
+  // $8000: LDA #$80, STA $2000 (enable nmi while in vblank), NOP
+  // $8010: LDX #$42, BRK
+  let mut program = vec![0xA9, 0x80, 0x8D, 0x00, 0x20, 0xEA];
+  program.resize(0x10, 0xEA);
+  program.extend([0xA2, 0x42, 0x00]);
+  let mut cpu = MyCPU::new(Bus::new(create_test_rom_with_vectors(&program, 0x8010, 0x0000)).unwrap());
+  cpu.reset();
+  cpu.bus.ppu.status.insert(StatusRegister::VBLANK_STARTED);
+
+  cpu.run();
+
+  assert_eq!(0x42, cpu.register_x);
+  assert_eq!(0x80, cpu.mem_read(0x01FF));
+  assert_eq!(0x05, cpu.mem_read(0x01FE));
+  let pushed_status = CpuFlags::from_bits_truncate(cpu.mem_read(0x01FD));
+  assert!(!pushed_status.contains(CpuFlags::BREAK));
+  assert!(pushed_status.contains(CpuFlags::BREAK2));
+  assert!(cpu.status.contains(CpuFlags::INTERRUPT_DISABLE));
+  assert_eq!(FrameKind::Interrupt, cpu.call_stack.frames()[0].kind);
+}
+
+#[test]
+fn test_brk_jumps_through_irq_vector() {
+  // $8000: LDX #$01, BRK, padding; $8010: LDY #$42
+  let mut program = vec![0xA2, 0x01, 0x00, 0xFF];
+  program.resize(0x10, 0xEA);
+  program.extend([0xA0, 0x42]);
+  let mut cpu = MyCPU::new(Bus::new(create_test_rom_with_vectors(&program, 0x0000, 0x8010)).unwrap());
+  cpu.reset();
+  cpu.stop_condition = StopCondition::Never;
+
+  assert!(cpu.step().is_some());
+  assert!(cpu.step().is_some());
+  assert_eq!(0x8010, cpu.program_counter);
+  assert!(cpu.step().is_some());
+
+  assert_eq!(0x42, cpu.register_y);
+  assert_eq!(0xFC, cpu.stack_pointer);
+  assert_eq!(0x80, cpu.mem_read(0x01FF));
+  assert_eq!(0x04, cpu.mem_read(0x01FE));
+  assert!(CpuFlags::from_bits_truncate(cpu.mem_read(0x01FD)).contains(CpuFlags::BREAK));
+}
+
+#[test]
+fn test_irq_is_serviced_when_enabled() {
+  // $8000: CLI, NOP; $8010: LDY #$42
+  let mut program = vec![0x58, 0xEA];
+  program.resize(0x10, 0xEA);
+  program.extend([0xA0, 0x42]);
+  let mut cpu = MyCPU::new(Bus::new(create_test_rom_with_vectors(&program, 0x0000, 0x8010)).unwrap());
+  cpu.reset();
+  cpu.bus.set_irq(IrqSource::MAPPER, true);
+
+  // masked by the interrupt disable flag after reset
+  assert!(!cpu.interrupt_pending());
+  cpu.step();
+  assert!(cpu.interrupt_pending());
+  cpu.step();
+
+  assert_eq!(0x42, cpu.register_y);
+  assert_eq!(0x01, cpu.mem_read(0x01FE));
+  assert!(!CpuFlags::from_bits_truncate(cpu.mem_read(0x01FD)).contains(CpuFlags::BREAK));
+  assert!(cpu.status.contains(CpuFlags::INTERRUPT_DISABLE));
+  assert!(!cpu.interrupt_pending());
+}
+
+#[test]
+fn test_unofficial_lax_and_sax() {
+  let mut cpu = init_cpu();
+  cpu.mem_write(0x10, 0x8F);
+
+  // LAX $10, LDX #$F0, SAX $11
+  cpu.load_and_run(vec![0xA7, 0x10, 0xA2, 0xF0, 0x87, 0x11]);
+
+  assert_eq!(0x8F, cpu.register_a);
+  assert_eq!(0x80, cpu.mem_read(0x11));
+}
+
+#[test]
+fn test_unofficial_dcp_and_isb() {
+  let mut cpu = init_cpu();
+  cpu.mem_write(0x10, 0x43);
+  cpu.mem_write(0x11, 0x0F);
+  cpu.register_a = 0x42;
+
+  // DCP $10 (compare A with the decremented value)
+  cpu.load_and_run(vec![0xC7, 0x10]);
+  assert_eq!(0x42, cpu.mem_read(0x10));
+  assert!(cpu.status.contains(CpuFlags::ZERO | CpuFlags::CARRY));
+
+  // ISB $11: A = 0x42 - 0x10 with carry set
+  cpu.program_counter = START_ADDR;
+  cpu.load_and_run(vec![0xE7, 0x11]);
+  assert_eq!(0x10, cpu.mem_read(0x11));
+  assert_eq!(0x32, cpu.register_a);
+}
+
+#[test]
+fn test_unofficial_shift_combinations() {
+  let mut cpu = init_cpu();
+  cpu.mem_write(0x10, 0b1000_0001);
+  cpu.mem_write(0x11, 0b1000_0001);
+  cpu.mem_write(0x12, 0b0000_0011);
+
+  // SLO $10, RLA $11, SRE $12
+  cpu.load_and_run(vec![0x07, 0x10, 0x27, 0x11, 0x47, 0x12]);
+
+  assert_eq!(0b0000_0010, cpu.mem_read(0x10));
+  assert_eq!(0b0000_0011, cpu.mem_read(0x11)); // carry from SLO rotated in
+  assert_eq!(0b0000_0001, cpu.mem_read(0x12));
+  assert_eq!(0b0000_0011, cpu.register_a);
+  assert!(cpu.status.contains(CpuFlags::CARRY));
+}
+
+#[test]
+fn test_unofficial_rra_adds_rotated_value() {
+  let mut cpu = init_cpu();
+  cpu.mem_write(0x10, 0b0000_0101);
+  cpu.register_a = 0x10;
+
+  cpu.load_and_run(vec![0x67, 0x10]);
+
+  // 0b101 >> 1 = 2, carry out = 1 is added as well
+  assert_eq!(0x02, cpu.mem_read(0x10));
+  assert_eq!(0x13, cpu.register_a);
+}
+
+#[test]
+fn test_unofficial_axs() {
+  let mut cpu = init_cpu();
+  cpu.register_a = 0xF0;
+  cpu.register_x = 0x3C;
+
+  cpu.load_and_run(vec![0xCB, 0x10]);
+
+  assert_eq!(0x20, cpu.register_x);
+  assert!(cpu.status.contains(CpuFlags::CARRY));
+}
+
+#[test]
+fn test_unofficial_nops_skip_their_operands() {
+  let mut cpu = init_cpu();
+
+  // NOP #$FF, NOP $10, NOP $1234,X, NOP, LDA #$42
+  cpu.load_and_run(vec![0x80, 0xFF, 0x04, 0x10, 0x1C, 0x34, 0x12, 0x1A, 0xA9, 0x42]);
+
+  assert_eq!(0x42, cpu.register_a);
+}
+
+#[test]
+fn test_oam_dma_stalls_cpu() {
+  // LDA #$02, STA $4014
+  let mut cpu = init_cpu();
+  cpu.load_and_run(vec![0xA9, 0x02, 0x8D, 0x14, 0x40]);
+
+  // LDA, STA, even cycle dma, BRK
+  assert_eq!(2 + 4 + 513 + 7, cpu.cycles);
+}
+
+#[test]
+fn test_stop_condition_program_counter() {
+  let mut cpu = init_cpu();
+  cpu.stop_condition = StopCondition::ProgramCounter(0x0604);
+  // INX, INX, INX, INX
+  cpu.load_and_run(vec![0xE8, 0xE8, 0xE8, 0xE8, 0xE8]);
+
+  assert_eq!(4, cpu.register_x);
+  assert_eq!(0x0604, cpu.program_counter);
+}
+
+#[test]
+fn test_stop_condition_instructions() {
+  let mut cpu = init_cpu();
+  cpu.stop_condition = StopCondition::Instructions(4);
+  // loop: INX, JMP loop
+  cpu.load_and_run(vec![0xE8, 0x4C, 0x00, 0x06]);
+
+  assert_eq!(2, cpu.register_x);
+  assert_eq!(0x0600, cpu.program_counter);
+}
+
+#[test]
+fn test_instruction_limit_ends_an_endless_loop() {
+  let mut cpu = init_cpu();
+  cpu.limits = RunLimits { instructions: Some(1_000), time: None };
+  // loop: INX, JMP loop
+  let exit = cpu.load_and_run(vec![0xE8, 0x4C, 0x00, 0x06]);
+
+  assert_eq!(RunExit::LimitReached, exit);
+  // 500 INX, wrapped around
+  assert_eq!((500 % 256) as u8, cpu.register_x);
+}
+
+#[test]
+fn test_time_limit_ends_an_endless_loop() {
+  let mut cpu = init_cpu();
+  cpu.limits = RunLimits { instructions: None, time: Some(Duration::from_millis(20)) };
+  // loop: JMP loop
+  let exit = cpu.load_and_run(vec![0x4C, 0x00, 0x06]);
+
+  assert_eq!(RunExit::LimitReached, exit);
+}
+
+#[test]
+fn test_run_within_limits_stops_normally() {
+  let mut cpu = init_cpu();
+  cpu.limits = RunLimits { instructions: Some(1_000), time: Some(Duration::from_secs(10)) };
+  // INX, BRK
+  let exit = cpu.load_and_run(vec![0xE8, 0x00]);
+
+  assert_eq!(RunExit::Brk, exit);
+  assert_eq!(1, cpu.register_x);
+}
+
+#[test]
+fn test_run_exit_stop_condition() {
+  let mut cpu = init_cpu();
+  cpu.stop_condition = StopCondition::ProgramCounter(0x0602);
+  // INX, INX, INX
+  assert_eq!(RunExit::StopCondition, cpu.load_and_run(vec![0xE8, 0xE8, 0xE8]));
+
+  cpu.program_counter = START_ADDR;
+  cpu.stop_condition = StopCondition::Instructions(1);
+  assert_eq!(RunExit::StopCondition, cpu.run());
+}
+
+#[test]
+fn test_run_exit_breakpoint() {
+  let mut cpu = init_cpu();
+  cpu.breakpoints.add(Breakpoint::Address(0x0601));
+
+  // INX, INX, BRK
+  let exit = cpu.load_and_run(vec![0xE8, 0xE8, 0x00]);
+
+  assert_eq!(RunExit::Breakpoint(Breakpoint::Address(0x0601)), exit);
+  // continuing passes the breakpoint and ends at BRK
+  assert_eq!(RunExit::Brk, cpu.run());
+}
+
+#[test]
+fn test_run_exit_jammed() {
+  let mut cpu = init_cpu();
+
+  // INX, KIL
+  let exit = cpu.load_and_run(vec![0xE8, 0x02]);
+
+  assert_eq!(RunExit::Jammed { code: 0x02, program_counter: 0x0601 }, exit);
+}
+
+#[test]
+fn test_callback_requests_stop() {
+  let mut cpu = init_cpu();
+  // loop: INX, JMP loop
+  cpu.load(vec![0xE8, 0x4C, 0x00, 0x06]);
+
+  let exit = cpu.run_with_callback(|cpu| {
+    if cpu.register_x == 3 { ControlFlow::Break(()) } else { ControlFlow::Continue(()) }
+  });
+
+  assert_eq!(RunExit::Requested, exit);
+  assert_eq!(3, cpu.register_x);
+}
+
+#[test]
+fn test_stop_condition_never_runs_through_brk() {
+  // $8000: BRK, padding, LDX #$42, NOP (breakpoint); $8010: RTI
+  let mut program = vec![0x00, 0xFF, 0xA2, 0x42, 0xEA];
+  program.resize(0x10, 0xEA);
+  program.push(0x40);
+  let mut cpu = MyCPU::new(Bus::new(create_test_rom_with_vectors(&program, 0x0000, 0x8010)).unwrap());
+  cpu.reset();
+  cpu.stop_condition = StopCondition::Never;
+  cpu.breakpoints.add(Breakpoint::Address(0x8004));
+
+  cpu.run();
+
+  assert_eq!(0x42, cpu.register_x);
+  assert_eq!(0x8004, cpu.program_counter);
+}
+
+#[test]
+fn test_kil_jams_until_reset() {
+  let mut cpu = init_cpu();
+  // INX, KIL, INX
+  cpu.load_and_run(vec![0xE8, 0x02, 0xE8]);
+
+  assert_eq!(1, cpu.register_x);
+  assert_eq!(CpuState::Jammed { code: 0x02, program_counter: 0x0601 }, cpu.state);
+  assert!(cpu.step().is_none());
+  assert_eq!(0x0601, cpu.program_counter);
+
+  cpu.reset();
+  assert_eq!(CpuState::Running, cpu.state);
+}
+
+#[test]
+fn test_jammed_cpu_ignores_nmi() {
+  let mut cpu = init_cpu();
+  cpu.load_and_run(vec![0xF2]);
+  cpu.bus.ppu.status.insert(StatusRegister::VBLANK_STARTED);
+  cpu.bus.mem_write(0x2000, 0b1000_0000);
+
+  assert!(cpu.step().is_none());
+  assert_eq!(0x0600, cpu.program_counter);
+  assert_eq!(0xFF, cpu.stack_pointer);
+}
+
+#[test]
+fn test_step_reports_executed_instruction() {
+  let mut cpu = init_cpu();
+  cpu.register_y = 0x10;
+  cpu.mem_write(0x0010, 0xF8);
+  cpu.mem_write(0x0011, 0x02);
+  // LDA ($10),Y; STA $0200
+  cpu.load(vec![0xB1, 0x10, 0x8D, 0x00, 0x02]);
+
+  let info = cpu.step().unwrap();
+  assert_eq!(0x0600, info.program_counter);
+  assert_eq!((0xB1, "LDA"), (info.opcode, info.mnemonic));
+  assert_eq!(&[0x10], info.operands());
+  assert_eq!(Some(0x0308), info.effective_address);
+  assert!(info.page_crossed);
+  assert_eq!(6, info.cycles);
+
+  let info = cpu.step().unwrap();
+  assert_eq!(&[0x00, 0x02], info.operands());
+  assert_eq!(Some(0x0200), info.effective_address);
+  assert!(!info.page_crossed);
+}
+
+#[test]
+fn test_page_crossing_reads_take_a_cycle_more() {
+  let mut cpu = init_cpu();
+  cpu.register_x = 0x10;
+  // LDA $02F8,X; LDA $0200,X; STA $02F8,X
+  cpu.load(vec![0xBD, 0xF8, 0x02, 0xBD, 0x00, 0x02, 0x9D, 0xF8, 0x02]);
+
+  assert_eq!(5, cpu.step().unwrap().cycles);
+  assert_eq!(4, cpu.step().unwrap().cycles);
+  // always 5, crossing or not
+  assert_eq!(5, cpu.step().unwrap().cycles);
+}
+
+#[test]
+fn test_taken_branches_take_extra_cycles() {
+  let mut cpu = init_cpu();
+  // $0600: BNE +0 (not taken, Z set), BEQ +2, 2 bytes skipped, BEQ -$10 (to $05F8)
+  cpu.load(vec![0xD0, 0x00, 0xF0, 0x02, 0xEA, 0xEA, 0xF0, 0xF0]);
+  cpu.status.insert(CpuFlags::ZERO);
+
+  assert_eq!(2, cpu.step().unwrap().cycles);
+  assert_eq!(3, cpu.step().unwrap().cycles);
+  assert_eq!(0x0606, cpu.program_counter);
+  assert_eq!(4, cpu.step().unwrap().cycles);
+  assert_eq!(0x05F8, cpu.program_counter);
+}
+
+#[test]
+fn test_step_reports_branch_target() {
+  let mut cpu = init_cpu();
+  // INX; BNE -3
+  cpu.load(vec![0xE8, 0xD0, 0xFD]);
+
+  let info = cpu.step().unwrap();
+  assert_eq!(None, info.effective_address);
+  assert!(info.operands().is_empty());
+
+  let info = cpu.step().unwrap();
+  assert_eq!(Some(0x0600), info.effective_address);
+  assert!(!info.page_crossed);
+  assert_eq!(0x0600, cpu.program_counter);
+}
+
+#[test]
+fn test_crash_dump_after_jam() {
+  let mut cpu = init_cpu();
+  cpu.history.set_capacity(2);
+  // LDX #$01, JSR $0606, (padding), INX, KIL
+  cpu.load_and_run(vec![0xA2, 0x01, 0x20, 0x06, 0x06, 0xEA, 0xE8, 0x02]);
+
+  let dump = cpu.crash_dump("jammed");
+  let lines: Vec<&str> = dump.lines().collect();
+
+  assert_eq!("jammed", lines[0]);
+  assert_eq!("A:00 X:02 Y:00 P:24 SP:FD PC:0607 CYC:10", lines[1]);
+  assert_eq!("last 2 executed instructions:", lines[2]);
+  assert!(lines[3].starts_with("0602  20 06 06  JSR"));
+  assert!(lines[4].starts_with("0606  E8        INX"));
+  assert_eq!("call stack:", lines[5]);
+  assert_eq!("#0 $0606 called from $0602", lines[6]);
+}
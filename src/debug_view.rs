@@ -0,0 +1,162 @@
+use std::io;
+use std::path::Path;
+use crate::event_log::{EventLog, FrameEventKind};
+use crate::frame::Frame;
+use crate::png;
+use crate::ppu::{NesPPU, DOTS_PER_SCANLINE, SCANLINES_PER_FRAME};
+use crate::render::pattern_pixel;
+
+pub const PATTERN_TABLES_WIDTH: usize = 256;
+pub const PATTERN_TABLES_HEIGHT: usize = 128;
+// pixels per palette entry in the palette view
+pub const SWATCH_SIZE: usize = 8;
+// loudest output of the mixer, the top of the oscilloscope
+const MAX_APU_OUTPUT: f32 = 0.26;
+const SCOPE_COLOR: (u8, u8, u8) = (0x40, 0xFF, 0x40);
+// one pixel per dot and scanline
+pub const EVENT_VIEWER_WIDTH: usize = DOTS_PER_SCANLINE;
+pub const EVENT_VIEWER_HEIGHT: usize = SCANLINES_PER_FRAME as usize;
+const PICTURE_COLOR: (u8, u8, u8) = (0x30, 0x30, 0x30);
+const BLANK_COLOR: (u8, u8, u8) = (0x10, 0x10, 0x10);
+
+// rgb picture of any size, for inspector windows next to the game
+#[derive(Debug, Clone, PartialEq)]
+pub struct ImageBuffer {
+  pub width: usize,
+  pub height: usize,
+  pub data: Vec<u8>,
+}
+
+impl ImageBuffer {
+  pub fn new(width: usize, height: usize) -> Self {
+    ImageBuffer { width, height, data: vec![0; width * height * 3] }
+  }
+
+  pub fn set_pixel(&mut self, x: usize, y: usize, rgb: (u8, u8, u8)) {
+    let base = (y * self.width + x) * 3;
+    self.data[base..base + 3].copy_from_slice(&[rgb.0, rgb.1, rgb.2]);
+  }
+
+  pub fn get_pixel(&self, x: usize, y: usize) -> (u8, u8, u8) {
+    let base = (y * self.width + x) * 3;
+    (self.data[base], self.data[base + 1], self.data[base + 2])
+  }
+
+  pub fn save_png(&self, path: &Path) -> io::Result<()> {
+    std::fs::write(path, png::encode_rgb(self.width, self.height, &self.data))
+  }
+}
+
+// $0000 on the left, $1000 on the right, 16x16 tiles each.
+// palette 0-3 are the background palettes, 4-7 the sprite palettes
+pub fn pattern_tables(ppu: &NesPPU, palette: u8) -> ImageBuffer {
+  let mut image = ImageBuffer::new(PATTERN_TABLES_WIDTH, PATTERN_TABLES_HEIGHT);
+  let colors: Vec<(u8, u8, u8)> = (0..4)
+    .map(|value| {
+      let entry = if value == 0 { 0 } else { (palette as usize & 0b111) * 4 + value };
+      ppu.palette.color(ppu.palette_table[entry], 0)
+    })
+    .collect();
+
+  for table in 0..2 {
+    for tile in 0..256 {
+      let tile_addr = (table * 0x1000 + tile * 16) as u16;
+      let left = table * 128 + tile % 16 * 8;
+      let top = tile / 16 * 8;
+      for row in 0..8 {
+        for col in 0..8 {
+          let value = pattern_pixel(ppu, tile_addr, row, col);
+          image.set_pixel(left + col, top + row, colors[value as usize]);
+        }
+      }
+    }
+  }
+  image
+}
+
+// background palettes in the top row, sprite palettes below
+pub fn palettes(ppu: &NesPPU) -> ImageBuffer {
+  let mut image = ImageBuffer::new(16 * SWATCH_SIZE, 2 * SWATCH_SIZE);
+  for (entry, &color) in ppu.palette_table.iter().enumerate() {
+    let rgb = ppu.palette.color(color, 0);
+    let (left, top) = (entry % 16 * SWATCH_SIZE, entry / 16 * SWATCH_SIZE);
+    for y in top..top + SWATCH_SIZE {
+      for x in left..left + SWATCH_SIZE {
+        image.set_pixel(x, y, rgb);
+      }
+    }
+  }
+  image
+}
+
+// samples as a green line on black, stretched or squeezed to the width
+pub fn oscilloscope(samples: &[f32], width: usize, height: usize) -> ImageBuffer {
+  let mut image = ImageBuffer::new(width, height);
+  if samples.is_empty() || width == 0 || height == 0 {
+    return image;
+  }
+  let to_y = |sample: f32| {
+    let level = (sample / MAX_APU_OUTPUT).clamp(0.0, 1.0);
+    height - 1 - (level * (height - 1) as f32).round() as usize
+  };
+  let mut previous = None;
+  for x in 0..width {
+    let y = to_y(samples[x * samples.len() / width]);
+    // vertical steps are drawn too, so edges of square waves show up
+    let (from, to) = match previous {
+      Some(previous_y) => (y.min(previous_y), y.max(previous_y)),
+      None => (y, y),
+    };
+    for line_y in from..=to {
+      image.set_pixel(x, line_y, SCOPE_COLOR);
+    }
+    previous = Some(y);
+  }
+  image
+}
+
+// the color of a register write in the event viewer
+pub fn register_color(address: u16) -> (u8, u8, u8) {
+  match address {
+    0x2000 => (0xFF, 0x40, 0x40), // PPUCTRL
+    0x2001 => (0xFF, 0xA0, 0x40), // PPUMASK
+    0x2003 | 0x2004 | 0x4014 => (0xC0, 0x60, 0xFF), // oam
+    0x2005 => (0x40, 0xC0, 0xFF), // PPUSCROLL
+    0x2006 => (0x40, 0x60, 0xFF), // PPUADDR
+    0x2007 => (0x40, 0xFF, 0xFF), // PPUDATA
+    _ => (0xA0, 0xA0, 0xA0), // apu and joypads
+  }
+}
+
+pub fn event_color(kind: FrameEventKind) -> (u8, u8, u8) {
+  match kind {
+    FrameEventKind::Nmi => (0xFF, 0xFF, 0x40),
+    FrameEventKind::Sprite0Hit => (0x40, 0xFF, 0x40),
+    FrameEventKind::MapperIrq => (0xFF, 0x40, 0xFF),
+  }
+}
+
+// the grid of an event viewer for one frame of the log: scanlines top to bottom, dots left
+// to right, the visible picture lighter than the blanking. Every write and event is a 3x3
+// mark centered on its dot, the events on top of the writes.
+// Front-ends show or save it, there is no live window that draws it over the game
+pub fn event_viewer(log: &EventLog, frame: u64) -> ImageBuffer {
+  let mut image = ImageBuffer::new(EVENT_VIEWER_WIDTH, EVENT_VIEWER_HEIGHT);
+  for y in 0..EVENT_VIEWER_HEIGHT {
+    for x in 0..EVENT_VIEWER_WIDTH {
+      let visible = y < Frame::HEIGHT && (1..=Frame::WIDTH).contains(&x);
+      image.set_pixel(x, y, if visible { PICTURE_COLOR } else { BLANK_COLOR });
+    }
+  }
+  let writes = log.writes(frame).iter().map(|write| (write.scanline, write.dot, register_color(write.address)));
+  let events = log.events(frame).iter().map(|event| (event.scanline, event.dot, event_color(event.kind)));
+  for (scanline, dot, rgb) in writes.chain(events) {
+    let (x, y) = (dot as usize, scanline as usize);
+    for mark_y in y.saturating_sub(1)..=(y + 1).min(EVENT_VIEWER_HEIGHT - 1) {
+      for mark_x in x.saturating_sub(1)..=(x + 1).min(EVENT_VIEWER_WIDTH - 1) {
+        image.set_pixel(mark_x, mark_y, rgb);
+      }
+    }
+  }
+  image
+}
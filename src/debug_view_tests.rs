@@ -0,0 +1,90 @@
+use crate::cartridge::Mirroring;
+use crate::debug_view::{event_color, event_viewer, oscilloscope, palettes, pattern_tables, register_color, SWATCH_SIZE,
+                        EVENT_VIEWER_HEIGHT, EVENT_VIEWER_WIDTH};
+use crate::event_log::{EventLog, FrameEventKind};
+use crate::palette::SYSTEM_PALETTE;
+use crate::ppu::NesPPU;
+
+// tile 1 of the left table: top row color 1; tile 0 of the right table: top row color 3
+fn init_ppu() -> NesPPU {
+  let mut chr = vec![0; 0x2000];
+  chr[0x10] = 0xFF;
+  chr[0x1000] = 0xFF;
+  chr[0x1008] = 0xFF;
+  let mut ppu = NesPPU::new(chr, Mirroring::HORIZONTAL);
+  ppu.palette_table[0x00] = 0x0F;
+  ppu.palette_table[0x01] = 0x16;
+  ppu.palette_table[0x03] = 0x2A;
+  ppu.palette_table[0x11] = 0x12;
+  ppu
+}
+
+#[test]
+fn test_pattern_tables_side_by_side() {
+  let ppu = init_ppu();
+
+  let image = pattern_tables(&ppu, 0);
+
+  assert_eq!((256, 128), (image.width, image.height));
+  assert_eq!(SYSTEM_PALETTE[0x0F], image.get_pixel(0, 0));
+  assert_eq!(SYSTEM_PALETTE[0x16], image.get_pixel(8, 0));
+  assert_eq!(SYSTEM_PALETTE[0x0F], image.get_pixel(8, 1));
+  assert_eq!(SYSTEM_PALETTE[0x2A], image.get_pixel(128, 0));
+}
+
+#[test]
+fn test_pattern_tables_with_sprite_palette() {
+  let ppu = init_ppu();
+
+  let image = pattern_tables(&ppu, 4);
+
+  assert_eq!(SYSTEM_PALETTE[0x12], image.get_pixel(8, 0));
+  // color 0 is always the backdrop
+  assert_eq!(SYSTEM_PALETTE[0x0F], image.get_pixel(0, 0));
+}
+
+#[test]
+fn test_palette_swatches() {
+  let ppu = init_ppu();
+
+  let image = palettes(&ppu);
+
+  assert_eq!((16 * SWATCH_SIZE, 2 * SWATCH_SIZE), (image.width, image.height));
+  assert_eq!(SYSTEM_PALETTE[0x16], image.get_pixel(SWATCH_SIZE, 0));
+  assert_eq!(SYSTEM_PALETTE[0x12], image.get_pixel(SWATCH_SIZE + 7, SWATCH_SIZE + 7));
+}
+
+#[test]
+fn test_oscilloscope_draws_square_wave_edges() {
+  let samples = [0.0, 0.0, 0.26, 0.26];
+
+  let image = oscilloscope(&samples, 4, 10);
+
+  assert_eq!((0, 0, 0), image.get_pixel(0, 0));
+  assert_ne!((0, 0, 0), image.get_pixel(0, 9));
+  assert_ne!((0, 0, 0), image.get_pixel(3, 0));
+  // the rising edge connects bottom and top
+  for y in 0..10 {
+    assert_ne!((0, 0, 0), image.get_pixel(2, y));
+  }
+}
+
+#[test]
+fn test_event_viewer_marks_writes_and_events() {
+  let mut log = EventLog::new(2);
+  log.record(20, 100, 0x8000, 0x2005, 0);
+  log.record_event(30, 256, FrameEventKind::Sprite0Hit);
+  log.record_event(261, 340, FrameEventKind::MapperIrq);
+
+  let image = event_viewer(&log, 0);
+
+  assert_eq!((341, 262), (EVENT_VIEWER_WIDTH, EVENT_VIEWER_HEIGHT));
+  assert_eq!((image.width, image.height), (EVENT_VIEWER_WIDTH, EVENT_VIEWER_HEIGHT));
+  assert_eq!(register_color(0x2005), image.get_pixel(99, 21));
+  assert_eq!(event_color(FrameEventKind::Sprite0Hit), image.get_pixel(256, 30));
+  // clipped at the corner
+  assert_eq!(event_color(FrameEventKind::MapperIrq), image.get_pixel(340, 261));
+  // the picture is lighter than the blanking
+  assert!(image.get_pixel(10, 10).0 > image.get_pixel(300, 10).0);
+  assert_eq!(image.get_pixel(300, 10), image.get_pixel(10, 250));
+}
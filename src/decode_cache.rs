@@ -0,0 +1,65 @@
+use crate::bus::Bus;
+use crate::cpu::{CpuBus, Handler, HandlerTable};
+use crate::opcodes::{self, OpCode};
+
+pub struct DecodedInstruction<B: CpuBus = Bus> {
+  pub opcode: &'static OpCode,
+  pub handler: Option<Handler<B>>, // None for BRK
+}
+
+// derive would require the bus to be Copy as well
+impl<B: CpuBus> Clone for DecodedInstruction<B> {
+  fn clone(&self) -> Self {
+    *self
+  }
+}
+
+impl<B: CpuBus> Copy for DecodedInstruction<B> {}
+
+impl<B: CpuBus> DecodedInstruction<B> {
+  pub fn decode(code: u8, handlers: &HandlerTable<B>) -> Option<DecodedInstruction<B>> {
+    let opcode: &'static OpCode = opcodes::lookup(code)?;
+    Some(DecodedInstruction { opcode, handler: handlers[code as usize] })
+  }
+}
+
+// decoded instructions of the immutable prg rom, indexed by rom offset
+pub struct DecodeCache<B: CpuBus = Bus> {
+  entries: Vec<Option<DecodedInstruction<B>>>,
+}
+
+impl<B: CpuBus> Default for DecodeCache<B> {
+  fn default() -> Self {
+    DecodeCache { entries: Vec::new() }
+  }
+}
+
+impl<B: CpuBus> DecodeCache<B> {
+  pub fn new() -> Self {
+    DecodeCache::default()
+  }
+
+  pub fn get(&self, offset: usize) -> Option<DecodedInstruction<B>> {
+    self.entries.get(offset).copied().flatten()
+  }
+
+  pub fn insert(&mut self, offset: usize, decoded: DecodedInstruction<B>) {
+    if offset >= self.entries.len() {
+      self.entries.resize(offset + 1, None);
+    }
+    self.entries[offset] = Some(decoded);
+  }
+
+  pub fn len(&self) -> usize {
+    self.entries.iter().filter(|e| e.is_some()).count()
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.len() == 0
+  }
+
+  // only needed if the rom content itself gets replaced
+  pub fn invalidate(&mut self) {
+    self.entries.clear();
+  }
+}
@@ -0,0 +1,33 @@
+use crate::bus::Bus;
+use crate::cartridge_tests::create_test_rom_with_program;
+use crate::cpu::{MyCPU, MyMem};
+
+fn init_cpu(program: &[u8]) -> MyCPU {
+  let mut cpu = MyCPU::new(Bus::new(create_test_rom_with_program(program)).unwrap());
+  cpu.reset();
+  cpu
+}
+
+#[test]
+fn test_rom_instructions_are_decoded_once() {
+  // $8000: LDX #$03, loop: INC $10, DEX, BNE loop, BRK
+  let mut cpu = init_cpu(&[0xA2, 0x03, 0xE6, 0x10, 0xCA, 0xD0, 0xFB, 0x00]);
+
+  cpu.run();
+
+  assert_eq!(3, cpu.mem_read(0x10));
+  assert_eq!(5, cpu.decode_cache.len());
+  assert_eq!(0xE6, cpu.decode_cache.get(2).unwrap().opcode.code);
+  assert!(cpu.decode_cache.get(1).is_none());
+}
+
+#[test]
+fn test_ram_instructions_are_not_cached() {
+  let mut cpu = init_cpu(&[]);
+  cpu.program_counter = 0x0600;
+
+  cpu.load_and_run(vec![0xE8, 0xE8]);
+
+  assert_eq!(2, cpu.register_x);
+  assert!(cpu.decode_cache.is_empty());
+}
@@ -0,0 +1,108 @@
+use std::collections::VecDeque;
+
+// delta = (base xor target), stored as: [zero run u16 le][literal count u16 le][literals]...
+// consecutive machine states mostly differ in a few bytes, so the xor is mostly zeros.
+pub fn encode(base: &[u8], target: &[u8]) -> Vec<u8> {
+  assert_eq!(base.len(), target.len(), "delta encoding needs states of equal size");
+  let mut encoded = Vec::new();
+  let mut i = 0;
+  while i < target.len() {
+    let zeros_start = i;
+    while i < target.len() && i - zeros_start < u16::MAX as usize && base[i] == target[i] {
+      i += 1;
+    }
+    let zeros = i - zeros_start;
+
+    let literals_start = i;
+    while i < target.len() && i - literals_start < u16::MAX as usize && base[i] != target[i] {
+      i += 1;
+    }
+
+    encoded.extend_from_slice(&(zeros as u16).to_le_bytes());
+    encoded.extend_from_slice(&((i - literals_start) as u16).to_le_bytes());
+    encoded.extend(base[literals_start..i].iter().zip(&target[literals_start..i]).map(|(b, t)| b ^ t));
+  }
+  encoded
+}
+
+pub fn apply(base: &[u8], delta: &[u8]) -> Result<Vec<u8>, String> {
+  let mut result = base.to_vec();
+  let mut pos = 0;
+  let mut i = 0;
+  while i < delta.len() {
+    if i + 4 > delta.len() {
+      return Err("truncated delta".to_string());
+    }
+    let zeros = u16::from_le_bytes([delta[i], delta[i + 1]]) as usize;
+    let literals = u16::from_le_bytes([delta[i + 2], delta[i + 3]]) as usize;
+    i += 4;
+    pos += zeros;
+    if i + literals > delta.len() || pos + literals > result.len() {
+      return Err("delta doesn't match base".to_string());
+    }
+    for (value, xor) in result[pos..pos + literals].iter_mut().zip(&delta[i..i + literals]) {
+      *value ^= xor;
+    }
+    pos += literals;
+    i += literals;
+  }
+  Ok(result)
+}
+
+// sequence of equally sized states: the newest is kept in full, every older one only as
+// delta to its successor (xor is symmetric, so walking back from the newest restores any state)
+#[derive(Default)]
+pub struct DeltaHistory {
+  deltas: VecDeque<Vec<u8>>,
+  newest: Option<Vec<u8>>,
+}
+
+impl DeltaHistory {
+  pub fn new() -> Self {
+    DeltaHistory::default()
+  }
+
+  pub fn len(&self) -> usize {
+    self.newest.as_ref().map_or(0, |_| self.deltas.len() + 1)
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.newest.is_none()
+  }
+
+  pub fn push(&mut self, state: Vec<u8>) {
+    if let Some(newest) = self.newest.take() {
+      self.deltas.push_back(encode(&state, &newest));
+    }
+    self.newest = Some(state);
+  }
+
+  pub fn pop_front(&mut self) {
+    if self.deltas.pop_front().is_none() {
+      self.newest = None;
+    }
+  }
+
+  pub fn pop_back(&mut self) {
+    self.newest = match (self.newest.take(), self.deltas.pop_back()) {
+      (Some(newest), Some(delta)) => Some(apply(&newest, &delta).expect("corrupt delta history")),
+      _ => None,
+    };
+  }
+
+  pub fn get(&self, idx: usize) -> Option<Vec<u8>> {
+    let mut state = self.newest.clone()?;
+    if idx >= self.len() {
+      return None;
+    }
+    for delta in self.deltas.iter().skip(idx).rev() {
+      state = apply(&state, delta).expect("corrupt delta history");
+    }
+    Some(state)
+  }
+
+  // bytes held by the stored states
+  pub fn memory_usage(&self) -> usize {
+    self.newest.as_ref().map_or(0, |n| n.len()) + self.deltas.iter().map(|d| d.len()).sum::<usize>()
+  }
+}
@@ -0,0 +1,81 @@
+use crate::bus::Bus;
+use crate::cartridge_tests::create_test_rom;
+use crate::cpu::MyCPU;
+use crate::delta::{apply, encode, DeltaHistory};
+use crate::snapshot::Snapshot;
+use crate::time_travel::TimeTravel;
+
+#[test]
+fn test_encode_and_apply_roundtrip() {
+  let base = vec![0, 1, 2, 3, 4, 5, 6, 7];
+  let target = vec![0, 1, 9, 9, 4, 5, 6, 8];
+
+  let delta = encode(&base, &target);
+
+  // 2 equal, 2 changed, 3 equal, 1 changed
+  assert_eq!(vec![2, 0, 2, 0, 2 ^ 9, 3 ^ 9, 3, 0, 1, 0, 7 ^ 8], delta);
+  assert_eq!(Ok(target), apply(&base, &delta));
+}
+
+#[test]
+fn test_identical_states_encode_small() {
+  let state = vec![0x42; 4096];
+
+  let delta = encode(&state, &state);
+
+  assert!(delta.len() <= 4);
+  assert_eq!(Ok(state.clone()), apply(&state, &delta));
+}
+
+#[test]
+fn test_apply_rejects_truncated_delta() {
+  assert!(apply(&[1, 2, 3], &[0, 0, 5, 0, 1]).is_err());
+}
+
+#[test]
+fn test_history_restores_every_state() {
+  let mut history = DeltaHistory::new();
+  let states: Vec<Vec<u8>> = (0..5u8).map(|i| vec![i, 0, i * 2, 0xFF]).collect();
+  for state in &states {
+    history.push(state.clone());
+  }
+
+  history.pop_front();
+  history.pop_back();
+
+  assert_eq!(3, history.len());
+  assert_eq!(Some(states[1].clone()), history.get(0));
+  assert_eq!(Some(states[2].clone()), history.get(1));
+  assert_eq!(Some(states[3].clone()), history.get(2));
+  assert_eq!(None, history.get(3));
+}
+
+#[test]
+fn test_snapshot_bytes_roundtrip() {
+  let mut cpu = MyCPU::new(Bus::new(create_test_rom()).unwrap());
+  cpu.register_a = 0x12;
+  cpu.program_counter = 0x8123;
+  cpu.cycles = 1_000_000;
+
+  let snapshot = cpu.snapshot();
+
+  assert_eq!(Ok(snapshot.clone()), Snapshot::from_bytes(&snapshot.to_bytes()));
+}
+
+#[test]
+fn test_time_travel_history_stays_small() {
+  let mut cpu = MyCPU::new(Bus::new(create_test_rom()).unwrap());
+  // $0600: INC $10, JMP $0600
+  cpu.load(vec![0xE6, 0x10, 0x4C, 0x00, 0x06]);
+  cpu.program_counter = 0x0600;
+  let mut time_travel = TimeTravel::new(10, 100);
+
+  for _ in 0..1000 {
+    time_travel.step(&mut cpu);
+  }
+
+  let full_size = 100 * cpu.save_state().len();
+  assert!(time_travel.memory_usage() < full_size / 10, "{} bytes", time_travel.memory_usage());
+  assert!(time_travel.step_back(&mut cpu));
+  assert_eq!(999, time_travel.position());
+}
@@ -0,0 +1,79 @@
+use crate::cpu::AddressingMode;
+use crate::opcodes;
+
+// static, linear disassembly: data between the code is decoded as instructions too
+pub struct Line {
+  pub address: u16,
+  pub bytes: Vec<u8>,
+  pub text: String,
+  pub label: Option<String>,
+}
+
+impl Line {
+  // a label goes on its own line before the instruction
+  pub fn format(&self) -> String {
+    let hex = self.bytes.iter().map(|b| format!("{:02X}", b)).collect::<Vec<String>>().join(" ");
+    let line = format!("{:04X}  {:8}  {}", self.address, hex, self.text);
+    match &self.label {
+      Some(label) => format!("{}:\n{}", label, line),
+      None => line,
+    }
+  }
+}
+
+// `code` starts at `base`, a truncated last instruction is shown as data
+pub fn disassemble(code: &[u8], base: u16) -> Vec<Line> {
+  disassemble_with_labels(code, base, |_| None)
+}
+
+// label lookup is used for the instruction addresses and the operands which address memory
+pub fn disassemble_with_labels<F>(code: &[u8], base: u16, label: F) -> Vec<Line>
+  where F: Fn(u16) -> Option<String>
+{
+  let mut lines = Vec::new();
+  let mut offset = 0;
+  while offset < code.len() {
+    let address = base.wrapping_add(offset as u16);
+    let line = match opcodes::lookup(code[offset]) {
+      Some(op) if offset + op.len as usize <= code.len() => {
+        let bytes = code[offset..offset + op.len as usize].to_vec();
+        let operand = format_operand(&op.mode, op.code, &bytes, address, &label);
+        let text = if operand.is_empty() { op.mnemonic.to_string() } else { format!("{} {}", op.mnemonic, operand) };
+        Line { address, bytes, text, label: label(address) }
+      }
+      _ => Line { address, bytes: vec![code[offset]], text: format!(".byte ${:02X}", code[offset]), label: label(address) },
+    };
+    offset += line.bytes.len();
+    lines.push(line);
+  }
+  lines
+}
+
+fn format_operand<F>(mode: &AddressingMode, code: u8, bytes: &[u8], address: u16, label: &F) -> String
+  where F: Fn(u16) -> Option<String>
+{
+  let u8_operand = *bytes.get(1).unwrap_or(&0);
+  let u16_operand = u16::from_le_bytes([u8_operand, *bytes.get(2).unwrap_or(&0)]);
+  let zero_page = label(u8_operand as u16).unwrap_or_else(|| format!("${:02X}", u8_operand));
+  let absolute = label(u16_operand).unwrap_or_else(|| format!("${:04X}", u16_operand));
+  match mode {
+    AddressingMode::NoneAddressing => match code {
+      0x0A | 0x4A | 0x2A | 0x6A => "A".to_string(),
+      _ => String::new(),
+    },
+    AddressingMode::Immediate => format!("#${:02X}", u8_operand),
+    AddressingMode::ZeroPage => zero_page,
+    AddressingMode::ZeroPage_X => format!("{},X", zero_page),
+    AddressingMode::ZeroPage_Y => format!("{},Y", zero_page),
+    AddressingMode::Absolute => absolute,
+    AddressingMode::Absolute_X => format!("{},X", absolute),
+    AddressingMode::Absolute_Y => format!("{},Y", absolute),
+    AddressingMode::Indirect_X => format!("({},X)", zero_page),
+    AddressingMode::Indirect_Y => format!("({}),Y", zero_page),
+    AddressingMode::Indirect => format!("({})", absolute),
+    AddressingMode::Relative => {
+      let target = address.wrapping_add(2).wrapping_add(u8_operand as i8 as u16);
+      label(target).unwrap_or_else(|| format!("${:04X}", target))
+    }
+  }
+}
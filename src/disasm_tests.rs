@@ -0,0 +1,50 @@
+use crate::disasm::{disassemble, disassemble_with_labels};
+
+#[test]
+fn test_disassemble_addressing_modes() {
+  let code = [
+    0xA9, 0x01,       // LDA #$01
+    0x8D, 0x00, 0x02, // STA $0200
+    0xB1, 0x10,       // LDA ($10),Y
+    0x0A,             // ASL A
+    0xD0, 0xF6,       // BNE $8000
+    0x6C, 0xFC, 0xFF, // JMP ($FFFC)
+    0xEA,             // NOP
+  ];
+
+  let lines: Vec<String> = disassemble(&code, 0x8000).iter().map(|l| l.text.clone()).collect();
+
+  assert_eq!(vec!["LDA #$01", "STA $0200", "LDA ($10),Y", "ASL A", "BNE $8000", "JMP ($FFFC)", "NOP"], lines);
+}
+
+#[test]
+fn test_unknown_and_truncated_bytes_are_data() {
+  let lines = disassemble(&[0x02, 0xAD, 0x00], 0xC000);
+
+  assert_eq!(3, lines.len());
+  assert_eq!(".byte $02", lines[0].text);
+  assert_eq!("C001  AD        .byte $AD", lines[1].format());
+}
+
+#[test]
+fn test_labels_replace_addresses() {
+  let code = [
+    0xA5, 0x10,       // LDA player_x
+    0x20, 0x00, 0x80, // JSR reset
+    0xD0, 0xF9,       // BNE reset
+    0x8D, 0x00, 0x20, // STA PPUCTRL
+  ];
+  let label = |address: u16| match address {
+    0x0010 => Some("player_x".to_string()),
+    0x8000 => Some("reset".to_string()),
+    0x2000 => Some("PPUCTRL".to_string()),
+    _ => None,
+  };
+
+  let lines = disassemble_with_labels(&code, 0x8000, label);
+
+  let texts: Vec<&str> = lines.iter().map(|l| l.text.as_str()).collect();
+  assert_eq!(vec!["LDA player_x", "JSR reset", "BNE reset", "STA PPUCTRL"], texts);
+  assert_eq!("reset:\n8000  A5 10     LDA player_x", lines[0].format());
+  assert_eq!(None, lines[1].label);
+}
@@ -0,0 +1,50 @@
+use std::fmt;
+use std::io;
+use crate::cartridge::RomError;
+use crate::nsf::NsfError;
+
+// everything that can go wrong before the emulation runs
+#[derive(Debug)]
+pub enum EmuError {
+  Rom(RomError),
+  Io(io::Error),
+  Nsf(NsfError),
+}
+
+impl fmt::Display for EmuError {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self {
+      EmuError::Rom(e) => write!(f, "invalid rom: {}", e),
+      EmuError::Io(e) => write!(f, "{}", e),
+      EmuError::Nsf(e) => write!(f, "invalid nsf: {}", e),
+    }
+  }
+}
+
+impl std::error::Error for EmuError {
+  fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+    match self {
+      EmuError::Rom(e) => Some(e),
+      EmuError::Io(e) => Some(e),
+      EmuError::Nsf(e) => Some(e),
+    }
+  }
+}
+
+impl From<RomError> for EmuError {
+  fn from(e: RomError) -> Self {
+    EmuError::Rom(e)
+  }
+}
+
+impl From<io::Error> for EmuError {
+  fn from(e: io::Error) -> Self {
+    EmuError::Io(e)
+  }
+}
+
+impl From<NsfError> for EmuError {
+  fn from(e: NsfError) -> Self {
+    EmuError::Nsf(e)
+  }
+}
@@ -0,0 +1,109 @@
+use std::collections::VecDeque;
+
+pub const DEFAULT_MAX_FRAMES: usize = 60;
+
+// a write to a ppu or apu register, where the ppu was when it happened
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RegisterWrite {
+  pub frame: u64,
+  pub scanline: u16,
+  pub dot: u16,
+  // start of the instruction that wrote
+  pub program_counter: u16,
+  // ppu register mirrors are folded onto $2000-$2007
+  pub address: u16,
+  pub value: u8,
+}
+
+// what else shows up in an event viewer next to the register writes
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FrameEventKind {
+  Nmi,
+  Sprite0Hit,
+  MapperIrq,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FrameEvent {
+  pub frame: u64,
+  pub scanline: u16,
+  pub dot: u16,
+  pub kind: FrameEventKind,
+}
+
+// ppu registers and their mirrors, then the apu and i/o registers
+pub fn is_logged_register(addr: u16) -> bool {
+  (0x2000..=0x4017).contains(&addr)
+}
+
+#[derive(Default)]
+struct FrameRecord {
+  number: u64,
+  writes: Vec<RegisterWrite>,
+  events: Vec<FrameEvent>,
+}
+
+// keeps the writes and events of the last max_frames frames, like an event viewer shows them
+// (debug_view::event_viewer). A frame starts and ends with vblank. The ppu position of a write
+// is the one after the writing instruction's cycles ran, events have the exact dot
+pub struct EventLog {
+  max_frames: usize,
+  frame: u64,
+  frames: VecDeque<FrameRecord>,
+}
+
+impl EventLog {
+  pub fn new(max_frames: usize) -> Self {
+    let mut frames = VecDeque::new();
+    frames.push_back(FrameRecord::default());
+    EventLog { max_frames: max_frames.max(1), frame: 0, frames }
+  }
+
+  pub fn current_frame(&self) -> u64 {
+    self.frame
+  }
+
+  pub fn record(&mut self, scanline: u16, dot: u16, program_counter: u16, addr: u16, value: u8) {
+    let address = if addr < 0x4000 { 0x2000 | (addr & 0b111) } else { addr };
+    let write = RegisterWrite { frame: self.frame, scanline, dot, program_counter, address, value };
+    if let Some(record) = self.frames.back_mut() {
+      record.writes.push(write);
+    }
+  }
+
+  pub fn record_event(&mut self, scanline: u16, dot: u16, kind: FrameEventKind) {
+    let event = FrameEvent { frame: self.frame, scanline, dot, kind };
+    if let Some(record) = self.frames.back_mut() {
+      record.events.push(event);
+    }
+  }
+
+  pub fn end_frame(&mut self) {
+    self.frame += 1;
+    self.frames.push_back(FrameRecord { number: self.frame, ..FrameRecord::default() });
+    while self.frames.len() > self.max_frames {
+      self.frames.pop_front();
+    }
+  }
+
+  // empty for frames that were dropped or haven't happened yet
+  pub fn writes(&self, frame: u64) -> &[RegisterWrite] {
+    self.frame_record(frame).map_or(&[], |record| record.writes.as_slice())
+  }
+
+  pub fn events(&self, frame: u64) -> &[FrameEvent] {
+    self.frame_record(frame).map_or(&[], |record| record.events.as_slice())
+  }
+
+  fn frame_record(&self, frame: u64) -> Option<&FrameRecord> {
+    self.frames.iter().find(|record| record.number == frame)
+  }
+
+  pub fn last_complete_frame(&self) -> Option<u64> {
+    self.frame.checked_sub(1).filter(|frame| !self.writes_dropped(*frame))
+  }
+
+  fn writes_dropped(&self, frame: u64) -> bool {
+    self.frames.front().is_none_or(|oldest| frame < oldest.number)
+  }
+}
@@ -0,0 +1,97 @@
+use crate::cartridge_tests::create_test_rom_with_program;
+use crate::event_log::{EventLog, FrameEventKind, is_logged_register};
+use crate::nes::Nes;
+
+#[test]
+fn test_writes_are_grouped_by_frame() {
+  let mut log = EventLog::new(2);
+  log.record(10, 20, 0x8000, 0x2001, 0x1E);
+  log.end_frame();
+  log.record(30, 40, 0x8005, 0x4000, 0x3F);
+
+  assert_eq!(1, log.writes(0).len());
+  assert_eq!((10, 20, 0x8000), (log.writes(0)[0].scanline, log.writes(0)[0].dot, log.writes(0)[0].program_counter));
+  assert_eq!(0x4000, log.writes(1)[0].address);
+  assert_eq!(1, log.writes(1)[0].frame);
+  assert_eq!(Some(0), log.last_complete_frame());
+}
+
+#[test]
+fn test_old_frames_are_dropped() {
+  let mut log = EventLog::new(2);
+  log.record(0, 0, 0x8000, 0x2000, 0x80);
+  log.end_frame();
+  log.end_frame();
+
+  assert!(log.writes(0).is_empty());
+  assert_eq!(Some(1), log.last_complete_frame());
+  assert_eq!(2, log.current_frame());
+}
+
+#[test]
+fn test_ppu_register_mirrors_are_folded() {
+  let mut log = EventLog::new(1);
+  log.record(0, 0, 0, 0x3FF9, 0x00);
+
+  assert_eq!(0x2001, log.writes(0)[0].address);
+  assert!(is_logged_register(0x4017));
+  assert!(!is_logged_register(0x4018));
+  assert!(!is_logged_register(0x0200));
+}
+
+#[test]
+fn test_bus_logs_register_writes_with_pc_and_position() {
+  let program = [
+    0xA9, 0x1E,       // LDA #$1E
+    0x8D, 0x01, 0x20, // loop: STA $2001
+    0x8D, 0x00, 0x02, // STA $0200
+    0x4C, 0x02, 0x80, // JMP loop
+  ];
+  let mut nes = Nes::new(create_test_rom_with_program(&program)).unwrap();
+  nes.cpu.bus.enable_event_log(10);
+
+  nes.run_for_frames(2);
+
+  let log = nes.cpu.bus.event_log.as_ref().unwrap();
+  let frame = log.last_complete_frame().unwrap();
+  let writes = log.writes(frame);
+  assert!(writes.len() > 1000);
+  assert!(writes.iter().all(|w| w.address == 0x2001 && w.program_counter == 0x8002 && w.value == 0x1E));
+  // spread over the whole frame
+  assert!(writes.iter().any(|w| w.scanline < 10));
+  assert!(writes.iter().any(|w| w.scanline > 200));
+}
+
+#[test]
+fn test_events_are_grouped_by_frame() {
+  let mut log = EventLog::new(2);
+  log.record_event(30, 256, FrameEventKind::Sprite0Hit);
+  log.end_frame();
+  log.record_event(241, 1, FrameEventKind::Nmi);
+
+  assert_eq!(vec![(0, 30, 256, FrameEventKind::Sprite0Hit)],
+             log.events(0).iter().map(|e| (e.frame, e.scanline, e.dot, e.kind)).collect::<Vec<_>>());
+  assert_eq!(FrameEventKind::Nmi, log.events(1)[0].kind);
+  assert!(log.events(2).is_empty());
+}
+
+#[test]
+fn test_bus_logs_the_nmi_at_the_start_of_the_frame() {
+  let program = [
+    0xA9, 0x80,       // LDA #$80
+    0x8D, 0x00, 0x20, // STA $2000
+    0x4C, 0x05, 0x80, // loop: JMP loop
+  ];
+  let mut nes = Nes::new(create_test_rom_with_program(&program)).unwrap();
+  nes.cpu.bus.enable_event_log(10);
+
+  nes.run_for_frames(3);
+
+  let log = nes.cpu.bus.event_log.as_ref().unwrap();
+  let events = log.events(log.last_complete_frame().unwrap());
+  assert_eq!(1, events.len());
+  assert_eq!((241, 1, FrameEventKind::Nmi), (events[0].scanline, events[0].dot, events[0].kind));
+  // the frame of the write didn't start with an nmi yet
+  assert!(log.events(0).is_empty());
+  assert_eq!(0x2000, log.writes(0)[0].address);
+}
@@ -0,0 +1,87 @@
+use std::ptr;
+use std::slice;
+use crate::cartridge::Rom;
+use crate::error::EmuError;
+use crate::input::Player;
+use crate::joypad::JoypadButton;
+use crate::nes::Nes;
+
+// c interface of the cdylib for front-ends in c, c++, c# etc., declared in include/nes_emulator.h.
+// The caller owns the handle: nes_create, then nes_destroy exactly once. Null handles are ignored.
+
+pub const NES_OK: i32 = 0;
+pub const NES_ERROR_ARGUMENT: i32 = -1;
+pub const NES_ERROR_ROM: i32 = -2;
+
+// opaque to c, the machine exists once a rom is loaded
+pub struct NesHandle {
+  pub nes: Option<Nes>,
+}
+
+#[no_mangle]
+pub extern "C" fn nes_create() -> *mut NesHandle {
+  Box::into_raw(Box::new(NesHandle { nes: None }))
+}
+
+/// # Safety
+/// `handle` is null or comes from nes_create and was not destroyed before
+#[no_mangle]
+pub unsafe extern "C" fn nes_destroy(handle: *mut NesHandle) {
+  if !handle.is_null() {
+    drop(Box::from_raw(handle));
+  }
+}
+
+/// # Safety
+/// `handle` as for nes_destroy, `data` points to `len` readable bytes of an .nes file
+#[no_mangle]
+pub unsafe extern "C" fn nes_load_rom(handle: *mut NesHandle, data: *const u8, len: usize) -> i32 {
+  let handle = match handle.as_mut() {
+    Some(handle) if !data.is_null() => handle,
+    _ => return NES_ERROR_ARGUMENT,
+  };
+  match Rom::new(slice::from_raw_parts(data, len)).map_err(EmuError::from).and_then(Nes::new) {
+    Ok(nes) => {
+      handle.nes = Some(nes);
+      NES_OK
+    }
+    Err(_) => NES_ERROR_ROM,
+  }
+}
+
+/// # Safety
+/// `handle` as for nes_destroy
+#[no_mangle]
+pub unsafe extern "C" fn nes_run_frame(handle: *mut NesHandle) -> i32 {
+  let nes = match handle.as_mut().and_then(|handle| handle.nes.as_mut()) {
+    Some(nes) => nes,
+    None => return 0,
+  };
+  nes.step_frame().frame_completed as i32
+}
+
+/// # Safety
+/// `handle` as for nes_destroy
+#[no_mangle]
+pub unsafe extern "C" fn nes_get_framebuffer(handle: *const NesHandle) -> *const u8 {
+  match handle.as_ref().and_then(|handle| handle.nes.as_ref()) {
+    Some(nes) => nes.with_frame(|rgba| rgba.as_ptr()),
+    None => ptr::null(),
+  }
+}
+
+/// # Safety
+/// `handle` as for nes_destroy
+#[no_mangle]
+pub unsafe extern "C" fn nes_set_input(handle: *mut NesHandle, player: u32, buttons: u8) {
+  let nes = match handle.as_mut().and_then(|handle| handle.nes.as_mut()) {
+    Some(nes) => nes,
+    None => return,
+  };
+  let player = match player {
+    1 => Player::One,
+    2 => Player::Two,
+    _ => return,
+  };
+  nes.set_buttons(player, JoypadButton::from_bits_truncate(buttons));
+}
@@ -0,0 +1,93 @@
+use std::ptr;
+use crate::cartridge_tests::test_rom_bytes_with_program;
+use crate::ffi::*;
+use crate::frame::Frame;
+use crate::joypad::JoypadButton;
+use crate::palette::SYSTEM_PALETTE;
+
+const HEADER: &str = include_str!("../include/nes_emulator.h");
+
+// sets the backdrop color to $16, then copies the first button of player 2 to $10, forever
+fn rom_bytes() -> Vec<u8> {
+  let program = [
+    0xA9, 0x3F, 0x8D, 0x06, 0x20, // LDA #$3F, STA $2006
+    0xA9, 0x00, 0x8D, 0x06, 0x20, // LDA #$00, STA $2006
+    0xA9, 0x16, 0x8D, 0x07, 0x20, // LDA #$16, STA $2007
+    0xA9, 0x00, 0x8D, 0x06, 0x20, // LDA #$00, STA $2006
+    0x8D, 0x06, 0x20,             // STA $2006
+    0xA9, 0x01, 0x8D, 0x16, 0x40, // loop: LDA #$01, STA $4016
+    0xA9, 0x00, 0x8D, 0x16, 0x40, // LDA #$00, STA $4016
+    0xAD, 0x17, 0x40, 0x85, 0x10, // LDA $4017, STA $10
+    0x4C, 0x17, 0x80,             // JMP loop
+  ];
+  test_rom_bytes_with_program(&program)
+}
+
+fn define(name: &str) -> i64 {
+  let line = HEADER.lines().find(|line| line.starts_with(&format!("#define {} ", name)))
+    .unwrap_or_else(|| panic!("{} is not in the header", name));
+  let value = line.rsplit(' ').next().unwrap();
+  match value.strip_prefix("0x") {
+    Some(hex) => i64::from_str_radix(hex, 16).unwrap(),
+    None => value.parse().unwrap(),
+  }
+}
+
+#[test]
+fn test_run_frames_with_input() {
+  let rom = rom_bytes();
+  unsafe {
+    let handle = nes_create();
+    assert!(nes_get_framebuffer(handle).is_null());
+    assert_eq!(0, nes_run_frame(handle));
+
+    assert_eq!(NES_OK, nes_load_rom(handle, rom.as_ptr(), rom.len()));
+    nes_set_input(handle, 2, JoypadButton::BUTTON_A.bits());
+    assert_eq!(1, nes_run_frame(handle));
+
+    let frame = std::slice::from_raw_parts(nes_get_framebuffer(handle), Frame::WIDTH * Frame::HEIGHT * 4);
+    let (r, g, b) = SYSTEM_PALETTE[0x16];
+    assert_eq!(&[r, g, b, 0xFF], &frame[..4]);
+    assert_eq!(0, frame.as_ptr() as usize % 4096);
+    assert_eq!(1, (*handle).nes.as_ref().unwrap().cpu.bus.ram()[0x10]);
+    nes_destroy(handle);
+  }
+}
+
+#[test]
+fn test_invalid_arguments() {
+  let rom = rom_bytes();
+  unsafe {
+    let handle = nes_create();
+    assert_eq!(NES_ERROR_ARGUMENT, nes_load_rom(handle, ptr::null(), 0));
+    assert_eq!(NES_ERROR_ARGUMENT, nes_load_rom(ptr::null_mut(), rom.as_ptr(), rom.len()));
+    assert_eq!(NES_ERROR_ROM, nes_load_rom(handle, rom.as_ptr(), 16));
+    assert!(nes_get_framebuffer(handle).is_null());
+
+    assert_eq!(0, nes_run_frame(ptr::null_mut()));
+    nes_set_input(ptr::null_mut(), 1, 0);
+    nes_destroy(handle);
+    nes_destroy(ptr::null_mut());
+  }
+}
+
+#[test]
+fn test_header_matches_the_exports() {
+  let exports: Vec<&str> = include_str!("ffi.rs").lines()
+    .filter_map(|line| line.split("extern \"C\" fn ").nth(1))
+    .map(|rest| rest.split('(').next().unwrap())
+    .collect();
+  assert_eq!(6, exports.len());
+  for name in exports {
+    assert!(HEADER.contains(&format!(" {}(", name)) || HEADER.contains(&format!("*{}(", name)), "{} is not in the header", name);
+  }
+
+  assert_eq!((NES_OK, NES_ERROR_ARGUMENT, NES_ERROR_ROM),
+             (define("NES_OK") as i32, define("NES_ERROR_ARGUMENT") as i32, define("NES_ERROR_ROM") as i32));
+  assert_eq!((Frame::WIDTH, Frame::HEIGHT), (define("NES_FRAME_WIDTH") as usize, define("NES_FRAME_HEIGHT") as usize));
+  for (name, button) in [("A", JoypadButton::BUTTON_A), ("B", JoypadButton::BUTTON_B), ("SELECT", JoypadButton::SELECT),
+                         ("START", JoypadButton::START), ("UP", JoypadButton::UP), ("DOWN", JoypadButton::DOWN),
+                         ("LEFT", JoypadButton::LEFT), ("RIGHT", JoypadButton::RIGHT)] {
+    assert_eq!(button.bits() as i64, define(&format!("NES_BUTTON_{}", name)));
+  }
+}
@@ -0,0 +1,82 @@
+use crate::cpu::{CpuBus, MyMem};
+use crate::savestate::{StateReader, StateWriter, Stateful};
+
+// 64KB of ram and nothing else: no devices, no interrupts, no mirroring.
+// Enough to run the cpu core for other 6502 machines or plain test programs.
+pub struct FlatMemory {
+  data: Box<[u8; 0x10000]>,
+  load_address: u16,
+  reset_vector: u16,
+}
+
+impl FlatMemory {
+  pub fn new() -> Self {
+    FlatMemory { data: Box::new([0; 0x10000]), load_address: 0x0600, reset_vector: 0xFFFC }
+  }
+
+  pub fn with_load_address(mut self, load_address: u16) -> Self {
+    self.load_address = load_address;
+    self
+  }
+
+  pub fn with_reset_vector(mut self, reset_vector: u16) -> Self {
+    self.reset_vector = reset_vector;
+    self
+  }
+
+  pub fn data(&self) -> &[u8] {
+    &self.data[..]
+  }
+
+  // copies an image to the given address, e.g. a rom at the top of memory
+  pub fn load(&mut self, addr: u16, image: &[u8]) {
+    let start = addr as usize;
+    let len = image.len().min(self.data.len() - start);
+    self.data[start..start + len].copy_from_slice(&image[..len]);
+  }
+}
+
+impl Default for FlatMemory {
+  fn default() -> Self {
+    FlatMemory::new()
+  }
+}
+
+impl MyMem for FlatMemory {
+  fn mem_read(&mut self, addr: u16) -> u8 {
+    self.data[addr as usize]
+  }
+
+  fn mem_write(&mut self, addr: u16, data: u8) {
+    self.data[addr as usize] = data;
+  }
+}
+
+impl Stateful for FlatMemory {
+  fn save_state(&self, w: &mut StateWriter) {
+    w.write_bytes(&self.data[..]);
+  }
+
+  fn load_state(&mut self, r: &mut StateReader) -> Result<(), String> {
+    r.read_into(&mut self.data[..])
+  }
+}
+
+impl CpuBus for FlatMemory {
+  fn peek(&self, addr: u16) -> u8 {
+    self.data[addr as usize]
+  }
+
+  fn poke(&mut self, addr: u16, data: u8) -> bool {
+    self.data[addr as usize] = data;
+    true
+  }
+
+  fn load_address(&self) -> u16 {
+    self.load_address
+  }
+
+  fn reset_vector(&self) -> u16 {
+    self.reset_vector
+  }
+}
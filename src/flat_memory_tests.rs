@@ -0,0 +1,139 @@
+use crate::cpu::{CpuVariant, MyCPU, MyMem, StopCondition};
+use crate::flat_memory::FlatMemory;
+
+#[test]
+fn test_program_runs_on_plain_memory() {
+  let mut cpu = MyCPU::new(FlatMemory::new().with_load_address(0x0200));
+  cpu.load(vec![0xA9, 0x42, 0x8D, 0x00, 0x40, 0x00]); // LDA #$42, STA $4000, BRK
+  cpu.program_counter = 0x0200;
+
+  cpu.run();
+
+  assert_eq!(0x42, cpu.bus.data()[0x4000]);
+  assert_eq!(0x42, cpu.mem_read(0x4000));
+}
+
+#[test]
+fn test_load_past_the_top_of_memory_wraps_around() {
+  let mut cpu = MyCPU::new(FlatMemory::new().with_load_address(0xFFFE));
+  cpu.load(vec![1, 2, 3, 4]);
+
+  assert_eq!([1, 2], cpu.bus.data()[0xFFFE..]);
+  assert_eq!([3, 4], cpu.bus.data()[..2]);
+
+  // a whole 64KB image, nothing is cut off
+  let mut cpu = MyCPU::new(FlatMemory::new().with_load_address(0));
+  cpu.load((0..0x10000).map(|i| (i % 251) as u8).collect());
+  assert_eq!((0xFFFF % 251) as u8, cpu.bus.data()[0xFFFF]);
+}
+
+#[test]
+fn test_reset_uses_the_injected_vector() {
+  let mut memory = FlatMemory::new().with_reset_vector(0x1000);
+  memory.load(0x1000, &[0x00, 0xC0]);
+  let mut cpu = MyCPU::new(memory);
+
+  cpu.reset();
+
+  assert_eq!(0xC000, cpu.program_counter);
+}
+
+#[test]
+fn test_load_with_address_points_the_reset_vector_at_the_program() {
+  let mut cpu = MyCPU::new(FlatMemory::new());
+  cpu.load_with_address(vec![0xE8, 0x00], 0x0300).unwrap(); // INX, BRK
+
+  cpu.reset();
+  cpu.run();
+
+  assert_eq!(0x0300, cpu.mem_read_u16(0xFFFC));
+  assert_eq!(1, cpu.register_x);
+}
+
+#[test]
+fn test_load_with_address_up_to_the_top_of_memory() {
+  let mut cpu = MyCPU::new(FlatMemory::new());
+  let mut program = vec![0xEA; 0x100];
+  // the program brings its own reset vector
+  program[0xFC] = 0x34;
+  program[0xFD] = 0x12;
+
+  cpu.load_with_address(program, 0xFF00).unwrap();
+
+  assert_eq!(0xEA, cpu.mem_read(0xFF00));
+  assert_eq!(0x1234, cpu.mem_read_u16(0xFFFC));
+  assert_eq!(0xEA, cpu.mem_read(0xFFFF));
+}
+
+#[test]
+fn test_load_with_address_fills_all_of_memory() {
+  let mut cpu = MyCPU::new(FlatMemory::new());
+
+  cpu.load_with_address(vec![0x42; 0x10000], 0x0000).unwrap();
+
+  assert!(cpu.bus.data().iter().all(|&b| b == 0x42));
+}
+
+#[test]
+fn test_decimal_mode_on_a_generic_6502() {
+  let mut cpu = MyCPU::new(FlatMemory::new());
+  cpu.variant = CpuVariant::Nmos6502;
+  cpu.stop_condition = StopCondition::Brk;
+  cpu.load(vec![0xF8, 0xA9, 0x19, 0x69, 0x01, 0x00]); // SED, LDA #$19, ADC #$01, BRK
+  cpu.program_counter = 0x0600;
+
+  cpu.run();
+
+  assert_eq!(0x20, cpu.register_a);
+}
+
+#[test]
+fn test_last_address_is_addressable() {
+  let mut memory = FlatMemory::new();
+
+  memory.mem_write(0xFFFF, 0x42);
+
+  assert_eq!(0x10000, memory.data().len());
+  assert_eq!(0x42, memory.mem_read(0xFFFF));
+}
+
+#[test]
+fn test_u16_access_at_ffff_wraps_to_0000() {
+  let mut memory = FlatMemory::new();
+  memory.load(0xFFFF, &[0x34]);
+  memory.load(0x0000, &[0x12]);
+
+  assert_eq!(0x1234, memory.mem_read_u16(0xFFFF));
+
+  memory.mem_write_u16(0xFFFF, 0xBEEF);
+  assert_eq!(0xEF, memory.data()[0xFFFF]);
+  assert_eq!(0xBE, memory.data()[0x0000]);
+}
+
+#[test]
+fn test_brk_reads_the_irq_vector_from_the_top_of_memory() {
+  let mut memory = FlatMemory::new();
+  memory.load(0xFFFE, &[0x00, 0x30]);
+  let mut cpu = MyCPU::new(memory);
+  cpu.stop_condition = StopCondition::Never;
+  cpu.load(vec![0x00]);
+  cpu.program_counter = 0x0600;
+
+  cpu.step();
+
+  assert_eq!(0x3000, cpu.program_counter);
+}
+
+#[test]
+fn test_program_counter_wraps_after_ffff() {
+  let mut memory = FlatMemory::new();
+  memory.load(0xFFFF, &[0xA9]); // LDA #$42, the operand is at $0000
+  memory.load(0x0000, &[0x42]);
+  let mut cpu = MyCPU::new(memory);
+  cpu.program_counter = 0xFFFF;
+
+  cpu.step();
+
+  assert_eq!(0x42, cpu.register_a);
+  assert_eq!(0x0001, cpu.program_counter);
+}
@@ -0,0 +1,149 @@
+use std::io;
+use std::ops::{Deref, DerefMut};
+use std::path::Path;
+use crate::palette::Palette;
+use crate::png;
+use crate::rgba;
+
+#[derive(Clone, PartialEq)]
+pub struct Frame {
+  pub data: Vec<u8>,
+}
+
+impl Frame {
+  pub const WIDTH: usize = 256;
+  pub const HEIGHT: usize = 240;
+
+  pub fn new() -> Self {
+    Frame {
+      data: vec![0; Frame::WIDTH * Frame::HEIGHT * 3],
+    }
+  }
+
+  pub fn set_pixel(&mut self, x: usize, y: usize, rgb: (u8, u8, u8)) {
+    let base = y * 3 * Frame::WIDTH + x * 3;
+    if base + 2 < self.data.len() {
+      self.data[base] = rgb.0;
+      self.data[base + 1] = rgb.1;
+      self.data[base + 2] = rgb.2;
+    }
+  }
+
+  pub fn get_pixel(&self, x: usize, y: usize) -> (u8, u8, u8) {
+    let base = y * 3 * Frame::WIDTH + x * 3;
+    (self.data[base], self.data[base + 1], self.data[base + 2])
+  }
+
+  // without the alpha channel of a FrameBuffer
+  pub fn from_rgba(rgba: &[u8]) -> Frame {
+    Frame { data: rgba.chunks_exact(4).flat_map(|pixel| pixel[..3].iter().copied()).collect() }
+  }
+
+  pub fn to_png(&self) -> Vec<u8> {
+    png::encode_rgb(Frame::WIDTH, Frame::HEIGHT, &self.data)
+  }
+
+  pub fn save_png(&self, path: &Path) -> io::Result<()> {
+    std::fs::write(path, self.to_png())
+  }
+}
+
+impl Default for Frame {
+  fn default() -> Self {
+    Frame::new()
+  }
+}
+
+// the picture as the ppu draws it, a palette::color_index per pixel. The colors are only
+// looked up when a front-end needs them, through whatever palette it uses then
+#[derive(Clone, PartialEq)]
+pub struct IndexedFrame {
+  pub pixels: Vec<u16>,
+}
+
+impl IndexedFrame {
+  pub fn new() -> Self {
+    IndexedFrame { pixels: vec![0; Frame::WIDTH * Frame::HEIGHT] }
+  }
+
+  pub fn set_pixel(&mut self, x: usize, y: usize, index: u16) {
+    if let Some(pixel) = self.pixels.get_mut(y * Frame::WIDTH + x) {
+      *pixel = index;
+    }
+  }
+
+  pub fn get_pixel(&self, x: usize, y: usize) -> u16 {
+    self.pixels[y * Frame::WIDTH + x]
+  }
+
+  pub fn line(&self, y: usize) -> &[u16] {
+    &self.pixels[y * Frame::WIDTH..(y + 1) * Frame::WIDTH]
+  }
+
+  pub fn to_frame(&self, palette: &Palette) -> Frame {
+    let mut frame = Frame::new();
+    for (rgb, &index) in frame.data.chunks_exact_mut(3).zip(&self.pixels) {
+      let (r, g, b) = palette.rgb(index);
+      rgb.copy_from_slice(&[r, g, b]);
+    }
+    frame
+  }
+
+  // 4 bytes per pixel into `out`, the layout of most textures
+  pub fn to_rgba(&self, palette: &Palette, out: &mut [u8]) {
+    rgba::indexed_to_rgba(palette.rgba_table(), &self.pixels, out);
+  }
+}
+
+impl Default for IndexedFrame {
+  fn default() -> Self {
+    IndexedFrame::new()
+  }
+}
+
+const PAGE_SIZE: usize = 4096;
+
+// an rgba picture in memory a texture upload can take as it is: page aligned
+// and exactly the size of a 256x240 rgba texture (60 pages)
+pub struct FrameBuffer {
+  memory: Vec<u8>,
+  start: usize,
+}
+
+impl FrameBuffer {
+  pub const SIZE: usize = Frame::WIDTH * Frame::HEIGHT * 4;
+
+  pub fn new() -> Self {
+    // the heap memory doesn't move with the struct, so the offset stays valid
+    let memory = vec![0; FrameBuffer::SIZE + PAGE_SIZE];
+    let start = memory.as_ptr().align_offset(PAGE_SIZE);
+    FrameBuffer { memory, start }
+  }
+
+  pub fn set_frame(&mut self, frame: &Frame) {
+    for (rgba, rgb) in self.chunks_exact_mut(4).zip(frame.data.chunks_exact(3)) {
+      rgba[..3].copy_from_slice(rgb);
+      rgba[3] = 0xFF;
+    }
+  }
+}
+
+impl Deref for FrameBuffer {
+  type Target = [u8];
+
+  fn deref(&self) -> &[u8] {
+    &self.memory[self.start..self.start + FrameBuffer::SIZE]
+  }
+}
+
+impl DerefMut for FrameBuffer {
+  fn deref_mut(&mut self) -> &mut [u8] {
+    &mut self.memory[self.start..self.start + FrameBuffer::SIZE]
+  }
+}
+
+impl Default for FrameBuffer {
+  fn default() -> Self {
+    FrameBuffer::new()
+  }
+}
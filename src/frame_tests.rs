@@ -0,0 +1,83 @@
+use crate::frame::{Frame, IndexedFrame};
+use crate::palette::{color_index, Palette, SYSTEM_PALETTE};
+use crate::power_on::crc32;
+
+// (type, data) of every chunk, checks the crcs on the way
+fn chunks(png: &[u8]) -> Vec<(String, Vec<u8>)> {
+  let mut chunks = Vec::new();
+  let mut pos = 8;
+  while pos < png.len() {
+    let len = u32::from_be_bytes([png[pos], png[pos + 1], png[pos + 2], png[pos + 3]]) as usize;
+    let body = &png[pos + 4..pos + 8 + len];
+    let crc = &png[pos + 8 + len..pos + 12 + len];
+    assert_eq!(crc32(body).to_be_bytes(), crc);
+    chunks.push((String::from_utf8(body[..4].to_vec()).unwrap(), body[4..].to_vec()));
+    pos += 12 + len;
+  }
+  chunks
+}
+
+// stored deflate blocks only, which is all the writer produces
+fn inflate_stored(zlib: &[u8]) -> Vec<u8> {
+  let mut data = Vec::new();
+  let mut pos = 2;
+  loop {
+    let last = zlib[pos] & 1 == 1;
+    let len = u16::from_le_bytes([zlib[pos + 1], zlib[pos + 2]]) as usize;
+    data.extend_from_slice(&zlib[pos + 5..pos + 5 + len]);
+    pos += 5 + len;
+    if last {
+      return data;
+    }
+  }
+}
+
+#[test]
+fn test_png_has_header_and_pixels() {
+  let mut frame = Frame::new();
+  frame.set_pixel(0, 0, (1, 2, 3));
+  frame.set_pixel(Frame::WIDTH - 1, Frame::HEIGHT - 1, (4, 5, 6));
+
+  let png = frame.to_png();
+
+  assert_eq!(b"\x89PNG\r\n\x1a\n", &png[..8]);
+  let chunks = chunks(&png);
+  let names: Vec<&str> = chunks.iter().map(|(name, _)| name.as_str()).collect();
+  assert_eq!(vec!["IHDR", "IDAT", "IEND"], names);
+  assert_eq!(vec![0, 0, 1, 0, 0, 0, 0, 240, 8, 2, 0, 0, 0], chunks[0].1);
+
+  let raw = inflate_stored(&chunks[1].1);
+  let row = 1 + Frame::WIDTH * 3;
+  assert_eq!(row * Frame::HEIGHT, raw.len());
+  assert_eq!([0, 1, 2, 3], raw[..4]);
+  assert_eq!([4, 5, 6], raw[raw.len() - 3..]);
+}
+
+#[test]
+fn test_save_png() {
+  let path = std::env::temp_dir().join(format!("nes_emulator_frame_{}.png", std::process::id()));
+  let frame = Frame::new();
+
+  frame.save_png(&path).unwrap();
+
+  assert_eq!(frame.to_png(), std::fs::read(&path).unwrap());
+  std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn test_indexed_frame_in_rgb_and_rgba() {
+  let palette = Palette::default();
+  let mut indexed = IndexedFrame::new();
+  indexed.set_pixel(1, 0, color_index(0x16, 0));
+  indexed.set_pixel(Frame::WIDTH - 1, Frame::HEIGHT - 1, color_index(0x21, 0b111));
+
+  let frame = indexed.to_frame(&palette);
+  let mut rgba = vec![0; Frame::WIDTH * Frame::HEIGHT * 4];
+  indexed.to_rgba(&palette, &mut rgba);
+
+  assert_eq!(SYSTEM_PALETTE[0x16], frame.get_pixel(1, 0));
+  assert_eq!(palette.color(0x21, 0b111), frame.get_pixel(Frame::WIDTH - 1, Frame::HEIGHT - 1));
+  for (rgba, rgb) in rgba.chunks_exact(4).zip(frame.data.chunks_exact(3)) {
+    assert_eq!((rgb, 0xFF), (&rgba[..3], rgba[3]));
+  }
+}
@@ -0,0 +1,350 @@
+use std::ops::ControlFlow;
+use std::path::PathBuf;
+use std::thread;
+use std::time::Duration;
+use sdl2::audio::{AudioCallback, AudioSpecDesired};
+use sdl2::controller::GameController;
+use sdl2::event::Event;
+use sdl2::keyboard::Keycode;
+use sdl2::pixels::PixelFormatEnum;
+use sdl2::rect::Rect;
+use crate::apu::DEFAULT_SAMPLE_RATE;
+use crate::audio::{AudioRing, SharedAudioRing};
+use crate::audio_worker::AudioWorker;
+use crate::battery;
+use crate::bus::Bus;
+use crate::cartridge::Rom;
+use crate::cli::PROFILE_ENTRIES;
+use crate::clock::Region;
+use crate::cpu::{MyCPU, RunExit, StopCondition};
+use crate::debug_view::event_viewer;
+use crate::error::EmuError;
+use crate::event_log::DEFAULT_MAX_FRAMES;
+use crate::frame::Frame;
+use crate::input::{InputMap, InputSource, Player};
+use crate::nes::hash_frame;
+use crate::netplay::{Netplay, PEER_TIMEOUT};
+use crate::nsf::{Nsf, NsfPlayer};
+use crate::rewind::Rewind;
+use crate::pacing::FramePacer;
+use crate::palette::Palette;
+use crate::profiler::Profiler;
+use crate::speed::EmulationSpeed;
+use crate::stats::StatsCollector;
+use crate::symbols::Symbols;
+use crate::trace::Tracer;
+use crate::video::VideoOptions;
+use crate::wav::WavRecorder;
+
+// samples waiting for the audio device, more would only add latency
+const AUDIO_RING_SIZE: usize = DEFAULT_SAMPLE_RATE as usize / 10;
+const PAUSE_POLL_TIME: Duration = Duration::from_millis(10);
+
+// sdl pulls the samples from its own thread
+struct RingPlayback(SharedAudioRing);
+
+impl AudioCallback for RingPlayback {
+  type Channel = f32;
+
+  fn callback(&mut self, out: &mut [f32]) {
+    self.0.lock().unwrap().fill(out);
+  }
+}
+
+pub struct FrontendOptions {
+  pub video: VideoOptions,
+  pub region: Region,
+  // battery backed ram is loaded from and saved to save_path
+  pub save_path: Option<PathBuf>,
+  pub tracer: Tracer,
+  pub symbols: Symbols,
+  // prints the profiler report on exit
+  pub profile: bool,
+  pub palette: Palette,
+  // bindings are read from input_path and written back on exit
+  pub input_path: Option<PathBuf>,
+  // player 2 is on the other side, the local player uses the player 1 bindings
+  pub netplay: Option<Netplay>,
+}
+
+impl Default for FrontendOptions {
+  fn default() -> Self {
+    FrontendOptions { video: VideoOptions::default(), region: Region::Ntsc, save_path: None, tracer: Tracer::default(), symbols: Symbols::new(), profile: false, palette: Palette::default(), input_path: None, netplay: None }
+  }
+}
+
+// opens a window and renders every ppu frame
+pub fn run(rom: Rom, options: FrontendOptions) -> Result<(), EmuError> {
+  let FrontendOptions { video, region, save_path, tracer, symbols, profile, palette, input_path, mut netplay } = options;
+  let sdl_context = sdl2::init().unwrap();
+  let video_subsystem = sdl_context.video().unwrap();
+  let (width, height) = video.window_size();
+  let window = video_subsystem
+    .window("NES", width, height)
+    .position_centered()
+    .resizable()
+    .build().unwrap();
+
+  let mut canvas = window.into_canvas().build().unwrap();
+  let mut event_pump = sdl_context.event_pump().unwrap();
+  let controller_subsystem = sdl_context.game_controller().unwrap();
+  // index = pad number of the input map, disconnected pads keep their slot
+  let mut pads: Vec<Option<GameController>> = Vec::new();
+
+  let creator = canvas.texture_creator();
+  let mut texture = creator
+    .create_texture_target(PixelFormatEnum::RGBA32, Frame::WIDTH as u32, Frame::HEIGHT as u32)
+    .unwrap();
+
+  let audio_subsystem = sdl_context.audio().unwrap();
+  let audio_spec = AudioSpecDesired { freq: Some(DEFAULT_SAMPLE_RATE as i32), channels: Some(1), samples: None };
+  let ring = AudioRing::shared(AUDIO_RING_SIZE);
+  let audio = audio_subsystem.open_playback(None, &audio_spec, |_| RingPlayback(ring.clone())).unwrap();
+  audio.resume();
+
+  let mut cpu = MyCPU::new(Bus::new(rom)?);
+  // mixing and resampling run next to the emulation, straight into the ring
+  let (audio_worker, raw_output) = AudioWorker::spawn(DEFAULT_SAMPLE_RATE, ring.clone());
+  cpu.bus.apu.offload_mixing(raw_output);
+  cpu.tracer = tracer;
+  cpu.symbols = symbols;
+  if profile {
+    cpu.profiler = Some(Profiler::new());
+  }
+  cpu.bus.ppu.palette = palette;
+  // for the event viewer (F8)
+  cpu.bus.enable_event_log(DEFAULT_MAX_FRAMES);
+  if let Some(path) = &save_path {
+    if let Err(e) = battery::load(&mut cpu.bus, path) {
+      eprintln!("could not load {}: {}", path.display(), e);
+    }
+  }
+  cpu.stop_condition = StopCondition::Never;
+  cpu.reset();
+
+  let mut stats = StatsCollector::new();
+  let mut pacer = FramePacer::new(region.frames_per_second());
+  let frame_time = pacer.frame_time();
+  let mut input = match &input_path {
+    Some(path) => InputMap::load(path).unwrap_or_else(|e| {
+      eprintln!("could not load {}: {}", path.display(), e);
+      InputMap::with_defaults()
+    }),
+    None => InputMap::with_defaults(),
+  };
+  let mut rewind = Rewind::default();
+  let mut speed = EmulationSpeed::new();
+
+  // both machines start the first frame with the same inputs
+  if let Some(netplay) = netplay.as_mut() {
+    netplay.wait_for_frame(&mut cpu, input.buttons(Player::One), PEER_TIMEOUT)?;
+  }
+
+  let exit = cpu.run_with_callback(move |cpu| {
+    if !cpu.bus.take_frame_ready() {
+      return ControlFlow::Continue(());
+    }
+    let hash = netplay.as_ref().map(|_| hash_frame(cpu.bus.ram(), &cpu.bus.ppu.frame().data));
+
+    // straight from the ppu's buffer
+    cpu.bus.ppu.with_frame(|rgba| texture.update(None, rgba, Frame::WIDTH * 4)).unwrap();
+    let (width, height) = canvas.output_size().unwrap();
+    let viewport = video.viewport(width, height);
+    canvas.clear();
+    canvas.copy(&texture, None, Rect::new(viewport.x, viewport.y, viewport.width, viewport.height)).unwrap();
+    canvas.present();
+
+    rewind.on_frame(cpu);
+
+    audio_worker.set_playing(speed.plays_audio());
+
+    // while paused only the events are handled
+    loop {
+      for event in event_pump.poll_iter() {
+        match event {
+          Event::Quit { .. } | Event::KeyDown { keycode: Some(Keycode::Escape), .. } => {
+            if let Some(path) = &save_path {
+              if let Err(e) = battery::store(&cpu.bus, path) {
+                eprintln!("could not save {}: {}", path.display(), e);
+              }
+            }
+            if cpu.bus.apu.is_recording() {
+              toggle_recording(cpu);
+            }
+            if let Some(path) = &input_path {
+              if let Err(e) = input.save(path) {
+                eprintln!("could not save {}: {}", path.display(), e);
+              }
+            }
+            if let Some(profiler) = &cpu.profiler {
+              print!("{}", profiler.report_with_symbols(PROFILE_ENTRIES, &cpu.symbols));
+            }
+            return ControlFlow::Break(());
+          }
+          Event::KeyDown { keycode: Some(Keycode::Backspace), .. } => {
+            rewind.rewind(cpu, 1.0);
+          }
+          Event::KeyDown { keycode: Some(Keycode::F9), .. } => {
+            toggle_recording(cpu);
+          }
+          Event::KeyDown { keycode: Some(Keycode::F8), .. } => save_event_viewer(cpu),
+          Event::KeyDown { keycode: Some(Keycode::F12), .. } => {
+            let path = screenshot_path();
+            match cpu.bus.ppu.frame().save_png(&path) {
+              Ok(()) => println!("saved {}", path.display()),
+              Err(e) => eprintln!("could not save {}: {}", path.display(), e),
+            }
+          }
+          Event::KeyDown { keycode: Some(Keycode::Tab), .. } => speed.set_unlimited(true),
+          Event::KeyUp { keycode: Some(Keycode::Tab), .. } => speed.set_unlimited(false),
+          Event::KeyDown { keycode: Some(Keycode::P), .. } => speed.toggle_pause(),
+          Event::KeyDown { keycode: Some(Keycode::N), .. } => speed.advance_frame(),
+          Event::KeyDown { keycode: Some(Keycode::F1), .. } => speed.set_multiplier(0.5),
+          Event::KeyDown { keycode: Some(Keycode::F2), .. } => speed.set_multiplier(1.0),
+          Event::KeyDown { keycode: Some(Keycode::F3), .. } => speed.set_multiplier(2.0),
+          Event::KeyDown { keycode: Some(Keycode::F4), .. } => speed.set_multiplier(4.0),
+          Event::KeyDown { keycode: Some(keycode), .. } => {
+            input.press(InputSource::Key(keycode.name()));
+          }
+          Event::KeyUp { keycode: Some(keycode), .. } => {
+            input.release(&InputSource::Key(keycode.name()));
+          }
+          Event::ControllerDeviceAdded { which, .. } => match controller_subsystem.open(which) {
+            Ok(controller) => {
+              println!("gamepad {} connected: {}", pads.len(), controller.name());
+              pads.push(Some(controller));
+            }
+            Err(e) => eprintln!("could not open gamepad: {}", e),
+          },
+          Event::ControllerDeviceRemoved { which, .. } => {
+            if let Some(pad) = pad_number(&pads, which) {
+              pads[pad as usize] = None;
+            }
+          }
+          Event::ControllerButtonDown { which, button, .. } => {
+            if let Some(pad) = pad_number(&pads, which) {
+              input.press(InputSource::Gamepad { pad, button: button.string() });
+            }
+          }
+          Event::ControllerButtonUp { which, button, .. } => {
+            if let Some(pad) = pad_number(&pads, which) {
+              input.release(&InputSource::Gamepad { pad, button: button.string() });
+            }
+          }
+          _ => {}
+        }
+      }
+      cpu.bus.joypad1.set_buttons(input.buttons(Player::One));
+      cpu.bus.joypad2.set_buttons(input.buttons(Player::Two));
+      if speed.run_next_frame() {
+        break;
+      }
+      thread::sleep(PAUSE_POLL_TIME);
+    }
+
+    if let (Some(netplay), Some(hash)) = (netplay.as_mut(), hash) {
+      let synced = netplay.end_frame(cpu, hash)
+        .and_then(|_| netplay.wait_for_frame(cpu, input.buttons(Player::One), PEER_TIMEOUT));
+      if let Err(e) = synced {
+        eprintln!("netplay: {}", e);
+        return ControlFlow::Break(());
+      }
+    }
+
+    match speed.frame_time(frame_time) {
+      Some(frame_time) => {
+        pacer.set_frame_time(frame_time);
+        pacer.wait();
+      }
+      None => pacer.reset(),
+    }
+
+    stats.end_frame(cpu.cycles);
+    if stats.stats().frames.is_multiple_of(60) {
+      let title = format!("NES - {:.1} fps", stats.stats().fps);
+      canvas.window_mut().set_title(&title).unwrap();
+    }
+    ControlFlow::Continue(())
+  });
+
+  if let RunExit::Jammed { code, program_counter } = exit {
+    eprintln!("{}", cpu.crash_dump(&format!("cpu jammed by opcode {:#04x} at ${:04X}", code, program_counter)));
+  }
+  Ok(())
+}
+
+// events name controllers by their sdl instance id
+fn pad_number(pads: &[Option<GameController>], instance_id: u32) -> Option<u32> {
+  pads.iter().position(|pad| pad.as_ref().is_some_and(|pad| pad.instance_id() == instance_id)).map(|pad| pad as u32)
+}
+
+// first free <prefix>-N.<extension> in the working directory
+fn numbered_path(prefix: &str, extension: &str) -> PathBuf {
+  (0..).map(|i| PathBuf::from(format!("{}-{}.{}", prefix, i, extension)))
+    .find(|path| !path.exists())
+    .unwrap()
+}
+
+fn screenshot_path() -> PathBuf {
+  numbered_path("screenshot", "png")
+}
+
+// the grid of the last complete frame as a png, there is no window drawing it over the game
+fn save_event_viewer(cpu: &MyCPU) {
+  let log = match cpu.bus.event_log.as_ref() {
+    Some(log) => log,
+    None => return,
+  };
+  let frame = match log.last_complete_frame() {
+    Some(frame) => frame,
+    None => return,
+  };
+  let path = numbered_path("events", "png");
+  match event_viewer(log, frame).save_png(&path) {
+    Ok(()) => println!("saved {}", path.display()),
+    Err(e) => eprintln!("could not save {}: {}", path.display(), e),
+  }
+}
+
+fn toggle_recording(cpu: &mut MyCPU) {
+  let apu = &mut cpu.bus.apu;
+  if apu.is_recording() {
+    match apu.stop_recording() {
+      Ok(()) => println!("recording stopped"),
+      Err(e) => eprintln!("could not write recording: {}", e),
+    }
+    return;
+  }
+  let path = numbered_path("recording", "wav");
+  match WavRecorder::create(&path, apu.sample_rate()) {
+    Ok(recorder) => {
+      apu.start_recording(recorder);
+      println!("recording to {}", path.display());
+    }
+    Err(e) => eprintln!("could not create {}: {}", path.display(), e),
+  }
+}
+
+// audio only, plays the track until the process is stopped
+pub fn play_nsf(nsf: Nsf, track: u8) -> Result<(), EmuError> {
+  let sdl_context = sdl2::init().unwrap();
+  let audio_subsystem = sdl_context.audio().unwrap();
+  let audio_spec = AudioSpecDesired { freq: Some(DEFAULT_SAMPLE_RATE as i32), channels: Some(1), samples: None };
+  let ring = AudioRing::shared(AUDIO_RING_SIZE);
+  let audio = audio_subsystem.open_playback(None, &audio_spec, |_| RingPlayback(ring.clone())).unwrap();
+
+  println!("{} - {} ({})", nsf.name, nsf.artist, nsf.copyright);
+  println!("track {} of {}", track, nsf.songs);
+  let mut player = NsfPlayer::new(nsf, DEFAULT_SAMPLE_RATE);
+  player.start_track(track)?;
+  audio.resume();
+
+  loop {
+    // the audio device sets the pace, keep the ring half full
+    while ring.lock().unwrap().len() > AUDIO_RING_SIZE / 2 {
+      thread::sleep(Duration::from_millis(1));
+    }
+    player.play_frame()?;
+    ring.lock().unwrap().push(&player.take_samples());
+  }
+}
@@ -0,0 +1,49 @@
+use crate::bus::Bus;
+use crate::cartridge::{PRG_ROM_PAGE_SIZE, Rom};
+use crate::cpu::MyCPU;
+
+// entry points for fuzzers, any input has to come back without a panic.
+// cargo-fuzz targets need a library crate (and the crate itself), fuzz_tests feeds these seeded random input
+pub const FUZZ_STEPS: usize = 10_000;
+
+// a rom file: parsing, the mapper and, if it loads, the program in it
+pub fn fuzz_rom(data: &[u8]) {
+  if let Ok(rom) = Rom::new(data) {
+    run(rom);
+  }
+}
+
+// a program at the start of a 32KB nrom, the last bytes of the data are the vectors.
+// the data is repeated to fill the rom, so jumps anywhere still land in random code
+pub fn fuzz_cpu(data: &[u8]) {
+  if data.is_empty() {
+    return;
+  }
+  let mut prg_rom: Vec<u8> = data.iter().cycle().take(2 * PRG_ROM_PAGE_SIZE).copied().collect();
+  let vectors = prg_rom.len() - 6;
+  for (i, byte) in data.iter().rev().take(6).enumerate() {
+    prg_rom[vectors + 5 - i] = *byte;
+  }
+  run(nrom(prg_rom));
+}
+
+fn nrom(prg_rom: Vec<u8>) -> Rom {
+  let mut raw = vec![0x4E, 0x45, 0x53, 0x1A, (prg_rom.len() / PRG_ROM_PAGE_SIZE) as u8, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0];
+  raw.extend(prg_rom);
+  Rom::new(&raw).expect("nrom image")
+}
+
+fn run(rom: Rom) {
+  let bus = match Bus::new(rom) {
+    Ok(bus) => bus,
+    Err(_) => return,
+  };
+  let mut cpu = MyCPU::new(bus);
+  cpu.reset();
+  for _ in 0..FUZZ_STEPS {
+    // jammed
+    if cpu.step().is_none() {
+      break;
+    }
+  }
+}
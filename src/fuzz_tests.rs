@@ -0,0 +1,54 @@
+use rand::{Rng, SeedableRng};
+use rand::rngs::StdRng;
+use crate::cartridge::{CHR_ROM_PAGE_SIZE, PRG_ROM_PAGE_SIZE};
+use crate::fuzz::{fuzz_cpu, fuzz_rom};
+
+const SEED: u64 = 0xF022;
+
+fn random_bytes(rng: &mut StdRng, max_len: usize) -> Vec<u8> {
+  let len = rng.gen_range(0, max_len);
+  (0..len).map(|_| rng.gen()).collect()
+}
+
+#[test]
+fn test_fuzz_rom_random_files() {
+  let mut rng = StdRng::seed_from_u64(SEED);
+  for _ in 0..2_000 {
+    fuzz_rom(&random_bytes(&mut rng, 64));
+  }
+}
+
+// a valid tag and random header bytes get past the magic check into the size and mapper handling
+#[test]
+fn test_fuzz_rom_random_headers() {
+  let mut rng = StdRng::seed_from_u64(SEED);
+  for _ in 0..200 {
+    let mut data = vec![0x4E, 0x45, 0x53, 0x1A];
+    data.extend((0..12).map(|_| rng.gen::<u8>()));
+    // small page counts, so some of the files are complete
+    data[4] = rng.gen_range(0, 3);
+    data[5] = rng.gen_range(0, 2);
+    let len = rng.gen_range(0, 512 + 2 * PRG_ROM_PAGE_SIZE + CHR_ROM_PAGE_SIZE + 16);
+    data.extend((0..len).map(|_| rng.gen::<u8>()));
+    fuzz_rom(&data);
+  }
+}
+
+#[test]
+fn test_fuzz_cpu_random_programs() {
+  let mut rng = StdRng::seed_from_u64(SEED);
+  for _ in 0..200 {
+    fuzz_cpu(&random_bytes(&mut rng, 256));
+  }
+}
+
+#[test]
+fn test_fuzz_cpu_edge_cases() {
+  fuzz_cpu(&[]);
+  // BRK everywhere with all vectors at $FFFF
+  fuzz_cpu(&[0x00, 0xFF]);
+  // JMP ($FFFF)
+  fuzz_cpu(&[0x6C, 0xFF, 0xFF]);
+  // the reset vector points at the last byte, the program counter has to wrap
+  fuzz_cpu(&[0xEA, 0xEA, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF]);
+}
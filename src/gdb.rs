@@ -0,0 +1,376 @@
+use std::io::{self, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use crate::breakpoints::Breakpoint;
+use crate::cpu::{CpuBus, CpuFlags, CpuState, MyCPU};
+use crate::savestate::Stateful;
+use crate::time_travel::TimeTravel;
+
+// gdb remote serial protocol, enough for breakpoints, memory access and stepping (also backwards)
+// https://sourceware.org/gdb/onlinedocs/gdb/Remote-Protocol.html
+
+pub const DEFAULT_PORT: u16 = 6502;
+
+// registers in `g` packets: a, x, y, p, sp (one byte each) and pc (little endian)
+const TARGET_XML: &str = "<?xml version=\"1.0\"?><!DOCTYPE target SYSTEM \"gdb-target.dtd\">\
+<target><feature name=\"org.gnu.gdb.m6502.core\">\
+<reg name=\"a\" bitsize=\"8\" regnum=\"0\"/>\
+<reg name=\"x\" bitsize=\"8\" regnum=\"1\"/>\
+<reg name=\"y\" bitsize=\"8\" regnum=\"2\"/>\
+<reg name=\"p\" bitsize=\"8\" regnum=\"3\"/>\
+<reg name=\"sp\" bitsize=\"8\" regnum=\"4\"/>\
+<reg name=\"pc\" bitsize=\"16\" regnum=\"5\" type=\"code_ptr\"/>\
+</feature></target>";
+
+// check for a ctrl-c from the debugger every this many instructions
+const INTERRUPT_POLL_INSTRUCTIONS: u32 = 4096;
+
+// stop replies, the numbers are unix signals
+const SIGINT: &str = "S02";
+const SIGILL: &str = "S04";
+const SIGTRAP: &str = "S05";
+// reverse execution reached the oldest recorded instruction
+const HISTORY_BEGIN: &str = "T05replaylog:begin;";
+
+#[derive(Debug, PartialEq)]
+pub enum Incoming {
+  Packet(String),
+  BadChecksum,
+  // ctrl-c, sent outside of a packet
+  Interrupt,
+}
+
+// splits the byte stream into packets, acks of our replies are skipped
+#[derive(Default)]
+pub struct PacketReader {
+  buffer: Vec<u8>,
+}
+
+impl PacketReader {
+  pub fn new() -> Self {
+    PacketReader::default()
+  }
+
+  pub fn push(&mut self, bytes: &[u8]) -> Vec<Incoming> {
+    self.buffer.extend_from_slice(bytes);
+    let mut incoming = Vec::new();
+    loop {
+      match self.buffer.first() {
+        None => break,
+        Some(0x03) => {
+          self.buffer.remove(0);
+          incoming.push(Incoming::Interrupt);
+        }
+        Some(b'$') => {
+          let end = match self.buffer.iter().position(|&b| b == b'#') {
+            Some(end) if end + 2 < self.buffer.len() => end,
+            // wait for the rest of the packet
+            _ => break,
+          };
+          let data = &self.buffer[1..end];
+          let expected = std::str::from_utf8(&self.buffer[end + 1..end + 3]).ok()
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+          incoming.push(if expected == Some(checksum(data)) {
+            Incoming::Packet(String::from_utf8_lossy(data).into_owned())
+          } else {
+            Incoming::BadChecksum
+          });
+          self.buffer.drain(..end + 3);
+        }
+        // '+' / '-' acks and line noise
+        Some(_) => {
+          self.buffer.remove(0);
+        }
+      }
+    }
+    incoming
+  }
+}
+
+pub fn checksum(data: &[u8]) -> u8 {
+  data.iter().fold(0u8, |sum, &b| sum.wrapping_add(b))
+}
+
+pub fn frame(data: &str) -> String {
+  format!("${}#{:02x}", data, checksum(data.as_bytes()))
+}
+
+// what the server does after a packet
+#[derive(Debug, PartialEq)]
+pub enum Action {
+  Reply(String),
+  Continue,
+  Step,
+  ReverseContinue,
+  ReverseStep,
+  // reply OK and close the connection
+  Detach,
+  Kill,
+}
+
+// an empty reply tells the debugger the packet is not supported
+pub fn handle<B: CpuBus>(cpu: &mut MyCPU<B>, packet: &str) -> Action {
+  let (command, args) = match packet.chars().next() {
+    Some(command) => (command, &packet[1..]),
+    None => return Action::Reply(String::new()),
+  };
+  let reply = match command {
+    '?' => Some(SIGTRAP.to_string()),
+    'g' => Some(read_registers(cpu)),
+    'G' => write_registers(cpu, args),
+    'p' => read_register(cpu, args),
+    'P' => write_register(cpu, args),
+    'm' => read_memory(cpu, args),
+    'M' => write_memory(cpu, args),
+    'Z' | 'z' => return Action::Reply(breakpoint(cpu, command == 'Z', args).unwrap_or_default()),
+    'c' | 's' => {
+      // optional address to resume at
+      if !args.is_empty() {
+        match parse_hex(args) {
+          Some(address) => cpu.program_counter = address,
+          None => return Action::Reply("E01".to_string()),
+        }
+      }
+      return if command == 'c' { Action::Continue } else { Action::Step };
+    }
+    'b' => return match args {
+      "c" => Action::ReverseContinue,
+      "s" => Action::ReverseStep,
+      _ => Action::Reply(String::new()),
+    },
+    'D' => return Action::Detach,
+    'k' => return Action::Kill,
+    // there is only one thread
+    'H' => Some("OK".to_string()),
+    'q' => return Action::Reply(query(args)),
+    _ => return Action::Reply(String::new()),
+  };
+  Action::Reply(reply.unwrap_or_else(|| "E01".to_string()))
+}
+
+fn query(args: &str) -> String {
+  if args.starts_with("Supported") {
+    return "PacketSize=1000;qXfer:features:read+;ReverseStep+;ReverseContinue+".to_string();
+  }
+  if let Some(range) = args.strip_prefix("Xfer:features:read:target.xml:") {
+    return read_target_xml(range).unwrap_or_else(|| "E01".to_string());
+  }
+  match args {
+    "Attached" => "1",
+    "C" => "QC1",
+    "fThreadInfo" => "m1",
+    "sThreadInfo" => "l",
+    _ => "",
+  }.to_string()
+}
+
+// offset,length - 'l' marks the last chunk
+fn read_target_xml(range: &str) -> Option<String> {
+  let (offset, length) = range.split_once(',')?;
+  let offset = usize::from_str_radix(offset, 16).ok()?;
+  let length = usize::from_str_radix(length, 16).ok()?;
+  let start = offset.min(TARGET_XML.len());
+  let end = start.saturating_add(length).min(TARGET_XML.len());
+  let marker = if end == TARGET_XML.len() { 'l' } else { 'm' };
+  Some(format!("{}{}", marker, &TARGET_XML[start..end]))
+}
+
+fn registers<B: CpuBus>(cpu: &MyCPU<B>) -> [u8; 7] {
+  let [pc_lo, pc_hi] = cpu.program_counter.to_le_bytes();
+  [cpu.register_a, cpu.register_x, cpu.register_y, cpu.status.bits(), cpu.stack_pointer, pc_lo, pc_hi]
+}
+
+fn read_registers<B: CpuBus>(cpu: &MyCPU<B>) -> String {
+  to_hex(&registers(cpu))
+}
+
+fn write_registers<B: CpuBus>(cpu: &mut MyCPU<B>, args: &str) -> Option<String> {
+  let values = from_hex(args)?;
+  if values.len() != 7 {
+    return None;
+  }
+  cpu.register_a = values[0];
+  cpu.register_x = values[1];
+  cpu.register_y = values[2];
+  cpu.status = CpuFlags::from_bits_truncate(values[3]);
+  cpu.stack_pointer = values[4];
+  cpu.program_counter = u16::from_le_bytes([values[5], values[6]]);
+  Some("OK".to_string())
+}
+
+fn read_register<B: CpuBus>(cpu: &MyCPU<B>, args: &str) -> Option<String> {
+  let registers = registers(cpu);
+  match usize::from_str_radix(args, 16).ok()? {
+    number @ 0..=4 => Some(to_hex(&registers[number..number + 1])),
+    5 => Some(to_hex(&registers[5..7])),
+    _ => None,
+  }
+}
+
+fn write_register<B: CpuBus>(cpu: &mut MyCPU<B>, args: &str) -> Option<String> {
+  let (number, value) = args.split_once('=')?;
+  let value = from_hex(value)?;
+  match (usize::from_str_radix(number, 16).ok()?, value.as_slice()) {
+    (0, [a]) => cpu.register_a = *a,
+    (1, [x]) => cpu.register_x = *x,
+    (2, [y]) => cpu.register_y = *y,
+    (3, [p]) => cpu.status = CpuFlags::from_bits_truncate(*p),
+    (4, [sp]) => cpu.stack_pointer = *sp,
+    (5, [lo, hi]) => cpu.program_counter = u16::from_le_bytes([*lo, *hi]),
+    _ => return None,
+  }
+  Some("OK".to_string())
+}
+
+// addr,length
+fn read_memory<B: CpuBus>(cpu: &MyCPU<B>, args: &str) -> Option<String> {
+  let (address, length) = args.split_once(',')?;
+  let address = parse_hex(address)?;
+  let length = parse_hex(length)?;
+  let values: Vec<u8> = (0..length).map(|i| cpu.bus.peek(address.wrapping_add(i))).collect();
+  Some(to_hex(&values))
+}
+
+// addr,length:data - rom is not writable
+fn write_memory<B: CpuBus>(cpu: &mut MyCPU<B>, args: &str) -> Option<String> {
+  let (range, data) = args.split_once(':')?;
+  let (address, length) = range.split_once(',')?;
+  let address = parse_hex(address)?;
+  let values = from_hex(data)?;
+  if values.len() != parse_hex(length)? as usize {
+    return None;
+  }
+  for (i, value) in values.into_iter().enumerate() {
+    cpu.memory_editor.poke(&mut cpu.bus, address.wrapping_add(i as u16), value).ok()?;
+  }
+  Some("OK".to_string())
+}
+
+// type,addr,kind - software and hardware breakpoints are the same, no watchpoints
+fn breakpoint<B: CpuBus>(cpu: &mut MyCPU<B>, insert: bool, args: &str) -> Option<String> {
+  let mut parts = args.split(',');
+  let kind = parts.next()?;
+  if kind != "0" && kind != "1" {
+    return None;
+  }
+  let breakpoint = Breakpoint::Address(parse_hex(parts.next()?)?);
+  if insert {
+    cpu.breakpoints.add(breakpoint);
+  } else {
+    cpu.breakpoints.remove(&breakpoint);
+  }
+  Some("OK".to_string())
+}
+
+// runs until a breakpoint, a jam or an interrupt from the debugger, returns the stop reply.
+// the instructions are recorded in `history` for reverse
+pub fn resume<B: CpuBus + Stateful, F>(cpu: &mut MyCPU<B>, history: &mut TimeTravel, single_step: bool, mut interrupted: F) -> String
+  where
+    F: FnMut() -> bool,
+{
+  let mut instructions: u32 = 0;
+  while history.step(cpu) {
+    if single_step {
+      return SIGTRAP.to_string();
+    }
+    instructions = instructions.wrapping_add(1);
+    if instructions.is_multiple_of(INTERRUPT_POLL_INSTRUCTIONS) && interrupted() {
+      return SIGINT.to_string();
+    }
+  }
+  match cpu.state {
+    CpuState::Jammed { .. } => SIGILL.to_string(),
+    CpuState::Running => SIGTRAP.to_string(),
+  }
+}
+
+// steps or runs back to the previous address breakpoint, returns the stop reply
+pub fn reverse<B: CpuBus + Stateful>(cpu: &mut MyCPU<B>, history: &mut TimeTravel, single_step: bool) -> String {
+  let stopped = if single_step {
+    history.step_back(cpu)
+  } else {
+    history.run_back_until(cpu, |cpu| cpu.breakpoints.list().contains(&Breakpoint::Address(cpu.program_counter)))
+  };
+  if stopped { SIGTRAP } else { HISTORY_BEGIN }.to_string()
+}
+
+// serves a single debugger connection until it detaches
+pub fn serve<B: CpuBus + Stateful>(cpu: &mut MyCPU<B>, listener: &TcpListener) -> io::Result<()> {
+  let mut history = TimeTravel::default();
+  let (mut stream, _) = listener.accept()?;
+  stream.set_nodelay(true)?;
+  let mut reader = PacketReader::new();
+  let mut buffer = [0u8; 4096];
+  loop {
+    let len = stream.read(&mut buffer)?;
+    if len == 0 {
+      return Ok(());
+    }
+    for incoming in reader.push(&buffer[..len]) {
+      let packet = match incoming {
+        Incoming::Packet(packet) => packet,
+        Incoming::BadChecksum => {
+          stream.write_all(b"-")?;
+          continue;
+        }
+        // already stopped
+        Incoming::Interrupt => {
+          send(&mut stream, SIGINT)?;
+          continue;
+        }
+      };
+      stream.write_all(b"+")?;
+      match handle(cpu, &packet) {
+        Action::Reply(reply) => send(&mut stream, &reply)?,
+        action @ (Action::Continue | Action::Step) => {
+          let reply = resume(cpu, &mut history, action == Action::Step, || interrupt_requested(&stream));
+          send(&mut stream, &reply)?;
+        }
+        action @ (Action::ReverseContinue | Action::ReverseStep) => {
+          let reply = reverse(cpu, &mut history, action == Action::ReverseStep);
+          send(&mut stream, &reply)?;
+        }
+        Action::Detach => {
+          send(&mut stream, "OK")?;
+          return Ok(());
+        }
+        Action::Kill => return Ok(()),
+      }
+    }
+  }
+}
+
+fn send(stream: &mut TcpStream, data: &str) -> io::Result<()> {
+  stream.write_all(frame(data).as_bytes())
+}
+
+// ctrl-c while the cpu runs, other data is left for the next read
+fn interrupt_requested(mut stream: &TcpStream) -> bool {
+  let mut byte = [0u8];
+  if stream.set_nonblocking(true).is_err() {
+    return false;
+  }
+  let peeked = stream.peek(&mut byte);
+  let _ = stream.set_nonblocking(false);
+  if matches!(peeked, Ok(1)) && byte[0] == 0x03 {
+    let _ = stream.read(&mut byte);
+    return true;
+  }
+  false
+}
+
+fn parse_hex(value: &str) -> Option<u16> {
+  u16::from_str_radix(value, 16).ok()
+}
+
+fn to_hex(values: &[u8]) -> String {
+  values.iter().map(|v| format!("{:02x}", v)).collect()
+}
+
+fn from_hex(value: &str) -> Option<Vec<u8>> {
+  if !value.len().is_multiple_of(2) {
+    return None;
+  }
+  (0..value.len()).step_by(2)
+    .map(|i| u8::from_str_radix(value.get(i..i + 2)?, 16).ok())
+    .collect()
+}
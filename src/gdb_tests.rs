@@ -0,0 +1,172 @@
+use std::io::{Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::thread;
+use crate::bus::Bus;
+use crate::breakpoints::Breakpoint;
+use crate::cartridge_tests::create_test_rom;
+use crate::cpu::{MyCPU, MyMem, StopCondition};
+use crate::gdb::{Action, frame, handle, Incoming, PacketReader, resume, reverse, serve};
+use crate::time_travel::TimeTravel;
+
+fn init_cpu() -> MyCPU {
+  let mut cpu = MyCPU::new(Bus::new(create_test_rom()).unwrap());
+  cpu.stop_condition = StopCondition::Never;
+  // INX, INX, INX, loop: JMP loop
+  cpu.load(vec![0xE8, 0xE8, 0xE8, 0x4C, 0x03, 0x06]);
+  cpu.program_counter = 0x0600;
+  cpu
+}
+
+fn reply(text: &str) -> Action {
+  Action::Reply(text.to_string())
+}
+
+#[test]
+fn test_framing() {
+  assert_eq!("$OK#9a", frame("OK"));
+
+  let mut reader = PacketReader::new();
+  assert_eq!(Vec::<Incoming>::new(), reader.push(b"+$g#6"));
+  assert_eq!(vec![Incoming::Packet("g".to_string())], reader.push(b"7"));
+  assert_eq!(vec![Incoming::BadChecksum, Incoming::Interrupt, Incoming::Packet("?".to_string())],
+             reader.push(b"$g#00\x03-$?#3f"));
+}
+
+#[test]
+fn test_registers() {
+  let mut cpu = init_cpu();
+  cpu.register_a = 0x12;
+  cpu.stack_pointer = 0xFD;
+  cpu.program_counter = 0x8000;
+
+  assert_eq!(reply("12000024fd0080"), handle(&mut cpu, "g"));
+  assert_eq!(reply("0080"), handle(&mut cpu, "p5"));
+
+  assert_eq!(reply("OK"), handle(&mut cpu, "G010203a5ff0006"));
+  assert_eq!((1, 2, 3, 0xFF), (cpu.register_a, cpu.register_x, cpu.register_y, cpu.stack_pointer));
+  assert_eq!(0xA5, cpu.status.bits());
+  assert_eq!(0x0600, cpu.program_counter);
+
+  assert_eq!(reply("OK"), handle(&mut cpu, "P1=7f"));
+  assert_eq!(0x7F, cpu.register_x);
+  assert_eq!(reply("E01"), handle(&mut cpu, "P6=00"));
+}
+
+#[test]
+fn test_memory() {
+  let mut cpu = init_cpu();
+
+  assert_eq!(reply("e8e8"), handle(&mut cpu, "m600,2"));
+  assert_eq!(reply("OK"), handle(&mut cpu, "M10,2:abcd"));
+  assert_eq!(0xCD, cpu.mem_read(0x0011));
+  assert_eq!(reply("E01"), handle(&mut cpu, "M8000,1:00"));
+  assert_eq!(reply("E01"), handle(&mut cpu, "M10,2:ab"));
+}
+
+#[test]
+fn test_queries() {
+  let mut cpu = init_cpu();
+
+  assert_eq!(reply("PacketSize=1000;qXfer:features:read+;ReverseStep+;ReverseContinue+"), handle(&mut cpu, "qSupported:multiprocess+"));
+  assert_eq!(reply("1"), handle(&mut cpu, "qAttached"));
+  assert_eq!(reply("m<?xml"), handle(&mut cpu, "qXfer:features:read:target.xml:0,5"));
+  match handle(&mut cpu, "qXfer:features:read:target.xml:0,1000") {
+    Action::Reply(xml) => assert!(xml.starts_with('l') && xml.ends_with("</target>")),
+    other => panic!("{:?}", other),
+  }
+  // lengths from the client can't overflow
+  match handle(&mut cpu, "qXfer:features:read:target.xml:5,ffffffffffffffff") {
+    Action::Reply(xml) => assert!(xml.starts_with('l') && xml.ends_with("</target>")),
+    other => panic!("{:?}", other),
+  }
+  assert_eq!(reply(""), handle(&mut cpu, "vCont?"));
+}
+
+#[test]
+fn test_breakpoints_continue_and_step() {
+  let mut cpu = init_cpu();
+
+  assert_eq!(reply("OK"), handle(&mut cpu, "Z0,602,1"));
+  assert_eq!(vec![Breakpoint::Address(0x0602)], cpu.breakpoints.list());
+  assert_eq!(reply(""), handle(&mut cpu, "Z2,10,1"));
+
+  assert_eq!(Action::Continue, handle(&mut cpu, "c"));
+  assert_eq!("S05", resume(&mut cpu, &mut TimeTravel::default(), false, || false));
+  assert_eq!(0x0602, cpu.program_counter);
+
+  assert_eq!(Action::Step, handle(&mut cpu, "s"));
+  assert_eq!("S05", resume(&mut cpu, &mut TimeTravel::default(), true, || false));
+  assert_eq!((0x0603, 3), (cpu.program_counter, cpu.register_x));
+
+  assert_eq!(reply("OK"), handle(&mut cpu, "z0,602,1"));
+  assert!(cpu.breakpoints.is_empty());
+}
+
+#[test]
+fn test_reverse_step_and_continue() {
+  let mut cpu = init_cpu();
+  let mut history = TimeTravel::default();
+  handle(&mut cpu, "Z0,601,1");
+  resume(&mut cpu, &mut history, false, || false);
+  handle(&mut cpu, "z0,601,1");
+  for _ in 0..2 {
+    resume(&mut cpu, &mut history, true, || false);
+  }
+  assert_eq!((0x0603, 3), (cpu.program_counter, cpu.register_x));
+
+  assert_eq!(Action::ReverseStep, handle(&mut cpu, "bs"));
+  assert_eq!("S05", reverse(&mut cpu, &mut history, true));
+  assert_eq!((0x0602, 2), (cpu.program_counter, cpu.register_x));
+
+  handle(&mut cpu, "Z0,601,1");
+  assert_eq!(Action::ReverseContinue, handle(&mut cpu, "bc"));
+  assert_eq!("S05", reverse(&mut cpu, &mut history, false));
+  assert_eq!((0x0601, 1), (cpu.program_counter, cpu.register_x));
+  assert_eq!("T05replaylog:begin;", reverse(&mut cpu, &mut history, false));
+  assert_eq!(0x0600, cpu.program_counter);
+}
+
+#[test]
+fn test_interrupt_stops_a_running_cpu() {
+  let mut cpu = init_cpu();
+
+  assert_eq!("S02", resume(&mut cpu, &mut TimeTravel::default(), false, || true));
+  assert_eq!(0x0603, cpu.program_counter);
+}
+
+#[test]
+fn test_jam_is_reported() {
+  let mut cpu = init_cpu();
+  cpu.load(vec![0x02]);
+
+  assert_eq!("S04", resume(&mut cpu, &mut TimeTravel::default(), false, || false));
+}
+
+#[test]
+fn test_session_over_tcp() {
+  let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+  let address = listener.local_addr().unwrap();
+  let client = thread::spawn(move || {
+    let mut stream = TcpStream::connect(address).unwrap();
+    let mut exchange = |packet: &str| {
+      stream.write_all(frame(packet).as_bytes()).unwrap();
+      let mut received = String::new();
+      let mut byte = [0u8];
+      // ack, then the reply up to its checksum
+      while !received.contains('#') || received.len() < received.find('#').unwrap() + 3 {
+        stream.read_exact(&mut byte).unwrap();
+        received.push(byte[0] as char);
+      }
+      stream.write_all(b"+").unwrap();
+      received
+    };
+    vec![exchange("Z0,602,1"), exchange("c"), exchange("p1"), exchange("D")]
+  });
+
+  let mut cpu = init_cpu();
+  serve(&mut cpu, &listener).unwrap();
+
+  assert_eq!(vec![format!("+{}", frame("OK")), format!("+{}", frame("S05")), format!("+{}", frame("02")),
+                  format!("+{}", frame("OK"))], client.join().unwrap());
+  assert_eq!(0x0602, cpu.program_counter);
+}
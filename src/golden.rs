@@ -0,0 +1,75 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use crate::cartridge::Rom;
+use crate::nes::Nes;
+
+// golden images: every game.nes in a directory is run headless for a number of frames
+// (game.frames, default DEFAULT_FRAMES) and the last picture is compared with game.png.
+// A missing reference is recorded on the first run, a mismatch leaves game.actual.png
+// next to it to look at. References are compared byte by byte, so they have to be
+// written by this harness (the png writer is deterministic).
+pub const DEFAULT_FRAMES: usize = 120;
+
+#[derive(Debug, PartialEq)]
+pub enum GoldenResult {
+  Matched,
+  Recorded(PathBuf),
+  Mismatch(PathBuf),
+}
+
+fn frames_to_run(rom_path: &Path) -> Result<usize, String> {
+  match fs::read_to_string(rom_path.with_extension("frames")) {
+    Ok(text) => text.trim().parse().map_err(|_| format!("invalid frame count: '{}'", text.trim())),
+    Err(_) => Ok(DEFAULT_FRAMES),
+  }
+}
+
+pub fn render_png(rom: Rom, frames: usize) -> Result<Vec<u8>, String> {
+  let mut nes = Nes::new(rom).map_err(|e| e.to_string())?;
+  Ok(nes.run_for_frames(frames).frame.to_png())
+}
+
+pub fn check_rom(rom_path: &Path) -> Result<GoldenResult, String> {
+  let rom = Rom::load(rom_path).map_err(|e| e.to_string())?;
+  let actual = render_png(rom, frames_to_run(rom_path)?)?;
+
+  let reference = rom_path.with_extension("png");
+  let actual_path = rom_path.with_extension("actual.png");
+  match fs::read(&reference) {
+    Ok(expected) if expected == actual => {
+      let _ = fs::remove_file(&actual_path);
+      Ok(GoldenResult::Matched)
+    }
+    Ok(_) => {
+      fs::write(&actual_path, &actual).map_err(|e| e.to_string())?;
+      Ok(GoldenResult::Mismatch(actual_path))
+    }
+    Err(_) => {
+      fs::write(&reference, &actual).map_err(|e| e.to_string())?;
+      Ok(GoldenResult::Recorded(reference))
+    }
+  }
+}
+
+// checks every rom of the directory, the failures as readable lines
+pub fn check_dir(dir: &Path) -> Result<Vec<String>, String> {
+  let mut roms: Vec<PathBuf> = fs::read_dir(dir).map_err(|e| format!("{}: {}", dir.display(), e))?
+    .filter_map(|e| e.ok().map(|e| e.path()))
+    .filter(|p| p.extension().is_some_and(|e| e == "nes"))
+    .collect();
+  if roms.is_empty() {
+    return Err(format!("no .nes files in {}", dir.display()));
+  }
+  roms.sort();
+
+  let mut failures = Vec::new();
+  for rom in roms {
+    match check_rom(&rom) {
+      Ok(GoldenResult::Matched) => {}
+      Ok(GoldenResult::Recorded(path)) => println!("recorded {}", path.display()),
+      Ok(GoldenResult::Mismatch(path)) => failures.push(format!("{}: differs, see {}", rom.display(), path.display())),
+      Err(e) => failures.push(format!("{}: {}", rom.display(), e)),
+    }
+  }
+  Ok(failures)
+}
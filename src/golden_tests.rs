@@ -0,0 +1,75 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use crate::cartridge_tests::test_rom_bytes_with_program;
+use crate::golden::{check_dir, check_rom, GoldenResult};
+
+// sets the backdrop color, then loops
+fn rom_bytes(color: u8) -> Vec<u8> {
+  let program = [
+    0xA9, 0x3F, 0x8D, 0x06, 0x20, // LDA #$3F, STA $2006
+    0xA9, 0x00, 0x8D, 0x06, 0x20, // LDA #$00, STA $2006
+    0xA9, color, 0x8D, 0x07, 0x20, // LDA #color, STA $2007
+    0xA9, 0x00, 0x8D, 0x06, 0x20, // LDA #$00, STA $2006
+    0x8D, 0x06, 0x20,             // STA $2006
+    0x4C, 0x17, 0x80,             // loop: JMP loop
+  ];
+  test_rom_bytes_with_program(&program)
+}
+
+fn temp_dir(name: &str) -> PathBuf {
+  let dir = std::env::temp_dir().join(format!("nes_golden_{}_{}", name, std::process::id()));
+  fs::create_dir_all(&dir).unwrap();
+  dir
+}
+
+#[test]
+fn test_reference_is_recorded_then_matched() {
+  let dir = temp_dir("match");
+  let rom = dir.join("backdrop.nes");
+  fs::write(&rom, rom_bytes(0x16)).unwrap();
+  fs::write(dir.join("backdrop.frames"), "2").unwrap();
+
+  assert_eq!(Ok(GoldenResult::Recorded(dir.join("backdrop.png"))), check_rom(&rom));
+  assert_eq!(Ok(GoldenResult::Matched), check_rom(&rom));
+  assert_eq!(Ok(vec![]), check_dir(&dir));
+  fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_mismatch_writes_actual_picture() {
+  let dir = temp_dir("mismatch");
+  let rom = dir.join("backdrop.nes");
+  fs::write(dir.join("backdrop.frames"), "2").unwrap();
+  fs::write(&rom, rom_bytes(0x16)).unwrap();
+  check_rom(&rom).unwrap();
+
+  fs::write(&rom, rom_bytes(0x2A)).unwrap();
+
+  let actual = dir.join("backdrop.actual.png");
+  assert_eq!(Ok(GoldenResult::Mismatch(actual.clone())), check_rom(&rom));
+  assert!(actual.exists());
+  assert_eq!(1, check_dir(&dir).unwrap().len());
+  fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_invalid_frame_count() {
+  let dir = temp_dir("frames");
+  let rom = dir.join("backdrop.nes");
+  fs::write(&rom, rom_bytes(0x16)).unwrap();
+  fs::write(dir.join("backdrop.frames"), "many").unwrap();
+
+  assert!(check_rom(&rom).is_err());
+  fs::remove_dir_all(&dir).unwrap();
+}
+
+// homebrew roms and their references in test_roms/golden next to Cargo.toml,
+// only runs with --features test-roms
+#[test]
+#[cfg_attr(not(feature = "test-roms"), ignore = "needs test_roms/golden, run with --features test-roms")]
+fn test_golden_roms() {
+  let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("test_roms").join("golden");
+  assert!(dir.is_dir(), "{} not found", dir.display());
+  let failures = check_dir(&dir).unwrap();
+  assert!(failures.is_empty(), "golden image mismatches:\n{}", failures.join("\n"));
+}
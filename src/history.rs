@@ -0,0 +1,99 @@
+use std::collections::VecDeque;
+use crate::cpu::CpuFlags;
+
+pub const DEFAULT_HISTORY_SIZE: usize = 64;
+
+// cpu state right before an instruction was executed
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExecutedInstruction {
+  pub program_counter: u16,
+  pub code: u8,
+  pub mnemonic: &'static str,
+  pub operands: Vec<u8>,
+  pub register_a: u8,
+  pub register_x: u8,
+  pub register_y: u8,
+  pub stack_pointer: u8,
+  pub status: CpuFlags,
+}
+
+impl ExecutedInstruction {
+  pub fn format(&self) -> String {
+    let operands: Vec<String> = self.operands.iter().map(|b| format!("{:02X}", b)).collect();
+    format!("{:04X}  {:02X} {:<6} {}  A:{:02X} X:{:02X} Y:{:02X} P:{:02X} SP:{:02X}",
+            self.program_counter, self.code, operands.join(" "), self.mnemonic,
+            self.register_a, self.register_x, self.register_y, self.status.bits(), self.stack_pointer)
+  }
+}
+
+// keeps the last n executed instructions, so a crash can be explained without tracing enabled
+pub struct ExecutionHistory {
+  capacity: usize,
+  entries: VecDeque<ExecutedInstruction>,
+}
+
+impl ExecutionHistory {
+  pub fn new(capacity: usize) -> Self {
+    ExecutionHistory {
+      capacity,
+      entries: VecDeque::with_capacity(capacity),
+    }
+  }
+
+  pub fn capacity(&self) -> usize {
+    self.capacity
+  }
+
+  pub fn set_capacity(&mut self, capacity: usize) {
+    self.capacity = capacity;
+    while self.entries.len() > capacity {
+      self.entries.pop_front();
+    }
+  }
+
+  pub fn record(&mut self, instruction: ExecutedInstruction) {
+    if self.capacity == 0 {
+      return;
+    }
+    if self.entries.len() == self.capacity {
+      self.entries.pop_front();
+    }
+    self.entries.push_back(instruction);
+  }
+
+  pub fn len(&self) -> usize {
+    self.entries.len()
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.entries.is_empty()
+  }
+
+  pub fn clear(&mut self) {
+    self.entries.clear();
+  }
+
+  // oldest first
+  pub fn iter(&self) -> impl Iterator<Item=&ExecutedInstruction> {
+    self.entries.iter()
+  }
+
+  pub fn last(&self) -> Option<&ExecutedInstruction> {
+    self.entries.back()
+  }
+
+  pub fn dump(&self) -> String {
+    let mut dump = format!("last {} executed instructions:\n", self.entries.len());
+    for instruction in &self.entries {
+      dump.push_str(&instruction.format());
+      dump.push('\n');
+    }
+    dump
+  }
+}
+
+impl Default for ExecutionHistory {
+  fn default() -> Self {
+    ExecutionHistory::new(DEFAULT_HISTORY_SIZE)
+  }
+}
@@ -0,0 +1,71 @@
+use crate::bus::Bus;
+use crate::cartridge_tests::create_test_rom;
+use crate::cpu::{CpuFlags, MyCPU};
+use crate::history::{ExecutedInstruction, ExecutionHistory};
+
+fn instruction(program_counter: u16) -> ExecutedInstruction {
+  ExecutedInstruction {
+    program_counter,
+    code: 0xEA,
+    mnemonic: "NOP",
+    operands: vec![],
+    register_a: 0,
+    register_x: 0,
+    register_y: 0,
+    stack_pointer: 0xFF,
+    status: CpuFlags::empty(),
+  }
+}
+
+#[test]
+fn test_history_keeps_only_last_entries() {
+  let mut history = ExecutionHistory::new(2);
+
+  history.record(instruction(0x0600));
+  history.record(instruction(0x0601));
+  history.record(instruction(0x0602));
+
+  let pcs: Vec<u16> = history.iter().map(|i| i.program_counter).collect();
+  assert_eq!(vec![0x0601, 0x0602], pcs);
+}
+
+#[test]
+fn test_history_disabled_with_zero_capacity() {
+  let mut history = ExecutionHistory::new(0);
+
+  history.record(instruction(0x0600));
+
+  assert!(history.is_empty());
+}
+
+#[test]
+fn test_history_shrinking_drops_oldest() {
+  let mut history = ExecutionHistory::new(3);
+  history.record(instruction(0x0600));
+  history.record(instruction(0x0601));
+  history.record(instruction(0x0602));
+
+  history.set_capacity(1);
+
+  assert_eq!(1, history.len());
+  assert_eq!(0x0602, history.last().unwrap().program_counter);
+}
+
+#[test]
+fn test_cpu_records_executed_instructions_with_registers() {
+  let mut cpu = MyCPU::new(Bus::new(create_test_rom()).unwrap());
+  cpu.program_counter = 0x0600;
+
+  // LDA #$C0, TAX, INX, BRK
+  cpu.load_and_run(vec![0xA9, 0xC0, 0xAA, 0xE8]);
+
+  let entries: Vec<&ExecutedInstruction> = cpu.history.iter().collect();
+  assert_eq!(4, entries.len());
+  assert_eq!("LDA", entries[0].mnemonic);
+  assert_eq!(vec![0xC0], entries[0].operands);
+  assert_eq!(0x0602, entries[1].program_counter);
+  assert_eq!(0xC0, entries[1].register_a);
+  assert_eq!(0xC0, entries[2].register_x);
+  assert_eq!("BRK", entries[3].mnemonic);
+  assert!(cpu.history.dump().contains("0602  AA        TAX"));
+}
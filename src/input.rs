@@ -0,0 +1,180 @@
+use std::collections::{HashMap, HashSet};
+use std::io;
+use std::path::Path;
+use crate::joypad::JoypadButton;
+
+pub const CONFIG_FILE: &str = "input.cfg";
+
+// a physical input, named like sdl names them so the mapping works without sdl
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum InputSource {
+  Key(String),
+  // gamepads are numbered in the order they were connected
+  Gamepad { pad: u32, button: String },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Player {
+  One,
+  Two,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Binding {
+  pub player: Player,
+  pub button: JoypadButton,
+}
+
+const BUTTON_NAMES: [(&str, JoypadButton); 8] = [
+  ("A", JoypadButton::BUTTON_A),
+  ("B", JoypadButton::BUTTON_B),
+  ("SELECT", JoypadButton::SELECT),
+  ("START", JoypadButton::START),
+  ("UP", JoypadButton::UP),
+  ("DOWN", JoypadButton::DOWN),
+  ("LEFT", JoypadButton::LEFT),
+  ("RIGHT", JoypadButton::RIGHT),
+];
+
+// any number of keys and gamepads can drive the same button, it's
+// released when the last of them is
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct InputMap {
+  bindings: HashMap<InputSource, Binding>,
+  pressed: HashSet<InputSource>,
+}
+
+impl InputMap {
+  pub fn new() -> Self {
+    InputMap::default()
+  }
+
+  // keyboard for player 1, first gamepad for player 1, second for player 2
+  pub fn with_defaults() -> Self {
+    let mut map = InputMap::new();
+    let keys = [
+      ("Down", JoypadButton::DOWN), ("Up", JoypadButton::UP),
+      ("Right", JoypadButton::RIGHT), ("Left", JoypadButton::LEFT),
+      ("Space", JoypadButton::SELECT), ("Return", JoypadButton::START),
+      ("A", JoypadButton::BUTTON_A), ("S", JoypadButton::BUTTON_B),
+    ];
+    for (key, button) in keys {
+      map.bind(InputSource::Key(key.to_string()), Player::One, button);
+    }
+    let pad_buttons = [
+      ("dpdown", JoypadButton::DOWN), ("dpup", JoypadButton::UP),
+      ("dpright", JoypadButton::RIGHT), ("dpleft", JoypadButton::LEFT),
+      ("back", JoypadButton::SELECT), ("start", JoypadButton::START),
+      ("a", JoypadButton::BUTTON_A), ("b", JoypadButton::BUTTON_B),
+    ];
+    for (pad, player) in [(0, Player::One), (1, Player::Two)] {
+      for (button_name, button) in pad_buttons {
+        map.bind(InputSource::Gamepad { pad, button: button_name.to_string() }, player, button);
+      }
+    }
+    map
+  }
+
+  // replaces an older binding of the same source
+  pub fn bind(&mut self, source: InputSource, player: Player, button: JoypadButton) {
+    self.bindings.insert(source, Binding { player, button });
+  }
+
+  pub fn unbind(&mut self, source: &InputSource) -> Option<Binding> {
+    self.pressed.remove(source);
+    self.bindings.remove(source)
+  }
+
+  pub fn binding(&self, source: &InputSource) -> Option<Binding> {
+    self.bindings.get(source).copied()
+  }
+
+  // false for unbound sources
+  pub fn press(&mut self, source: InputSource) -> bool {
+    if !self.bindings.contains_key(&source) {
+      return false;
+    }
+    self.pressed.insert(source);
+    true
+  }
+
+  pub fn release(&mut self, source: &InputSource) -> bool {
+    self.pressed.remove(source)
+  }
+
+  pub fn buttons(&self, player: Player) -> JoypadButton {
+    self.pressed.iter()
+      .filter_map(|source| self.bindings.get(source))
+      .filter(|binding| binding.player == player)
+      .fold(JoypadButton::empty(), |buttons, binding| buttons | binding.button)
+  }
+
+  // one binding per line: `key Down = 1 DOWN` or `pad 0 a = 2 A`
+  pub fn to_config(&self) -> String {
+    let mut sources: Vec<&InputSource> = self.bindings.keys().collect();
+    sources.sort();
+    sources.iter().map(|source| {
+      let binding = self.bindings[source];
+      let player = match binding.player {
+        Player::One => 1,
+        Player::Two => 2,
+      };
+      let button = BUTTON_NAMES.iter().find(|(_, b)| *b == binding.button).map_or("?", |(name, _)| name);
+      match source {
+        InputSource::Key(key) => format!("key {} = {} {}\n", key, player, button),
+        InputSource::Gamepad { pad, button: pad_button } => format!("pad {} {} = {} {}\n", pad, pad_button, player, button),
+      }
+    }).collect()
+  }
+
+  // empty lines and lines starting with # are skipped
+  pub fn from_config(config: &str) -> Result<InputMap, String> {
+    let mut map = InputMap::new();
+    for (index, line) in config.lines().enumerate() {
+      let line = line.trim();
+      if line.is_empty() || line.starts_with('#') {
+        continue;
+      }
+      let (source, player, button) = parse_line(line).ok_or(format!("line {}: invalid binding '{}'", index + 1, line))?;
+      map.bind(source, player, button);
+    }
+    Ok(map)
+  }
+
+  // without a config file the defaults are used
+  pub fn load(path: &Path) -> io::Result<InputMap> {
+    match std::fs::read_to_string(path) {
+      Ok(config) => InputMap::from_config(&config).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e)),
+      Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(InputMap::with_defaults()),
+      Err(e) => Err(e),
+    }
+  }
+
+  pub fn save(&self, path: &Path) -> io::Result<()> {
+    std::fs::write(path, self.to_config())
+  }
+}
+
+fn parse_line(line: &str) -> Option<(InputSource, Player, JoypadButton)> {
+  let (source, target) = line.split_once('=')?;
+  let source = match source.trim().split_once(' ')? {
+    ("key", key) => InputSource::Key(key.trim().to_string()),
+    ("pad", rest) => {
+      let (pad, button) = rest.trim().split_once(' ')?;
+      InputSource::Gamepad { pad: pad.parse().ok()?, button: button.trim().to_string() }
+    }
+    _ => return None,
+  };
+  let (player, button) = target.trim().split_once(' ')?;
+  let player = match player {
+    "1" => Player::One,
+    "2" => Player::Two,
+    _ => return None,
+  };
+  Some((source, player, button_by_name(button.trim())?))
+}
+
+// the names used in input.cfg: A, B, SELECT, START, UP, DOWN, LEFT, RIGHT
+pub fn button_by_name(name: &str) -> Option<JoypadButton> {
+  BUTTON_NAMES.iter().find(|(button, _)| *button == name).map(|(_, button)| *button)
+}
@@ -0,0 +1,67 @@
+use crate::input::{InputMap, InputSource, Player};
+use crate::joypad::JoypadButton;
+
+fn key(name: &str) -> InputSource {
+  InputSource::Key(name.to_string())
+}
+
+fn pad(pad: u32, button: &str) -> InputSource {
+  InputSource::Gamepad { pad, button: button.to_string() }
+}
+
+#[test]
+fn test_defaults_map_keyboard_and_two_gamepads() {
+  let mut input = InputMap::with_defaults();
+
+  input.press(key("Return"));
+  input.press(pad(1, "a"));
+
+  assert_eq!(JoypadButton::START, input.buttons(Player::One));
+  assert_eq!(JoypadButton::BUTTON_A, input.buttons(Player::Two));
+}
+
+#[test]
+fn test_button_is_held_until_every_source_is_released() {
+  let mut input = InputMap::with_defaults();
+
+  input.press(key("Right"));
+  input.press(pad(0, "dpright"));
+  input.release(&key("Right"));
+  assert_eq!(JoypadButton::RIGHT, input.buttons(Player::One));
+
+  input.release(&pad(0, "dpright"));
+  assert_eq!(JoypadButton::empty(), input.buttons(Player::One));
+}
+
+#[test]
+fn test_rebinding_at_runtime() {
+  let mut input = InputMap::new();
+  assert!(!input.press(key("X")));
+
+  input.bind(key("X"), Player::Two, JoypadButton::BUTTON_B);
+  assert!(input.press(key("X")));
+  assert_eq!(JoypadButton::BUTTON_B, input.buttons(Player::Two));
+
+  input.unbind(&key("X"));
+  assert_eq!(JoypadButton::empty(), input.buttons(Player::Two));
+  assert_eq!(None, input.binding(&key("X")));
+}
+
+#[test]
+fn test_config_round_trip() {
+  let mut input = InputMap::with_defaults();
+  input.bind(key("Left Shift"), Player::Two, JoypadButton::SELECT);
+
+  let config = input.to_config();
+  assert!(config.contains("key Left Shift = 2 SELECT\n"), "{}", config);
+  assert!(config.contains("pad 1 dpup = 2 UP\n"), "{}", config);
+
+  assert_eq!(input, InputMap::from_config(&config).unwrap());
+}
+
+#[test]
+fn test_invalid_config_line() {
+  let config = "# comment\n\nkey A = 1 A\nkey B = 3 A\n";
+
+  assert_eq!(Err("line 4: invalid binding 'key B = 3 A'".to_string()), InputMap::from_config(config));
+}
@@ -0,0 +1,88 @@
+use crate::savestate::{StateReader, StateWriter, Stateful};
+
+bitflags! {
+  // order in which the controller shifts out its buttons: A first, RIGHT last
+  #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+  pub struct JoypadButton: u8 {
+    const RIGHT = 0b1000_0000;
+    const LEFT = 0b0100_0000;
+    const DOWN = 0b0010_0000;
+    const UP = 0b0001_0000;
+    const START = 0b0000_1000;
+    const SELECT = 0b0000_0100;
+    const BUTTON_B = 0b0000_0010;
+    const BUTTON_A = 0b0000_0001;
+  }
+}
+
+// standard controller: writing 1 to the strobe reloads the shift register,
+// every read while strobe is off returns the next button
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Debug, Clone, PartialEq)]
+pub struct Joypad {
+  strobe: bool,
+  button_index: u8,
+  button_status: JoypadButton,
+}
+
+impl Joypad {
+  pub fn new() -> Self {
+    Joypad {
+      strobe: false,
+      button_index: 0,
+      button_status: JoypadButton::empty(),
+    }
+  }
+
+  pub fn write(&mut self, data: u8) {
+    self.strobe = data & 1 == 1;
+    if self.strobe {
+      self.button_index = 0;
+    }
+  }
+
+  pub fn read(&mut self) -> u8 {
+    // official controllers return 1 after all 8 buttons were read
+    if self.button_index > 7 {
+      return 1;
+    }
+    let response = (self.button_status.bits() >> self.button_index) & 1;
+    if !self.strobe {
+      self.button_index += 1;
+    }
+    response
+  }
+
+  pub fn buttons(&self) -> JoypadButton {
+    self.button_status
+  }
+
+  pub fn set_buttons(&mut self, buttons: JoypadButton) {
+    self.button_status = buttons;
+  }
+
+  pub fn set_button_pressed_status(&mut self, button: JoypadButton, pressed: bool) {
+    self.button_status.set(button, pressed);
+  }
+}
+
+impl Stateful for Joypad {
+  fn save_state(&self, w: &mut StateWriter) {
+    w.write_bool(self.strobe);
+    w.write_u8(self.button_index);
+    w.write_u8(self.button_status.bits());
+  }
+
+  fn load_state(&mut self, r: &mut StateReader) -> Result<(), String> {
+    self.strobe = r.read_bool()?;
+    self.button_index = r.read_u8()?;
+    self.button_status = JoypadButton::from_bits_truncate(r.read_u8()?);
+    Ok(())
+  }
+}
+
+impl Default for Joypad {
+  fn default() -> Self {
+    Joypad::new()
+  }
+}
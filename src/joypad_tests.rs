@@ -0,0 +1,58 @@
+use crate::bus::Bus;
+use crate::cartridge_tests::create_test_rom;
+use crate::cpu::MyMem;
+use crate::joypad::{Joypad, JoypadButton};
+
+#[test]
+fn test_strobe_mode() {
+  let mut joypad = Joypad::new();
+  joypad.write(1);
+  joypad.set_button_pressed_status(JoypadButton::BUTTON_A, true);
+
+  for _ in 0..10 {
+    assert_eq!(joypad.read(), 1);
+  }
+}
+
+#[test]
+fn test_strobe_mode_on_off() {
+  let mut joypad = Joypad::new();
+
+  joypad.write(0);
+  joypad.set_button_pressed_status(JoypadButton::RIGHT, true);
+  joypad.set_button_pressed_status(JoypadButton::LEFT, true);
+  joypad.set_button_pressed_status(JoypadButton::SELECT, true);
+  joypad.set_button_pressed_status(JoypadButton::BUTTON_B, true);
+
+  for _ in 0..=1 {
+    assert_eq!(joypad.read(), 0);
+    assert_eq!(joypad.read(), 1);
+    assert_eq!(joypad.read(), 1);
+    assert_eq!(joypad.read(), 0);
+    assert_eq!(joypad.read(), 0);
+    assert_eq!(joypad.read(), 0);
+    assert_eq!(joypad.read(), 1);
+    assert_eq!(joypad.read(), 1);
+
+    for _ in 0..10 {
+      assert_eq!(joypad.read(), 1);
+    }
+    joypad.write(1);
+    joypad.write(0);
+  }
+}
+
+#[test]
+fn test_bus_strobes_both_controllers() {
+  let mut bus = Bus::new(create_test_rom()).unwrap();
+  bus.joypad1.set_button_pressed_status(JoypadButton::BUTTON_A, true);
+  bus.joypad2.set_button_pressed_status(JoypadButton::BUTTON_B, true);
+
+  bus.mem_write(0x4016, 1);
+  bus.mem_write(0x4016, 0);
+
+  assert_eq!(1, bus.mem_read(0x4016));
+  assert_eq!(0, bus.mem_read(0x4016));
+  assert_eq!(0, bus.mem_read(0x4017));
+  assert_eq!(1, bus.mem_read(0x4017));
+}
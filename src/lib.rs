@@ -0,0 +1,194 @@
+pub mod cpu;
+pub mod opcodes;
+pub mod asm;
+#[cfg(test)]
+mod asm_tests;
+#[cfg(test)]
+mod cpu_tests;
+#[cfg(test)]
+mod cpu_model_tests;
+pub mod fuzz;
+#[cfg(test)]
+mod fuzz_tests;
+pub mod bus;
+#[cfg(test)]
+mod bus_tests;
+pub mod clock;
+#[cfg(test)]
+mod clock_tests;
+pub mod cartridge;
+#[cfg(test)]
+mod cartridge_tests;
+pub mod cheats;
+#[cfg(test)]
+mod cheats_tests;
+pub mod mapper;
+#[cfg(test)]
+mod mapper_tests;
+pub mod mmc3;
+#[cfg(test)]
+mod mmc3_tests;
+pub mod battery;
+#[cfg(test)]
+mod battery_tests;
+pub mod error;
+pub mod cli;
+#[cfg(test)]
+mod cli_tests;
+pub mod history;
+#[cfg(test)]
+mod history_tests;
+pub mod call_stack;
+#[cfg(test)]
+mod call_stack_tests;
+pub mod snapshot;
+pub mod savestate;
+#[cfg(test)]
+mod savestate_tests;
+pub mod rewind;
+#[cfg(test)]
+mod rewind_tests;
+pub mod movie;
+#[cfg(test)]
+mod movie_tests;
+pub mod nes;
+pub mod netplay;
+#[cfg(test)]
+mod netplay_tests;
+#[cfg(test)]
+mod nes_tests;
+pub mod blargg;
+#[cfg(test)]
+mod blargg_tests;
+pub mod delta;
+#[cfg(test)]
+mod delta_tests;
+pub mod time_travel;
+#[cfg(test)]
+mod time_travel_tests;
+pub mod profiler;
+#[cfg(test)]
+mod profiler_tests;
+pub mod event_log;
+#[cfg(test)]
+mod event_log_tests;
+pub mod trace;
+#[cfg(test)]
+mod trace_tests;
+pub mod memory_editor;
+#[cfg(test)]
+mod memory_editor_tests;
+pub mod breakpoints;
+#[cfg(test)]
+mod breakpoints_tests;
+pub mod gdb;
+#[cfg(test)]
+mod gdb_tests;
+pub mod control;
+#[cfg(test)]
+mod control_tests;
+pub mod symbols;
+#[cfg(test)]
+mod symbols_tests;
+pub mod monitor;
+#[cfg(test)]
+mod monitor_tests;
+pub mod bench;
+#[cfg(test)]
+mod bench_tests;
+pub mod decode_cache;
+#[cfg(test)]
+mod decode_cache_tests;
+pub mod pacing;
+#[cfg(test)]
+mod pacing_tests;
+pub mod speed;
+#[cfg(test)]
+mod speed_tests;
+pub mod stats;
+#[cfg(test)]
+mod stats_tests;
+pub mod power_on;
+#[cfg(test)]
+mod power_on_tests;
+pub mod ppu;
+#[cfg(test)]
+mod ppu_tests;
+pub mod frame;
+pub mod debug_view;
+#[cfg(test)]
+mod debug_view_tests;
+pub mod video;
+#[cfg(test)]
+mod video_tests;
+#[cfg(test)]
+mod frame_tests;
+pub mod golden;
+#[cfg(test)]
+mod golden_tests;
+pub mod png;
+pub mod palette;
+#[cfg(test)]
+mod palette_tests;
+pub mod rgba;
+#[cfg(test)]
+mod rgba_tests;
+pub mod render;
+#[cfg(test)]
+mod render_tests;
+pub mod nestest;
+pub mod disasm;
+#[cfg(test)]
+mod disasm_tests;
+#[cfg(test)]
+mod nestest_tests;
+pub mod joypad;
+#[cfg(test)]
+mod joypad_tests;
+pub mod input;
+#[cfg(test)]
+mod input_tests;
+pub mod apu;
+#[cfg(test)]
+mod apu_tests;
+pub mod audio;
+#[cfg(test)]
+mod audio_tests;
+pub mod audio_worker;
+#[cfg(test)]
+mod audio_worker_tests;
+pub mod resampler;
+#[cfg(test)]
+mod resampler_tests;
+pub mod wav;
+#[cfg(test)]
+mod wav_tests;
+pub mod nsf;
+#[cfg(test)]
+mod nsf_tests;
+pub mod simple_device;
+#[cfg(test)]
+mod simple_device_tests;
+pub mod ffi;
+#[cfg(test)]
+mod ffi_tests;
+#[cfg(feature = "sdl2")]
+mod frontend;
+#[cfg(feature = "sdl2")]
+mod snake;
+#[cfg(feature = "cached-decode")]
+pub mod block_cache;
+#[cfg(all(test, feature = "cached-decode"))]
+mod block_cache_tests;
+#[cfg(feature = "generic-6502")]
+pub mod flat_memory;
+#[cfg(all(test, feature = "generic-6502"))]
+mod flat_memory_tests;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+#[cfg(all(test, feature = "wasm"))]
+mod wasm_tests;
+
+#[macro_use]
+extern crate bitflags;
+extern crate core;
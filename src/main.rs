@@ -1,132 +1,15 @@
-mod cpu;
-mod opcodes;
-mod cpu_tests;
-mod bus;
-mod cartridge;
-mod cartridge_tests;
-
-#[macro_use]
-extern crate lazy_static;
-
-#[macro_use]
-extern crate bitflags;
-extern crate core;
-
-use rand::Rng;
-use sdl2::event::Event;
-use sdl2::EventPump;
-use sdl2::keyboard::Keycode;
-use sdl2::pixels::{Color, PixelFormatEnum};
-use crate::bus::Bus;
-use crate::cartridge::Rom;
-use crate::cpu::{MyCPU, MyMem};
+use nes_emulator::cli;
 
 fn main() {
-    // init sdl2
-    let sdl_context = sdl2::init().unwrap();
-    let video_subsystem = sdl_context.video().unwrap();
-    let window = video_subsystem
-      .window("Snake game", (32.0 * 10.0) as u32, (32.0 * 10.0) as u32)
-      .position_centered()
-      .build().unwrap();
-
-    let mut canvas = window.into_canvas().present_vsync().build().unwrap();
-    let mut event_pump = sdl_context.event_pump().unwrap();
-    canvas.set_scale(10.0, 10.0).unwrap();
-
-    let creator = canvas.texture_creator();
-    let mut texture = creator
-      .create_texture_target(PixelFormatEnum::RGB24, 32, 32)
-      .unwrap();
-
-    // load the game
-    let bytes: Vec<u8> = std::fs::read("snake.nes").unwrap();
-    let rom = Rom::new(&bytes).unwrap();
-
-    let bus = Bus::new(rom);
-    let mut cpu = MyCPU::new(bus);
-    cpu.reset();
-
-    let mut screen_state = [0 as u8; 32 * 3 * 32];
-    let mut rng = rand::thread_rng();
-
-    // run game cycle
-    cpu.run_with_callback(move |cpu| {
-        handle_user_input(cpu, &mut event_pump);
-
-        cpu.mem_write(0xFE, rng.gen_range(1, 16));
-
-        if read_screen_state(cpu, &mut screen_state) {
-            texture.update(None, &screen_state, 32 * 3).unwrap();
-
-            canvas.copy(&texture, None, None).unwrap();
-
-            canvas.present();
-        }
-
-        ::std::thread::sleep(std::time::Duration::new(0, 40_000));
-    });
-}
-
-fn handle_user_input(cpu: &mut MyCPU, event_pump: &mut EventPump) {
-    for event in event_pump.poll_iter() {
-        match event {
-            Event::Quit { .. } | Event::KeyDown { keycode: Some(Keycode::Escape), ..} => {
-                println!("input quit");
-                std::process::exit(0)
-            },
-            // where are the direction-values documented...?
-            Event::KeyDown { keycode: Some(Keycode::W), .. } => {
-                println!("input W");
-                cpu.mem_write(0xff, 0x77);
-            },
-            Event::KeyDown { keycode: Some(Keycode::S), .. } => {
-                println!("input S");
-                cpu.mem_write(0xff, 0x73);
-            },
-            Event::KeyDown { keycode: Some(Keycode::A), .. } => {
-                println!("input A");
-                cpu.mem_write(0xff, 0x61);
-            },
-            Event::KeyDown { keycode: Some(Keycode::D), .. } => {
-                println!("input D");
-                cpu.mem_write(0xff, 0x64);
-            },
-            _ => {
-                println!("input other");
-                /* do nothing */
-            }
+    let command = match cli::parse(std::env::args().skip(1)) {
+        Ok(command) => command,
+        Err(e) => {
+            eprintln!("{}\n{}", e, cli::USAGE);
+            std::process::exit(2);
         }
+    };
+    if let Err(e) = cli::execute(command) {
+        eprintln!("{}", e);
+        std::process::exit(1);
     }
 }
-
-fn read_screen_state(cpu: &MyCPU, frame: &mut [u8; 32 * 3 * 32]) -> bool {
-    let mut frame_idx = 0;
-    let mut update = false;
-    for i in 0x0200..0x600 {
-        let color_idx = cpu.mem_read(i as u16);
-        let (b1, b2, b3) = color(color_idx).rgb();
-        if frame[frame_idx] != b1 || frame[frame_idx + 1] != b2 || frame[frame_idx + 2] != b3 {
-            frame[frame_idx] = b1;
-            frame[frame_idx + 1] = b2;
-            frame[frame_idx + 2] = b3;
-            update = true;
-        }
-        frame_idx += 3;
-    }
-    update
-}
-
-fn color(byte: u8) -> Color {
-    match byte {
-        0 => sdl2::pixels::Color::BLACK,
-        1 => sdl2::pixels::Color::WHITE,
-        2 | 9 => sdl2::pixels::Color::GREY,
-        3 | 10 => sdl2::pixels::Color::RED,
-        4 | 11 => sdl2::pixels::Color::GREEN,
-        5 | 12 => sdl2::pixels::Color::BLUE,
-        6 | 13 => sdl2::pixels::Color::MAGENTA,
-        7 | 14 => sdl2::pixels::Color::YELLOW,
-        _ => sdl2::pixels::Color::CYAN,
-    }
-}
\ No newline at end of file
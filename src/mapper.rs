@@ -0,0 +1,372 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+use crate::cartridge::{Mirroring, Rom, RomError, CHR_ROM_PAGE_SIZE};
+use crate::mmc3::Mmc3;
+#[cfg(feature = "serde")]
+use crate::mmc3::Mmc3State;
+use crate::savestate::{StateReader, StateWriter, Stateful};
+#[cfg(feature = "serde")]
+use crate::savestate::copy_exact;
+
+// cartridge hardware between the rom chips and the cpu/ppu buses
+// https://wiki.nesdev.org/w/index.php/Mapper
+// the state covers bank registers and chr ram, never the rom itself
+pub trait Mapper: Stateful {
+  // cpu $8000-$FFFF
+  fn prg_read(&self, addr: u16) -> u8;
+  fn prg_write(&mut self, addr: u16, data: u8);
+  // ppu $0000-$1FFF
+  fn chr_read(&self, addr: u16) -> u8;
+  fn chr_write(&mut self, addr: u16, data: u8);
+  fn mirroring(&self) -> Mirroring;
+  // true while the cartridge pulls the irq line
+  fn irq(&self) -> bool {
+    false
+  }
+  // pattern table addresses the ppu puts on its bus while rendering,
+  // lets mappers watch A12 to count scanlines
+  fn ppu_bus_address(&mut self, _addr: u16) {}
+  // position inside the prg rom the cpu address is currently mapped to
+  fn prg_rom_offset(&self, addr: u16) -> Option<usize>;
+  // what the Stateful impl saves, for serde
+  #[cfg(feature = "serde")]
+  fn state(&self) -> MapperState;
+  #[cfg(feature = "serde")]
+  fn set_state(&mut self, state: MapperState) -> Result<(), String>;
+}
+
+// one variant per mapper, setting the state of another one fails
+#[cfg(feature = "serde")]
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub enum MapperState {
+  Nrom { chr_ram: Vec<u8> },
+  Cnrom { chr_ram: Vec<u8>, chr_bank: usize },
+  Axrom { chr_ram: Vec<u8>, prg_bank: usize, mirroring: Mirroring },
+  Mmc3(Mmc3State),
+}
+
+#[cfg(feature = "serde")]
+pub const OTHER_MAPPER: &str = "save state is for another mapper";
+
+// the cpu and the ppu both talk to the cartridge
+pub type SharedMapper = Rc<RefCell<dyn Mapper>>;
+
+pub fn for_rom(rom: Rom) -> Result<SharedMapper, RomError> {
+  let mapper: SharedMapper = match rom.mapper {
+    0 => Rc::new(RefCell::new(Nrom::new(rom))),
+    3 => Rc::new(RefCell::new(Cnrom::new(rom))),
+    4 => Rc::new(RefCell::new(Mmc3::new(rom))),
+    7 => Rc::new(RefCell::new(Axrom::new(rom))),
+    id => return Err(RomError::UnsupportedMapper(id)),
+  };
+  Ok(mapper)
+}
+
+// mapper 0: 16KB (mirrored) or 32KB prg, 8KB chr, no registers
+// https://wiki.nesdev.org/w/index.php/NROM
+pub struct Nrom {
+  prg_rom: Vec<u8>,
+  chr: ChrMemory,
+  mirroring: Mirroring,
+}
+
+impl Nrom {
+  pub fn new(mut rom: Rom) -> Self {
+    Nrom { chr: ChrMemory::new(&mut rom), prg_rom: rom.prg_rom, mirroring: rom.screen_mirroring }
+  }
+}
+
+impl Mapper for Nrom {
+  fn prg_read(&self, addr: u16) -> u8 {
+    self.prg_rom_offset(addr).and_then(|offset| self.prg_rom.get(offset)).copied().unwrap_or(0)
+  }
+
+  // no registers, writes to rom are ignored
+  fn prg_write(&mut self, _addr: u16, _data: u8) {}
+
+  fn chr_read(&self, addr: u16) -> u8 {
+    self.chr.read(addr as usize)
+  }
+
+  fn chr_write(&mut self, addr: u16, data: u8) {
+    self.chr.write(addr as usize, data);
+  }
+
+  fn mirroring(&self) -> Mirroring {
+    self.mirroring
+  }
+
+  fn prg_rom_offset(&self, addr: u16) -> Option<usize> {
+    unbanked_prg_offset(&self.prg_rom, addr)
+  }
+
+  #[cfg(feature = "serde")]
+  fn state(&self) -> MapperState {
+    MapperState::Nrom { chr_ram: self.chr.ram() }
+  }
+
+  #[cfg(feature = "serde")]
+  fn set_state(&mut self, state: MapperState) -> Result<(), String> {
+    match state {
+      MapperState::Nrom { chr_ram } => self.chr.set_ram(&chr_ram),
+      _ => Err(OTHER_MAPPER.to_string()),
+    }
+  }
+}
+
+// chr rom, or chr ram for cartridges without chr rom
+pub struct ChrMemory {
+  data: Vec<u8>,
+  writable: bool,
+}
+
+impl ChrMemory {
+  pub fn new(rom: &mut Rom) -> Self {
+    ChrMemory { data: std::mem::take(&mut rom.chr_rom), writable: rom.chr_ram }
+  }
+
+  pub fn len(&self) -> usize {
+    self.data.len()
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.data.is_empty()
+  }
+
+  pub fn read(&self, offset: usize) -> u8 {
+    self.data.get(offset).copied().unwrap_or(0)
+  }
+
+  pub fn write(&mut self, offset: usize, data: u8) {
+    // chr rom ignores writes
+    if !self.writable {
+      return;
+    }
+    if let Some(value) = self.data.get_mut(offset) {
+      *value = data;
+    }
+  }
+}
+
+// only chr ram is part of the state
+impl Stateful for ChrMemory {
+  fn save_state(&self, w: &mut StateWriter) {
+    w.write_bytes(if self.writable { &self.data } else { &[] });
+  }
+
+  fn load_state(&mut self, r: &mut StateReader) -> Result<(), String> {
+    if self.writable {
+      r.read_into(&mut self.data)
+    } else {
+      r.read_into(&mut [])
+    }
+  }
+}
+
+#[cfg(feature = "serde")]
+impl ChrMemory {
+  // empty for chr rom, as in the Stateful impl
+  pub fn ram(&self) -> Vec<u8> {
+    if self.writable { self.data.clone() } else { Vec::new() }
+  }
+
+  pub fn set_ram(&mut self, ram: &[u8]) -> Result<(), String> {
+    if self.writable {
+      copy_exact(&mut self.data, ram)
+    } else {
+      copy_exact(&mut [], ram)
+    }
+  }
+}
+
+pub fn save_mirroring(w: &mut StateWriter, mirroring: Mirroring) {
+  w.write_u8(mirroring as u8);
+}
+
+pub fn load_mirroring(r: &mut StateReader) -> Result<Mirroring, String> {
+  match r.read_u8()? {
+    0 => Ok(Mirroring::VERTICAL),
+    1 => Ok(Mirroring::HORIZONTAL),
+    2 => Ok(Mirroring::FOUR_SCREEN),
+    3 => Ok(Mirroring::SINGLE_SCREEN_LOWER),
+    4 => Ok(Mirroring::SINGLE_SCREEN_UPPER),
+    value => Err(format!("invalid mirroring {} in save state", value)),
+  }
+}
+
+// a single 16KB bank is mirrored into $C000-$FFFF
+fn unbanked_prg_offset(prg_rom: &[u8], addr: u16) -> Option<usize> {
+  if addr < 0x8000 || prg_rom.is_empty() {
+    return None;
+  }
+  Some((addr - 0x8000) as usize % prg_rom.len())
+}
+
+impl Stateful for Nrom {
+  fn save_state(&self, w: &mut StateWriter) {
+    self.chr.save_state(w);
+  }
+
+  fn load_state(&mut self, r: &mut StateReader) -> Result<(), String> {
+    self.chr.load_state(r)
+  }
+}
+
+// mapper 3: NROM prg, any write to $8000-$FFFF selects the 8KB chr bank
+// (bus conflicts with the rom value are ignored)
+// https://wiki.nesdev.org/w/index.php/INES_Mapper_003
+pub struct Cnrom {
+  prg_rom: Vec<u8>,
+  chr: ChrMemory,
+  mirroring: Mirroring,
+  chr_bank: usize,
+}
+
+impl Cnrom {
+  pub fn new(mut rom: Rom) -> Self {
+    Cnrom { chr: ChrMemory::new(&mut rom), prg_rom: rom.prg_rom, mirroring: rom.screen_mirroring, chr_bank: 0 }
+  }
+
+  fn chr_banks(&self) -> usize {
+    (self.chr.len() / CHR_ROM_PAGE_SIZE).max(1)
+  }
+}
+
+impl Mapper for Cnrom {
+  fn prg_read(&self, addr: u16) -> u8 {
+    self.prg_rom_offset(addr).and_then(|offset| self.prg_rom.get(offset)).copied().unwrap_or(0)
+  }
+
+  fn prg_write(&mut self, _addr: u16, data: u8) {
+    self.chr_bank = data as usize % self.chr_banks();
+  }
+
+  fn chr_read(&self, addr: u16) -> u8 {
+    self.chr.read(self.chr_bank * CHR_ROM_PAGE_SIZE + addr as usize)
+  }
+
+  fn chr_write(&mut self, addr: u16, data: u8) {
+    self.chr.write(self.chr_bank * CHR_ROM_PAGE_SIZE + addr as usize, data);
+  }
+
+  fn mirroring(&self) -> Mirroring {
+    self.mirroring
+  }
+
+  fn prg_rom_offset(&self, addr: u16) -> Option<usize> {
+    unbanked_prg_offset(&self.prg_rom, addr)
+  }
+
+  #[cfg(feature = "serde")]
+  fn state(&self) -> MapperState {
+    MapperState::Cnrom { chr_ram: self.chr.ram(), chr_bank: self.chr_bank }
+  }
+
+  #[cfg(feature = "serde")]
+  fn set_state(&mut self, state: MapperState) -> Result<(), String> {
+    match state {
+      MapperState::Cnrom { chr_ram, chr_bank } => {
+        self.chr.set_ram(&chr_ram)?;
+        self.chr_bank = chr_bank % self.chr_banks();
+        Ok(())
+      }
+      _ => Err(OTHER_MAPPER.to_string()),
+    }
+  }
+}
+
+impl Stateful for Cnrom {
+  fn save_state(&self, w: &mut StateWriter) {
+    self.chr.save_state(w);
+    w.write_u64(self.chr_bank as u64);
+  }
+
+  fn load_state(&mut self, r: &mut StateReader) -> Result<(), String> {
+    self.chr.load_state(r)?;
+    self.chr_bank = r.read_u64()? as usize % self.chr_banks();
+    Ok(())
+  }
+}
+
+const AXROM_PRG_BANK_SIZE: usize = 0x8000;
+
+// mapper 7: 32KB prg banks, single screen mirroring selected by the same register
+// https://wiki.nesdev.org/w/index.php/AxROM
+pub struct Axrom {
+  prg_rom: Vec<u8>,
+  chr: ChrMemory,
+  prg_bank: usize,
+  mirroring: Mirroring,
+}
+
+impl Axrom {
+  pub fn new(mut rom: Rom) -> Self {
+    Axrom { chr: ChrMemory::new(&mut rom), prg_rom: rom.prg_rom, prg_bank: 0, mirroring: Mirroring::SINGLE_SCREEN_LOWER }
+  }
+}
+
+impl Mapper for Axrom {
+  fn prg_read(&self, addr: u16) -> u8 {
+    self.prg_rom_offset(addr).and_then(|offset| self.prg_rom.get(offset)).copied().unwrap_or(0)
+  }
+
+  // ---M -PPP
+  fn prg_write(&mut self, _addr: u16, data: u8) {
+    let banks = (self.prg_rom.len() / AXROM_PRG_BANK_SIZE).max(1);
+    self.prg_bank = (data & 0b111) as usize % banks;
+    self.mirroring = if data & 0b1_0000 == 0 { Mirroring::SINGLE_SCREEN_LOWER } else { Mirroring::SINGLE_SCREEN_UPPER };
+  }
+
+  fn chr_read(&self, addr: u16) -> u8 {
+    self.chr.read(addr as usize)
+  }
+
+  fn chr_write(&mut self, addr: u16, data: u8) {
+    self.chr.write(addr as usize, data);
+  }
+
+  fn mirroring(&self) -> Mirroring {
+    self.mirroring
+  }
+
+  fn prg_rom_offset(&self, addr: u16) -> Option<usize> {
+    if addr < 0x8000 {
+      return None;
+    }
+    Some(self.prg_bank * AXROM_PRG_BANK_SIZE + (addr - 0x8000) as usize)
+  }
+
+  #[cfg(feature = "serde")]
+  fn state(&self) -> MapperState {
+    MapperState::Axrom { chr_ram: self.chr.ram(), prg_bank: self.prg_bank, mirroring: self.mirroring }
+  }
+
+  #[cfg(feature = "serde")]
+  fn set_state(&mut self, state: MapperState) -> Result<(), String> {
+    match state {
+      MapperState::Axrom { chr_ram, prg_bank, mirroring } => {
+        self.chr.set_ram(&chr_ram)?;
+        self.prg_bank = prg_bank % (self.prg_rom.len() / AXROM_PRG_BANK_SIZE).max(1);
+        self.mirroring = mirroring;
+        Ok(())
+      }
+      _ => Err(OTHER_MAPPER.to_string()),
+    }
+  }
+}
+
+impl Stateful for Axrom {
+  fn save_state(&self, w: &mut StateWriter) {
+    self.chr.save_state(w);
+    w.write_u64(self.prg_bank as u64);
+    save_mirroring(w, self.mirroring);
+  }
+
+  fn load_state(&mut self, r: &mut StateReader) -> Result<(), String> {
+    self.chr.load_state(r)?;
+    let banks = (self.prg_rom.len() / AXROM_PRG_BANK_SIZE).max(1);
+    self.prg_bank = r.read_u64()? as usize % banks;
+    self.mirroring = load_mirroring(r)?;
+    Ok(())
+  }
+}
@@ -0,0 +1,107 @@
+use crate::bus::Bus;
+use crate::cartridge::{Mirroring, Rom, CHR_ROM_PAGE_SIZE, PRG_ROM_PAGE_SIZE};
+use crate::cartridge_tests::create_test_rom;
+use crate::cpu::MyMem;
+use crate::mapper::{self, Axrom, Cnrom, Mapper, Nrom};
+
+fn rom_with_banks(mapper: u8, prg_banks: usize, chr_banks: usize) -> Rom {
+  // every byte holds the number of its bank
+  let mut prg_rom = Vec::new();
+  for bank in 0..prg_banks {
+    prg_rom.extend(vec![bank as u8; PRG_ROM_PAGE_SIZE]);
+  }
+  let mut chr_rom = Vec::new();
+  for bank in 0..chr_banks {
+    chr_rom.extend(vec![bank as u8; CHR_ROM_PAGE_SIZE]);
+  }
+  Rom { prg_rom, chr_rom, chr_ram: false, mapper, screen_mirroring: Mirroring::VERTICAL, battery: false }
+}
+
+#[test]
+fn test_nrom_mirrors_16kb_prg() {
+  let mut rom = rom_with_banks(0, 1, 1);
+  rom.prg_rom[0x0123] = 0x42;
+  let nrom = Nrom::new(rom);
+
+  assert_eq!(0x42, nrom.prg_read(0x8123));
+  assert_eq!(0x42, nrom.prg_read(0xC123));
+  assert_eq!(Some(0x0123), nrom.prg_rom_offset(0xC123));
+  assert_eq!(None, nrom.prg_rom_offset(0x6000));
+}
+
+#[test]
+fn test_nrom_32kb_prg_and_read_only_chr() {
+  let mut nrom = Nrom::new(rom_with_banks(0, 2, 1));
+  assert_eq!(0, nrom.prg_read(0xBFFF));
+  assert_eq!(1, nrom.prg_read(0xC000));
+
+  nrom.prg_write(0x8000, 0x42);
+  nrom.chr_write(0x0000, 0x42);
+  assert_eq!(0, nrom.prg_read(0x8000));
+  assert_eq!(0, nrom.chr_read(0x0000));
+  assert_eq!(Mirroring::VERTICAL, nrom.mirroring());
+  assert!(!nrom.irq());
+}
+
+#[test]
+fn test_for_rom_selects_mapper() {
+  let mapper = mapper::for_rom(rom_with_banks(0, 2, 1)).unwrap();
+  assert_eq!(1, mapper.borrow().prg_read(0xFFFF));
+}
+
+#[test]
+fn test_cnrom_switches_chr_banks() {
+  let mut cnrom = Cnrom::new(rom_with_banks(3, 2, 4));
+  assert_eq!(0, cnrom.chr_read(0x1FFF));
+
+  cnrom.prg_write(0x8000, 2);
+  assert_eq!(2, cnrom.chr_read(0x0000));
+  assert_eq!(2, cnrom.chr_read(0x1FFF));
+
+  // unused high bits wrap around the available banks
+  cnrom.prg_write(0xFFFF, 0b0000_0101);
+  assert_eq!(1, cnrom.chr_read(0x0000));
+  assert_eq!(1, cnrom.prg_read(0xC000));
+}
+
+#[test]
+fn test_test_rom_loads_with_cnrom() {
+  let mut bus = Bus::new(create_test_rom()).unwrap();
+  bus.mem_write(0x8000, 0);
+  assert_eq!(2, bus.ppu.chr_read(0x0000));
+}
+
+#[test]
+fn test_axrom_switches_32kb_banks_and_single_screen() {
+  // 4 banks of 16KB = 2 banks of 32KB
+  let mut axrom = Axrom::new(rom_with_banks(7, 4, 1));
+  assert_eq!(0, axrom.prg_read(0x8000));
+  assert_eq!(1, axrom.prg_read(0xFFFF));
+  assert_eq!(Mirroring::SINGLE_SCREEN_LOWER, axrom.mirroring());
+
+  axrom.prg_write(0x8000, 0b0001_0001);
+  assert_eq!(2, axrom.prg_read(0x8000));
+  assert_eq!(3, axrom.prg_read(0xFFFF));
+  assert_eq!(Mirroring::SINGLE_SCREEN_UPPER, axrom.mirroring());
+}
+
+#[test]
+fn test_chr_ram_is_writable_through_ppudata() {
+  let mut rom = rom_with_banks(0, 1, 1);
+  rom.chr_ram = true;
+  let mut bus = Bus::new(rom).unwrap();
+
+  bus.mem_write(0x2006, 0x12);
+  bus.mem_write(0x2006, 0x34);
+  bus.mem_write(0x2007, 0x42);
+
+  assert_eq!(0x42, bus.ppu.chr_read(0x1234));
+}
+
+#[test]
+fn test_unsupported_mapper_is_rejected() {
+  match Bus::new(rom_with_banks(99, 1, 1)) {
+    Ok(_) => panic!("should not create a bus"),
+    Err(e) => assert_eq!("invalid rom: Mapper 99 is not supported", e.to_string()),
+  }
+}
@@ -0,0 +1,107 @@
+use std::collections::BTreeMap;
+use crate::cpu::CpuBus;
+
+// edits memory of a running machine and keeps frozen addresses at their value
+#[derive(Default)]
+pub struct MemoryEditor {
+  frozen: BTreeMap<u16, u8>,
+}
+
+impl MemoryEditor {
+  pub fn new() -> Self {
+    MemoryEditor::default()
+  }
+
+  pub fn peek<B: CpuBus>(&self, bus: &B, addr: u16) -> u8 {
+    bus.peek(addr)
+  }
+
+  pub fn poke<B: CpuBus>(&mut self, bus: &mut B, addr: u16, value: u8) -> Result<(), String> {
+    if !bus.poke(addr, value) {
+      return Err(format!("${:04X} is not writable", addr));
+    }
+    // an explicit edit replaces a frozen value
+    if let Some(frozen) = self.frozen.get_mut(&addr) {
+      *frozen = value;
+    }
+    Ok(())
+  }
+
+  pub fn freeze<B: CpuBus>(&mut self, bus: &mut B, addr: u16, value: u8) -> Result<(), String> {
+    self.poke(bus, addr, value)?;
+    self.frozen.insert(addr, value);
+    Ok(())
+  }
+
+  pub fn unfreeze(&mut self, addr: u16) -> bool {
+    self.frozen.remove(&addr).is_some()
+  }
+
+  pub fn frozen(&self) -> impl Iterator<Item=(&u16, &u8)> {
+    self.frozen.iter()
+  }
+
+  // called by the cpu after each instruction
+  pub fn apply<B: CpuBus>(&self, bus: &mut B) {
+    for (&addr, &value) in &self.frozen {
+      bus.poke(addr, value);
+    }
+  }
+
+  // debugger commands: peek <addr> [len], poke <addr> <value>..., freeze <addr> <value>, unfreeze <addr>, frozen
+  pub fn execute<B: CpuBus>(&mut self, bus: &mut B, command: &str) -> Result<String, String> {
+    let parts: Vec<&str> = command.split_whitespace().collect();
+    match parts.as_slice() {
+      ["peek", addr] => self.execute(bus, &format!("peek {} 1", addr)),
+      ["peek", addr, len] => {
+        let addr = parse_hex(addr)?;
+        let len = parse_hex(len)?;
+        let values: Vec<String> = (0..len)
+          .map(|i| format!("{:02X}", self.peek(bus, addr.wrapping_add(i))))
+          .collect();
+        Ok(format!("{:04X}: {}", addr, values.join(" ")))
+      }
+      ["poke", addr, values @ ..] if !values.is_empty() => {
+        let addr = parse_hex(addr)?;
+        for (i, value) in values.iter().enumerate() {
+          self.poke(bus, addr.wrapping_add(i as u16), parse_byte(value)?)?;
+        }
+        Ok(format!("wrote {} byte(s) at {:04X}", values.len(), addr))
+      }
+      ["freeze", addr, value] => {
+        let addr = parse_hex(addr)?;
+        let value = parse_byte(value)?;
+        self.freeze(bus, addr, value)?;
+        Ok(format!("froze {:04X} = {:02X}", addr, value))
+      }
+      ["unfreeze", addr] => {
+        let addr = parse_hex(addr)?;
+        if self.unfreeze(addr) {
+          Ok(format!("unfroze {:04X}", addr))
+        } else {
+          Err(format!("{:04X} is not frozen", addr))
+        }
+      }
+      ["frozen"] => {
+        let entries: Vec<String> = self.frozen.iter()
+          .map(|(addr, value)| format!("{:04X} = {:02X}", addr, value))
+          .collect();
+        Ok(entries.join("\n"))
+      }
+      _ => Err(format!("unknown memory command: '{}'", command)),
+    }
+  }
+}
+
+pub fn parse_hex(value: &str) -> Result<u16, String> {
+  let digits = value.trim_start_matches('$');
+  u16::from_str_radix(digits, 16).map_err(|_| format!("invalid hex value: '{}'", value))
+}
+
+pub fn parse_byte(value: &str) -> Result<u8, String> {
+  let parsed = parse_hex(value)?;
+  if parsed > 0xFF {
+    return Err(format!("value doesn't fit into a byte: '{}'", value));
+  }
+  Ok(parsed as u8)
+}
@@ -0,0 +1,50 @@
+use crate::bus::Bus;
+use crate::cartridge_tests::create_test_rom;
+use crate::cpu::{MyCPU, MyMem};
+use crate::memory_editor::MemoryEditor;
+
+#[test]
+fn test_poke_and_peek() {
+  let mut bus = Bus::new(create_test_rom()).unwrap();
+  let mut editor = MemoryEditor::new();
+
+  editor.poke(&mut bus, 0x0010, 0x42).unwrap();
+
+  assert_eq!(0x42, editor.peek(&bus, 0x0010));
+  assert_eq!(0x42, editor.peek(&bus, 0x0810));
+}
+
+#[test]
+fn test_poke_rom_is_rejected() {
+  let mut bus = Bus::new(create_test_rom()).unwrap();
+  let mut editor = MemoryEditor::new();
+
+  assert_eq!(Err("$8000 is not writable".to_string()), editor.poke(&mut bus, 0x8000, 0x42));
+}
+
+#[test]
+fn test_commands() {
+  let mut bus = Bus::new(create_test_rom()).unwrap();
+  let mut editor = MemoryEditor::new();
+
+  assert_eq!(Ok("wrote 3 byte(s) at 0200".to_string()), editor.execute(&mut bus, "poke 0200 01 02 $03"));
+  assert_eq!(Ok("0200: 01 02 03 00".to_string()), editor.execute(&mut bus, "peek $0200 4"));
+  assert_eq!(Ok("froze 0010 = 05".to_string()), editor.execute(&mut bus, "freeze 10 5"));
+  assert_eq!(Ok("0010 = 05".to_string()), editor.execute(&mut bus, "frozen"));
+  assert_eq!(Ok("unfroze 0010".to_string()), editor.execute(&mut bus, "unfreeze 10"));
+  assert!(editor.execute(&mut bus, "unfreeze 10").is_err());
+  assert!(editor.execute(&mut bus, "poke 10 100").is_err());
+  assert!(editor.execute(&mut bus, "jump 10").is_err());
+}
+
+#[test]
+fn test_frozen_address_survives_cpu_writes() {
+  let mut cpu = MyCPU::new(Bus::new(create_test_rom()).unwrap());
+  cpu.program_counter = 0x0600;
+  cpu.memory_editor.freeze(&mut cpu.bus, 0x10, 0x05).unwrap();
+
+  // INC $10, INC $10
+  cpu.load_and_run(vec![0xE6, 0x10, 0xE6, 0x10]);
+
+  assert_eq!(0x05, cpu.mem_read(0x10));
+}
@@ -0,0 +1,240 @@
+use crate::cartridge::{Mirroring, Rom};
+use crate::mapper::{load_mirroring, save_mirroring, ChrMemory, Mapper};
+#[cfg(feature = "serde")]
+use crate::mapper::{MapperState, OTHER_MAPPER};
+use crate::savestate::{StateReader, StateWriter, Stateful};
+
+const PRG_BANK_SIZE: usize = 0x2000;
+const CHR_BANK_SIZE: usize = 0x0400;
+
+// mapper 4: 8KB prg banks, 1KB/2KB chr banks and a scanline counter,
+// clocked by rising edges of the ppu address line A12
+// https://wiki.nesdev.org/w/index.php/MMC3
+pub struct Mmc3 {
+  prg_rom: Vec<u8>,
+  chr: ChrMemory,
+  mirroring: Mirroring,
+  four_screen: bool,
+
+  bank_select: u8,
+  // R0-R7
+  registers: [u8; 8],
+
+  irq_latch: u8,
+  irq_counter: u8,
+  irq_reload: bool,
+  irq_enabled: bool,
+  irq_pending: bool,
+  a12: bool,
+}
+
+impl Mmc3 {
+  pub fn new(mut rom: Rom) -> Self {
+    Mmc3 {
+      chr: ChrMemory::new(&mut rom),
+      prg_rom: rom.prg_rom,
+      mirroring: rom.screen_mirroring,
+      four_screen: rom.screen_mirroring == Mirroring::FOUR_SCREEN,
+      bank_select: 0,
+      registers: [0; 8],
+      irq_latch: 0,
+      irq_counter: 0,
+      irq_reload: false,
+      irq_enabled: false,
+      irq_pending: false,
+      a12: false,
+    }
+  }
+
+  fn prg_banks(&self) -> usize {
+    (self.prg_rom.len() / PRG_BANK_SIZE).max(1)
+  }
+
+  // $8000: R6 or second last, $A000: R7, $C000: second last or R6, $E000: last
+  fn prg_bank(&self, slot: usize) -> usize {
+    let second_last = self.prg_banks().saturating_sub(2);
+    let prg_mode = self.bank_select & 0b0100_0000 != 0;
+    let bank = match (slot, prg_mode) {
+      (0, false) | (2, true) => self.registers[6] as usize,
+      (0, true) | (2, false) => second_last,
+      (1, _) => self.registers[7] as usize,
+      _ => self.prg_banks() - 1,
+    };
+    bank % self.prg_banks()
+  }
+
+  // R0/R1 are 2KB banks (the low bit is ignored), R2-R5 1KB banks,
+  // chr inversion swaps $0000-$0FFF with $1000-$1FFF
+  fn chr_bank(&self, addr: u16) -> usize {
+    let mut slot = (addr as usize / CHR_BANK_SIZE) & 0b111;
+    if self.bank_select & 0b1000_0000 != 0 {
+      slot ^= 0b100;
+    }
+    let bank = match slot {
+      0 => self.registers[0] & 0xFE,
+      1 => self.registers[0] | 0x01,
+      2 => self.registers[1] & 0xFE,
+      3 => self.registers[1] | 0x01,
+      _ => self.registers[slot - 2],
+    };
+    bank as usize % (self.chr.len() / CHR_BANK_SIZE).max(1)
+  }
+
+  fn chr_offset(&self, addr: u16) -> usize {
+    self.chr_bank(addr) * CHR_BANK_SIZE + (addr as usize % CHR_BANK_SIZE)
+  }
+
+  fn clock_irq_counter(&mut self) {
+    if self.irq_counter == 0 || self.irq_reload {
+      self.irq_counter = self.irq_latch;
+      self.irq_reload = false;
+    } else {
+      self.irq_counter -= 1;
+    }
+    if self.irq_counter == 0 && self.irq_enabled {
+      self.irq_pending = true;
+    }
+  }
+}
+
+// what the Stateful impl saves, for serde
+#[cfg(feature = "serde")]
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct Mmc3State {
+  pub chr_ram: Vec<u8>,
+  pub mirroring: Mirroring,
+  pub bank_select: u8,
+  pub registers: [u8; 8],
+  pub irq_latch: u8,
+  pub irq_counter: u8,
+  pub irq_reload: bool,
+  pub irq_enabled: bool,
+  pub irq_pending: bool,
+  pub a12: bool,
+}
+
+impl Mapper for Mmc3 {
+  fn prg_read(&self, addr: u16) -> u8 {
+    self.prg_rom_offset(addr).and_then(|offset| self.prg_rom.get(offset)).copied().unwrap_or(0)
+  }
+
+  // registers are selected by address range and the lowest address bit
+  fn prg_write(&mut self, addr: u16, data: u8) {
+    let even = addr & 1 == 0;
+    match (addr, even) {
+      (0x8000..=0x9FFF, true) => self.bank_select = data,
+      (0x8000..=0x9FFF, false) => self.registers[(self.bank_select & 0b111) as usize] = data,
+      (0xA000..=0xBFFF, true) if !self.four_screen => {
+        self.mirroring = if data & 1 == 0 { Mirroring::VERTICAL } else { Mirroring::HORIZONTAL };
+      }
+      (0xC000..=0xDFFF, true) => self.irq_latch = data,
+      (0xC000..=0xDFFF, false) => {
+        self.irq_counter = 0;
+        self.irq_reload = true;
+      }
+      (0xE000..=0xFFFF, true) => {
+        self.irq_enabled = false;
+        self.irq_pending = false;
+      }
+      (0xE000..=0xFFFF, false) => self.irq_enabled = true,
+      // prg ram protect, mirroring of four screen boards
+      _ => {}
+    }
+  }
+
+  fn chr_read(&self, addr: u16) -> u8 {
+    self.chr.read(self.chr_offset(addr))
+  }
+
+  fn chr_write(&mut self, addr: u16, data: u8) {
+    self.chr.write(self.chr_offset(addr), data);
+  }
+
+  fn mirroring(&self) -> Mirroring {
+    self.mirroring
+  }
+
+  fn irq(&self) -> bool {
+    self.irq_pending
+  }
+
+  fn ppu_bus_address(&mut self, addr: u16) {
+    let a12 = addr & 0x1000 != 0;
+    if a12 && !self.a12 {
+      self.clock_irq_counter();
+    }
+    self.a12 = a12;
+  }
+
+  fn prg_rom_offset(&self, addr: u16) -> Option<usize> {
+    if addr < 0x8000 {
+      return None;
+    }
+    let slot = (addr as usize - 0x8000) / PRG_BANK_SIZE;
+    Some(self.prg_bank(slot) * PRG_BANK_SIZE + (addr as usize % PRG_BANK_SIZE))
+  }
+
+  #[cfg(feature = "serde")]
+  fn state(&self) -> MapperState {
+    MapperState::Mmc3(Mmc3State {
+      chr_ram: self.chr.ram(),
+      mirroring: self.mirroring,
+      bank_select: self.bank_select,
+      registers: self.registers,
+      irq_latch: self.irq_latch,
+      irq_counter: self.irq_counter,
+      irq_reload: self.irq_reload,
+      irq_enabled: self.irq_enabled,
+      irq_pending: self.irq_pending,
+      a12: self.a12,
+    })
+  }
+
+  #[cfg(feature = "serde")]
+  fn set_state(&mut self, state: MapperState) -> Result<(), String> {
+    let state = match state {
+      MapperState::Mmc3(state) => state,
+      _ => return Err(OTHER_MAPPER.to_string()),
+    };
+    self.chr.set_ram(&state.chr_ram)?;
+    self.mirroring = state.mirroring;
+    self.bank_select = state.bank_select;
+    self.registers = state.registers;
+    self.irq_latch = state.irq_latch;
+    self.irq_counter = state.irq_counter;
+    self.irq_reload = state.irq_reload;
+    self.irq_enabled = state.irq_enabled;
+    self.irq_pending = state.irq_pending;
+    self.a12 = state.a12;
+    Ok(())
+  }
+}
+
+impl Stateful for Mmc3 {
+  fn save_state(&self, w: &mut StateWriter) {
+    self.chr.save_state(w);
+    save_mirroring(w, self.mirroring);
+    w.write_u8(self.bank_select);
+    w.write_bytes(&self.registers);
+    w.write_u8(self.irq_latch);
+    w.write_u8(self.irq_counter);
+    w.write_bool(self.irq_reload);
+    w.write_bool(self.irq_enabled);
+    w.write_bool(self.irq_pending);
+    w.write_bool(self.a12);
+  }
+
+  fn load_state(&mut self, r: &mut StateReader) -> Result<(), String> {
+    self.chr.load_state(r)?;
+    self.mirroring = load_mirroring(r)?;
+    self.bank_select = r.read_u8()?;
+    r.read_into(&mut self.registers)?;
+    self.irq_latch = r.read_u8()?;
+    self.irq_counter = r.read_u8()?;
+    self.irq_reload = r.read_bool()?;
+    self.irq_enabled = r.read_bool()?;
+    self.irq_pending = r.read_bool()?;
+    self.a12 = r.read_bool()?;
+    Ok(())
+  }
+}
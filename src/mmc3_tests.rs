@@ -0,0 +1,146 @@
+use crate::bus::Bus;
+use crate::cartridge::{Mirroring, Rom};
+use crate::cpu::MyMem;
+use crate::event_log::FrameEventKind;
+use crate::mapper::Mapper;
+use crate::mmc3::Mmc3;
+use crate::ppu::MaskRegister;
+use crate::savestate::{StateReader, StateWriter, Stateful};
+
+// every byte holds the number of its bank: 8 prg banks of 8KB, 16 chr banks of 1KB
+fn init_mmc3_rom() -> Rom {
+  let mut prg_rom = Vec::new();
+  for bank in 0..8 {
+    prg_rom.extend(vec![bank as u8; 0x2000]);
+  }
+  let mut chr_rom = Vec::new();
+  for bank in 0..16 {
+    chr_rom.extend(vec![bank as u8; 0x0400]);
+  }
+  Rom { prg_rom, chr_rom, chr_ram: false, mapper: 4, screen_mirroring: Mirroring::VERTICAL, battery: false }
+}
+
+fn select(mmc3: &mut Mmc3, register: u8, bank: u8) {
+  mmc3.prg_write(0x8000, register);
+  mmc3.prg_write(0x8001, bank);
+}
+
+#[test]
+fn test_prg_banking_modes() {
+  let mut mmc3 = Mmc3::new(init_mmc3_rom());
+  select(&mut mmc3, 6, 2);
+  select(&mut mmc3, 7, 3);
+
+  assert_eq!(2, mmc3.prg_read(0x8000));
+  assert_eq!(3, mmc3.prg_read(0xA000));
+  assert_eq!(6, mmc3.prg_read(0xC000));
+  assert_eq!(7, mmc3.prg_read(0xE000));
+
+  // prg mode 1 swaps $8000 and $C000
+  mmc3.prg_write(0x8000, 0b0100_0000);
+  assert_eq!(6, mmc3.prg_read(0x8000));
+  assert_eq!(2, mmc3.prg_read(0xC000));
+  assert_eq!(Some(7 * 0x2000 + 0x1FFF), mmc3.prg_rom_offset(0xFFFF));
+}
+
+#[test]
+fn test_chr_banking_and_inversion() {
+  let mut mmc3 = Mmc3::new(init_mmc3_rom());
+  select(&mut mmc3, 0, 5); // 2KB bank, low bit ignored
+  select(&mut mmc3, 2, 9);
+  select(&mut mmc3, 5, 12);
+
+  assert_eq!(4, mmc3.chr_read(0x0000));
+  assert_eq!(5, mmc3.chr_read(0x0400));
+  assert_eq!(9, mmc3.chr_read(0x1000));
+  assert_eq!(12, mmc3.chr_read(0x1FFF));
+
+  mmc3.prg_write(0x8000, 0b1000_0000);
+  assert_eq!(9, mmc3.chr_read(0x0000));
+  assert_eq!(4, mmc3.chr_read(0x1000));
+}
+
+#[test]
+fn test_mirroring_register() {
+  let mut mmc3 = Mmc3::new(init_mmc3_rom());
+  mmc3.prg_write(0xA000, 1);
+  assert_eq!(Mirroring::HORIZONTAL, mmc3.mirroring());
+  mmc3.prg_write(0xA000, 0);
+  assert_eq!(Mirroring::VERTICAL, mmc3.mirroring());
+}
+
+#[test]
+fn test_irq_counter_on_a12_rising_edges() {
+  let mut mmc3 = Mmc3::new(init_mmc3_rom());
+  mmc3.prg_write(0xC000, 2); // latch
+  mmc3.prg_write(0xC001, 0); // reload
+  mmc3.prg_write(0xE001, 0); // enable
+
+  let scanline = |mmc3: &mut Mmc3| {
+    mmc3.ppu_bus_address(0x0000);
+    mmc3.ppu_bus_address(0x1000);
+    mmc3.ppu_bus_address(0x1010); // no edge while A12 stays high
+  };
+  scanline(&mut mmc3); // reload to 2
+  scanline(&mut mmc3); // 1
+  assert!(!mmc3.irq());
+  scanline(&mut mmc3); // 0
+  assert!(mmc3.irq());
+
+  // disabling acknowledges
+  mmc3.prg_write(0xE000, 0);
+  assert!(!mmc3.irq());
+}
+
+#[test]
+fn test_scanline_irq_reaches_the_bus() {
+  let mut bus = Bus::new(init_mmc3_rom()).unwrap();
+  bus.mem_write(0xC000, 10);
+  bus.mem_write(0xC001, 0);
+  bus.mem_write(0xE001, 0);
+  bus.mem_write(0x2000, 0b0000_1000); // sprites at $1000
+  bus.ppu.mask = MaskRegister::SHOW_BACKGROUND;
+
+  // 11 scanlines: reload, then count down to 0, clocked at dot 260 of each
+  for _ in 0..((10 * 341 + 259) / 3) {
+    bus.tick(1);
+  }
+  assert!(!bus.irq_pending());
+  bus.tick(1);
+  assert!(bus.irq_pending());
+}
+
+#[test]
+fn test_scanline_irq_shows_up_in_the_event_log() {
+  let mut bus = Bus::new(init_mmc3_rom()).unwrap();
+  bus.enable_event_log(1);
+  bus.mem_write(0xC000, 10);
+  bus.mem_write(0xC001, 0);
+  bus.mem_write(0xE001, 0);
+  bus.mem_write(0x2000, 0b0000_1000);
+  bus.ppu.mask = MaskRegister::SHOW_BACKGROUND;
+
+  for _ in 0..(11 * 341 / 3) {
+    bus.tick(1);
+  }
+
+  let events = bus.event_log.as_ref().unwrap().events(0);
+  assert_eq!(vec![(10, 260, FrameEventKind::MapperIrq)],
+             events.iter().map(|e| (e.scanline, e.dot, e.kind)).collect::<Vec<_>>());
+}
+
+#[test]
+fn test_bank_registers_are_part_of_the_state() {
+  let mut mmc3 = Mmc3::new(init_mmc3_rom());
+  select(&mut mmc3, 6, 3);
+  mmc3.prg_write(0xA000, 1);
+  let mut w = StateWriter::new();
+  mmc3.save_state(&mut w);
+  let bytes = w.into_bytes();
+
+  let mut restored = Mmc3::new(init_mmc3_rom());
+  restored.load_state(&mut StateReader::new(&bytes).unwrap()).unwrap();
+
+  assert_eq!(3, restored.prg_read(0x8000));
+  assert_eq!(Mirroring::HORIZONTAL, restored.mirroring());
+}
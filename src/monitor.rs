@@ -0,0 +1,259 @@
+use std::fs;
+use std::io::{self, BufRead, Write};
+use crate::asm::assemble;
+use crate::breakpoints::Breakpoint;
+use crate::cpu::{CpuBus, CpuFlags, CpuState, MyCPU};
+use crate::disasm::disassemble_with_labels;
+use crate::memory_editor::{parse_byte, parse_hex};
+use crate::savestate::Stateful;
+use crate::symbols::Symbols;
+use crate::time_travel::TimeTravel;
+
+pub const HELP: &str = "m [addr] [len]         examine memory
+> addr bytes...         modify memory
+d [addr] [count]       disassemble
+a addr instruction     assemble one instruction
+r [reg value]          show or set registers (a, x, y, p, sp, pc)
+s [count]              step instructions
+g [addr]               run until addr, a breakpoint or the instruction limit
+sb [count]             step back
+gb [addr]              run back until addr or a breakpoint
+b [addr] / bc addr     list or set / clear breakpoints
+l file [addr]          load a binary, .s/.asm files are assembled
+peek, poke, freeze, unfreeze, frozen  memory editor commands
+q                      quit
+addresses are hex or symbol names";
+
+// g stops after this many instructions, e.g. in a wait-for-vblank loop without breakpoints
+pub const RUN_LIMIT: usize = 10_000_000;
+
+const MEMORY_ROW: u16 = 16;
+
+// classic 6502 machine monitor on top of the debugger api, m and d continue where they stopped.
+// s and g record a history, sb and gb move back in it
+#[derive(Default)]
+pub struct Monitor {
+  next_memory: u16,
+  next_disasm: Option<u16>,
+  history: TimeTravel,
+}
+
+impl Monitor {
+  pub fn new() -> Self {
+    Monitor::default()
+  }
+
+  pub fn execute<B: CpuBus + Stateful>(&mut self, cpu: &mut MyCPU<B>, line: &str) -> Result<String, String> {
+    let parts: Vec<&str> = line.split_whitespace().collect();
+    let address = |text: &str| parse_address(&cpu.symbols, text);
+    match parts.as_slice() {
+      [] => Ok(String::new()),
+      ["?"] | ["help"] => Ok(HELP.to_string()),
+      ["m", rest @ ..] if rest.len() <= 2 => {
+        let start = rest.first().map(|a| address(a)).transpose()?.unwrap_or(self.next_memory);
+        let len = rest.get(1).map(|l| parse_hex(l)).transpose()?.unwrap_or(0x40);
+        self.next_memory = start.wrapping_add(len);
+        Ok(examine(cpu, start, len))
+      }
+      [">", addr, values @ ..] if !values.is_empty() => {
+        let start = address(addr)?;
+        for (i, value) in values.iter().enumerate() {
+          cpu.memory_editor.poke(&mut cpu.bus, start.wrapping_add(i as u16), parse_byte(value)?)?;
+        }
+        Ok(examine(cpu, start, values.len() as u16))
+      }
+      ["d", rest @ ..] if rest.len() <= 2 => {
+        let start = match rest.first() {
+          Some(a) => address(a)?,
+          None => self.next_disasm.unwrap_or(cpu.program_counter),
+        };
+        let count = rest.get(1).map(|c| parse_hex(c)).transpose()?.unwrap_or(0x10);
+        let (text, next) = disassemble_memory(cpu, start, count as usize);
+        self.next_disasm = Some(next);
+        Ok(text)
+      }
+      ["a", addr, instruction @ ..] if !instruction.is_empty() => {
+        let start = address(addr)?;
+        let bytes = assemble(&instruction.join(" "), start).map_err(|e| e.message)?;
+        for (i, byte) in bytes.iter().enumerate() {
+          cpu.memory_editor.poke(&mut cpu.bus, start.wrapping_add(i as u16), *byte)?;
+        }
+        Ok(disassemble_memory(cpu, start, 1).0)
+      }
+      ["r"] => Ok(registers(cpu)),
+      ["r", register, value] => {
+        let value = parse_hex(value)?;
+        let byte = || u8::try_from(value).map_err(|_| format!("value doesn't fit into a byte: '{:X}'", value));
+        match register.to_ascii_lowercase().as_str() {
+          "a" => cpu.register_a = byte()?,
+          "x" => cpu.register_x = byte()?,
+          "y" => cpu.register_y = byte()?,
+          "p" => cpu.status = CpuFlags::from_bits_truncate(byte()?),
+          "sp" => cpu.stack_pointer = byte()?,
+          "pc" => cpu.program_counter = value,
+          other => return Err(format!("unknown register: '{}'", other)),
+        }
+        Ok(registers(cpu))
+      }
+      ["s", rest @ ..] if rest.len() <= 1 => {
+        let count = rest.first().map(|c| parse_hex(c)).transpose()?.unwrap_or(1);
+        let mut lines = Vec::new();
+        for _ in 0..count {
+          let (line, _) = disassemble_memory(cpu, cpu.program_counter, 1);
+          if !self.history.step(cpu) {
+            break;
+          }
+          lines.push(line);
+        }
+        self.next_disasm = None;
+        lines.push(registers(cpu));
+        Ok(lines.join("\n"))
+      }
+      ["g", rest @ ..] if rest.len() <= 1 => {
+        let until = rest.first().map(|a| address(a)).transpose()?;
+        self.next_disasm = None;
+        Ok(format!("{}\n{}", run(cpu, &mut self.history, until), registers(cpu)))
+      }
+      ["sb", rest @ ..] if rest.len() <= 1 => {
+        let count = rest.first().map(|c| parse_hex(c)).transpose()?.unwrap_or(1);
+        self.next_disasm = None;
+        let stepped = (0..count).take_while(|_| self.history.step_back(cpu)).count();
+        if stepped == 0 {
+          return Err("no earlier instruction recorded".to_string());
+        }
+        Ok(format!("{}\n{}", disassemble_memory(cpu, cpu.program_counter, 1).0, registers(cpu)))
+      }
+      ["gb", rest @ ..] if rest.len() <= 1 => {
+        let until = rest.first().map(|a| address(a)).transpose()?;
+        self.next_disasm = None;
+        let reached = self.history.run_back_until(cpu, |cpu| {
+          Some(cpu.program_counter) == until || cpu.breakpoints.list().contains(&Breakpoint::Address(cpu.program_counter))
+        });
+        let result = if reached {
+          format!("reached {:04X}", cpu.program_counter)
+        } else {
+          format!("oldest recorded instruction at {:04X}", cpu.program_counter)
+        };
+        Ok(format!("{}\n{}", result, registers(cpu)))
+      }
+      ["b"] => {
+        let list: Vec<String> = cpu.breakpoints.list().iter().map(|b| match b {
+          Breakpoint::Address(address) => format!("{:04X}{}", address, label_suffix(cpu, *address)),
+          other => format!("{:?}", other),
+        }).collect();
+        Ok(list.join("\n"))
+      }
+      ["b", addr] => {
+        let address = address(addr)?;
+        cpu.breakpoints.add(Breakpoint::Address(address));
+        Ok(format!("breakpoint at {:04X}", address))
+      }
+      ["bc", addr] => {
+        let address = address(addr)?;
+        if cpu.breakpoints.remove(&Breakpoint::Address(address)) {
+          Ok(format!("cleared {:04X}", address))
+        } else {
+          Err(format!("no breakpoint at {:04X}", address))
+        }
+      }
+      ["l", file, rest @ ..] if rest.len() <= 1 => {
+        let start = match rest.first() {
+          Some(a) => address(a)?,
+          None => cpu.bus.load_address(),
+        };
+        let bytes = load_program(file, start)?;
+        for (i, byte) in bytes.iter().enumerate() {
+          cpu.memory_editor.poke(&mut cpu.bus, start.wrapping_add(i as u16), *byte)?;
+        }
+        Ok(format!("loaded {} byte(s) at {:04X}", bytes.len(), start))
+      }
+      _ => cpu.memory_editor.execute(&mut cpu.bus, line)
+        .map_err(|_| format!("unknown command: '{}', ? shows the commands", line.trim())),
+    }
+  }
+}
+
+// reads commands until q or the end of the input
+pub fn repl<B: CpuBus + Stateful>(cpu: &mut MyCPU<B>, input: impl BufRead, mut output: impl Write) -> io::Result<()> {
+  let mut monitor = Monitor::new();
+  writeln!(output, "{}", registers(cpu))?;
+  write!(output, ". ")?;
+  output.flush()?;
+  for line in input.lines() {
+    let line = line?;
+    if matches!(line.trim(), "q" | "x" | "quit") {
+      break;
+    }
+    match monitor.execute(cpu, &line) {
+      Ok(text) if text.is_empty() => {}
+      Ok(text) => writeln!(output, "{}", text)?,
+      Err(e) => writeln!(output, "error: {}", e)?,
+    }
+    write!(output, ". ")?;
+    output.flush()?;
+  }
+  Ok(())
+}
+
+pub fn registers<B: CpuBus>(cpu: &MyCPU<B>) -> String {
+  format!("PC:{:04X} A:{:02X} X:{:02X} Y:{:02X} P:{:02X} SP:{:02X} CYC:{}{}",
+          cpu.program_counter, cpu.register_a, cpu.register_x, cpu.register_y,
+          cpu.status.bits(), cpu.stack_pointer, cpu.cycles, label_suffix(cpu, cpu.program_counter))
+}
+
+fn label_suffix<B: CpuBus>(cpu: &MyCPU<B>, address: u16) -> String {
+  cpu.symbols.label(address).map_or(String::new(), |label| format!(" ({})", label))
+}
+
+fn parse_address(symbols: &Symbols, text: &str) -> Result<u16, String> {
+  symbols.address(text).map_or_else(|| parse_hex(text), Ok)
+}
+
+fn examine<B: CpuBus>(cpu: &MyCPU<B>, start: u16, len: u16) -> String {
+  let mut rows = Vec::new();
+  let mut offset = 0;
+  while offset < len {
+    let row = start.wrapping_add(offset);
+    let values: Vec<String> = (offset..len.min(offset.saturating_add(MEMORY_ROW)))
+      .map(|i| format!("{:02X}", cpu.bus.peek(start.wrapping_add(i))))
+      .collect();
+    rows.push(format!("{:04X}: {}", row, values.join(" ")));
+    offset = offset.saturating_add(MEMORY_ROW);
+  }
+  rows.join("\n")
+}
+
+// `count` instructions from memory, returns them and the address after the last one
+fn disassemble_memory<B: CpuBus>(cpu: &MyCPU<B>, start: u16, count: usize) -> (String, u16) {
+  // at most 3 bytes per instruction
+  let code: Vec<u8> = (0..count * 3).map(|i| cpu.bus.peek(start.wrapping_add(i as u16))).collect();
+  let lines = disassemble_with_labels(&code, start, cpu.symbols.lookup());
+  let lines = &lines[..count.min(lines.len())];
+  let next = lines.last().map_or(start, |l| l.address.wrapping_add(l.bytes.len() as u16));
+  (lines.iter().map(|l| l.format()).collect::<Vec<String>>().join("\n"), next)
+}
+
+fn run<B: CpuBus + Stateful>(cpu: &mut MyCPU<B>, history: &mut TimeTravel, until: Option<u16>) -> String {
+  for _ in 0..RUN_LIMIT {
+    if Some(cpu.program_counter) == until {
+      return format!("reached {:04X}", cpu.program_counter);
+    }
+    if !history.step(cpu) {
+      return match cpu.state {
+        CpuState::Jammed { code, program_counter } => format!("jammed by {:02X} at {:04X}", code, program_counter),
+        CpuState::Running => format!("stopped at {:04X}", cpu.program_counter),
+      };
+    }
+  }
+  format!("still running after {} instructions", RUN_LIMIT)
+}
+
+// .s and .asm sources are assembled for `start`, everything else is loaded as is
+fn load_program(file: &str, start: u16) -> Result<Vec<u8>, String> {
+  if file.ends_with(".s") || file.ends_with(".asm") {
+    let source = fs::read_to_string(file).map_err(|e| format!("{}: {}", file, e))?;
+    assemble(&source, start).map_err(|e| format!("{}: {}", file, e))
+  } else {
+    fs::read(file).map_err(|e| format!("{}: {}", file, e))
+  }
+}
@@ -0,0 +1,123 @@
+use crate::bus::Bus;
+use crate::cartridge_tests::create_test_rom;
+use crate::cpu::{MyCPU, StopCondition};
+use crate::monitor::{Monitor, repl};
+
+fn init_cpu() -> MyCPU {
+  let mut cpu = MyCPU::new(Bus::new(create_test_rom()).unwrap());
+  cpu.stop_condition = StopCondition::Never;
+  cpu.program_counter = 0x0600;
+  cpu
+}
+
+#[test]
+fn test_examine_and_modify_memory() {
+  let mut cpu = init_cpu();
+  let mut monitor = Monitor::new();
+
+  assert_eq!(Ok("0010: 01 02 03".to_string()), monitor.execute(&mut cpu, "> 10 01 02 03"));
+  assert_eq!(Ok("0010: 01 02 03 00".to_string()), monitor.execute(&mut cpu, "m 10 4"));
+  // continues after the last row
+  assert!(monitor.execute(&mut cpu, "m").unwrap().starts_with("0014: 00"));
+
+  let rows = monitor.execute(&mut cpu, "m 0600 20").unwrap();
+  assert_eq!(2, rows.lines().count());
+  assert!(rows.lines().nth(1).unwrap().starts_with("0610: "));
+  assert!(monitor.execute(&mut cpu, "> 8000 00").is_err());
+}
+
+#[test]
+fn test_assemble_disassemble_and_step() {
+  let mut cpu = init_cpu();
+  let mut monitor = Monitor::new();
+
+  assert_eq!(Ok("0600  A2 05     LDX #$05".to_string()), monitor.execute(&mut cpu, "a 0600 LDX #$05"));
+  monitor.execute(&mut cpu, "a 0602 DEX").unwrap();
+  monitor.execute(&mut cpu, "a 0603 BNE $0602").unwrap();
+
+  let listing = monitor.execute(&mut cpu, "d 0600 3").unwrap();
+  assert_eq!(vec!["0600  A2 05     LDX #$05", "0602  CA        DEX", "0603  D0 FD     BNE $0602"],
+             listing.lines().collect::<Vec<&str>>());
+
+  let stepped = monitor.execute(&mut cpu, "s 2").unwrap();
+  assert!(stepped.ends_with("PC:0603 A:00 X:04 Y:00 P:24 SP:FF CYC:4"), "{}", stepped);
+}
+
+#[test]
+fn test_registers() {
+  let mut cpu = init_cpu();
+  let mut monitor = Monitor::new();
+
+  monitor.execute(&mut cpu, "r a 42").unwrap();
+  let registers = monitor.execute(&mut cpu, "r pc c000").unwrap();
+
+  assert!(registers.starts_with("PC:C000 A:42"), "{}", registers);
+  assert!(monitor.execute(&mut cpu, "r x 100").is_err());
+  assert!(monitor.execute(&mut cpu, "r q 1").is_err());
+}
+
+#[test]
+fn test_run_to_address_and_breakpoints() {
+  let mut cpu = init_cpu();
+  let mut monitor = Monitor::new();
+  cpu.symbols.add(0x0604, "done");
+  // INX, INX, INX, INX, done: JMP done
+  for (i, line) in ["INX", "INX", "INX", "INX", "JMP $0604"].iter().enumerate() {
+    monitor.execute(&mut cpu, &format!("a {:04X} {}", 0x0600 + i, line)).unwrap();
+  }
+
+  assert!(monitor.execute(&mut cpu, "g 0602").unwrap().starts_with("reached 0602\nPC:0602 A:00 X:02"));
+
+  monitor.execute(&mut cpu, "b done").unwrap();
+  assert_eq!(Ok("0604 (done)".to_string()), monitor.execute(&mut cpu, "b"));
+  assert!(monitor.execute(&mut cpu, "g").unwrap().starts_with("stopped at 0604\nPC:0604 A:00 X:04"));
+
+  assert_eq!(Ok("cleared 0604".to_string()), monitor.execute(&mut cpu, "bc done"));
+  assert!(monitor.execute(&mut cpu, "bc done").is_err());
+}
+
+#[test]
+fn test_step_and_run_backwards() {
+  let mut cpu = init_cpu();
+  let mut monitor = Monitor::new();
+  for (i, line) in ["INX", "INX", "INX", "INX", "JMP $0604"].iter().enumerate() {
+    monitor.execute(&mut cpu, &format!("a {:04X} {}", 0x0600 + i, line)).unwrap();
+  }
+  monitor.execute(&mut cpu, "s 4").unwrap();
+
+  assert!(monitor.execute(&mut cpu, "sb").unwrap().starts_with("0603  E8        INX\nPC:0603 A:00 X:03"));
+
+  monitor.execute(&mut cpu, "b 0601").unwrap();
+  let reached = monitor.execute(&mut cpu, "gb").unwrap();
+  assert!(reached.starts_with("reached 0601\nPC:0601 A:00 X:01"), "{}", reached);
+  // forward again, over the breakpoint
+  let stepped = monitor.execute(&mut cpu, "s").unwrap();
+  assert!(stepped.ends_with("PC:0602 A:00 X:02 Y:00 P:24 SP:FF CYC:4"), "{}", stepped);
+
+  assert!(monitor.execute(&mut cpu, "sb 10").unwrap().contains("PC:0600 A:00 X:00"));
+  assert!(monitor.execute(&mut cpu, "sb").is_err());
+  assert!(monitor.execute(&mut cpu, "gb").unwrap().starts_with("oldest recorded instruction at 0600"));
+}
+
+#[test]
+fn test_memory_editor_commands_and_unknown_commands() {
+  let mut cpu = init_cpu();
+  let mut monitor = Monitor::new();
+
+  assert_eq!(Ok("froze 0010 = 07".to_string()), monitor.execute(&mut cpu, "freeze 10 07"));
+  assert_eq!(Err("unknown command: 'jump', ? shows the commands".to_string()), monitor.execute(&mut cpu, "jump"));
+}
+
+#[test]
+fn test_repl_runs_until_quit() {
+  let mut cpu = init_cpu();
+  let mut output = Vec::new();
+
+  repl(&mut cpu, "r x 3\nnonsense\nq\nr x 4\n".as_bytes(), &mut output).unwrap();
+
+  let output = String::from_utf8(output).unwrap();
+  assert!(output.contains("X:03"));
+  assert!(output.contains("error: unknown command: 'nonsense'"));
+  assert!(!output.contains("X:04"));
+  assert_eq!(3, cpu.register_x);
+}
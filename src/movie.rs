@@ -0,0 +1,105 @@
+use std::fs;
+use std::io;
+use std::path::Path;
+use crate::bus::Bus;
+use crate::cpu::MyCPU;
+use crate::joypad::JoypadButton;
+
+const MOVIE_MAGIC: [u8; 4] = *b"NESM";
+const MOVIE_VERSION: u16 = 1;
+
+// controller input per frame on top of a save state. Input is only applied at frame
+// boundaries, so replaying it on the same state reproduces the run exactly.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Movie {
+  pub initial_state: Vec<u8>,
+  // buttons of controller 1 and 2
+  pub frames: Vec<[u8; 2]>,
+}
+
+impl Movie {
+  pub fn start(cpu: &MyCPU) -> Self {
+    Movie { initial_state: cpu.save_state(), frames: Vec::new() }
+  }
+
+  pub fn len(&self) -> usize {
+    self.frames.len()
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.frames.is_empty()
+  }
+
+  // call at the start of every frame, after the front-end updated the controllers
+  pub fn record_frame(&mut self, bus: &Bus) {
+    self.frames.push([bus.joypad1.buttons().bits(), bus.joypad2.buttons().bits()]);
+  }
+
+  // puts the machine back to where the recording started
+  pub fn rewind_to_start(&self, cpu: &mut MyCPU) -> Result<(), String> {
+    cpu.load_state(&self.initial_state)
+  }
+
+  // sets the controllers for the given frame, false once the movie is over
+  pub fn play_frame(&self, frame: usize, bus: &mut Bus) -> bool {
+    match self.frames.get(frame) {
+      Some([joypad1, joypad2]) => {
+        bus.joypad1.set_buttons(JoypadButton::from_bits_truncate(*joypad1));
+        bus.joypad2.set_buttons(JoypadButton::from_bits_truncate(*joypad2));
+        true
+      }
+      None => false,
+    }
+  }
+
+  // magic, version, state length (u64 le), state, frame count (u64 le), 2 bytes per frame
+  pub fn to_bytes(&self) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(22 + self.initial_state.len() + self.frames.len() * 2);
+    bytes.extend_from_slice(&MOVIE_MAGIC);
+    bytes.extend_from_slice(&MOVIE_VERSION.to_le_bytes());
+    bytes.extend_from_slice(&(self.initial_state.len() as u64).to_le_bytes());
+    bytes.extend_from_slice(&self.initial_state);
+    bytes.extend_from_slice(&(self.frames.len() as u64).to_le_bytes());
+    for frame in &self.frames {
+      bytes.extend_from_slice(frame);
+    }
+    bytes
+  }
+
+  pub fn from_bytes(bytes: &[u8]) -> Result<Movie, String> {
+    if bytes.len() < 6 || bytes[0..4] != MOVIE_MAGIC {
+      return Err("not a movie file".to_string());
+    }
+    let version = u16::from_le_bytes([bytes[4], bytes[5]]);
+    if version > MOVIE_VERSION {
+      return Err(format!("movie version {} is not supported", version));
+    }
+
+    let read_len = |pos: usize| -> Result<usize, String> {
+      let data = bytes.get(pos..pos + 8).ok_or("movie file is truncated")?;
+      let mut len = [0; 8];
+      len.copy_from_slice(data);
+      Ok(u64::from_le_bytes(len) as usize)
+    };
+    let state_len = read_len(6)?;
+    let state_end = 14usize.checked_add(state_len).filter(|&end| end <= bytes.len()).ok_or("movie file is truncated")?;
+    let frame_count = read_len(state_end)?;
+    let frames_start = state_end + 8;
+    if bytes.len() - frames_start != frame_count.saturating_mul(2) {
+      return Err("movie file is truncated".to_string());
+    }
+
+    Ok(Movie {
+      initial_state: bytes[14..state_end].to_vec(),
+      frames: bytes[frames_start..].chunks(2).map(|c| [c[0], c[1]]).collect(),
+    })
+  }
+
+  pub fn save(&self, path: &Path) -> io::Result<()> {
+    fs::write(path, self.to_bytes())
+  }
+
+  pub fn load(path: &Path) -> io::Result<Movie> {
+    Movie::from_bytes(&fs::read(path)?).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+  }
+}
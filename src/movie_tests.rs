@@ -0,0 +1,56 @@
+use crate::cartridge_tests::create_test_rom_with_program;
+use crate::input::Player;
+use crate::joypad::JoypadButton;
+use crate::movie::Movie;
+use crate::nes::Nes;
+
+// strobes controller 1 and adds its first button (A) to $10, forever
+fn init_nes() -> Nes {
+  let program = [
+    0xA9, 0x01, 0x8D, 0x16, 0x40, // LDA #1, STA $4016
+    0xA9, 0x00, 0x8D, 0x16, 0x40, // LDA #0, STA $4016
+    0xAD, 0x16, 0x40,             // LDA $4016
+    0x18, 0x65, 0x10, 0x85, 0x10, // CLC, ADC $10, STA $10
+    0x4C, 0x00, 0x80,             // JMP $8000
+  ];
+  Nes::new(create_test_rom_with_program(&program)).unwrap()
+}
+
+#[test]
+fn test_replay_reproduces_recording() {
+  let mut nes = init_nes();
+  nes.run_frame();
+  let mut movie = Movie::start(&nes.cpu);
+  for frame in 0..6 {
+    let buttons = if frame % 3 == 0 { JoypadButton::BUTTON_A } else { JoypadButton::empty() };
+    nes.set_buttons(Player::One, buttons);
+    movie.record_frame(&nes.cpu.bus);
+    nes.run_frame();
+  }
+  let recorded = nes.cpu.save_state();
+  assert_ne!(0, nes.cpu.bus.peek(0x10));
+
+  let movie = Movie::from_bytes(&movie.to_bytes()).unwrap();
+  let mut replay = init_nes();
+  movie.rewind_to_start(&mut replay.cpu).unwrap();
+  let mut frame = 0;
+  while movie.play_frame(frame, &mut replay.cpu.bus) {
+    replay.run_frame();
+    frame += 1;
+  }
+
+  assert_eq!(6, frame);
+  assert_eq!(recorded, replay.cpu.save_state());
+}
+
+#[test]
+fn test_invalid_movies_are_rejected() {
+  let nes = init_nes();
+  let mut movie = Movie::start(&nes.cpu);
+  movie.record_frame(&nes.cpu.bus);
+  let bytes = movie.to_bytes();
+
+  assert_eq!(Err("not a movie file".to_string()), Movie::from_bytes(b"NESS"));
+  assert!(Movie::from_bytes(&bytes[..bytes.len() - 1]).is_err());
+  assert_eq!(Ok(movie), Movie::from_bytes(&bytes));
+}
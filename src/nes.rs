@@ -0,0 +1,208 @@
+use std::path::Path;
+use crate::bus::Bus;
+use crate::cartridge::Rom;
+use crate::cpu::{MyCPU, StopCondition};
+use crate::error::EmuError;
+use crate::frame::Frame;
+use crate::input::Player;
+use crate::joypad::JoypadButton;
+use crate::savestate::{StateReader, StateWriter};
+use crate::snapshot::Snapshot;
+
+// what a headless run leaves behind: the last complete picture and the cpu
+pub struct RunResult {
+  pub frame: Frame,
+  pub cpu: Snapshot,
+}
+
+// what a step_* call produced
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct StepResult {
+  // cpu cycles, including interrupts and dma
+  pub cycles: usize,
+  // visible scanlines the ppu drew, in order. They are in cpu.bus.ppu.frame() until the frame completes
+  pub lines: Vec<u16>,
+  pub frame_completed: bool,
+}
+
+// fnv-1a, fixed by its spec unlike std's DefaultHasher, so hashes can be stored and compared across builds
+const FNV_OFFSET_BASIS: u64 = 0xCBF2_9CE4_8422_2325;
+const FNV_PRIME: u64 = 0x0000_0100_0000_01B3;
+
+// the console without any front-end, e.g. for running test roms in ci. The cpu owns the bus
+// with ppu, apu, cartridge and joypads, cpu and cpu.bus stay public for debuggers and tools.
+// it is deterministic: no wall clock and no unseeded randomness, the same rom, power-on state
+// and inputs at the same frames always produce the same frame hashes
+pub struct Nes {
+  pub cpu: MyCPU,
+  frames: usize,
+  // cpu ram at the moment the last frame completed
+  frame_ram: [u8; 2048],
+}
+
+impl Nes {
+  pub fn new(rom: Rom) -> Result<Nes, EmuError> {
+    let mut cpu = MyCPU::new(Bus::new(rom)?);
+    cpu.stop_condition = StopCondition::Never;
+    cpu.reset();
+    Ok(Nes { cpu, frames: 0, frame_ram: [0; 2048] })
+  }
+
+  // power on with an .nes file
+  pub fn from_rom_file(path: &Path) -> Result<Nes, EmuError> {
+    Nes::new(Rom::load(path)?)
+  }
+
+  // runs until the next picture is complete (or the cpu stops) and returns the last complete one
+  pub fn run_frame(&mut self) -> Frame {
+    self.step_frame();
+    self.frame()
+  }
+
+  // held until they are set again
+  pub fn set_buttons(&mut self, player: Player, buttons: JoypadButton) {
+    let joypad = match player {
+      Player::One => &mut self.cpu.bus.joypad1,
+      Player::Two => &mut self.cpu.bus.joypad2,
+    };
+    joypad.set_buttons(buttons);
+  }
+
+  // mono samples at cpu.bus.apu.sample_rate() produced since the last call,
+  // the apu keeps only the most recent ones if nobody takes them
+  pub fn audio_samples(&mut self) -> Vec<f32> {
+    self.cpu.bus.apu.take_samples()
+  }
+
+  // the machine as of MyCPU::save_state plus the frame count and the last complete
+  // frame, so frame(), frame_count() and frame_hash() continue where the state was saved
+  pub fn save_state(&self) -> Vec<u8> {
+    let mut w = StateWriter::new();
+    w.write_bytes(&self.cpu.save_state());
+    w.write_u64(self.frames as u64);
+    w.write_bytes(&self.frame_ram);
+    w.write_bytes(&self.frame().data);
+    w.into_bytes()
+  }
+
+  // as MyCPU::load_state, a failed load of the cpu part can leave the machine half restored
+  pub fn load_state(&mut self, state: &[u8]) -> Result<(), String> {
+    let mut r = StateReader::new(state)?;
+    let cpu = r.read_bytes()?;
+    let frames = r.read_u64()? as usize;
+    let mut frame_ram = [0; 2048];
+    r.read_into(&mut frame_ram)?;
+    let mut picture = Frame::new();
+    r.read_into(&mut picture.data)?;
+    r.finish()?;
+    self.cpu.load_state(&cpu)?;
+    self.frames = frames;
+    self.frame_ram = frame_ram;
+    self.cpu.bus.ppu.set_completed_frame(&picture);
+    Ok(())
+  }
+
+  // last picture completed by the ppu, a copy in rgb
+  pub fn frame(&self) -> Frame {
+    self.cpu.bus.ppu.with_frame(Frame::from_rgba)
+  }
+
+  // the same picture as rgba without copying, see NesPPU::with_frame
+  pub fn with_frame<R>(&self, f: impl FnOnce(&[u8]) -> R) -> R {
+    self.cpu.bus.ppu.with_frame(f)
+  }
+
+  // frames completed since power on
+  pub fn frame_count(&self) -> usize {
+    self.frames
+  }
+
+  // hash of cpu ram and picture of the last completed frame, e.g. to verify a replay or
+  // to check that netplay peers are still in sync. It only changes when a frame completes
+  pub fn frame_hash(&self) -> u64 {
+    hash_frame(&self.frame_ram, &self.frame().data)
+  }
+
+  fn step(&mut self, result: &mut StepResult) -> bool {
+    let before = self.ppu_position();
+    let running = match self.cpu.step() {
+      Some(info) => {
+        result.cycles += info.cycles;
+        true
+      }
+      None => false,
+    };
+    drawn_lines(before, self.ppu_position(), &mut result.lines);
+    if self.cpu.bus.take_frame_ready() {
+      self.frame_ram.copy_from_slice(self.cpu.bus.ram());
+      self.frames += 1;
+      result.frame_completed = true;
+    }
+    running
+  }
+
+  fn ppu_position(&self) -> (u16, usize) {
+    (self.cpu.bus.ppu.scanline, self.cpu.bus.ppu.dot())
+  }
+
+  // the granularities of a debugger: nothing is produced if the cpu does not run (breakpoint, jam)
+  pub fn step_instruction(&mut self) -> StepResult {
+    let mut result = StepResult::default();
+    self.step(&mut result);
+    result
+  }
+
+  // runs until the ppu is on the next scanline, instructions are never split
+  pub fn step_scanline(&mut self) -> StepResult {
+    let mut result = StepResult::default();
+    let scanline = self.cpu.bus.ppu.scanline;
+    while self.step(&mut result) && self.cpu.bus.ppu.scanline == scanline {}
+    result
+  }
+
+  // runs until the next frame is complete, i.e. until vblank
+  pub fn step_frame(&mut self) -> StepResult {
+    let mut result = StepResult::default();
+    while self.step(&mut result) && !result.frame_completed {}
+    result
+  }
+
+  // runs until `frames` more pictures are complete (or the cpu stops at a breakpoint)
+  pub fn run_for_frames(&mut self, frames: usize) -> RunResult {
+    let target = self.frames + frames;
+    while self.frames < target && self.step(&mut StepResult::default()) {}
+    self.result()
+  }
+
+  // runs at least `cycles` cpu cycles, instructions are never split
+  pub fn run_for_cycles(&mut self, cycles: usize) -> RunResult {
+    let target = self.cpu.cycles + cycles;
+    while self.cpu.cycles < target && self.step(&mut StepResult::default()) {}
+    self.result()
+  }
+
+  fn result(&self) -> RunResult {
+    RunResult { frame: self.frame(), cpu: self.cpu.snapshot() }
+  }
+}
+
+// a line is drawn at dot 256, lines with that dot in (before, after] are new.
+// an instruction, even with dma, is far shorter than a frame, so after wraps at most once
+fn drawn_lines(before: (u16, usize), after: (u16, usize), lines: &mut Vec<u16>) {
+  let first = if before.1 < 256 { before.0 } else { before.0 + 1 };
+  // exclusive
+  let end = if after.1 < 256 { after.0 } else { after.0 + 1 };
+  let height = Frame::HEIGHT as u16;
+  if before <= after {
+    lines.extend(first..end.min(height));
+  } else {
+    lines.extend(first..height);
+    lines.extend(0..end.min(height));
+  }
+}
+
+// frame_hash for front-ends which run the cpu themselves, ram and picture right after the frame completed
+pub fn hash_frame(ram: &[u8], picture: &[u8]) -> u64 {
+  ram.iter().chain(picture.iter())
+    .fold(FNV_OFFSET_BASIS, |hash, &byte| (hash ^ byte as u64).wrapping_mul(FNV_PRIME))
+}
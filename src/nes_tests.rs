@@ -0,0 +1,243 @@
+use std::path::Path;
+use crate::breakpoints::Breakpoint;
+use crate::cartridge_tests::{create_test_rom_with_program, test_rom_bytes_with_program};
+use crate::frame::Frame;
+use crate::input::Player;
+use crate::joypad::JoypadButton;
+use crate::nes::{Nes, StepResult};
+use crate::palette::SYSTEM_PALETTE;
+
+// sets the backdrop color to $16, then loops
+// (v is moved out of the palette, with rendering off the ppu would show the entry it points at)
+fn init_nes() -> Nes {
+  let program = [
+    0xA9, 0x3F, 0x8D, 0x06, 0x20, // LDA #$3F, STA $2006
+    0xA9, 0x00, 0x8D, 0x06, 0x20, // LDA #$00, STA $2006
+    0xA9, 0x16, 0x8D, 0x07, 0x20, // LDA #$16, STA $2007
+    0xA9, 0x00, 0x8D, 0x06, 0x20, // LDA #$00, STA $2006
+    0x8D, 0x06, 0x20,             // STA $2006
+    0xE8, 0x4C, 0x17, 0x80,       // loop: INX, JMP loop
+  ];
+  Nes::new(create_test_rom_with_program(&program)).unwrap()
+}
+
+#[test]
+fn test_run_for_frames_renders_last_frame() {
+  let mut nes = init_nes();
+  let result = nes.run_for_frames(2);
+
+  assert_eq!(2, nes.frame_count());
+  assert_eq!(SYSTEM_PALETTE[0x16], result.frame.get_pixel(0, 0));
+  assert_eq!(SYSTEM_PALETTE[0x16], result.frame.get_pixel(Frame::WIDTH - 1, Frame::HEIGHT - 1));
+  // pictures are complete at vblank: 241 scanlines, then one more frame of 262
+  assert!(result.cpu.cycles > 57_000);
+  assert!((0x8017..0x801B).contains(&result.cpu.program_counter));
+}
+
+#[test]
+fn test_run_for_cycles() {
+  let mut nes = init_nes();
+  let result = nes.run_for_cycles(1000);
+
+  assert!(result.cpu.cycles >= 1000 && result.cpu.cycles < 1010);
+  assert_eq!(0, nes.frame_count());
+  assert!(result.cpu.register_x > 0);
+}
+
+// reads the first button of joypad 1 into $10 and counts in $11, forever
+fn joypad_nes() -> Nes {
+  let program = [
+    0xA9, 0x01, 0x8D, 0x16, 0x40, // loop: LDA #$01, STA $4016
+    0xA9, 0x00, 0x8D, 0x16, 0x40, // LDA #$00, STA $4016
+    0xAD, 0x16, 0x40, 0x85, 0x10, // LDA $4016, STA $10
+    0xE6, 0x11,                   // INC $11
+    0x4C, 0x00, 0x80,             // JMP loop
+  ];
+  Nes::new(create_test_rom_with_program(&program)).unwrap()
+}
+
+fn hashes(nes: &mut Nes, frames: usize, press_a_at: Option<usize>) -> Vec<u64> {
+  (0..frames).map(|frame| {
+    if Some(frame) == press_a_at {
+      nes.cpu.bus.joypad1.set_buttons(JoypadButton::BUTTON_A);
+    }
+    nes.run_for_frames(1);
+    nes.frame_hash()
+  }).collect()
+}
+
+#[test]
+fn test_same_rom_and_inputs_give_same_frame_hashes() {
+  let first = hashes(&mut joypad_nes(), 5, Some(2));
+  let second = hashes(&mut joypad_nes(), 5, Some(2));
+
+  assert_eq!(first, second);
+  // the counter in ram changes every frame
+  assert!(first.windows(2).all(|pair| pair[0] != pair[1]));
+}
+
+#[test]
+fn test_different_inputs_change_the_frame_hash() {
+  let pressed = hashes(&mut joypad_nes(), 5, Some(2));
+  let released = hashes(&mut joypad_nes(), 5, None);
+
+  assert_eq!(pressed[..2], released[..2]);
+  assert_ne!(pressed[2..], released[2..]);
+}
+
+#[test]
+fn test_frame_hash_only_changes_when_a_frame_completes() {
+  let mut nes = joypad_nes();
+  nes.run_for_frames(1);
+  let hash = nes.frame_hash();
+
+  nes.run_for_cycles(1000);
+
+  assert_eq!(hash, nes.frame_hash());
+}
+
+#[test]
+fn test_step_instruction() {
+  let mut nes = init_nes();
+
+  let result = nes.step_instruction();
+
+  // LDA #$3F
+  assert_eq!(2, result.cycles);
+  assert!(result.lines.is_empty());
+  assert!(!result.frame_completed);
+  assert_eq!(0x8002, nes.cpu.program_counter);
+}
+
+#[test]
+fn test_step_scanline_draws_each_line_once() {
+  let mut nes = init_nes();
+  let mut lines = Vec::new();
+
+  while nes.frame_count() == 0 {
+    let scanline = nes.cpu.bus.ppu.scanline;
+    let result = nes.step_scanline();
+    assert_ne!(scanline, nes.cpu.bus.ppu.scanline);
+    // 341 dots at 3 per cpu cycle, plus the rest of the last instruction
+    assert!((100..120).contains(&result.cycles), "{} cycles", result.cycles);
+    lines.extend(result.lines);
+  }
+
+  assert_eq!((0..240).collect::<Vec<u16>>(), lines);
+  assert_eq!(241, nes.cpu.bus.ppu.scanline);
+}
+
+#[test]
+fn test_step_frame() {
+  let mut nes = init_nes();
+  nes.step_frame();
+
+  let result = nes.step_frame();
+
+  assert!(result.frame_completed);
+  assert_eq!(2, nes.frame_count());
+  assert_eq!((0..240).collect::<Vec<u16>>(), result.lines);
+  // 262 lines of 341 dots
+  assert!((29_770..29_790).contains(&result.cycles), "{} cycles", result.cycles);
+  assert_eq!(SYSTEM_PALETTE[0x16], nes.frame().get_pixel(0, 0));
+}
+
+#[test]
+fn test_step_frame_stops_at_a_breakpoint() {
+  let mut nes = init_nes();
+  nes.cpu.breakpoints.add(Breakpoint::Address(0x8005));
+
+  let result = nes.step_frame();
+
+  // LDA #$3F, STA $2006
+  assert_eq!(StepResult { cycles: 6, lines: Vec::new(), frame_completed: false }, result);
+  assert_eq!(0x8005, nes.cpu.program_counter);
+}
+
+#[test]
+fn test_a_jammed_cpu_produces_nothing() {
+  let mut nes = Nes::new(create_test_rom_with_program(&[0x02])).unwrap();
+
+  assert_eq!(StepResult::default(), nes.step_instruction());
+  assert_eq!(StepResult::default(), nes.step_scanline());
+  assert_eq!(StepResult::default(), nes.step_frame());
+}
+
+#[test]
+fn test_from_rom_file() {
+  let path = std::env::temp_dir().join(format!("nes_emulator_nes_{}.nes", std::process::id()));
+  // LDA #$42, STA $20, loop: JMP loop
+  std::fs::write(&path, test_rom_bytes_with_program(&[0xA9, 0x42, 0x85, 0x20, 0x4C, 0x04, 0x80])).unwrap();
+
+  let nes = Nes::from_rom_file(&path);
+  std::fs::remove_file(&path).unwrap();
+
+  let mut nes = nes.unwrap();
+  assert_eq!(SYSTEM_PALETTE[0x00], nes.run_frame().get_pixel(0, 0));
+  assert_eq!(0x42, nes.cpu.bus.ram()[0x20]);
+  assert!(Nes::from_rom_file(Path::new("missing.nes")).is_err());
+}
+
+#[test]
+fn test_run_frame_with_buttons_of_both_players() {
+  let mut nes = init_nes();
+
+  nes.set_buttons(Player::Two, JoypadButton::START);
+  let frame = nes.run_frame();
+
+  assert_eq!(1, nes.frame_count());
+  assert!(frame == nes.frame());
+  assert_eq!(JoypadButton::empty(), nes.cpu.bus.joypad1.buttons());
+  assert_eq!(JoypadButton::START, nes.cpu.bus.joypad2.buttons());
+}
+
+#[test]
+fn test_audio_samples_are_taken_once() {
+  let mut nes = init_nes();
+  nes.run_frame();
+  nes.audio_samples();
+
+  nes.run_frame();
+  let samples = nes.audio_samples();
+
+  // a frame is 1/60 s
+  let per_frame = nes.cpu.bus.apu.sample_rate() as usize / 60;
+  assert!((per_frame - 5..per_frame + 5).contains(&samples.len()), "{} samples", samples.len());
+  assert!(nes.audio_samples().is_empty());
+}
+
+#[test]
+fn test_load_state_continues_from_the_saved_frame() {
+  let mut nes = joypad_nes();
+  nes.run_for_frames(2);
+  let state = nes.save_state();
+  nes.set_buttons(Player::One, JoypadButton::BUTTON_A);
+  nes.run_for_frames(3);
+  let hash = nes.frame_hash();
+
+  let mut restored = joypad_nes();
+  restored.load_state(&state).unwrap();
+  restored.set_buttons(Player::One, JoypadButton::BUTTON_A);
+  restored.run_for_frames(3);
+
+  assert_eq!(hash, restored.frame_hash());
+  assert!(restored.load_state(&state[..10]).is_err());
+}
+
+#[test]
+fn test_load_state_restores_the_last_frame() {
+  let mut nes = joypad_nes();
+  nes.set_buttons(Player::One, JoypadButton::BUTTON_A);
+  nes.run_for_frames(2);
+  let state = nes.save_state();
+  let (hash, picture) = (nes.frame_hash(), nes.frame().data.clone());
+
+  let mut restored = joypad_nes();
+  restored.run_for_frames(5);
+  assert_ne!(hash, restored.frame_hash());
+  restored.load_state(&state).unwrap();
+
+  assert_eq!(hash, restored.frame_hash());
+  assert_eq!(2, restored.frame_count());
+  assert_eq!(picture, restored.frame().data);
+}
@@ -0,0 +1,81 @@
+use crate::cpu::{AddressingMode, MyCPU};
+use crate::opcodes;
+
+// next instruction in the format of nestest.log, e.g.
+// C000  4C F5 C5  JMP $C5F5                       A:00 X:00 Y:00 P:24 SP:FD PPU:  0, 21 CYC:7
+// memory is only peeked, tracing never changes the machine state
+pub fn trace(cpu: &MyCPU) -> String {
+  let begin = cpu.program_counter;
+  let code = cpu.bus.peek(begin);
+  let ops = opcodes::lookup(code)
+    .unwrap_or_else(|| panic!("OpCode {:#04x} is not recognized (pc={:04X})", code, begin));
+
+  let hex_dump: Vec<u8> = (0..ops.len as u16).map(|i| cpu.bus.peek(begin.wrapping_add(i))).collect();
+  let operand_u8 = *hex_dump.get(1).unwrap_or(&0);
+  let operand_u16 = u16::from_le_bytes([operand_u8, *hex_dump.get(2).unwrap_or(&0)]);
+  let mem_addr = effective_address(cpu, &ops.mode, begin.wrapping_add(1));
+  let stored_value = cpu.bus.peek(mem_addr);
+
+  let operand = match ops.mode {
+    AddressingMode::NoneAddressing => match code {
+      0x0A | 0x4A | 0x2A | 0x6A => "A ".to_string(),
+      _ => String::new(),
+    },
+    AddressingMode::Immediate => format!("#${:02x}", operand_u8),
+    AddressingMode::ZeroPage => format!("${:02x} = {:02x}", mem_addr, stored_value),
+    AddressingMode::ZeroPage_X => format!("${:02x},X @ {:02x} = {:02x}", operand_u8, mem_addr, stored_value),
+    AddressingMode::ZeroPage_Y => format!("${:02x},Y @ {:02x} = {:02x}", operand_u8, mem_addr, stored_value),
+    AddressingMode::Indirect_X => format!("(${:02x},X) @ {:02x} = {:04x} = {:02x}",
+                                          operand_u8, operand_u8.wrapping_add(cpu.register_x), mem_addr, stored_value),
+    AddressingMode::Indirect_Y => format!("(${:02x}),Y = {:04x} @ {:04x} = {:02x}",
+                                          operand_u8, mem_addr.wrapping_sub(cpu.register_y as u16), mem_addr, stored_value),
+    AddressingMode::Relative => format!("${:04x}", mem_addr),
+    AddressingMode::Absolute if matches!(ops.mnemonic, "JMP" | "JSR") => format!("${:04x}", operand_u16),
+    AddressingMode::Absolute => format!("${:04x} = {:02x}", mem_addr, stored_value),
+    AddressingMode::Absolute_X => format!("${:04x},X @ {:04x} = {:02x}", operand_u16, mem_addr, stored_value),
+    AddressingMode::Absolute_Y => format!("${:04x},Y @ {:04x} = {:02x}", operand_u16, mem_addr, stored_value),
+    AddressingMode::Indirect => format!("(${:04x}) = {:04x}", operand_u16, mem_addr),
+  };
+
+  let hex_str = hex_dump.iter().map(|b| format!("{:02x}", b)).collect::<Vec<String>>().join(" ");
+  let asm_str = format!("{:04x}  {:8} {: >4} {}", begin, hex_str, ops.mnemonic, operand).trim().to_string();
+  format!("{:47} A:{:02x} X:{:02x} Y:{:02x} P:{:02x} SP:{:02x} PPU:{:3},{:3} CYC:{}",
+          asm_str, cpu.register_a, cpu.register_x, cpu.register_y, cpu.status.bits(), cpu.stack_pointer,
+          cpu.bus.ppu.scanline, cpu.bus.ppu.dot(), cpu.cycles)
+    .to_ascii_uppercase()
+}
+
+fn peek_u16(cpu: &MyCPU, addr: u16) -> u16 {
+  u16::from_le_bytes([cpu.bus.peek(addr), cpu.bus.peek(addr.wrapping_add(1))])
+}
+
+// same as the cpu computes it, without side effects on the bus
+fn effective_address(cpu: &MyCPU, mode: &AddressingMode, addr: u16) -> u16 {
+  match mode {
+    AddressingMode::ZeroPage => cpu.bus.peek(addr) as u16,
+    AddressingMode::ZeroPage_X => cpu.bus.peek(addr).wrapping_add(cpu.register_x) as u16,
+    AddressingMode::ZeroPage_Y => cpu.bus.peek(addr).wrapping_add(cpu.register_y) as u16,
+    AddressingMode::Absolute => peek_u16(cpu, addr),
+    AddressingMode::Absolute_X => peek_u16(cpu, addr).wrapping_add(cpu.register_x as u16),
+    AddressingMode::Absolute_Y => peek_u16(cpu, addr).wrapping_add(cpu.register_y as u16),
+    AddressingMode::Indirect_X => {
+      let ptr = cpu.bus.peek(addr).wrapping_add(cpu.register_x);
+      u16::from_le_bytes([cpu.bus.peek(ptr as u16), cpu.bus.peek(ptr.wrapping_add(1) as u16)])
+    }
+    AddressingMode::Indirect_Y => {
+      let base = cpu.bus.peek(addr);
+      let deref_base = u16::from_le_bytes([cpu.bus.peek(base as u16), cpu.bus.peek(base.wrapping_add(1) as u16)]);
+      deref_base.wrapping_add(cpu.register_y as u16)
+    }
+    AddressingMode::Indirect => {
+      let ptr = peek_u16(cpu, addr);
+      let hi_addr = if ptr & 0x00FF == 0x00FF { ptr & 0xFF00 } else { ptr.wrapping_add(1) };
+      u16::from_le_bytes([cpu.bus.peek(ptr), cpu.bus.peek(hi_addr)])
+    }
+    AddressingMode::Relative => {
+      let offset = cpu.bus.peek(addr) as i8;
+      addr.wrapping_add(1).wrapping_add(offset as u16)
+    }
+    AddressingMode::Immediate | AddressingMode::NoneAddressing => 0,
+  }
+}
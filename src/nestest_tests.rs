@@ -0,0 +1,120 @@
+use std::fs;
+use std::path::Path;
+use crate::bus::Bus;
+use crate::cartridge::Rom;
+use crate::cartridge_tests::create_test_rom;
+use crate::cpu::{MyCPU, MyMem, StopCondition};
+use crate::nestest::trace;
+
+fn init_cpu() -> MyCPU {
+  let mut cpu = MyCPU::new(Bus::new(create_test_rom()).unwrap());
+  cpu.stack_pointer = 0xFD;
+  cpu
+}
+
+fn collect_traces(cpu: &mut MyCPU) -> Vec<String> {
+  let mut result = vec![];
+  loop {
+    result.push(trace(cpu));
+    if cpu.step().is_none() {
+      return result;
+    }
+  }
+}
+
+// registers part of a log line
+fn cpu_part(line: &str) -> &str {
+  line.split(" PPU:").next().unwrap()
+}
+
+// cpu cycles before the instruction
+fn cycles_part(line: &str) -> &str {
+  line.rsplit(" CYC:").next().unwrap()
+}
+
+#[test]
+fn test_format_trace() {
+  let mut cpu = init_cpu();
+  cpu.mem_write(100, 0xA2);
+  cpu.mem_write(101, 0x01);
+  cpu.mem_write(102, 0xCA);
+  cpu.mem_write(103, 0x88);
+  cpu.mem_write(104, 0x00);
+  cpu.program_counter = 0x64;
+  cpu.register_a = 1;
+  cpu.register_x = 2;
+  cpu.register_y = 3;
+
+  let result = collect_traces(&mut cpu);
+
+  assert_eq!("0064  A2 01     LDX #$01                        A:01 X:02 Y:03 P:24 SP:FD PPU:  0,  0 CYC:0", result[0]);
+  assert_eq!("0066  CA        DEX                             A:01 X:01 Y:03 P:24 SP:FD PPU:  0,  6 CYC:2", result[1]);
+  assert_eq!("0067  88        DEY                             A:01 X:00 Y:03 P:26 SP:FD PPU:  0, 12 CYC:4", result[2]);
+}
+
+#[test]
+fn test_format_mem_access() {
+  let mut cpu = init_cpu();
+  // ORA ($33), Y
+  cpu.mem_write(100, 0x11);
+  cpu.mem_write(101, 0x33);
+  // data
+  cpu.mem_write(0x33, 0x00);
+  cpu.mem_write(0x34, 0x04);
+  // target cell
+  cpu.mem_write(0x400, 0xAA);
+  cpu.program_counter = 0x64;
+
+  let result = collect_traces(&mut cpu);
+
+  assert_eq!("0064  11 33     ORA ($33),Y = 0400 @ 0400 = AA  A:00 X:00 Y:00 P:24 SP:FD", cpu_part(&result[0]));
+}
+
+#[test]
+fn test_format_jumps_and_branches() {
+  let mut cpu = init_cpu();
+  cpu.mem_write(0x0200, 0x00);
+  cpu.mem_write(0x0201, 0x03);
+  // BNE +2, JMP ($0200), JSR $0300
+  cpu.load(vec![0xD0, 0x02, 0x6C, 0x00, 0x02, 0x20, 0x00, 0x03]);
+  cpu.program_counter = 0x0600;
+
+  assert!(cpu_part(&trace(&cpu)).starts_with("0600  D0 02     BNE $0604 "));
+  cpu.program_counter = 0x0602;
+  assert!(cpu_part(&trace(&cpu)).starts_with("0602  6C 00 02  JMP ($0200) = 0300 "));
+  cpu.program_counter = 0x0605;
+  assert!(cpu_part(&trace(&cpu)).starts_with("0605  20 00 03  JSR $0300 "));
+}
+
+// runs nestest.nes in automation mode against the reference log, both have to be placed
+// next to Cargo.toml (see https://www.qmtpro.com/~nes/misc/), the test is skipped otherwise
+#[test]
+fn test_nestest_golden_log() {
+  let root = Path::new(env!("CARGO_MANIFEST_DIR"));
+  let (rom_path, log_path) = (root.join("nestest.nes"), root.join("nestest.log"));
+  if !rom_path.exists() || !log_path.exists() {
+    println!("skipping nestest: nestest.nes/nestest.log not found in {}", root.display());
+    return;
+  }
+
+  let rom = Rom::new(&fs::read(rom_path).unwrap()).unwrap();
+  let expected = fs::read_to_string(log_path).unwrap();
+  let mut cpu = MyCPU::new(Bus::new(rom).unwrap());
+  cpu.stop_condition = StopCondition::Never;
+  cpu.program_counter = 0xC000;
+  cpu.stack_pointer = 0xFD;
+  cpu.cycles = 7;
+  cpu.bus.tick(7);
+
+  let mut previous: Vec<String> = vec![];
+  for (line_number, expected_line) in expected.lines().enumerate() {
+    let actual = trace(&cpu);
+    assert_eq!((cpu_part(expected_line), cycles_part(expected_line)), (cpu_part(&actual), cycles_part(&actual)),
+               "nestest diverged in line {}, preceding lines:\n{}", line_number + 1, previous.join("\n"));
+    previous.push(actual);
+    if previous.len() > 5 {
+      previous.remove(0);
+    }
+    cpu.step();
+  }
+}
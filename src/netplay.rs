@@ -0,0 +1,343 @@
+use std::collections::{BTreeMap, VecDeque};
+use std::io;
+use std::net::{SocketAddr, ToSocketAddrs, UdpSocket};
+use std::thread;
+use std::time::{Duration, Instant};
+use crate::cpu::MyCPU;
+use crate::joypad::JoypadButton;
+use crate::nes::Nes;
+
+// delay based lockstep over udp: every frame runs with the inputs of both players, local inputs
+// are sent `delay` frames ahead so they usually arrive before they are needed. Both sides send
+// the frame hash of every frame, on a mismatch the host sends its save state to the guest
+pub const DEFAULT_PORT: u16 = 6503;
+pub const DEFAULT_INPUT_DELAY: u32 = 2;
+// how long wait_for_frame waits for the peer
+pub const PEER_TIMEOUT: Duration = Duration::from_secs(30);
+
+// inputs of this many frames go into every packet, a lost packet is covered by the next one
+const RESEND_WINDOW: u32 = 8;
+// inputs and hashes older than this are dropped
+const HISTORY: u32 = 120;
+// save states are sent in pieces of this size
+const STATE_CHUNK: usize = 1024;
+// pieces sent per begin_frame, a whole state at once overflows the peer's receive buffer
+const STATE_CHUNKS_PER_SEND: usize = 16;
+const MAX_PACKET: usize = 2048;
+
+const INPUTS: u8 = 1;
+const HASH: u8 = 2;
+const STATE: u8 = 3;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Role {
+  // player 1, its state wins on a desync
+  Host,
+  // player 2
+  Guest,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Peer {
+  // waits on this port for the guest
+  Host(u16),
+  // address of the host
+  Connect(String),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Message {
+  // buttons for first_frame, first_frame + 1, ...
+  Inputs { first_frame: u32, buttons: Vec<u8> },
+  // generation: number of resyncs the sender has seen, older hashes are stale
+  Hash { generation: u16, frame: u32, hash: u64 },
+  // piece `index` of `count` of the state before `frame`
+  State { generation: u16, frame: u32, index: u16, count: u16, data: Vec<u8> },
+}
+
+impl Message {
+  fn encode(&self) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    match self {
+      Message::Inputs { first_frame, buttons } => {
+        bytes.push(INPUTS);
+        bytes.extend_from_slice(&first_frame.to_le_bytes());
+        bytes.extend_from_slice(buttons);
+      }
+      Message::Hash { generation, frame, hash } => {
+        bytes.push(HASH);
+        bytes.extend_from_slice(&generation.to_le_bytes());
+        bytes.extend_from_slice(&frame.to_le_bytes());
+        bytes.extend_from_slice(&hash.to_le_bytes());
+      }
+      Message::State { generation, frame, index, count, data } => {
+        bytes.push(STATE);
+        bytes.extend_from_slice(&generation.to_le_bytes());
+        bytes.extend_from_slice(&frame.to_le_bytes());
+        bytes.extend_from_slice(&index.to_le_bytes());
+        bytes.extend_from_slice(&count.to_le_bytes());
+        bytes.extend_from_slice(data);
+      }
+    }
+    bytes
+  }
+
+  // None for anything malformed, udp delivers whatever was sent to the port
+  fn decode(bytes: &[u8]) -> Option<Message> {
+    let u16_at = |at: usize| Some(u16::from_le_bytes(bytes.get(at..at + 2)?.try_into().ok()?));
+    let u32_at = |at: usize| Some(u32::from_le_bytes(bytes.get(at..at + 4)?.try_into().ok()?));
+    match *bytes.first()? {
+      INPUTS => Some(Message::Inputs { first_frame: u32_at(1)?, buttons: bytes[5..].to_vec() }),
+      HASH if bytes.len() == 15 => Some(Message::Hash {
+        generation: u16_at(1)?,
+        frame: u32_at(3)?,
+        hash: u64::from_le_bytes(bytes[7..15].try_into().ok()?),
+      }),
+      STATE if bytes.len() >= 11 => {
+        let (index, count) = (u16_at(7)?, u16_at(9)?);
+        if index >= count {
+          return None;
+        }
+        Some(Message::State { generation: u16_at(1)?, frame: u32_at(3)?, index, count, data: bytes[11..].to_vec() })
+      }
+      _ => None,
+    }
+  }
+}
+
+struct IncomingState {
+  generation: u16,
+  chunks: Vec<Option<Vec<u8>>>,
+}
+
+pub struct Netplay {
+  socket: UdpSocket,
+  // the host learns it from the first packet
+  peer: Option<SocketAddr>,
+  role: Role,
+  delay: u32,
+  // next frame to run
+  frame: u32,
+  local_inputs: BTreeMap<u32, u8>,
+  remote_inputs: BTreeMap<u32, u8>,
+  // first frame without a local input yet
+  next_local_frame: u32,
+  // host only, compared frame by frame
+  hashes: BTreeMap<u32, u64>,
+  remote_hashes: BTreeMap<u32, u64>,
+  generation: u16,
+  incoming_state: Option<IncomingState>,
+  // host: pieces of the last resync's state not sent yet
+  outgoing_state: VecDeque<Message>,
+  // save states sent (host) or loaded (guest)
+  pub resyncs: usize,
+}
+
+impl Netplay {
+  // the socket is switched to non-blocking
+  pub fn new(socket: UdpSocket, peer: Option<SocketAddr>, role: Role, delay: u32) -> io::Result<Netplay> {
+    socket.set_nonblocking(true)?;
+    Ok(Netplay {
+      socket,
+      peer,
+      role,
+      delay,
+      frame: 0,
+      local_inputs: BTreeMap::new(),
+      remote_inputs: BTreeMap::new(),
+      next_local_frame: 0,
+      hashes: BTreeMap::new(),
+      remote_hashes: BTreeMap::new(),
+      generation: 0,
+      incoming_state: None,
+      outgoing_state: VecDeque::new(),
+      resyncs: 0,
+    })
+  }
+
+  pub fn open(peer: &Peer, delay: u32) -> io::Result<Netplay> {
+    match peer {
+      Peer::Host(port) => Netplay::new(UdpSocket::bind(("0.0.0.0", *port))?, None, Role::Host, delay),
+      Peer::Connect(address) => {
+        let address = address.to_socket_addrs()?.next()
+          .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, format!("no address for {}", address)))?;
+        Netplay::new(UdpSocket::bind(("0.0.0.0", 0))?, Some(address), Role::Guest, delay)
+      }
+    }
+  }
+
+  // next frame to run
+  pub fn frame(&self) -> u32 {
+    self.frame
+  }
+
+  // handles what the peer sent, sends our inputs and sets both joypads once the inputs of the
+  // next frame are known. false means the peer's input is missing, call again later
+  pub fn begin_frame(&mut self, cpu: &mut MyCPU, local: JoypadButton) -> io::Result<bool> {
+    self.receive(cpu)?;
+    while self.next_local_frame <= self.frame + self.delay {
+      self.local_inputs.insert(self.next_local_frame, local.bits());
+      self.next_local_frame += 1;
+    }
+    self.send_inputs()?;
+    self.send_state()?;
+
+    let (local, remote) = match (self.local_inputs.get(&self.frame), self.remote_inputs.get(&self.frame)) {
+      (Some(&local), Some(&remote)) => (local, remote),
+      _ => return Ok(false),
+    };
+    let (one, two) = match self.role {
+      Role::Host => (local, remote),
+      Role::Guest => (remote, local),
+    };
+    cpu.bus.joypad1.set_buttons(JoypadButton::from_bits_truncate(one));
+    cpu.bus.joypad2.set_buttons(JoypadButton::from_bits_truncate(two));
+    Ok(true)
+  }
+
+  // begin_frame until the peer's input is there
+  pub fn wait_for_frame(&mut self, cpu: &mut MyCPU, local: JoypadButton, timeout: Duration) -> io::Result<()> {
+    let started = Instant::now();
+    while !self.begin_frame(cpu, local)? {
+      if started.elapsed() > timeout {
+        return Err(io::Error::new(io::ErrorKind::TimedOut, format!("no input from the peer for frame {}", self.frame)));
+      }
+      thread::sleep(Duration::from_millis(1));
+    }
+    Ok(())
+  }
+
+  // after the frame ran, `hash` as of Nes::frame_hash
+  pub fn end_frame(&mut self, cpu: &MyCPU, hash: u64) -> io::Result<()> {
+    let frame = self.frame;
+    self.frame += 1;
+    self.send(&Message::Hash { generation: self.generation, frame, hash })?;
+    if self.role == Role::Host {
+      self.hashes.insert(frame, hash);
+      self.check_sync(cpu, frame)?;
+    }
+
+    let oldest = self.frame.saturating_sub(HISTORY);
+    for inputs in [&mut self.local_inputs, &mut self.remote_inputs] {
+      *inputs = inputs.split_off(&oldest);
+    }
+    for hashes in [&mut self.hashes, &mut self.remote_hashes] {
+      *hashes = hashes.split_off(&oldest);
+    }
+    Ok(())
+  }
+
+  // one frame in lockstep, false while waiting for the peer
+  pub fn step(&mut self, nes: &mut Nes, local: JoypadButton) -> io::Result<bool> {
+    if !self.begin_frame(&mut nes.cpu, local)? {
+      return Ok(false);
+    }
+    nes.run_for_frames(1);
+    self.end_frame(&nes.cpu, nes.frame_hash())?;
+    Ok(true)
+  }
+
+  fn send(&self, message: &Message) -> io::Result<()> {
+    match self.peer {
+      Some(peer) => self.socket.send_to(&message.encode(), peer).map(|_| ()),
+      None => Ok(()),
+    }
+  }
+
+  fn send_inputs(&self) -> io::Result<()> {
+    let first = self.next_local_frame.saturating_sub(RESEND_WINDOW);
+    let mut inputs = self.local_inputs.range(first..).peekable();
+    let first_frame = match inputs.peek() {
+      Some((&frame, _)) => frame,
+      None => return Ok(()),
+    };
+    self.send(&Message::Inputs { first_frame, buttons: inputs.map(|(_, &buttons)| buttons).collect() })
+  }
+
+  fn receive(&mut self, cpu: &mut MyCPU) -> io::Result<()> {
+    let mut buffer = [0; MAX_PACKET];
+    loop {
+      let (len, from) = match self.socket.recv_from(&mut buffer) {
+        Ok(received) => received,
+        Err(e) if e.kind() == io::ErrorKind::WouldBlock => return Ok(()),
+        // windows reports an unreachable peer of an earlier send here
+        Err(e) if e.kind() == io::ErrorKind::ConnectionReset => continue,
+        Err(e) => return Err(e),
+      };
+      match self.peer {
+        None => self.peer = Some(from),
+        Some(peer) if peer != from => continue,
+        Some(_) => {}
+      }
+      match Message::decode(&buffer[..len]) {
+        Some(Message::Inputs { first_frame, buttons }) => {
+          for (i, buttons) in buttons.into_iter().enumerate() {
+            self.remote_inputs.insert(first_frame + i as u32, buttons);
+          }
+        }
+        Some(Message::Hash { generation, frame, hash }) if self.role == Role::Host && generation == self.generation => {
+          self.remote_hashes.insert(frame, hash);
+          self.check_sync(cpu, frame)?;
+        }
+        Some(Message::State { generation, frame, index, count, data }) if self.role == Role::Guest => {
+          self.receive_state(cpu, generation, frame, index, count, data)?;
+        }
+        _ => {}
+      }
+    }
+  }
+
+  // host: sends its state when both hashes of a frame are known and differ
+  fn check_sync(&mut self, cpu: &MyCPU, frame: u32) -> io::Result<()> {
+    match (self.hashes.get(&frame), self.remote_hashes.get(&frame)) {
+      (Some(hash), Some(remote)) if hash != remote => self.resync(cpu),
+      _ => Ok(()),
+    }
+  }
+
+  fn resync(&mut self, cpu: &MyCPU) -> io::Result<()> {
+    self.generation = self.generation.wrapping_add(1);
+    self.resyncs += 1;
+    self.remote_hashes.clear();
+    let state = cpu.save_state();
+    let count = state.len().div_ceil(STATE_CHUNK) as u16;
+    // replaces what is left of an older state
+    self.outgoing_state = state.chunks(STATE_CHUNK).enumerate()
+      .map(|(index, data)| Message::State { generation: self.generation, frame: self.frame, index: index as u16, count, data: data.to_vec() })
+      .collect();
+    self.send_state()
+  }
+
+  fn send_state(&mut self) -> io::Result<()> {
+    let count = self.outgoing_state.len().min(STATE_CHUNKS_PER_SEND);
+    let messages: Vec<Message> = self.outgoing_state.drain(..count).collect();
+    for message in messages {
+      self.send(&message)?;
+    }
+    Ok(())
+  }
+
+  // guest: loads the state once all pieces are there and continues from its frame
+  fn receive_state(&mut self, cpu: &mut MyCPU, generation: u16, frame: u32, index: u16, count: u16, data: Vec<u8>) -> io::Result<()> {
+    if generation == self.generation {
+      return Ok(());
+    }
+    let incoming = match &mut self.incoming_state {
+      Some(incoming) if incoming.generation == generation && incoming.chunks.len() == count as usize => incoming,
+      _ => self.incoming_state.insert(IncomingState { generation, chunks: vec![None; count as usize] }),
+    };
+    incoming.chunks[index as usize] = Some(data);
+    if incoming.chunks.iter().any(Option::is_none) {
+      return Ok(());
+    }
+
+    let state: Vec<u8> = incoming.chunks.iter().flatten().flatten().copied().collect();
+    self.incoming_state = None;
+    cpu.load_state(&state).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    self.generation = generation;
+    self.frame = frame;
+    self.next_local_frame = self.next_local_frame.max(frame);
+    self.resyncs += 1;
+    Ok(())
+  }
+}
@@ -0,0 +1,110 @@
+use std::collections::BTreeMap;
+use std::net::UdpSocket;
+use crate::cartridge_tests::create_test_rom_with_program;
+use crate::joypad::JoypadButton;
+use crate::netplay::{Netplay, Role};
+use crate::nes::Nes;
+
+// reads the first button of both joypads into $10 / $11 and counts in $12, forever
+fn two_player_nes() -> Nes {
+  let program = [
+    0xA9, 0x01, 0x8D, 0x16, 0x40, // loop: LDA #$01, STA $4016
+    0xA9, 0x00, 0x8D, 0x16, 0x40, // LDA #$00, STA $4016
+    0xAD, 0x16, 0x40, 0x85, 0x10, // LDA $4016, STA $10
+    0xAD, 0x17, 0x40, 0x85, 0x11, // LDA $4017, STA $11
+    0xE6, 0x12,                   // INC $12
+    0x4C, 0x00, 0x80,             // JMP loop
+  ];
+  Nes::new(create_test_rom_with_program(&program)).unwrap()
+}
+
+fn session_pair() -> (Netplay, Netplay) {
+  let host_socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+  let host_address = host_socket.local_addr().unwrap();
+  let host = Netplay::new(host_socket, None, Role::Host, 2).unwrap();
+  let guest = Netplay::new(UdpSocket::bind("127.0.0.1:0").unwrap(), Some(host_address), Role::Guest, 2).unwrap();
+  (host, guest)
+}
+
+struct Player {
+  netplay: Netplay,
+  nes: Nes,
+  // by frame, frames run again after a resync overwrite their hash
+  hashes: BTreeMap<u32, u64>,
+}
+
+impl Player {
+  fn new(netplay: Netplay) -> Player {
+    Player { netplay, nes: two_player_nes(), hashes: BTreeMap::new() }
+  }
+
+  fn step(&mut self, buttons: JoypadButton) {
+    let frame = self.netplay.frame();
+    if self.netplay.step(&mut self.nes, buttons).unwrap() {
+      self.hashes.insert(frame, self.nes.frame_hash());
+    }
+  }
+}
+
+// both sides in one thread, each call to `buttons` gives the inputs for a frame
+fn play(host: &mut Player, guest: &mut Player, frames: u32, buttons: impl Fn(u32) -> (JoypadButton, JoypadButton)) {
+  for _ in 0..10_000 {
+    if host.netplay.frame() >= frames && guest.netplay.frame() >= frames {
+      return;
+    }
+    let (one, _) = buttons(host.netplay.frame());
+    let (_, two) = buttons(guest.netplay.frame());
+    if host.netplay.frame() < frames {
+      host.step(one);
+    }
+    if guest.netplay.frame() < frames {
+      guest.step(two);
+    }
+  }
+  panic!("stuck at frames {} / {}", host.netplay.frame(), guest.netplay.frame());
+}
+
+fn alternating(frame: u32) -> (JoypadButton, JoypadButton) {
+  let one = if frame % 4 < 2 { JoypadButton::BUTTON_A } else { JoypadButton::empty() };
+  let two = if frame % 6 < 3 { JoypadButton::BUTTON_A } else { JoypadButton::empty() };
+  (one, two)
+}
+
+#[test]
+fn test_lockstep_keeps_both_sides_in_sync() {
+  let (host, guest) = session_pair();
+  let (mut host, mut guest) = (Player::new(host), Player::new(guest));
+
+  play(&mut host, &mut guest, 20, alternating);
+
+  assert_eq!(host.hashes, guest.hashes);
+  assert_eq!(0, host.netplay.resyncs);
+  // player 2's button reached the host's machine
+  assert_eq!(host.nes.cpu.bus.ram()[0x11], guest.nes.cpu.bus.ram()[0x11]);
+}
+
+#[test]
+fn test_desync_is_repaired_with_the_host_state() {
+  let (host, guest) = session_pair();
+  let (mut host, mut guest) = (Player::new(host), Player::new(guest));
+  play(&mut host, &mut guest, 10, alternating);
+
+  // a guest which went its own way
+  guest.nes.cpu.bus.load_ram(&[0x55; 0x800]);
+  play(&mut host, &mut guest, 30, alternating);
+
+  assert_eq!(1, host.netplay.resyncs);
+  assert_eq!(1, guest.netplay.resyncs);
+  assert_eq!(host.hashes.range(20..).collect::<Vec<_>>(), guest.hashes.range(20..).collect::<Vec<_>>());
+  assert_eq!(host.nes.cpu.bus.ram(), guest.nes.cpu.bus.ram());
+}
+
+#[test]
+fn test_waits_for_the_peer() {
+  let (host, _guest) = session_pair();
+  let mut host = Player::new(host);
+
+  host.step(JoypadButton::empty());
+
+  assert_eq!(0, host.netplay.frame());
+}
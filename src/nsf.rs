@@ -0,0 +1,265 @@
+use std::fmt;
+use std::path::Path;
+use crate::apu::{Apu, CPU_FREQUENCY};
+use crate::cpu::{CpuBus, MyCPU, MyMem, StopCondition};
+use crate::error::EmuError;
+
+// NES sound format: the music code of a game without the game, driven by the player.
+// https://wiki.nesdev.org/w/index.php/NSF
+const HEADER_SIZE: usize = 0x80;
+const NSF_TAG: [u8; 5] = [b'N', b'E', b'S', b'M', 0x1A];
+// init and play are called with JSR from here, their RTS lands on an address
+// no code lives at, the cpu stops there
+const RETURN_ADDR: u16 = 0x4100;
+// a routine that runs longer than this is considered stuck
+const MAX_ROUTINE_CYCLES: usize = 1_000_000;
+
+const RAM_END: u16 = 0x1FFF;
+const BANK_REGISTERS: u16 = 0x5FF8;
+const BANK_REGISTERS_END: u16 = 0x5FFF;
+const WRAM: u16 = 0x6000;
+const WRAM_END: u16 = 0x7FFF;
+const ROM: u16 = 0x8000;
+const BANK_SIZE: usize = 0x1000;
+
+#[derive(Debug, PartialEq)]
+pub enum NsfError {
+  TooSmall,
+  BadMagic,
+  InvalidTrack { track: u8, songs: u8 },
+  Stuck(u16),
+}
+
+impl fmt::Display for NsfError {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self {
+      NsfError::TooSmall => write!(f, "File is too small for an NSF header"),
+      NsfError::BadMagic => write!(f, "File is not in NSF format"),
+      NsfError::InvalidTrack { track, songs } => write!(f, "Track {} doesn't exist, there are {} tracks", track, songs),
+      NsfError::Stuck(addr) => write!(f, "Routine at ${:04X} didn't return", addr),
+    }
+  }
+}
+
+impl std::error::Error for NsfError {}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Nsf {
+  pub songs: u8,
+  pub starting_song: u8, // 1 based
+  pub load_addr: u16,
+  pub init_addr: u16,
+  pub play_addr: u16,
+  pub name: String,
+  pub artist: String,
+  pub copyright: String,
+  pub play_speed_us: u16, // ntsc
+  pub bank_init: [u8; 8], // all zero: no bank switching
+  pub data: Vec<u8>,
+}
+
+fn header_text(bytes: &[u8]) -> String {
+  let end = bytes.iter().position(|&b| b == 0).unwrap_or(bytes.len());
+  String::from_utf8_lossy(&bytes[..end]).trim().to_string()
+}
+
+impl Nsf {
+  pub fn load(path: &Path) -> Result<Nsf, EmuError> {
+    let raw = std::fs::read(path)?;
+    Ok(Nsf::new(&raw)?)
+  }
+
+  pub fn new(raw: &[u8]) -> Result<Nsf, NsfError> {
+    if raw.len() < HEADER_SIZE {
+      return Err(NsfError::TooSmall);
+    }
+    if raw[0..5] != NSF_TAG {
+      return Err(NsfError::BadMagic);
+    }
+    let u16_at = |pos: usize| u16::from_le_bytes([raw[pos], raw[pos + 1]]);
+    let mut bank_init = [0; 8];
+    bank_init.copy_from_slice(&raw[0x70..0x78]);
+    Ok(Nsf {
+      songs: raw[0x06],
+      starting_song: raw[0x07],
+      load_addr: u16_at(0x08),
+      init_addr: u16_at(0x0A),
+      play_addr: u16_at(0x0C),
+      name: header_text(&raw[0x0E..0x2E]),
+      artist: header_text(&raw[0x2E..0x4E]),
+      copyright: header_text(&raw[0x4E..0x6E]),
+      play_speed_us: u16_at(0x6E),
+      bank_init,
+      data: raw[HEADER_SIZE..].to_vec(),
+    })
+  }
+
+  pub fn is_bank_switched(&self) -> bool {
+    self.bank_init.iter().any(|&b| b != 0)
+  }
+}
+
+// what the music code sees: ram, apu, 8KB work ram and the (banked) data at $8000
+pub struct NsfBus {
+  ram: [u8; 0x800],
+  wram: [u8; 0x2000],
+  // banked: the data padded to 4KB banks, otherwise the complete $8000-$FFFF
+  rom: Vec<u8>,
+  banks: Option<[u8; 8]>,
+  pub apu: Apu,
+}
+
+impl NsfBus {
+  pub fn new(nsf: &Nsf, sample_rate: u32) -> Self {
+    let (rom, banks) = if nsf.is_bank_switched() {
+      let mut rom = vec![0; nsf.load_addr as usize & (BANK_SIZE - 1)];
+      rom.extend_from_slice(&nsf.data);
+      rom.resize(rom.len().div_ceil(BANK_SIZE) * BANK_SIZE, 0);
+      (rom, Some(nsf.bank_init))
+    } else {
+      let mut rom = vec![0; 0x8000];
+      let start = nsf.load_addr.saturating_sub(ROM) as usize;
+      let len = nsf.data.len().min(rom.len() - start);
+      rom[start..start + len].copy_from_slice(&nsf.data[..len]);
+      (rom, None)
+    };
+    NsfBus { ram: [0; 0x800], wram: [0; 0x2000], rom, banks, apu: Apu::with_sample_rate(sample_rate) }
+  }
+
+  fn read_rom(&self, addr: u16) -> u8 {
+    let offset = (addr - ROM) as usize;
+    let offset = match self.banks {
+      Some(banks) => banks[offset / BANK_SIZE] as usize * BANK_SIZE + offset % BANK_SIZE,
+      None => offset,
+    };
+    self.rom.get(offset).copied().unwrap_or(0)
+  }
+}
+
+impl MyMem for NsfBus {
+  fn mem_read(&mut self, addr: u16) -> u8 {
+    match addr {
+      0x4015 => self.apu.read_status(),
+      _ => self.peek(addr),
+    }
+  }
+
+  fn mem_write(&mut self, addr: u16, data: u8) {
+    match addr {
+      0 ..= RAM_END => self.ram[(addr & 0x7FF) as usize] = data,
+      0x4000 ..= 0x4013 => self.apu.write_register(addr, data),
+      0x4015 => self.apu.write_status(data),
+      0x4017 => self.apu.write_frame_counter(data),
+      0x4014 | 0x4016 => {}
+      BANK_REGISTERS ..= BANK_REGISTERS_END => {
+        if let Some(banks) = self.banks.as_mut() {
+          banks[(addr - BANK_REGISTERS) as usize] = data;
+        }
+      }
+      WRAM ..= WRAM_END => self.wram[(addr - WRAM) as usize] = data,
+      _ => {}
+    }
+  }
+}
+
+impl CpuBus for NsfBus {
+  fn peek(&self, addr: u16) -> u8 {
+    match addr {
+      0 ..= RAM_END => self.ram[(addr & 0x7FF) as usize],
+      WRAM ..= WRAM_END => self.wram[(addr - WRAM) as usize],
+      ROM ..= 0xFFFF => self.read_rom(addr),
+      _ => 0,
+    }
+  }
+
+  fn poke(&mut self, addr: u16, data: u8) -> bool {
+    match addr {
+      0 ..= RAM_END | WRAM ..= WRAM_END => {
+        self.mem_write(addr, data);
+        true
+      }
+      _ => false,
+    }
+  }
+
+  fn tick(&mut self, cycles: u16) {
+    self.apu.tick(cycles);
+  }
+}
+
+// calls init once per track and play at the speed from the header
+pub struct NsfPlayer {
+  pub nsf: Nsf,
+  pub cpu: MyCPU<NsfBus>,
+  cycles_per_play: f64,
+  // cycles the next play call may use, the rest of a call's time is spent idle
+  cycle_budget: f64,
+}
+
+impl NsfPlayer {
+  pub fn new(nsf: Nsf, sample_rate: u32) -> Self {
+    let mut cpu = MyCPU::new(NsfBus::new(&nsf, sample_rate));
+    cpu.stop_condition = StopCondition::ProgramCounter(RETURN_ADDR);
+    let speed = if nsf.play_speed_us == 0 { 16_639 } else { nsf.play_speed_us };
+    let cycles_per_play = CPU_FREQUENCY * speed as f64 / 1_000_000.0;
+    NsfPlayer { nsf, cpu, cycles_per_play, cycle_budget: 0.0 }
+  }
+
+  // track is 1 based, like starting_song in the header
+  pub fn start_track(&mut self, track: u8) -> Result<(), NsfError> {
+    if track == 0 || track > self.nsf.songs {
+      return Err(NsfError::InvalidTrack { track, songs: self.nsf.songs });
+    }
+    let sample_rate = self.cpu.bus.apu.sample_rate();
+    self.cpu.bus = NsfBus::new(&self.nsf, sample_rate);
+    for addr in 0x4000..=0x4013 {
+      self.cpu.mem_write(addr, 0x00);
+    }
+    self.cpu.mem_write(0x4015, 0x0F);
+    self.cpu.mem_write(0x4017, 0x40);
+
+    self.cpu.reset();
+    self.cpu.register_a = track - 1;
+    self.cpu.register_x = 0; // ntsc
+    self.cycle_budget = 0.0;
+    self.call(self.nsf.init_addr)
+  }
+
+  // one call of the play routine plus the idle time until the next one is due
+  pub fn play_frame(&mut self) -> Result<(), NsfError> {
+    self.cycle_budget += self.cycles_per_play;
+    self.call(self.nsf.play_addr)?;
+    while self.cycle_budget >= 1.0 {
+      let idle = self.cycle_budget.min(u16::MAX as f64) as u16;
+      self.cpu.bus.tick(idle);
+      self.cycle_budget -= idle as f64;
+    }
+    Ok(())
+  }
+
+  pub fn take_samples(&mut self) -> Vec<f32> {
+    self.cpu.bus.apu.take_samples()
+  }
+
+  // JSR to addr from RETURN_ADDR - 1, runs until the routine returned
+  fn call(&mut self, addr: u16) -> Result<(), NsfError> {
+    let return_addr = RETURN_ADDR - 1;
+    let sp = self.cpu.stack_pointer;
+    self.cpu.mem_write(0x0100 + sp as u16, (return_addr >> 8) as u8);
+    self.cpu.mem_write(0x0100 + sp.wrapping_sub(1) as u16, return_addr as u8);
+    self.cpu.stack_pointer = sp.wrapping_sub(2);
+    self.cpu.program_counter = addr;
+
+    let start = self.cpu.cycles;
+    while self.cpu.step().is_some() {
+      if self.cpu.cycles - start > MAX_ROUTINE_CYCLES {
+        return Err(NsfError::Stuck(addr));
+      }
+    }
+    if self.cpu.program_counter != RETURN_ADDR {
+      return Err(NsfError::Stuck(addr));
+    }
+    self.cycle_budget -= (self.cpu.cycles - start) as f64;
+    Ok(())
+  }
+}
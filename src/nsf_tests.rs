@@ -0,0 +1,124 @@
+use crate::cpu::{CpuBus, MyMem};
+use crate::nsf::{Nsf, NsfBus, NsfError, NsfPlayer};
+
+// init stores the song number at $0200, play counts its calls at $0201
+const PROGRAM: [u8; 8] = [
+  0x8D, 0x00, 0x02, // STA $0200
+  0x60,             // RTS
+  0xEE, 0x01, 0x02, // INC $0201
+  0x60,             // RTS
+];
+
+fn nsf_bytes(data: &[u8], bank_init: [u8; 8]) -> Vec<u8> {
+  let mut raw = vec![0; 0x80];
+  raw[0..5].copy_from_slice(b"NESM\x1A");
+  raw[0x05] = 1;
+  raw[0x06] = 3;
+  raw[0x07] = 2;
+  raw[0x08..0x0A].copy_from_slice(&0x8000u16.to_le_bytes());
+  raw[0x0A..0x0C].copy_from_slice(&0x8000u16.to_le_bytes());
+  raw[0x0C..0x0E].copy_from_slice(&0x8004u16.to_le_bytes());
+  raw[0x0E..0x14].copy_from_slice(b"Tunes\0");
+  raw[0x2E..0x32].copy_from_slice(b"Me\0\0");
+  raw[0x6E..0x70].copy_from_slice(&16_639u16.to_le_bytes());
+  raw[0x70..0x78].copy_from_slice(&bank_init);
+  raw.extend_from_slice(data);
+  raw
+}
+
+fn test_nsf() -> Nsf {
+  Nsf::new(&nsf_bytes(&PROGRAM, [0; 8])).unwrap()
+}
+
+#[test]
+fn test_header() {
+  let nsf = test_nsf();
+
+  assert_eq!(3, nsf.songs);
+  assert_eq!(2, nsf.starting_song);
+  assert_eq!(0x8000, nsf.load_addr);
+  assert_eq!(0x8000, nsf.init_addr);
+  assert_eq!(0x8004, nsf.play_addr);
+  assert_eq!("Tunes", nsf.name);
+  assert_eq!("Me", nsf.artist);
+  assert_eq!(16_639, nsf.play_speed_us);
+  assert!(!nsf.is_bank_switched());
+  assert_eq!(PROGRAM.to_vec(), nsf.data);
+}
+
+#[test]
+fn test_bad_header() {
+  let mut raw = nsf_bytes(&PROGRAM, [0; 8]);
+  raw[0] = b'X';
+
+  assert_eq!(Err(NsfError::BadMagic), Nsf::new(&raw));
+  assert_eq!(Err(NsfError::TooSmall), Nsf::new(&raw[..0x40]));
+}
+
+#[test]
+fn test_init_gets_the_song_index() {
+  let mut player = NsfPlayer::new(test_nsf(), 44_100);
+
+  player.start_track(3).unwrap();
+
+  assert_eq!(2, player.cpu.bus.peek(0x0200));
+  assert_eq!(0, player.cpu.bus.peek(0x0201));
+}
+
+#[test]
+fn test_invalid_track() {
+  let mut player = NsfPlayer::new(test_nsf(), 44_100);
+
+  assert_eq!(Err(NsfError::InvalidTrack { track: 4, songs: 3 }), player.start_track(4));
+  assert!(player.start_track(0).is_err());
+}
+
+#[test]
+fn test_play_is_called_once_per_frame() {
+  let mut player = NsfPlayer::new(test_nsf(), 44_100);
+  player.start_track(1).unwrap();
+
+  for _ in 0..5 {
+    player.play_frame().unwrap();
+  }
+
+  assert_eq!(5, player.cpu.bus.peek(0x0201));
+}
+
+#[test]
+fn test_a_frame_of_samples_per_play() {
+  let mut player = NsfPlayer::new(test_nsf(), 44_100);
+  player.start_track(1).unwrap();
+  player.take_samples();
+
+  for _ in 0..6 {
+    player.play_frame().unwrap();
+  }
+
+  // 6 calls at 16639us take about a tenth of a second
+  let samples = player.take_samples().len();
+  assert!((4_400..=4_410).contains(&samples), "{}", samples);
+}
+
+#[test]
+fn test_routine_without_rts_is_stuck() {
+  // JMP $8000
+  let nsf = Nsf::new(&nsf_bytes(&[0x4C, 0x00, 0x80], [0; 8])).unwrap();
+  let mut player = NsfPlayer::new(nsf, 44_100);
+
+  assert_eq!(Err(NsfError::Stuck(0x8000)), player.start_track(1));
+}
+
+#[test]
+fn test_bank_switching() {
+  let mut data = vec![0x11; 0x1000];
+  data.extend(vec![0x22; 0x1000]);
+  let nsf = Nsf::new(&nsf_bytes(&data, [0, 1, 0, 0, 0, 0, 0, 0])).unwrap();
+  let mut bus = NsfBus::new(&nsf, 44_100);
+
+  assert_eq!(0x11, bus.peek(0x8000));
+  assert_eq!(0x22, bus.peek(0x9000));
+
+  bus.mem_write(0x5FF8, 1);
+  assert_eq!(0x22, bus.peek(0x8000));
+}
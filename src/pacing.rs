@@ -0,0 +1,63 @@
+use std::thread;
+use std::time::{Duration, Instant};
+
+// sleep() can overshoot by a scheduler tick, the rest is waited for by spinning
+const SPIN_TIME: Duration = Duration::from_millis(2);
+// further behind than this (slow host, pause, debugger) the pacer starts over
+// instead of running fast until it caught up
+const MAX_LAG_FRAMES: u32 = 4;
+
+// deadlines are derived from the previous deadline, not from when the frame
+// actually ended, so oversleeping once doesn't add up over a long session
+#[derive(Debug, Clone)]
+pub struct FramePacer {
+  frame_time: Duration,
+  deadline: Option<Instant>,
+}
+
+impl FramePacer {
+  pub fn new(frames_per_second: f64) -> Self {
+    FramePacer { frame_time: Duration::from_secs_f64(1.0 / frames_per_second), deadline: None }
+  }
+
+  pub fn frame_time(&self) -> Duration {
+    self.frame_time
+  }
+
+  // e.g. for a speed multiplier, keeps the current deadline
+  pub fn set_frame_time(&mut self, frame_time: Duration) {
+    self.frame_time = frame_time;
+  }
+
+  // the next frame is timed from whenever it ends
+  pub fn reset(&mut self) {
+    self.deadline = None;
+  }
+
+  // when the frame that ended at `now` should be shown
+  pub fn next_deadline(&mut self, now: Instant) -> Instant {
+    let deadline = match self.deadline {
+      Some(previous) => previous + self.frame_time,
+      None => now + self.frame_time,
+    };
+    let deadline = if now > deadline + self.frame_time * MAX_LAG_FRAMES { now } else { deadline };
+    self.deadline = Some(deadline);
+    deadline
+  }
+
+  // blocks until the frame that just ended is due
+  pub fn wait(&mut self) {
+    let deadline = self.next_deadline(Instant::now());
+    sleep_until(deadline);
+  }
+}
+
+pub fn sleep_until(deadline: Instant) {
+  let now = Instant::now();
+  if deadline > now + SPIN_TIME {
+    thread::sleep(deadline - now - SPIN_TIME);
+  }
+  while Instant::now() < deadline {
+    std::hint::spin_loop();
+  }
+}
@@ -0,0 +1,70 @@
+use std::time::{Duration, Instant};
+use crate::clock::Region;
+use crate::pacing::FramePacer;
+
+#[test]
+fn test_ntsc_frame_time() {
+  let pacer = FramePacer::new(Region::Ntsc.frames_per_second());
+
+  assert_eq!(16_639, pacer.frame_time().as_micros());
+}
+
+#[test]
+fn test_late_frames_dont_drift() {
+  let start = Instant::now();
+  let mut pacer = FramePacer::new(Region::Ntsc.frames_per_second());
+  let frame_time = pacer.frame_time();
+
+  let mut deadline = pacer.next_deadline(start);
+  for _ in 1..600 {
+    // every frame ends a bit after its deadline
+    deadline = pacer.next_deadline(deadline + Duration::from_micros(500));
+  }
+
+  assert_eq!(start + frame_time * 600, deadline);
+}
+
+#[test]
+fn test_early_frames_wait_for_their_deadline() {
+  let start = Instant::now();
+  let mut pacer = FramePacer::new(50.0);
+
+  pacer.next_deadline(start);
+  let deadline = pacer.next_deadline(start + Duration::from_millis(1));
+
+  assert_eq!(start + Duration::from_millis(40), deadline);
+}
+
+#[test]
+fn test_lagging_far_behind_starts_over() {
+  let start = Instant::now();
+  let mut pacer = FramePacer::new(50.0);
+  pacer.next_deadline(start);
+
+  let late = start + Duration::from_secs(1);
+  assert_eq!(late, pacer.next_deadline(late));
+  assert_eq!(late + Duration::from_millis(20), pacer.next_deadline(late));
+}
+
+#[test]
+fn test_reset_and_new_frame_time() {
+  let start = Instant::now();
+  let mut pacer = FramePacer::new(50.0);
+  pacer.next_deadline(start);
+
+  pacer.reset();
+  pacer.set_frame_time(Duration::from_millis(10));
+
+  let later = start + Duration::from_millis(5);
+  assert_eq!(later + Duration::from_millis(10), pacer.next_deadline(later));
+}
+
+#[test]
+fn test_wait_blocks_until_the_deadline() {
+  let mut pacer = FramePacer::new(200.0);
+  let start = Instant::now();
+
+  pacer.wait();
+
+  assert!(start.elapsed() >= Duration::from_millis(5));
+}
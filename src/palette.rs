@@ -0,0 +1,177 @@
+use std::io;
+use std::path::Path;
+
+// 2C02 colors as rgb, indexed by the 6 bit values stored in palette ram
+#[rustfmt::skip]
+pub static SYSTEM_PALETTE: [(u8, u8, u8); 64] = [
+  (0x80, 0x80, 0x80), (0x00, 0x3D, 0xA6), (0x00, 0x12, 0xB0), (0x44, 0x00, 0x96), (0xA1, 0x00, 0x5E),
+  (0xC7, 0x00, 0x28), (0xBA, 0x06, 0x00), (0x8C, 0x17, 0x00), (0x5C, 0x2F, 0x00), (0x10, 0x45, 0x00),
+  (0x05, 0x4A, 0x00), (0x00, 0x47, 0x2E), (0x00, 0x41, 0x66), (0x00, 0x00, 0x00), (0x05, 0x05, 0x05),
+  (0x05, 0x05, 0x05), (0xC7, 0xC7, 0xC7), (0x00, 0x77, 0xFF), (0x21, 0x55, 0xFF), (0x82, 0x37, 0xFA),
+  (0xEB, 0x2F, 0xB5), (0xFF, 0x29, 0x50), (0xFF, 0x22, 0x00), (0xD6, 0x32, 0x00), (0xC4, 0x62, 0x00),
+  (0x35, 0x80, 0x00), (0x05, 0x8F, 0x00), (0x00, 0x8A, 0x55), (0x00, 0x99, 0xCC), (0x21, 0x21, 0x21),
+  (0x09, 0x09, 0x09), (0x09, 0x09, 0x09), (0xFF, 0xFF, 0xFF), (0x0F, 0xD7, 0xFF), (0x69, 0xA2, 0xFF),
+  (0xD4, 0x80, 0xFF), (0xFF, 0x45, 0xF3), (0xFF, 0x61, 0x8B), (0xFF, 0x88, 0x33), (0xFF, 0x9C, 0x12),
+  (0xFA, 0xBC, 0x20), (0x9F, 0xE3, 0x0E), (0x2B, 0xF0, 0x35), (0x0C, 0xF0, 0xA4), (0x05, 0xFB, 0xFF),
+  (0x5E, 0x5E, 0x5E), (0x0D, 0x0D, 0x0D), (0x0D, 0x0D, 0x0D), (0xFF, 0xFF, 0xFF), (0xA6, 0xFC, 0xFF),
+  (0xB3, 0xEC, 0xFF), (0xDA, 0xAB, 0xEB), (0xFF, 0xA8, 0xF9), (0xFF, 0xAB, 0xB3), (0xFF, 0xD2, 0xB0),
+  (0xFF, 0xEF, 0xA6), (0xFF, 0xF7, 0x9C), (0xD7, 0xE8, 0x95), (0xA6, 0xED, 0xAF), (0xA2, 0xF2, 0xDA),
+  (0x99, 0xFF, 0xFC), (0xDD, 0xDD, 0xDD), (0x11, 0x11, 0x11), (0x11, 0x11, 0x11),
+];
+
+// fceux's default palette
+#[rustfmt::skip]
+pub static FCEUX_PALETTE: [(u8, u8, u8); 64] = [
+  (0x74, 0x74, 0x74), (0x24, 0x18, 0x8C), (0x00, 0x00, 0xA8), (0x44, 0x00, 0x9C), (0x8C, 0x00, 0x74),
+  (0xA8, 0x00, 0x10), (0xA4, 0x00, 0x00), (0x7C, 0x08, 0x00), (0x40, 0x2C, 0x00), (0x00, 0x44, 0x00),
+  (0x00, 0x50, 0x00), (0x00, 0x3C, 0x14), (0x18, 0x3C, 0x5C), (0x00, 0x00, 0x00), (0x00, 0x00, 0x00),
+  (0x00, 0x00, 0x00), (0xBC, 0xBC, 0xBC), (0x00, 0x70, 0xEC), (0x20, 0x38, 0xEC), (0x80, 0x00, 0xF0),
+  (0xBC, 0x00, 0xBC), (0xE4, 0x00, 0x58), (0xD8, 0x28, 0x00), (0xC8, 0x4C, 0x0C), (0x88, 0x70, 0x00),
+  (0x00, 0x94, 0x00), (0x00, 0xA8, 0x00), (0x00, 0x90, 0x38), (0x00, 0x80, 0x88), (0x00, 0x00, 0x00),
+  (0x00, 0x00, 0x00), (0x00, 0x00, 0x00), (0xFC, 0xFC, 0xFC), (0x3C, 0xBC, 0xFC), (0x5C, 0x94, 0xFC),
+  (0xCC, 0x88, 0xFC), (0xF4, 0x78, 0xFC), (0xFC, 0x74, 0xB4), (0xFC, 0x74, 0x60), (0xFC, 0x98, 0x38),
+  (0xF0, 0xBC, 0x3C), (0x80, 0xD0, 0x10), (0x4C, 0xDC, 0x48), (0x58, 0xF8, 0x98), (0x00, 0xE8, 0xD8),
+  (0x78, 0x78, 0x78), (0x00, 0x00, 0x00), (0x00, 0x00, 0x00), (0xFC, 0xFC, 0xFC), (0xA8, 0xE4, 0xFC),
+  (0xC4, 0xD4, 0xFC), (0xD4, 0xC8, 0xFC), (0xFC, 0xC4, 0xFC), (0xFC, 0xC4, 0xD8), (0xFC, 0xBC, 0xB0),
+  (0xFC, 0xD8, 0xA8), (0xFC, 0xE4, 0xA0), (0xE0, 0xFC, 0xA0), (0xA8, 0xF0, 0xBC), (0xB0, 0xFC, 0xCC),
+  (0x9C, 0xFC, 0xF0), (0xC4, 0xC4, 0xC4), (0x00, 0x00, 0x00), (0x00, 0x00, 0x00),
+];
+
+// a tv with the sony cxa2025as decoder
+#[rustfmt::skip]
+pub static SONY_CXA_PALETTE: [(u8, u8, u8); 64] = [
+  (0x58, 0x58, 0x58), (0x00, 0x23, 0x8C), (0x00, 0x13, 0x9B), (0x2D, 0x05, 0x85), (0x5D, 0x00, 0x52),
+  (0x7A, 0x00, 0x17), (0x7A, 0x08, 0x00), (0x5F, 0x18, 0x00), (0x35, 0x2A, 0x00), (0x09, 0x39, 0x00),
+  (0x00, 0x3F, 0x00), (0x00, 0x3C, 0x22), (0x00, 0x32, 0x5D), (0x00, 0x00, 0x00), (0x00, 0x00, 0x00),
+  (0x00, 0x00, 0x00), (0xA1, 0xA1, 0xA1), (0x00, 0x53, 0xEE), (0x15, 0x3C, 0xFE), (0x60, 0x28, 0xE4),
+  (0xA9, 0x1D, 0x98), (0xD4, 0x1E, 0x41), (0xD2, 0x2C, 0x00), (0xAA, 0x44, 0x00), (0x6C, 0x5E, 0x00),
+  (0x2D, 0x73, 0x00), (0x00, 0x7D, 0x06), (0x00, 0x78, 0x52), (0x00, 0x69, 0xA9), (0x00, 0x00, 0x00),
+  (0x00, 0x00, 0x00), (0x00, 0x00, 0x00), (0xFF, 0xFF, 0xFF), (0x1F, 0xA5, 0xFE), (0x5E, 0x89, 0xFE),
+  (0xB5, 0x72, 0xFE), (0xFE, 0x65, 0xF6), (0xFE, 0x67, 0x90), (0xFE, 0x77, 0x3C), (0xFE, 0x93, 0x08),
+  (0xC4, 0xB2, 0x00), (0x79, 0xCA, 0x10), (0x3A, 0xD5, 0x4A), (0x11, 0xD1, 0xA4), (0x06, 0xBF, 0xFE),
+  (0x42, 0x42, 0x42), (0x00, 0x00, 0x00), (0x00, 0x00, 0x00), (0xFF, 0xFF, 0xFF), (0xA0, 0xD9, 0xFE),
+  (0xBD, 0xCC, 0xFE), (0xE1, 0xC2, 0xFE), (0xFE, 0xBC, 0xFB), (0xFE, 0xBD, 0xD0), (0xFE, 0xC5, 0xA9),
+  (0xFE, 0xD1, 0x8E), (0xE9, 0xDE, 0x86), (0xC7, 0xE9, 0x92), (0xA8, 0xEE, 0xB0), (0x95, 0xEC, 0xD9),
+  (0x91, 0xE4, 0xFE), (0xAC, 0xAC, 0xAC), (0x00, 0x00, 0x00), (0x00, 0x00, 0x00),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BuiltinPalette {
+  Ntsc2C02,
+  Fceux,
+  SonyCxa,
+}
+
+impl BuiltinPalette {
+  pub fn from_name(name: &str) -> Option<BuiltinPalette> {
+    match name {
+      "2c02" => Some(BuiltinPalette::Ntsc2C02),
+      "fceux" => Some(BuiltinPalette::Fceux),
+      "sony" => Some(BuiltinPalette::SonyCxa),
+      _ => None,
+    }
+  }
+
+  fn colors(&self) -> &'static [(u8, u8, u8); 64] {
+    match self {
+      BuiltinPalette::Ntsc2C02 => &SYSTEM_PALETTE,
+      BuiltinPalette::Fceux => &FCEUX_PALETTE,
+      BuiltinPalette::SonyCxa => &SONY_CXA_PALETTE,
+    }
+  }
+}
+
+// an emphasized channel keeps its level, the others are attenuated
+// https://wiki.nesdev.org/w/index.php/NTSC_video#Color_Tint_Bits
+const EMPHASIS_ATTENUATION: f32 = 0.816_328;
+
+// what the ppu's 6 bit colors look like, .pal files hold rgb triples:
+// 64 colors, or 512 with a block of 64 for each combination of the emphasis bits.
+// The blocks are computed for palettes without them, so lookups stay a table read
+#[derive(Debug, Clone, PartialEq)]
+pub struct Palette {
+  colors: Vec<(u8, u8, u8)>,
+  // the same colors as little endian rgba words, for rgba::indexed_to_rgba
+  rgba: Box<[u32; 512]>,
+}
+
+// what the ppu outputs per pixel: the 6 bit color with the emphasis bits above it
+pub fn color_index(entry: u8, emphasis: u8) -> u16 {
+  (emphasis & 0b111) as u16 * 64 + (entry & 0x3F) as u16
+}
+
+impl Default for Palette {
+  fn default() -> Self {
+    Palette::builtin(BuiltinPalette::Ntsc2C02)
+  }
+}
+
+impl Palette {
+  pub fn builtin(palette: BuiltinPalette) -> Self {
+    Palette::with_emphasis(palette.colors())
+  }
+
+  fn with_emphasis(base: &[(u8, u8, u8)]) -> Self {
+    let colors = (0..8u8)
+      .flat_map(|emphasis| base.iter().map(move |&rgb| emphasize(rgb, emphasis)))
+      .collect();
+    Palette::with_colors(colors)
+  }
+
+  fn with_colors(colors: Vec<(u8, u8, u8)>) -> Self {
+    let mut rgba = Box::new([0; 512]);
+    for (word, &(r, g, b)) in rgba.iter_mut().zip(&colors) {
+      *word = u32::from_le_bytes([r, g, b, 0xFF]);
+    }
+    Palette { colors, rgba }
+  }
+
+  pub fn from_pal(bytes: &[u8]) -> Result<Palette, String> {
+    if bytes.len() != 64 * 3 && bytes.len() != 512 * 3 {
+      return Err(format!("a palette has 64 or 512 colors (192 or 1536 bytes), not {} bytes", bytes.len()));
+    }
+    let colors: Vec<(u8, u8, u8)> = bytes.chunks(3).map(|rgb| (rgb[0], rgb[1], rgb[2])).collect();
+    if colors.len() == 64 {
+      return Ok(Palette::with_emphasis(&colors));
+    }
+    Ok(Palette::with_colors(colors))
+  }
+
+  pub fn load(path: &Path) -> io::Result<Palette> {
+    Palette::from_pal(&std::fs::read(path)?).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+  }
+
+  // a builtin name or a .pal file
+  pub fn by_name_or_path(name: &str) -> io::Result<Palette> {
+    match BuiltinPalette::from_name(name) {
+      Some(builtin) => Ok(Palette::builtin(builtin)),
+      None => Palette::load(Path::new(name)),
+    }
+  }
+
+  // emphasis: PPUMASK bits 5-7 (red, green, blue)
+  pub fn color(&self, entry: u8, emphasis: u8) -> (u8, u8, u8) {
+    self.rgb(color_index(entry, emphasis))
+  }
+
+  // of a color_index
+  pub fn rgb(&self, index: u16) -> (u8, u8, u8) {
+    self.colors[(index & 0x1FF) as usize]
+  }
+
+  pub fn rgba_table(&self) -> &[u32; 512] {
+    &self.rgba
+  }
+}
+
+// all three bits set darken every channel
+fn emphasize((r, g, b): (u8, u8, u8), emphasis: u8) -> (u8, u8, u8) {
+  let attenuate = |level: u8, bit: u8| {
+    if emphasis != 0 && (emphasis & bit == 0 || emphasis == 0b111) {
+      (level as f32 * EMPHASIS_ATTENUATION).round() as u8
+    } else {
+      level
+    }
+  };
+  (attenuate(r, 0b001), attenuate(g, 0b010), attenuate(b, 0b100))
+}
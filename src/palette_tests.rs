@@ -0,0 +1,77 @@
+use crate::palette::{BuiltinPalette, FCEUX_PALETTE, Palette, SYSTEM_PALETTE};
+use crate::ppu::{MaskRegister, NesPPU};
+use crate::render;
+use crate::frame::Frame;
+use crate::cartridge::Mirroring;
+
+#[test]
+fn test_builtin_palettes() {
+  assert_eq!(SYSTEM_PALETTE[0x16], Palette::default().color(0x16, 0));
+  assert_eq!(FCEUX_PALETTE[0x30], Palette::builtin(BuiltinPalette::Fceux).color(0x30, 0));
+  assert_eq!(Some(BuiltinPalette::SonyCxa), BuiltinPalette::from_name("sony"));
+  assert_eq!(None, BuiltinPalette::from_name("vga"));
+}
+
+#[test]
+fn test_pal_file_with_64_colors() {
+  let bytes: Vec<u8> = (0..64u8).flat_map(|i| [i, i, 255 - i]).collect();
+  let palette = Palette::from_pal(&bytes).unwrap();
+
+  assert_eq!((5, 5, 250), palette.color(5, 0));
+  // only the lower 6 bits select a color
+  assert_eq!((5, 5, 250), palette.color(0x45, 0));
+}
+
+#[test]
+fn test_pal_file_with_emphasis_colors() {
+  let bytes: Vec<u8> = (0..512u32).flat_map(|i| [(i / 64) as u8, (i % 64) as u8, 0]).collect();
+  let palette = Palette::from_pal(&bytes).unwrap();
+
+  assert_eq!((0, 3, 0), palette.color(3, 0));
+  assert_eq!((6, 3, 0), palette.color(3, 0b110));
+}
+
+#[test]
+fn test_invalid_pal_size() {
+  assert!(Palette::from_pal(&[0; 100]).is_err());
+}
+
+#[test]
+fn test_renderer_uses_the_ppu_palette() {
+  let mut ppu = NesPPU::new(vec![0; 0x2000], Mirroring::HORIZONTAL);
+  ppu.palette = Palette::builtin(BuiltinPalette::Fceux);
+  ppu.palette_table[0] = 0x21;
+  ppu.mask = MaskRegister::empty();
+  let mut frame = Frame::new();
+
+  render::render(&ppu, &mut frame);
+
+  assert_eq!(FCEUX_PALETTE[0x21], frame.get_pixel(0, 0));
+}
+
+#[test]
+fn test_emphasis_attenuates_the_other_channels() {
+  let bytes: Vec<u8> = (0..64).flat_map(|_| [200, 100, 50]).collect();
+  let palette = Palette::from_pal(&bytes).unwrap();
+
+  assert_eq!((200, 82, 41), palette.color(0, 0b001)); // red
+  assert_eq!((163, 100, 41), palette.color(0, 0b010)); // green
+  assert_eq!((200, 82, 50), palette.color(0, 0b101)); // red + blue
+  assert_eq!((163, 82, 41), palette.color(0, 0b111));
+}
+
+#[test]
+fn test_greyscale_and_emphasis_in_the_renderer() {
+  let mut ppu = NesPPU::new(vec![0; 0x2000], Mirroring::HORIZONTAL);
+  ppu.palette_table[0] = 0x21;
+  let mut frame = Frame::new();
+
+  ppu.mask = MaskRegister::GREYSCALE;
+  render::render(&ppu, &mut frame);
+  assert_eq!(SYSTEM_PALETTE[0x20], frame.get_pixel(0, 0));
+
+  ppu.mask = MaskRegister::EMPHASISE_BLUE;
+  render::render(&ppu, &mut frame);
+  assert_eq!(Palette::default().color(0x21, 0b100), frame.get_pixel(0, 0));
+  assert_ne!(SYSTEM_PALETTE[0x21], frame.get_pixel(0, 0));
+}
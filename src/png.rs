@@ -0,0 +1,62 @@
+use crate::power_on::crc32;
+
+// minimal png writer: 8 bit rgb, no filters, zlib with uncompressed (stored) deflate blocks.
+// Files are bigger than from a real encoder, but every viewer can open them.
+// https://www.w3.org/TR/png/
+
+const SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1A, b'\n'];
+const MAX_STORED_BLOCK: usize = 0xFFFF;
+
+pub fn encode_rgb(width: usize, height: usize, rgb: &[u8]) -> Vec<u8> {
+  assert_eq!(width * height * 3, rgb.len(), "rgb data doesn't match {}x{}", width, height);
+
+  let mut header = Vec::with_capacity(13);
+  header.extend_from_slice(&(width as u32).to_be_bytes());
+  header.extend_from_slice(&(height as u32).to_be_bytes());
+  header.extend_from_slice(&[8, 2, 0, 0, 0]); // bit depth, truecolor, deflate, no filter, no interlace
+
+  // every row starts with its filter type
+  let mut raw = Vec::with_capacity(rgb.len() + height);
+  for row in rgb.chunks_exact(width * 3) {
+    raw.push(0);
+    raw.extend_from_slice(row);
+  }
+
+  let mut png = SIGNATURE.to_vec();
+  write_chunk(&mut png, b"IHDR", &header);
+  write_chunk(&mut png, b"IDAT", &zlib_stored(&raw));
+  write_chunk(&mut png, b"IEND", &[]);
+  png
+}
+
+fn write_chunk(png: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+  png.extend_from_slice(&(data.len() as u32).to_be_bytes());
+  let start = png.len();
+  png.extend_from_slice(kind);
+  png.extend_from_slice(data);
+  let crc = crc32(&png[start..]);
+  png.extend_from_slice(&crc.to_be_bytes());
+}
+
+fn zlib_stored(data: &[u8]) -> Vec<u8> {
+  let mut zlib = vec![0x78, 0x01];
+  let blocks = data.len().div_ceil(MAX_STORED_BLOCK).max(1);
+  for i in 0..blocks {
+    let block = &data[i * MAX_STORED_BLOCK..data.len().min((i + 1) * MAX_STORED_BLOCK)];
+    zlib.push((i == blocks - 1) as u8);
+    zlib.extend_from_slice(&(block.len() as u16).to_le_bytes());
+    zlib.extend_from_slice(&(!(block.len() as u16)).to_le_bytes());
+    zlib.extend_from_slice(block);
+  }
+  zlib.extend_from_slice(&adler32(data).to_be_bytes());
+  zlib
+}
+
+fn adler32(data: &[u8]) -> u32 {
+  let (mut a, mut b) = (1u32, 0u32);
+  for &byte in data {
+    a = (a + byte as u32) % 65521;
+    b = (b + a) % 65521;
+  }
+  b << 16 | a
+}
@@ -0,0 +1,81 @@
+use std::collections::HashMap;
+use rand::{Rng, SeedableRng};
+use rand::rngs::StdRng;
+use crate::cartridge::Rom;
+
+// contents of memory right after power-on, real consoles don't start with zeroed ram
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RamInit {
+  Zero,
+  Ones,
+  // $00 and $FF alternating every 256 bytes page
+  AlternatingPages,
+  Random(u64),
+}
+
+impl RamInit {
+  pub fn fill(&self, memory: &mut [u8]) {
+    match self {
+      RamInit::Zero => memory.fill(0x00),
+      RamInit::Ones => memory.fill(0xFF),
+      RamInit::AlternatingPages => {
+        for (page, chunk) in memory.chunks_mut(0x100).enumerate() {
+          chunk.fill(if page % 2 == 0 { 0x00 } else { 0xFF });
+        }
+      }
+      RamInit::Random(seed) => StdRng::seed_from_u64(*seed).fill(memory),
+    }
+  }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PowerOnState {
+  pub cpu_ram: RamInit,
+  pub vram: RamInit,
+  pub oam: RamInit,
+  pub palette: RamInit,
+}
+
+impl Default for PowerOnState {
+  fn default() -> Self {
+    PowerOnState {
+      cpu_ram: RamInit::Zero,
+      vram: RamInit::Zero,
+      oam: RamInit::Zero,
+      palette: RamInit::Zero,
+    }
+  }
+}
+
+// per-game overrides, keyed by the crc32 of the prg rom
+#[derive(Default)]
+pub struct GameOverrides {
+  power_on: HashMap<u32, PowerOnState>,
+}
+
+impl GameOverrides {
+  pub fn new() -> Self {
+    GameOverrides::default()
+  }
+
+  pub fn insert(&mut self, prg_crc32: u32, state: PowerOnState) {
+    self.power_on.insert(prg_crc32, state);
+  }
+
+  pub fn power_on_state(&self, rom: &Rom, default: PowerOnState) -> PowerOnState {
+    self.power_on.get(&crc32(&rom.prg_rom)).copied().unwrap_or(default)
+  }
+}
+
+// crc-32 (ieee), as used by common rom databases
+pub fn crc32(data: &[u8]) -> u32 {
+  let mut crc = 0xFFFF_FFFFu32;
+  for &byte in data {
+    crc ^= byte as u32;
+    for _ in 0..8 {
+      let mask = (!(crc & 1)).wrapping_add(1);
+      crc = (crc >> 1) ^ (0xEDB8_8320 & mask);
+    }
+  }
+  !crc
+}
@@ -0,0 +1,59 @@
+use crate::bus::Bus;
+use crate::cartridge_tests::create_test_rom;
+use crate::power_on::{crc32, GameOverrides, PowerOnState, RamInit};
+
+#[test]
+fn test_fill_patterns() {
+  let mut memory = [0x42; 0x400];
+
+  RamInit::Zero.fill(&mut memory);
+  assert!(memory.iter().all(|&b| b == 0x00));
+
+  RamInit::Ones.fill(&mut memory);
+  assert!(memory.iter().all(|&b| b == 0xFF));
+
+  RamInit::AlternatingPages.fill(&mut memory);
+  assert_eq!((0x00, 0x00, 0xFF, 0xFF, 0x00), (memory[0x000], memory[0x0FF], memory[0x100], memory[0x1FF], memory[0x200]));
+}
+
+#[test]
+fn test_random_fill_is_reproducible() {
+  let mut first = [0; 0x800];
+  let mut second = [0; 0x800];
+  let mut other = [0; 0x800];
+
+  RamInit::Random(7).fill(&mut first);
+  RamInit::Random(7).fill(&mut second);
+  RamInit::Random(8).fill(&mut other);
+
+  assert_eq!(first, second);
+  assert_ne!(first, other);
+}
+
+#[test]
+fn test_bus_uses_power_on_state() {
+  let power_on = PowerOnState { cpu_ram: RamInit::Ones, ..PowerOnState::default() };
+
+  let bus = Bus::with_power_on(create_test_rom(), &power_on).unwrap();
+
+  assert_eq!(0xFF, bus.peek(0x0000));
+  assert_eq!(0xFF, bus.peek(0x07FF));
+}
+
+#[test]
+fn test_game_override_by_prg_crc() {
+  let rom = create_test_rom();
+  let special = PowerOnState { cpu_ram: RamInit::AlternatingPages, ..PowerOnState::default() };
+  let mut overrides = GameOverrides::new();
+
+  assert_eq!(PowerOnState::default(), overrides.power_on_state(&rom, PowerOnState::default()));
+
+  overrides.insert(crc32(&rom.prg_rom), special);
+  assert_eq!(special, overrides.power_on_state(&rom, PowerOnState::default()));
+}
+
+#[test]
+fn test_crc32() {
+  assert_eq!(0xCBF43926, crc32(b"123456789"));
+  assert_eq!(0, crc32(&[]));
+}
@@ -0,0 +1,675 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+use crate::breakpoints::DebugEvent;
+use crate::cartridge::{Mirroring, Rom};
+use crate::event_log::FrameEventKind;
+use crate::frame::{Frame, FrameBuffer, IndexedFrame};
+use crate::mapper::{Nrom, SharedMapper};
+use crate::palette::Palette;
+use crate::power_on::PowerOnState;
+use crate::render;
+use crate::rgba;
+use crate::savestate::{StateReader, StateWriter, Stateful};
+#[cfg(feature = "serde")]
+use crate::savestate::copy_exact;
+
+bitflags! {
+  // 7  bit  0
+  // ---- ----
+  // VPHB SINN
+  // https://wiki.nesdev.org/w/index.php/PPU_registers#PPUCTRL
+  #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+  pub struct ControlRegister: u8 {
+    const NAMETABLE1 = 0b0000_0001;
+    const NAMETABLE2 = 0b0000_0010;
+    const VRAM_ADD_INCREMENT = 0b0000_0100;
+    const SPRITE_PATTERN_ADDR = 0b0000_1000;
+    const BACKGROUND_PATTERN_ADDR = 0b0001_0000;
+    const SPRITE_SIZE = 0b0010_0000;
+    const MASTER_SLAVE_SELECT = 0b0100_0000;
+    const GENERATE_NMI = 0b1000_0000;
+  }
+}
+
+impl ControlRegister {
+  pub fn vram_addr_increment(&self) -> u8 {
+    if self.contains(ControlRegister::VRAM_ADD_INCREMENT) { 32 } else { 1 }
+  }
+
+  pub fn sprite_pattern_addr(&self) -> u16 {
+    if self.contains(ControlRegister::SPRITE_PATTERN_ADDR) { 0x1000 } else { 0 }
+  }
+
+  pub fn background_pattern_addr(&self) -> u16 {
+    if self.contains(ControlRegister::BACKGROUND_PATTERN_ADDR) { 0x1000 } else { 0 }
+  }
+
+  pub fn sprite_size(&self) -> u8 {
+    if self.contains(ControlRegister::SPRITE_SIZE) { 16 } else { 8 }
+  }
+}
+
+bitflags! {
+  // https://wiki.nesdev.org/w/index.php/PPU_registers#PPUMASK
+  #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+  pub struct MaskRegister: u8 {
+    const GREYSCALE = 0b0000_0001;
+    const LEFTMOST_8PXL_BACKGROUND = 0b0000_0010;
+    const LEFTMOST_8PXL_SPRITE = 0b0000_0100;
+    const SHOW_BACKGROUND = 0b0000_1000;
+    const SHOW_SPRITES = 0b0001_0000;
+    const EMPHASISE_RED = 0b0010_0000;
+    const EMPHASISE_GREEN = 0b0100_0000;
+    const EMPHASISE_BLUE = 0b1000_0000;
+  }
+}
+
+bitflags! {
+  // https://wiki.nesdev.org/w/index.php/PPU_registers#PPUSTATUS
+  #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+  pub struct StatusRegister: u8 {
+    const SPRITE_OVERFLOW = 0b0010_0000;
+    const SPRITE_ZERO_HIT = 0b0100_0000;
+    const VBLANK_STARTED = 0b1000_0000;
+  }
+}
+
+// background scroll position a visible scanline started with
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct LineScroll {
+  pub v: u16,
+  pub fine_x: u8,
+}
+
+pub struct NesPPU {
+  mapper: SharedMapper,
+  pub palette_table: [u8; 32],
+  // rgb of the colors, a front-end setting that isn't saved with the state
+  pub palette: Palette,
+  // 2KB in the console, four screen cartridges bring the upper 2KB
+  pub vram: [u8; 4096],
+  pub oam_data: [u8; 256],
+
+  pub ctrl: ControlRegister,
+  pub mask: MaskRegister,
+  pub status: StatusRegister,
+  pub oam_addr: u8,
+  // loopy registers: https://wiki.nesdev.org/w/index.php/PPU_scrolling
+  // v: current vram address, t: temporary address (top left of the screen), both
+  // 0yyy NNYY YYYX XXXX (fine y, nametable, coarse y, coarse x)
+  v: u16,
+  t: u16,
+  fine_x: u8,
+  // w, shared by PPUSCROLL and PPUADDR, reset by reading PPUSTATUS
+  write_toggle: bool,
+  // PPUDATA reads below the palette are delayed by one read
+  internal_data_buf: u8,
+  // last value written to any register, returned when reading write-only ones
+  io_latch: u8,
+
+  pub scanline: u16,
+  cycles: usize,
+  odd_frame: bool,
+  nmi_interrupt: Option<u8>,
+  // recorded while the frame runs, so mid-frame scroll changes show up in the picture
+  pub scanline_scroll: [LineScroll; 240],
+  picture: IndexedFrame,
+  // rgba of the picture line by line, swapped with the completed one when vblank starts
+  drawing: FrameBuffer,
+  completed: FrameBuffer,
+  events: Vec<DebugEvent>,
+  // nmi, sprite 0 hit and mapper irq with the scanline and dot they happened at,
+  // collected for the event log while it is on
+  pub signals: Option<Vec<(FrameEventKind, u16, u16)>>,
+  // first and last dot passed since the events were taken
+  advanced: Option<((u16, u16), (u16, u16))>,
+}
+
+pub const DOTS_PER_SCANLINE: usize = 341;
+pub const VBLANK_SCANLINE: u16 = 241;
+pub const PRE_RENDER_SCANLINE: u16 = 261;
+pub const SCANLINES_PER_FRAME: u16 = 262;
+const MAX_PENDING_EVENTS: usize = 16;
+
+impl NesPPU {
+  // standalone ppu with an NROM cartridge holding only chr rom
+  pub fn new(chr_rom: Vec<u8>, mirroring: Mirroring) -> Self {
+    let rom = Rom { prg_rom: vec![], chr_rom, chr_ram: false, mapper: 0, screen_mirroring: mirroring, battery: false };
+    NesPPU::with_mapper(Rc::new(RefCell::new(Nrom::new(rom))), &PowerOnState::default())
+  }
+
+  pub fn with_mapper(mapper: SharedMapper, power_on: &PowerOnState) -> Self {
+    let mut ppu = NesPPU {
+      mapper,
+      palette_table: [0; 32],
+      palette: Palette::default(),
+      vram: [0; 4096],
+      oam_data: [0; 256],
+      ctrl: ControlRegister::empty(),
+      mask: MaskRegister::empty(),
+      status: StatusRegister::empty(),
+      oam_addr: 0,
+      v: 0,
+      t: 0,
+      fine_x: 0,
+      write_toggle: false,
+      internal_data_buf: 0,
+      io_latch: 0,
+      scanline: 0,
+      cycles: 0,
+      odd_frame: false,
+      nmi_interrupt: None,
+      scanline_scroll: [LineScroll::default(); 240],
+      picture: IndexedFrame::new(),
+      drawing: FrameBuffer::new(),
+      completed: FrameBuffer::new(),
+      events: vec![],
+      signals: None,
+      advanced: None,
+    };
+    power_on.vram.fill(&mut ppu.vram);
+    power_on.oam.fill(&mut ppu.oam_data);
+    power_on.palette.fill(&mut ppu.palette_table);
+    ppu
+  }
+
+  // advances dot by dot, true once a frame is complete
+  pub fn tick(&mut self, dots: u16) -> bool {
+    let mut frame_complete = false;
+    for _ in 0..dots {
+      frame_complete |= self.step_dot();
+    }
+    frame_complete
+  }
+
+  // moves to the next dot and does what happens there:
+  // https://wiki.nesdev.org/w/index.php/PPU_rendering#Frame_timing_diagram
+  fn step_dot(&mut self) -> bool {
+    let mut frame_complete = false;
+    self.cycles += 1;
+    // odd frames are one dot shorter while rendering
+    let skip = self.scanline == PRE_RENDER_SCANLINE && self.cycles == DOTS_PER_SCANLINE - 1
+      && self.odd_frame && self.rendering_enabled();
+    if self.cycles == DOTS_PER_SCANLINE || skip {
+      self.cycles = 0;
+      self.scanline += 1;
+      if self.scanline == SCANLINES_PER_FRAME {
+        self.scanline = 0;
+        self.odd_frame = !self.odd_frame;
+        self.nmi_interrupt = None;
+        frame_complete = true;
+      }
+    }
+
+    let position = (self.scanline, self.cycles as u16);
+    self.advanced = Some((self.advanced.map_or(position, |(from, _)| from), position));
+
+    match position {
+      (VBLANK_SCANLINE, 1) => {
+        std::mem::swap(&mut self.drawing, &mut self.completed);
+        self.status.insert(StatusRegister::VBLANK_STARTED);
+        if self.ctrl.contains(ControlRegister::GENERATE_NMI) {
+          self.nmi_interrupt = Some(1);
+          self.push_signal(FrameEventKind::Nmi);
+        }
+        self.push_event(DebugEvent::VblankStart);
+      }
+      (PRE_RENDER_SCANLINE, 1) => {
+        self.status.remove(StatusRegister::VBLANK_STARTED | StatusRegister::SPRITE_ZERO_HIT | StatusRegister::SPRITE_OVERFLOW);
+        self.push_event(DebugEvent::VblankEnd);
+      }
+      _ => {}
+    }
+
+    let visible = self.scanline < Frame::HEIGHT as u16;
+    if visible && self.cycles == 256 {
+      self.render_scanline();
+    }
+    if self.rendering_enabled() && (visible || self.scanline == PRE_RENDER_SCANLINE) {
+      match self.cycles {
+        256 => self.increment_y(),
+        // back to the left edge
+        257 => self.v = (self.v & !0x041F) | (self.t & 0x041F),
+        260 => self.fetch_patterns(),
+        // back to the top
+        280 if self.scanline == PRE_RENDER_SCANLINE => self.v = (self.v & 0x041F) | (self.t & !0x041F),
+        _ => {}
+      }
+    }
+    frame_complete
+  }
+
+  // the whole line is drawn at once, with the registers as they are at its end
+  fn render_scanline(&mut self) {
+    let y = self.scanline as usize;
+    self.scanline_scroll[y] = LineScroll { v: self.v, fine_x: self.fine_x };
+    let mut picture = std::mem::replace(&mut self.picture, IndexedFrame { pixels: Vec::new() });
+    let line = render::render_scanline(self, y, &mut picture);
+    self.picture = picture;
+    let start = y * Frame::WIDTH * 4;
+    rgba::indexed_to_rgba(self.palette.rgba_table(), self.picture.line(y), &mut self.drawing[start..start + Frame::WIDTH * 4]);
+
+    if line.sprite_overflow {
+      self.status.insert(StatusRegister::SPRITE_OVERFLOW);
+    }
+    if let Some(x) = line.sprite_zero_hit.filter(|_| !self.status.contains(StatusRegister::SPRITE_ZERO_HIT)) {
+      self.status.insert(StatusRegister::SPRITE_ZERO_HIT);
+      self.push_event(DebugEvent::Sprite0Hit);
+      // the flag is only set with the whole line, but the event log shows where the hit happened:
+      // pixel x is output at dot x + 1
+      self.push_signal_at(FrameEventKind::Sprite0Hit, x as u16 + 1);
+    }
+  }
+
+  fn rendering_enabled(&self) -> bool {
+    self.mask.intersects(MaskRegister::SHOW_BACKGROUND | MaskRegister::SHOW_SPRITES)
+  }
+
+  fn increment_y(&mut self) {
+    if self.v & 0x7000 != 0x7000 {
+      self.v += 0x1000;
+      return;
+    }
+    self.v &= !0x7000;
+    let mut coarse_y = (self.v & 0x03E0) >> 5;
+    if coarse_y == 29 {
+      // next vertical nametable, rows 30 and 31 would be the attribute table
+      coarse_y = 0;
+      self.v ^= 0x0800;
+    } else if coarse_y == 31 {
+      coarse_y = 0;
+    } else {
+      coarse_y += 1;
+    }
+    self.v = (self.v & !0x03E0) | (coarse_y << 5);
+  }
+
+  // sprite patterns for the next line are fetched from dot 257 on, then the first
+  // background tiles, only the resulting A12 transitions are reported to the mapper
+  fn fetch_patterns(&mut self) {
+    let irq_raised = {
+      let mut mapper = self.mapper.borrow_mut();
+      let irq = mapper.irq();
+      mapper.ppu_bus_address(self.ctrl.sprite_pattern_addr());
+      mapper.ppu_bus_address(self.ctrl.background_pattern_addr());
+      !irq && mapper.irq()
+    };
+    if irq_raised {
+      self.push_signal(FrameEventKind::MapperIrq);
+    }
+  }
+
+  fn push_event(&mut self, event: DebugEvent) {
+    if self.events.len() < MAX_PENDING_EVENTS {
+      self.events.push(event);
+    }
+  }
+
+  fn push_signal(&mut self, kind: FrameEventKind) {
+    self.push_signal_at(kind, self.cycles as u16);
+  }
+
+  // at another dot of the current scanline
+  fn push_signal_at(&mut self, kind: FrameEventKind, dot: u16) {
+    let scanline = self.scanline;
+    if let Some(signals) = self.signals.as_mut() {
+      signals.push((kind, scanline, dot));
+    }
+  }
+
+  // hands everything that happened since the last call to `notify`, e.g. for breakpoints
+  pub fn drain_events<F: FnMut(DebugEvent)>(&mut self, mut notify: F) {
+    for event in self.events.drain(..) {
+      notify(event);
+    }
+    if let Some((from, to)) = self.advanced.take() {
+      notify(DebugEvent::PpuAdvanced { from, to });
+    }
+  }
+
+  // picture drawn so far, complete once vblank starts
+  pub fn indexed_frame(&self) -> &IndexedFrame {
+    &self.picture
+  }
+
+  // the picture in the colors of `palette`
+  pub fn frame(&self) -> Frame {
+    self.picture.to_frame(&self.palette)
+  }
+
+  // the last complete picture as rgba (FrameBuffer), without copying it. The borrow
+  // keeps the ppu from running while `f` does, so it never sees half a frame
+  pub fn with_frame<R>(&self, f: impl FnOnce(&[u8]) -> R) -> R {
+    f(&self.completed)
+  }
+
+  // e.g. when loading a state that holds the picture
+  pub fn set_completed_frame(&mut self, frame: &Frame) {
+    self.completed.set_frame(frame);
+  }
+
+  // position within the current scanline
+  pub fn dot(&self) -> usize {
+    self.cycles
+  }
+
+  pub fn nmi_pending(&self) -> bool {
+    self.nmi_interrupt.is_some()
+  }
+
+  pub fn poll_nmi_interrupt(&mut self) -> Option<u8> {
+    self.nmi_interrupt.take()
+  }
+
+  pub fn vram_addr(&self) -> u16 {
+    self.v
+  }
+
+  pub fn temp_vram_addr(&self) -> u16 {
+    self.t
+  }
+
+  pub fn fine_x(&self) -> u8 {
+    self.fine_x
+  }
+
+  // scroll position as last written to PPUSCROLL (or PPUADDR)
+  pub fn scroll_x(&self) -> u8 {
+    ((self.t & 0x1F) << 3) as u8 | self.fine_x
+  }
+
+  pub fn scroll_y(&self) -> u8 {
+    (((self.t & 0x03E0) >> 2) | ((self.t & 0x7000) >> 12)) as u8
+  }
+
+  pub fn io_latch(&self) -> u8 {
+    self.io_latch
+  }
+
+  pub fn mirroring(&self) -> Mirroring {
+    self.mapper.borrow().mirroring()
+  }
+
+  // pattern tables, $0000-$1FFF
+  pub fn chr_read(&self, addr: u16) -> u8 {
+    self.mapper.borrow().chr_read(addr)
+  }
+
+  pub fn write_to_ctrl(&mut self, value: u8) {
+    self.io_latch = value;
+    let before_nmi_status = self.ctrl.contains(ControlRegister::GENERATE_NMI);
+    self.ctrl = ControlRegister::from_bits_truncate(value);
+    self.t = (self.t & !0x0C00) | ((value as u16 & 0b11) << 10);
+    // enabling nmi during vblank fires it immediately
+    if !before_nmi_status && self.ctrl.contains(ControlRegister::GENERATE_NMI)
+      && self.status.contains(StatusRegister::VBLANK_STARTED) {
+      self.nmi_interrupt = Some(1);
+      self.push_signal(FrameEventKind::Nmi);
+    }
+  }
+
+  pub fn write_to_mask(&mut self, value: u8) {
+    self.io_latch = value;
+    self.mask = MaskRegister::from_bits_truncate(value);
+  }
+
+  pub fn read_status(&mut self) -> u8 {
+    // lower bits are whatever was last on the ppu data bus
+    let data = self.status.bits() | (self.io_latch & 0b0001_1111);
+    self.status.remove(StatusRegister::VBLANK_STARTED);
+    self.write_toggle = false;
+    data
+  }
+
+  pub fn write_to_oam_addr(&mut self, value: u8) {
+    self.io_latch = value;
+    self.oam_addr = value;
+  }
+
+  pub fn write_to_oam_data(&mut self, value: u8) {
+    self.io_latch = value;
+    self.oam_data[self.oam_addr as usize] = value;
+    self.oam_addr = self.oam_addr.wrapping_add(1);
+  }
+
+  // $4014, starts at the current oam address and wraps around
+  pub fn write_oam_dma(&mut self, data: &[u8; 256]) {
+    for &value in data.iter() {
+      self.oam_data[self.oam_addr as usize] = value;
+      self.oam_addr = self.oam_addr.wrapping_add(1);
+    }
+  }
+
+  pub fn read_oam_data(&self) -> u8 {
+    self.oam_data[self.oam_addr as usize]
+  }
+
+  pub fn write_to_scroll(&mut self, value: u8) {
+    self.io_latch = value;
+    if self.write_toggle {
+      self.t = (self.t & !0x73E0) | ((value as u16 & 0x07) << 12) | ((value as u16 & 0xF8) << 2);
+    } else {
+      self.t = (self.t & !0x001F) | (value as u16 >> 3);
+      self.fine_x = value & 0x07;
+    }
+    self.write_toggle = !self.write_toggle;
+  }
+
+  // high byte first, goes through t and only the second write changes v
+  pub fn write_to_ppu_addr(&mut self, value: u8) {
+    self.io_latch = value;
+    if self.write_toggle {
+      self.t = (self.t & 0xFF00) | value as u16;
+      self.v = self.t;
+    } else {
+      self.t = (((value as u16) & 0x3F) << 8) | (self.t & 0x00FF);
+    }
+    self.write_toggle = !self.write_toggle;
+  }
+
+  fn increment_vram_addr(&mut self) {
+    self.v = self.v.wrapping_add(self.ctrl.vram_addr_increment() as u16) & 0x7FFF;
+  }
+
+  pub fn write_to_data(&mut self, value: u8) {
+    self.io_latch = value;
+    let addr = self.v & 0x3FFF;
+    match addr {
+      0x0000..=0x1FFF => self.mapper.borrow_mut().chr_write(addr, value),
+      0x2000..=0x3EFF => self.vram[self.mirror_vram_addr(addr) as usize] = value,
+      0x3F00..=0x3FFF => self.palette_table[mirror_palette_addr(addr)] = value,
+      _ => unreachable!("ppu address {:04X} is outside of 14 bit range", addr),
+    }
+    self.increment_vram_addr();
+  }
+
+  pub fn read_data(&mut self) -> u8 {
+    let addr = self.v & 0x3FFF;
+    self.increment_vram_addr();
+
+    match addr {
+      0x0000..=0x1FFF => {
+        let result = self.internal_data_buf;
+        self.internal_data_buf = self.chr_read(addr);
+        result
+      }
+      0x2000..=0x3EFF => {
+        let result = self.internal_data_buf;
+        self.internal_data_buf = self.vram[self.mirror_vram_addr(addr) as usize];
+        result
+      }
+      // palette is not buffered, but the nametable byte "below" it still ends up in the buffer
+      0x3F00..=0x3FFF => {
+        self.internal_data_buf = self.vram[self.mirror_vram_addr(addr - 0x1000) as usize];
+        let entry = self.palette_table[mirror_palette_addr(addr)];
+        if self.mask.contains(MaskRegister::GREYSCALE) { entry & 0x30 } else { entry }
+      }
+      _ => unreachable!("ppu address {:04X} is outside of 14 bit range", addr),
+    }
+  }
+
+  // maps the four logical nametables ($2000, $2400, $2800, $2C00) onto 1KB pages of vram
+  // Horizontal:     Vertical:       Single screen:  Four screen:
+  //   [ A ] [ a ]     [ A ] [ B ]     [ A ] [ a ]     [ A ] [ B ]
+  //   [ B ] [ b ]     [ a ] [ b ]     [ a ] [ a ]     [ C ] [ D ]
+  pub fn mirror_vram_addr(&self, addr: u16) -> u16 {
+    let vram_index = addr & 0x0FFF; // $3000-$3EFF mirrors $2000-$2EFF
+    let name_table = vram_index / 0x400;
+    let page = match self.mirroring() {
+      Mirroring::HORIZONTAL => name_table / 2,
+      Mirroring::VERTICAL => name_table % 2,
+      Mirroring::SINGLE_SCREEN_LOWER => 0,
+      Mirroring::SINGLE_SCREEN_UPPER => 1,
+      Mirroring::FOUR_SCREEN => name_table,
+    };
+    page * 0x400 + (vram_index & 0x3FF)
+  }
+}
+
+// the cartridge is saved by the bus
+impl Stateful for NesPPU {
+  fn save_state(&self, w: &mut StateWriter) {
+    w.write_bytes(&self.palette_table);
+    w.write_bytes(&self.vram);
+    w.write_bytes(&self.oam_data);
+    w.write_u8(self.ctrl.bits());
+    w.write_u8(self.mask.bits());
+    w.write_u8(self.status.bits());
+    w.write_u8(self.oam_addr);
+    w.write_u16(self.v);
+    w.write_u16(self.t);
+    w.write_u8(self.fine_x);
+    w.write_bool(self.write_toggle);
+    w.write_u8(self.internal_data_buf);
+    w.write_u8(self.io_latch);
+    w.write_u16(self.scanline);
+    w.write_u64(self.cycles as u64);
+    w.write_bool(self.odd_frame);
+    // the lines drawn so far, a state saved mid-frame completes the same picture
+    let picture: Vec<u8> = self.picture.pixels.iter().flat_map(|pixel| pixel.to_le_bytes()).collect();
+    w.write_bytes(&picture);
+    w.write_bool(self.nmi_interrupt.is_some());
+    for line in self.scanline_scroll.iter() {
+      w.write_u16(line.v);
+      w.write_u8(line.fine_x);
+    }
+  }
+
+  fn load_state(&mut self, r: &mut StateReader) -> Result<(), String> {
+    r.read_into(&mut self.palette_table)?;
+    r.read_into(&mut self.vram)?;
+    r.read_into(&mut self.oam_data)?;
+    self.ctrl = ControlRegister::from_bits_truncate(r.read_u8()?);
+    self.mask = MaskRegister::from_bits_truncate(r.read_u8()?);
+    self.status = StatusRegister::from_bits_truncate(r.read_u8()?);
+    self.oam_addr = r.read_u8()?;
+    self.v = r.read_u16()?;
+    self.t = r.read_u16()?;
+    self.fine_x = r.read_u8()?;
+    self.write_toggle = r.read_bool()?;
+    self.internal_data_buf = r.read_u8()?;
+    self.io_latch = r.read_u8()?;
+    self.scanline = r.read_u16()?;
+    self.cycles = r.read_u64()? as usize;
+    self.odd_frame = r.read_bool()?;
+    let mut picture = vec![0; self.picture.pixels.len() * 2];
+    r.read_into(&mut picture)?;
+    for (pixel, bytes) in self.picture.pixels.iter_mut().zip(picture.chunks_exact(2)) {
+      *pixel = u16::from_le_bytes([bytes[0], bytes[1]]);
+    }
+    rgba::indexed_to_rgba(self.palette.rgba_table(), &self.picture.pixels, &mut self.drawing);
+    self.nmi_interrupt = if r.read_bool()? { Some(1) } else { None };
+    for line in self.scanline_scroll.iter_mut() {
+      line.v = r.read_u16()?;
+      line.fine_x = r.read_u8()?;
+    }
+    Ok(())
+  }
+}
+
+// what the Stateful impl saves, as a struct for serde
+#[cfg(feature = "serde")]
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct PpuState {
+  pub palette_table: Vec<u8>,
+  pub vram: Vec<u8>,
+  pub oam_data: Vec<u8>,
+  pub ctrl: ControlRegister,
+  pub mask: MaskRegister,
+  pub status: StatusRegister,
+  pub oam_addr: u8,
+  pub v: u16,
+  pub t: u16,
+  pub fine_x: u8,
+  pub write_toggle: bool,
+  pub internal_data_buf: u8,
+  pub io_latch: u8,
+  pub scanline: u16,
+  pub cycles: usize,
+  pub odd_frame: bool,
+  pub picture: Vec<u16>,
+  pub nmi_pending: bool,
+  pub scanline_scroll: Vec<LineScroll>,
+}
+
+#[cfg(feature = "serde")]
+impl NesPPU {
+  pub fn state(&self) -> PpuState {
+    PpuState {
+      palette_table: self.palette_table.to_vec(),
+      vram: self.vram.to_vec(),
+      oam_data: self.oam_data.to_vec(),
+      ctrl: self.ctrl,
+      mask: self.mask,
+      status: self.status,
+      oam_addr: self.oam_addr,
+      v: self.v,
+      t: self.t,
+      fine_x: self.fine_x,
+      write_toggle: self.write_toggle,
+      internal_data_buf: self.internal_data_buf,
+      io_latch: self.io_latch,
+      scanline: self.scanline,
+      cycles: self.cycles,
+      odd_frame: self.odd_frame,
+      picture: self.picture.pixels.clone(),
+      nmi_pending: self.nmi_interrupt.is_some(),
+      scanline_scroll: self.scanline_scroll.to_vec(),
+    }
+  }
+
+  // as load_state, a failed set can leave the ppu half restored
+  pub fn set_state(&mut self, state: PpuState) -> Result<(), String> {
+    copy_exact(&mut self.palette_table, &state.palette_table)?;
+    copy_exact(&mut self.vram, &state.vram)?;
+    copy_exact(&mut self.oam_data, &state.oam_data)?;
+    copy_exact(&mut self.picture.pixels, &state.picture)?;
+    copy_exact(&mut self.scanline_scroll, &state.scanline_scroll)?;
+    self.ctrl = state.ctrl;
+    self.mask = state.mask;
+    self.status = state.status;
+    self.oam_addr = state.oam_addr;
+    self.v = state.v;
+    self.t = state.t;
+    self.fine_x = state.fine_x;
+    self.write_toggle = state.write_toggle;
+    self.internal_data_buf = state.internal_data_buf;
+    self.io_latch = state.io_latch;
+    self.scanline = state.scanline;
+    self.cycles = state.cycles;
+    self.odd_frame = state.odd_frame;
+    self.nmi_interrupt = if state.nmi_pending { Some(1) } else { None };
+    rgba::indexed_to_rgba(self.palette.rgba_table(), &self.picture.pixels, &mut self.drawing);
+    Ok(())
+  }
+}
+
+// $3F10/$3F14/$3F18/$3F1C are mirrors of $3F00/$3F04/$3F08/$3F0C
+pub fn mirror_palette_addr(addr: u16) -> usize {
+  let index = (addr & 0x1F) as usize;
+  match index {
+    0x10 | 0x14 | 0x18 | 0x1C => index - 0x10,
+    _ => index,
+  }
+}
@@ -0,0 +1,552 @@
+use crate::bus::Bus;
+use crate::cartridge::Mirroring;
+use crate::cartridge_tests::create_test_rom;
+use crate::cpu::MyMem;
+use crate::event_log::FrameEventKind;
+use crate::palette::SYSTEM_PALETTE;
+use crate::ppu::{LineScroll, NesPPU, StatusRegister};
+
+fn new_empty_rom_ppu() -> NesPPU {
+  NesPPU::new(vec![0; 2048], Mirroring::HORIZONTAL)
+}
+
+#[test]
+fn test_ppu_vram_writes() {
+  let mut ppu = new_empty_rom_ppu();
+  ppu.write_to_ppu_addr(0x23);
+  ppu.write_to_ppu_addr(0x05);
+  ppu.write_to_data(0x66);
+
+  assert_eq!(ppu.vram[0x0305], 0x66);
+}
+
+#[test]
+fn test_ppu_vram_reads_are_buffered() {
+  let mut ppu = new_empty_rom_ppu();
+  ppu.write_to_ctrl(0);
+  ppu.vram[0x0305] = 0x66;
+
+  ppu.write_to_ppu_addr(0x23);
+  ppu.write_to_ppu_addr(0x05);
+
+  ppu.read_data(); // load into buffer
+  assert_eq!(ppu.vram_addr(), 0x2306);
+  assert_eq!(ppu.read_data(), 0x66);
+}
+
+#[test]
+fn test_ppu_vram_reads_cross_page() {
+  let mut ppu = new_empty_rom_ppu();
+  ppu.write_to_ctrl(0);
+  ppu.vram[0x01ff] = 0x66;
+  ppu.vram[0x0200] = 0x77;
+
+  ppu.write_to_ppu_addr(0x21);
+  ppu.write_to_ppu_addr(0xff);
+
+  ppu.read_data();
+  assert_eq!(ppu.read_data(), 0x66);
+  assert_eq!(ppu.read_data(), 0x77);
+}
+
+#[test]
+fn test_ppu_vram_reads_step_32() {
+  let mut ppu = new_empty_rom_ppu();
+  ppu.write_to_ctrl(0b100);
+  ppu.vram[0x01ff] = 0x66;
+  ppu.vram[0x01ff + 32] = 0x77;
+  ppu.vram[0x01ff + 64] = 0x88;
+
+  ppu.write_to_ppu_addr(0x21);
+  ppu.write_to_ppu_addr(0xff);
+
+  ppu.read_data();
+  assert_eq!(ppu.read_data(), 0x66);
+  assert_eq!(ppu.read_data(), 0x77);
+  assert_eq!(ppu.read_data(), 0x88);
+}
+
+// Horizontal: https://wiki.nesdev.com/w/index.php/Mirroring
+//   [0x2000 A ] [0x2400 a ]
+//   [0x2800 B ] [0x2C00 b ]
+#[test]
+fn test_vram_horizontal_mirror() {
+  let mut ppu = new_empty_rom_ppu();
+  ppu.write_to_ppu_addr(0x24);
+  ppu.write_to_ppu_addr(0x05);
+
+  ppu.write_to_data(0x66); // write to a
+
+  ppu.write_to_ppu_addr(0x28);
+  ppu.write_to_ppu_addr(0x05);
+
+  ppu.write_to_data(0x77); // write to B
+
+  ppu.write_to_ppu_addr(0x20);
+  ppu.write_to_ppu_addr(0x05);
+
+  ppu.read_data();
+  assert_eq!(ppu.read_data(), 0x66); // read from A
+
+  ppu.write_to_ppu_addr(0x2C);
+  ppu.write_to_ppu_addr(0x05);
+
+  ppu.read_data();
+  assert_eq!(ppu.read_data(), 0x77); // read from b
+}
+
+// Vertical: https://wiki.nesdev.com/w/index.php/Mirroring
+//   [0x2000 A ] [0x2400 B ]
+//   [0x2800 a ] [0x2C00 b ]
+#[test]
+fn test_vram_vertical_mirror() {
+  let mut ppu = NesPPU::new(vec![0; 2048], Mirroring::VERTICAL);
+
+  ppu.write_to_ppu_addr(0x20);
+  ppu.write_to_ppu_addr(0x05);
+
+  ppu.write_to_data(0x66); // write to A
+
+  ppu.write_to_ppu_addr(0x2C);
+  ppu.write_to_ppu_addr(0x05);
+
+  ppu.write_to_data(0x77); // write to b
+
+  ppu.write_to_ppu_addr(0x28);
+  ppu.write_to_ppu_addr(0x05);
+
+  ppu.read_data();
+  assert_eq!(ppu.read_data(), 0x66); // read from a
+
+  ppu.write_to_ppu_addr(0x24);
+  ppu.write_to_ppu_addr(0x05);
+
+  ppu.read_data();
+  assert_eq!(ppu.read_data(), 0x77); // read from B
+}
+
+#[test]
+fn test_read_status_resets_latch() {
+  let mut ppu = new_empty_rom_ppu();
+  ppu.vram[0x0305] = 0x66;
+
+  ppu.write_to_ppu_addr(0x21);
+  ppu.write_to_ppu_addr(0x23);
+  ppu.write_to_ppu_addr(0x05);
+
+  ppu.read_data();
+  assert_ne!(ppu.read_data(), 0x66);
+
+  ppu.read_status();
+
+  ppu.write_to_ppu_addr(0x23);
+  ppu.write_to_ppu_addr(0x05);
+
+  ppu.read_data();
+  assert_eq!(ppu.read_data(), 0x66);
+}
+
+#[test]
+fn test_ppu_vram_mirroring() {
+  let mut ppu = new_empty_rom_ppu();
+  ppu.write_to_ctrl(0);
+  ppu.vram[0x0305] = 0x66;
+
+  ppu.write_to_ppu_addr(0x63); // 0x6305 -> 0x2305
+  ppu.write_to_ppu_addr(0x05);
+
+  ppu.read_data();
+  assert_eq!(ppu.read_data(), 0x66);
+}
+
+#[test]
+fn test_read_status_resets_vblank() {
+  let mut ppu = new_empty_rom_ppu();
+  ppu.status.insert(StatusRegister::VBLANK_STARTED);
+
+  let status = ppu.read_status();
+
+  assert_eq!(status >> 7, 1);
+  assert_eq!(ppu.status.bits() >> 7, 0);
+}
+
+#[test]
+fn test_oam_read_write() {
+  let mut ppu = new_empty_rom_ppu();
+  ppu.write_to_oam_addr(0x10);
+  ppu.write_to_oam_data(0x66);
+  ppu.write_to_oam_data(0x77);
+
+  ppu.write_to_oam_addr(0x10);
+  assert_eq!(ppu.read_oam_data(), 0x66);
+
+  ppu.write_to_oam_addr(0x11);
+  assert_eq!(ppu.read_oam_data(), 0x77);
+}
+
+#[test]
+fn test_palette_reads_are_not_buffered_and_mirrored() {
+  let mut ppu = new_empty_rom_ppu();
+  ppu.write_to_ppu_addr(0x3F);
+  ppu.write_to_ppu_addr(0x10);
+  ppu.write_to_data(0x2A);
+
+  ppu.write_to_ppu_addr(0x3F);
+  ppu.write_to_ppu_addr(0x00);
+
+  assert_eq!(ppu.read_data(), 0x2A);
+}
+
+#[test]
+fn test_scroll_writes_alternate_x_and_y() {
+  let mut ppu = new_empty_rom_ppu();
+  ppu.write_to_scroll(0x12);
+  ppu.write_to_scroll(0x34);
+
+  assert_eq!((0x12, 0x34), (ppu.scroll_x(), ppu.scroll_y()));
+}
+
+#[test]
+fn test_bus_maps_mirrored_ppu_registers() {
+  let mut bus = Bus::new(create_test_rom()).unwrap();
+  // $3456 mirrors PPUADDR ($2006), $2FFF mirrors PPUDATA ($2007)
+  bus.mem_write(0x3456, 0x21);
+  bus.mem_write(0x3456, 0x00);
+  bus.mem_write(0x2FFF, 0x42);
+
+  assert_eq!(0x42, bus.ppu.vram[0x0100]);
+
+  bus.ppu.status.insert(StatusRegister::VBLANK_STARTED);
+  assert_eq!(0x80, bus.mem_read(0x200A) & 0x80);
+  assert_eq!(0x00, bus.mem_read(0x2002) & 0x80);
+}
+
+#[test]
+fn test_vblank_starts_at_scanline_241() {
+  let mut ppu = new_empty_rom_ppu();
+  ppu.write_to_ctrl(0b1000_0000);
+
+  for _ in 0..240 {
+    assert!(!ppu.tick(255));
+    assert!(!ppu.tick(86));
+  }
+  assert!(!ppu.status.contains(StatusRegister::VBLANK_STARTED));
+
+  ppu.tick(255);
+  ppu.tick(86);
+  assert!(!ppu.status.contains(StatusRegister::VBLANK_STARTED));
+  ppu.tick(1);
+
+  assert_eq!((241, 1), (ppu.scanline, ppu.dot()));
+  assert!(ppu.status.contains(StatusRegister::VBLANK_STARTED));
+  assert_eq!(Some(1), ppu.poll_nmi_interrupt());
+  assert_eq!(None, ppu.poll_nmi_interrupt());
+}
+
+#[test]
+fn test_no_nmi_when_disabled_and_frame_ends_at_262() {
+  let mut ppu = new_empty_rom_ppu();
+
+  let mut frames = 0;
+  for _ in 0..262 {
+    ppu.tick(255);
+    if ppu.tick(86) {
+      frames += 1;
+    }
+    assert!(!ppu.nmi_pending());
+  }
+
+  assert_eq!(1, frames);
+  assert_eq!(0, ppu.scanline);
+  assert!(!ppu.status.contains(StatusRegister::VBLANK_STARTED));
+}
+
+#[test]
+fn test_enabling_nmi_during_vblank_fires_immediately() {
+  let mut ppu = new_empty_rom_ppu();
+  ppu.status.insert(StatusRegister::VBLANK_STARTED);
+
+  ppu.write_to_ctrl(0b1000_0000);
+
+  assert!(ppu.nmi_pending());
+}
+
+#[test]
+fn test_bus_reports_frame_ready_once_per_vblank() {
+  let mut bus = Bus::new(create_test_rom()).unwrap();
+
+  // 241 scanlines of 341 dots = 27393.67 cpu cycles
+  for _ in 0..27393 {
+    bus.tick(1);
+  }
+  assert!(!bus.take_frame_ready());
+
+  bus.tick(1);
+  assert!(bus.take_frame_ready());
+  assert!(!bus.take_frame_ready());
+}
+
+#[test]
+fn test_oam_dma_copies_page_from_oam_addr() {
+  let mut bus = Bus::new(create_test_rom()).unwrap();
+  for i in 0..256u16 {
+    bus.mem_write(0x0200 + i, i as u8);
+  }
+  bus.mem_write(0x2003, 0x10);
+
+  bus.mem_write(0x4014, 0x02);
+
+  assert_eq!(0x00, bus.ppu.oam_data[0x10]);
+  assert_eq!(0xEF, bus.ppu.oam_data[0xFF]);
+  assert_eq!(0xF0, bus.ppu.oam_data[0x00]);
+  assert_eq!(0x10, bus.ppu.oam_addr);
+  assert_eq!(513, bus.take_dma_stall());
+  assert_eq!(0, bus.take_dma_stall());
+
+  bus.tick(1);
+  bus.mem_write(0x4014, 0x02);
+  assert_eq!(514, bus.take_dma_stall());
+}
+
+#[test]
+fn test_vram_single_screen_mirror() {
+  let lower = NesPPU::new(vec![0; 2048], Mirroring::SINGLE_SCREEN_LOWER);
+  let upper = NesPPU::new(vec![0; 2048], Mirroring::SINGLE_SCREEN_UPPER);
+
+  for nametable in [0x2000, 0x2400, 0x2800, 0x2C00] {
+    assert_eq!(0x0005, lower.mirror_vram_addr(nametable + 5));
+    assert_eq!(0x0405, upper.mirror_vram_addr(nametable + 5));
+  }
+}
+
+// example from https://wiki.nesdev.org/w/index.php/PPU_scrolling#Summary
+#[test]
+fn test_loopy_registers_from_scroll_and_addr_writes() {
+  let mut ppu = new_empty_rom_ppu();
+  ppu.write_to_ctrl(0);
+  ppu.read_status();
+
+  ppu.write_to_scroll(0x7D);
+  assert_eq!((0x000F, 5), (ppu.temp_vram_addr(), ppu.fine_x()));
+  ppu.write_to_scroll(0x5E);
+  assert_eq!(0x616F, ppu.temp_vram_addr());
+
+  ppu.write_to_ppu_addr(0x3D);
+  assert_eq!(0x3D6F, ppu.temp_vram_addr());
+  ppu.write_to_ppu_addr(0xF0);
+  assert_eq!((0x3DF0, 0x3DF0), (ppu.temp_vram_addr(), ppu.vram_addr()));
+  assert_eq!(5, ppu.fine_x());
+}
+
+fn tick_scanlines(ppu: &mut NesPPU, scanlines: usize) {
+  for _ in 0..scanlines {
+    ppu.tick(255);
+    ppu.tick(86);
+  }
+}
+
+#[test]
+fn test_mid_frame_scroll_split() {
+  let mut ppu = new_empty_rom_ppu();
+  ppu.write_to_mask(0b0000_1000);
+  ppu.write_to_scroll(8);
+  ppu.write_to_scroll(0);
+  // the first frame starts without a pre-render line
+  tick_scanlines(&mut ppu, 262 + 1);
+  assert_eq!(LineScroll { v: 0x0001, fine_x: 0 }, ppu.scanline_scroll[0]);
+
+  tick_scanlines(&mut ppu, 100);
+  ppu.write_to_scroll(19);
+  ppu.write_to_scroll(0);
+  tick_scanlines(&mut ppu, 2);
+
+  assert_eq!(LineScroll { v: 0x4181, fine_x: 0 }, ppu.scanline_scroll[100]);
+  // coarse x was already copied at the end of the previous line, fine x applies right away
+  assert_eq!(LineScroll { v: 0x5181, fine_x: 3 }, ppu.scanline_scroll[101]);
+  // only the horizontal position is taken over, the vertical one keeps counting
+  assert_eq!(LineScroll { v: 0x6182, fine_x: 3 }, ppu.scanline_scroll[102]);
+}
+
+#[test]
+fn test_vertical_scroll_wraps_into_next_nametable_after_row_29() {
+  let mut ppu = new_empty_rom_ppu();
+  ppu.write_to_mask(0b0000_1000);
+  ppu.write_to_scroll(0);
+  ppu.write_to_scroll(232); // coarse y 29
+  tick_scanlines(&mut ppu, 262 + 9);
+
+  assert_eq!(0x03A0, ppu.scanline_scroll[0].v);
+  assert_eq!(0x0800, ppu.scanline_scroll[8].v);
+}
+
+#[test]
+fn test_palette_read_fills_buffer_with_nametable_below() {
+  let mut ppu = new_empty_rom_ppu();
+  ppu.vram[0x0705] = 0x66; // $2F05 with horizontal mirroring
+  ppu.palette_table[0x05] = 0x11;
+
+  ppu.write_to_ppu_addr(0x3F);
+  ppu.write_to_ppu_addr(0x05);
+  assert_eq!(0x11, ppu.read_data());
+
+  ppu.write_to_ppu_addr(0x20);
+  ppu.write_to_ppu_addr(0x00);
+  assert_eq!(0x66, ppu.read_data());
+}
+
+#[test]
+fn test_greyscale_applies_to_palette_reads() {
+  let mut ppu = new_empty_rom_ppu();
+  ppu.palette_table[0x05] = 0x2A;
+  ppu.write_to_mask(0b0000_0001);
+
+  ppu.write_to_ppu_addr(0x3F);
+  ppu.write_to_ppu_addr(0x05);
+  assert_eq!(0x20, ppu.read_data());
+}
+
+#[test]
+fn test_sprite_palette_backdrop_entries_mirror_background() {
+  let mut ppu = new_empty_rom_ppu();
+  ppu.write_to_ctrl(0b100);
+  for (addr, value) in [(0x10, 0x01), (0x14, 0x02), (0x18, 0x03), (0x1C, 0x04)] {
+    ppu.write_to_ppu_addr(0x3F);
+    ppu.write_to_ppu_addr(addr);
+    ppu.write_to_data(value);
+  }
+
+  assert_eq!([0x01, 0x02, 0x03, 0x04], [ppu.palette_table[0x00], ppu.palette_table[0x04],
+                                        ppu.palette_table[0x08], ppu.palette_table[0x0C]]);
+  assert_eq!(0x3F3C, ppu.vram_addr());
+}
+
+#[test]
+fn test_ppudata_writes_increment_by_32() {
+  let mut ppu = new_empty_rom_ppu();
+  ppu.write_to_ctrl(0b100);
+  ppu.write_to_ppu_addr(0x20);
+  ppu.write_to_ppu_addr(0x01);
+
+  ppu.write_to_data(0x11);
+  ppu.write_to_data(0x22);
+
+  assert_eq!([0x11, 0x22], [ppu.vram[0x0001], ppu.vram[0x0021]]);
+  assert_eq!(0x2041, ppu.vram_addr());
+}
+
+#[test]
+fn test_vram_four_screen_uses_separate_nametables() {
+  let ppu = NesPPU::new(vec![0; 2048], Mirroring::FOUR_SCREEN);
+
+  assert_eq!(0x0005, ppu.mirror_vram_addr(0x2005));
+  assert_eq!(0x0405, ppu.mirror_vram_addr(0x2405));
+  assert_eq!(0x0805, ppu.mirror_vram_addr(0x2805));
+  assert_eq!(0x0C05, ppu.mirror_vram_addr(0x2C05));
+  assert_eq!(0x0C05, ppu.mirror_vram_addr(0x3C05));
+}
+
+#[test]
+fn test_vram_mirroring_translation_table() {
+  let horizontal = NesPPU::new(vec![0; 2048], Mirroring::HORIZONTAL);
+  let vertical = NesPPU::new(vec![0; 2048], Mirroring::VERTICAL);
+  let nametables = [0x2000, 0x2400, 0x2800, 0x2C00].map(|n| n + 0x3FF);
+
+  assert_eq!([0x3FF, 0x3FF, 0x7FF, 0x7FF], nametables.map(|a| horizontal.mirror_vram_addr(a)));
+  assert_eq!([0x3FF, 0x7FF, 0x3FF, 0x7FF], nametables.map(|a| vertical.mirror_vram_addr(a)));
+}
+
+#[test]
+fn test_mapper_switches_mirroring_at_runtime() {
+  // AxROM selects the upper nametable with bit 4 of any rom write
+  let mut rom = create_test_rom();
+  rom.mapper = 7;
+  let mut bus = Bus::new(rom).unwrap();
+  bus.mem_write(0x2006, 0x20);
+  bus.mem_write(0x2006, 0x05);
+  bus.mem_write(0x2007, 0x11);
+
+  bus.mem_write(0x8000, 0b1_0000);
+  bus.mem_write(0x2006, 0x2C);
+  bus.mem_write(0x2006, 0x05);
+  bus.mem_write(0x2007, 0x22);
+
+  assert_eq!(0x11, bus.ppu.vram[0x0005]);
+  assert_eq!(0x22, bus.ppu.vram[0x0405]);
+}
+
+fn dots_until_frame_complete(ppu: &mut NesPPU) -> usize {
+  let mut dots = 1;
+  while !ppu.tick(1) {
+    dots += 1;
+  }
+  dots
+}
+
+#[test]
+fn test_odd_frames_skip_a_dot_while_rendering() {
+  let mut ppu = new_empty_rom_ppu();
+  assert_eq!(89342, dots_until_frame_complete(&mut ppu));
+  assert_eq!(89342, dots_until_frame_complete(&mut ppu));
+
+  ppu.write_to_mask(0b0000_1000);
+  assert_eq!(89342, dots_until_frame_complete(&mut ppu));
+  assert_eq!(89341, dots_until_frame_complete(&mut ppu));
+}
+
+#[test]
+fn test_sprite_zero_hit_is_set_on_its_scanline() {
+  let mut chr = vec![0; 0x2000];
+  for row in 0..8 {
+    chr[0x10 + row] = 0xFF; // tile 1 is opaque
+  }
+  let mut ppu = NesPPU::new(chr, Mirroring::HORIZONTAL);
+  ppu.vram.fill(1);
+  ppu.oam_data = [0xFF; 256];
+  ppu.oam_data[0..4].copy_from_slice(&[29, 1, 0, 40]); // visible from scanline 30
+  ppu.write_to_mask(0b0001_1110);
+  ppu.signals = Some(vec![]);
+
+  tick_scanlines(&mut ppu, 30);
+  ppu.tick(255);
+  assert!(!ppu.status.contains(StatusRegister::SPRITE_ZERO_HIT));
+  ppu.tick(2);
+  assert!(ppu.status.contains(StatusRegister::SPRITE_ZERO_HIT));
+  // logged where the sprite starts, not where the line is drawn
+  assert_eq!(Some(vec![(FrameEventKind::Sprite0Hit, 30, 41)]), ppu.signals);
+
+  // cleared at the start of the pre-render line
+  tick_scanlines(&mut ppu, 261 - 30);
+  assert!(!ppu.status.contains(StatusRegister::SPRITE_ZERO_HIT));
+}
+
+#[test]
+fn test_mid_frame_palette_change_shows_up_in_the_frame() {
+  let mut ppu = new_empty_rom_ppu();
+  ppu.palette_table[0] = 0x01;
+  tick_scanlines(&mut ppu, 120);
+  ppu.palette_table[0] = 0x02;
+  tick_scanlines(&mut ppu, 121);
+
+  assert_eq!(SYSTEM_PALETTE[0x01], ppu.frame().get_pixel(0, 119));
+  assert_eq!(SYSTEM_PALETTE[0x02], ppu.frame().get_pixel(0, 120));
+}
+
+#[test]
+fn test_with_frame_only_sees_complete_frames() {
+  let mut ppu = new_empty_rom_ppu();
+  ppu.palette_table[0] = 0x01;
+  tick_scanlines(&mut ppu, 242);
+  ppu.palette_table[0] = 0x02;
+
+  // the next picture is half drawn
+  tick_scanlines(&mut ppu, 20 + 120);
+  let (r, g, b) = SYSTEM_PALETTE[0x01];
+  ppu.with_frame(|rgba| {
+    assert_eq!(0, rgba.as_ptr() as usize % 4096);
+    assert!(rgba.chunks_exact(4).all(|pixel| pixel == [r, g, b, 0xFF]));
+  });
+
+  tick_scanlines(&mut ppu, 122);
+  let (r, g, b) = SYSTEM_PALETTE[0x02];
+  ppu.with_frame(|rgba| assert!(rgba.chunks_exact(4).all(|pixel| pixel == [r, g, b, 0xFF])));
+}
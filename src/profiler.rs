@@ -0,0 +1,105 @@
+use std::collections::HashMap;
+use crate::symbols::Symbols;
+
+// entry point for code executed outside of any JSR (reset / main loop)
+pub const TOP_LEVEL: u16 = 0xFFFF;
+
+#[derive(Debug, PartialEq)]
+pub struct HotSpot {
+  pub address: u16,
+  pub cycles: u64,
+  pub share: f64, // of all profiled cycles
+}
+
+// accumulates cpu cycles per instruction address and per called function (JSR target)
+#[derive(Default)]
+pub struct Profiler {
+  total_cycles: u64,
+  by_address: HashMap<u16, u64>,
+  by_function: HashMap<u16, u64>,
+}
+
+impl Profiler {
+  pub fn new() -> Self {
+    Profiler::default()
+  }
+
+  pub fn record(&mut self, program_counter: u16, cycles: u64, function: Option<u16>) {
+    self.total_cycles += cycles;
+    *self.by_address.entry(program_counter).or_insert(0) += cycles;
+    *self.by_function.entry(function.unwrap_or(TOP_LEVEL)).or_insert(0) += cycles;
+  }
+
+  pub fn total_cycles(&self) -> u64 {
+    self.total_cycles
+  }
+
+  pub fn reset(&mut self) {
+    *self = Profiler::default();
+  }
+
+  pub fn hottest_addresses(&self, count: usize) -> Vec<HotSpot> {
+    self.hottest(&self.by_address, count)
+  }
+
+  pub fn hottest_functions(&self, count: usize) -> Vec<HotSpot> {
+    self.hottest(&self.by_function, count)
+  }
+
+  fn hottest(&self, buckets: &HashMap<u16, u64>, count: usize) -> Vec<HotSpot> {
+    let mut spots: Vec<HotSpot> = buckets.iter()
+      .map(|(&address, &cycles)| HotSpot {
+        address,
+        cycles,
+        share: cycles as f64 / self.total_cycles.max(1) as f64,
+      })
+      .collect();
+    // most cycles first, lower address wins ties to keep reports stable
+    spots.sort_by(|a, b| b.cycles.cmp(&a.cycles).then(a.address.cmp(&b.address)));
+    spots.truncate(count);
+    spots
+  }
+
+  // cycles of every address are added to the closest label before it, unlabelled code stays on its own
+  pub fn hottest_labels(&self, symbols: &Symbols, count: usize) -> Vec<HotSpot> {
+    let mut by_label: HashMap<u16, u64> = HashMap::new();
+    for (&address, &cycles) in &self.by_address {
+      let start = symbols.containing(address).map_or(address, |(start, _)| start);
+      *by_label.entry(start).or_insert(0) += cycles;
+    }
+    self.hottest(&by_label, count)
+  }
+
+  pub fn report(&self, count: usize) -> String {
+    self.report_with_symbols(count, &Symbols::new())
+  }
+
+  // with symbols functions are shown by name and a section grouped by label is added
+  pub fn report_with_symbols(&self, count: usize, symbols: &Symbols) -> String {
+    let line = |name: String, spot: &HotSpot| format!("  {:<24} {:>10} cycles {:>6.2}%\n", name, spot.cycles, spot.share * 100.0);
+    let mut report = format!("profiled {} cycles\nhottest functions:\n", self.total_cycles);
+    for spot in self.hottest_functions(count) {
+      let name = match spot.address {
+        TOP_LEVEL => "<top level>".to_string(),
+        address => symbols.label(address).map_or_else(|| format!("${:04X}", address), String::from),
+      };
+      report.push_str(&line(name, &spot));
+    }
+    if !symbols.is_empty() {
+      report.push_str("hottest labels:\n");
+      for spot in self.hottest_labels(symbols, count) {
+        let name = symbols.label(spot.address).map_or_else(|| format!("${:04X}", spot.address), String::from);
+        report.push_str(&line(name, &spot));
+      }
+    }
+    report.push_str("hottest addresses:\n");
+    for spot in self.hottest_addresses(count) {
+      let name = match symbols.describe(spot.address) {
+        Some(description) => format!("${:04X} {}", spot.address, description),
+        None => format!("${:04X}", spot.address),
+      };
+      report.push_str(&line(name, &spot));
+    }
+    report
+  }
+}
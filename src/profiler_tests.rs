@@ -0,0 +1,80 @@
+use crate::bus::Bus;
+use crate::cartridge_tests::create_test_rom;
+use crate::cpu::{MyCPU, MyMem};
+use crate::profiler::{Profiler, TOP_LEVEL};
+use crate::symbols::Symbols;
+
+#[test]
+fn test_hottest_addresses_sorted_by_cycles() {
+  let mut profiler = Profiler::new();
+  profiler.record(0x8000, 2, None);
+  profiler.record(0x8002, 5, None);
+  profiler.record(0x8000, 2, None);
+  profiler.record(0x8004, 1, None);
+
+  let spots = profiler.hottest_addresses(2);
+
+  assert_eq!(10, profiler.total_cycles());
+  assert_eq!(2, spots.len());
+  assert_eq!((0x8002, 5), (spots[0].address, spots[0].cycles));
+  assert_eq!((0x8000, 4), (spots[1].address, spots[1].cycles));
+  assert_eq!(0.5, spots[0].share);
+}
+
+#[test]
+fn test_cycles_bucketed_by_function() {
+  let mut profiler = Profiler::new();
+  profiler.record(0x8000, 6, None);
+  profiler.record(0x9000, 2, Some(0x9000));
+  profiler.record(0x9001, 2, Some(0x9000));
+
+  let spots = profiler.hottest_functions(5);
+
+  assert_eq!(TOP_LEVEL, spots[0].address);
+  assert_eq!((0x9000, 4), (spots[1].address, spots[1].cycles));
+  assert!(profiler.report(5).contains("<top level>"));
+}
+
+#[test]
+fn test_cpu_feeds_profiler() {
+  let mut cpu = MyCPU::new(Bus::new(create_test_rom()).unwrap());
+  cpu.program_counter = 0x0600;
+  cpu.profiler = Some(Profiler::new());
+  // JSR $0610, BRK; $0610: INX, RTS
+  cpu.mem_write(0x0610, 0xE8);
+  cpu.mem_write(0x0611, 0x60);
+
+  cpu.load_and_run(vec![0x20, 0x10, 0x06]);
+
+  let profiler = cpu.profiler.unwrap();
+  // JSR 6 + INX 2 + RTS 6 + BRK 7
+  assert_eq!(21, profiler.total_cycles());
+  assert_eq!(21, cpu.cycles);
+  let functions = profiler.hottest_functions(2);
+  assert_eq!((TOP_LEVEL, 13), (functions[0].address, functions[0].cycles));
+  assert_eq!((0x0610, 8), (functions[1].address, functions[1].cycles));
+}
+
+#[test]
+fn test_report_with_symbols() {
+  let mut profiler = Profiler::new();
+  profiler.record(0x8000, 2, None);
+  profiler.record(0x8002, 3, None);
+  profiler.record(0x9000, 4, Some(0x9000));
+  profiler.record(0x9003, 4, Some(0x9000));
+  profiler.record(0xA000, 1, None);
+  let mut symbols = Symbols::new();
+  symbols.add(0x8000, "main");
+  symbols.add(0x9000, "update");
+  symbols.add(0x0010, "player_x");
+
+  let labels = profiler.hottest_labels(&symbols, 5);
+  assert_eq!(vec![(0x9000, 8), (0x8000, 5), (0xA000, 1)],
+             labels.iter().map(|s| (s.address, s.cycles)).collect::<Vec<_>>());
+
+  let report = profiler.report_with_symbols(5, &symbols);
+  assert!(report.contains("hottest labels:\n  update "), "{}", report);
+  assert!(report.contains("  $9003 update+3 "), "{}", report);
+  assert!(report.contains("  $A000 "), "{}", report);
+  assert!(!profiler.report(5).contains("hottest labels"));
+}
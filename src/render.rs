@@ -0,0 +1,196 @@
+use crate::frame::{Frame, IndexedFrame};
+use crate::palette::color_index;
+use crate::ppu::{MaskRegister, mirror_palette_addr, NesPPU};
+
+pub const MAX_SPRITES_PER_SCANLINE: usize = 8;
+
+// what the ppu status register learns from drawing a scanline
+#[derive(Debug, Default, PartialEq)]
+pub struct ScanlineInfo {
+  // x of the first pixel where sprite 0 hit the background
+  pub sprite_zero_hit: Option<usize>,
+  pub sprite_overflow: bool,
+}
+
+// whole picture from the current ppu state, the ppu itself draws line by line while running
+pub fn render(ppu: &NesPPU, frame: &mut Frame) {
+  let mut indexed = IndexedFrame::new();
+  for y in 0..Frame::HEIGHT {
+    render_scanline(ppu, y, &mut indexed);
+  }
+  *frame = indexed.to_frame(&ppu.palette);
+}
+
+pub fn render_scanline(ppu: &NesPPU, y: usize, frame: &mut IndexedFrame) -> ScanlineInfo {
+  // remembers which background pixels aren't transparent, for sprite priority
+  let mut background_opaque = [false; Frame::WIDTH];
+
+  if ppu.mask.contains(MaskRegister::SHOW_BACKGROUND) {
+    render_background(ppu, y, frame, &mut background_opaque);
+  } else {
+    let index = system_index(ppu, backdrop(ppu, y));
+    for x in 0..Frame::WIDTH {
+      frame.set_pixel(x, y, index);
+    }
+  }
+
+  if ppu.mask.contains(MaskRegister::SHOW_SPRITES) {
+    render_sprites(ppu, y, frame, &background_opaque)
+  } else {
+    ScanlineInfo::default()
+  }
+}
+
+// with rendering off the backdrop is the palette entry v points at, if it points into the palette
+fn backdrop(ppu: &NesPPU, y: usize) -> u8 {
+  let v = ppu.scanline_scroll[y].v & 0x3FFF;
+  let rendering = ppu.mask.intersects(MaskRegister::SHOW_BACKGROUND | MaskRegister::SHOW_SPRITES);
+  if !rendering && v >= 0x3F00 {
+    ppu.palette_table[mirror_palette_addr(v)]
+  } else {
+    ppu.palette_table[0]
+  }
+}
+
+// greyscale keeps only the brightness column of the palette
+fn system_index(ppu: &NesPPU, palette_entry: u8) -> u16 {
+  let entry = if ppu.mask.contains(MaskRegister::GREYSCALE) { palette_entry & 0x30 } else { palette_entry };
+  color_index(entry, ppu.mask.bits() >> 5)
+}
+
+fn pattern_byte(ppu: &NesPPU, addr: u16) -> u8 {
+  ppu.chr_read(addr)
+}
+
+// 2 bit color of a pattern table pixel, 0 is transparent
+pub fn pattern_pixel(ppu: &NesPPU, tile_addr: u16, row: usize, col: usize) -> u8 {
+  let upper = pattern_byte(ppu, tile_addr + row as u16);
+  let lower = pattern_byte(ppu, tile_addr + row as u16 + 8);
+  let bit = 7 - col;
+  ((lower >> bit) & 1) << 1 | ((upper >> bit) & 1)
+}
+
+fn bg_palette(ppu: &NesPPU, nametable_start: usize, tile_column: usize, tile_row: usize) -> [u8; 4] {
+  let attr_table_idx = tile_row / 4 * 8 + tile_column / 4;
+  let attr_byte = ppu.vram[nametable_start + 0x3C0 + attr_table_idx];
+
+  let palette_idx = match (tile_column % 4 / 2, tile_row % 4 / 2) {
+    (0, 0) => attr_byte & 0b11,
+    (1, 0) => (attr_byte >> 2) & 0b11,
+    (0, 1) => (attr_byte >> 4) & 0b11,
+    _ => (attr_byte >> 6) & 0b11,
+  };
+
+  let start = 1 + (palette_idx as usize) * 4;
+  [ppu.palette_table[0], ppu.palette_table[start], ppu.palette_table[start + 1], ppu.palette_table[start + 2]]
+}
+
+// every scanline starts at the scroll position the ppu recorded for it
+fn render_background(ppu: &NesPPU, y: usize, frame: &mut IndexedFrame, opaque: &mut [bool]) {
+  let bank = ppu.ctrl.background_pattern_addr();
+  let scroll = ppu.scanline_scroll[y];
+  let fine_y = ((scroll.v >> 12) & 0b111) as usize;
+  let coarse_y = ((scroll.v >> 5) & 0b1_1111) as usize;
+  let clip_left = !ppu.mask.contains(MaskRegister::LEFTMOST_8PXL_BACKGROUND);
+
+  for (x, opaque) in opaque.iter_mut().enumerate() {
+    if clip_left && x < 8 {
+      frame.set_pixel(x, y, system_index(ppu, ppu.palette_table[0]));
+      continue;
+    }
+    let scrolled_x = x + scroll.fine_x as usize;
+    let column = (scroll.v & 0b1_1111) as usize + scrolled_x / 8;
+    // running past the right edge continues in the horizontally neighbouring nametable
+    let nametable = ((scroll.v >> 10) & 0b11) as usize ^ ((column / 32) & 1);
+    let coarse_x = column % 32;
+
+    let nametable_start = ppu.mirror_vram_addr(0x2000 + nametable as u16 * 0x400) as usize;
+    let tile = ppu.vram[nametable_start + coarse_y * 32 + coarse_x] as u16;
+    let palette = bg_palette(ppu, nametable_start, coarse_x, coarse_y);
+    let value = pattern_pixel(ppu, bank + tile * 16, fine_y, scrolled_x % 8);
+    frame.set_pixel(x, y, system_index(ppu, palette[value as usize]));
+    *opaque = value != 0;
+  }
+}
+
+// oam indices of the sprites the hardware would show on this scanline,
+// true if more sprites were in range than it can handle
+pub fn sprites_on_scanline(ppu: &NesPPU, scanline: usize) -> (Vec<usize>, bool) {
+  let height = ppu.ctrl.sprite_size() as usize;
+  let mut sprites = Vec::with_capacity(MAX_SPRITES_PER_SCANLINE);
+  for i in 0..64 {
+    // sprites are delayed by one scanline
+    let top = ppu.oam_data[i * 4] as usize + 1;
+    if scanline >= top && scanline < top + height {
+      if sprites.len() == MAX_SPRITES_PER_SCANLINE {
+        return (sprites, true);
+      }
+      sprites.push(i);
+    }
+  }
+  (sprites, false)
+}
+
+// palette entry and behind-background flag of an opaque sprite pixel
+fn sprite_pixel(ppu: &NesPPU, sprite: usize, x: usize, scanline: usize) -> Option<(u8, bool)> {
+  let oam = &ppu.oam_data[sprite * 4..sprite * 4 + 4];
+  let left = oam[3] as usize;
+  if x < left || x >= left + 8 {
+    return None;
+  }
+
+  let attributes = oam[2];
+  let flip_vertical = attributes & 0b1000_0000 != 0;
+  let flip_horizontal = attributes & 0b0100_0000 != 0;
+  let behind_background = attributes & 0b0010_0000 != 0;
+  let palette = (attributes & 0b11) as usize;
+
+  let height = ppu.ctrl.sprite_size() as usize;
+  let mut row = scanline - (oam[0] as usize + 1);
+  if flip_vertical {
+    row = height - 1 - row;
+  }
+  let mut col = x - left;
+  if flip_horizontal {
+    col = 7 - col;
+  }
+
+  // 8x16 sprites pick their bank with bit 0 and use two consecutive tiles
+  let (bank, tile) = if height == 16 {
+    ((oam[1] & 1) as u16 * 0x1000, (oam[1] & 0xFE) as u16 + (row / 8) as u16)
+  } else {
+    (ppu.ctrl.sprite_pattern_addr(), oam[1] as u16)
+  };
+
+  match pattern_pixel(ppu, bank + tile * 16, row % 8, col) {
+    0 => None,
+    value => Some((ppu.palette_table[0x10 + palette * 4 + value as usize], behind_background)),
+  }
+}
+
+fn render_sprites(ppu: &NesPPU, scanline: usize, frame: &mut IndexedFrame, background_opaque: &[bool]) -> ScanlineInfo {
+  let (sprites, sprite_overflow) = sprites_on_scanline(ppu, scanline);
+  let mut info = ScanlineInfo { sprite_zero_hit: None, sprite_overflow };
+  // no hit in the leftmost 8 pixels if either of them is clipped, never at x = 255
+  let clipped = !ppu.mask.contains(MaskRegister::LEFTMOST_8PXL_BACKGROUND | MaskRegister::LEFTMOST_8PXL_SPRITE);
+  let hit_range = if clipped { 8..Frame::WIDTH - 1 } else { 0..Frame::WIDTH - 1 };
+  let clip_left = !ppu.mask.contains(MaskRegister::LEFTMOST_8PXL_SPRITE);
+
+  for (x, &opaque) in background_opaque.iter().enumerate() {
+    if info.sprite_zero_hit.is_none() && sprites.first() == Some(&0) && hit_range.contains(&x) && opaque
+      && sprite_pixel(ppu, 0, x, scanline).is_some() {
+      info.sprite_zero_hit = Some(x);
+    }
+    if clip_left && x < 8 {
+      continue;
+    }
+    // the lowest oam index with an opaque pixel wins, even if it is hidden behind the background
+    let pixel = sprites.iter().find_map(|&sprite| sprite_pixel(ppu, sprite, x, scanline));
+    if let Some((palette_entry, behind_background)) = pixel {
+      if !(behind_background && opaque) {
+        frame.set_pixel(x, scanline, system_index(ppu, palette_entry));
+      }
+    }
+  }
+  info
+}
@@ -0,0 +1,231 @@
+use crate::cartridge::Mirroring;
+use crate::frame::Frame;
+use crate::palette::SYSTEM_PALETTE;
+use crate::ppu::{LineScroll, MaskRegister, NesPPU};
+use crate::render::{render, sprites_on_scanline};
+
+const SPRITE_COLOR: u8 = 0x16;
+const OTHER_SPRITE_COLOR: u8 = 0x2A;
+const BACKGROUND_COLOR: u8 = 0x0F;
+const TILE_COLOR: u8 = 0x30;
+
+// tile 1: only the top left pixel is set, tile 2: fully opaque
+fn test_chr() -> Vec<u8> {
+  let mut chr = vec![0; 0x2000];
+  chr[0x10] = 0b1000_0000;
+  for row in 0..8 {
+    chr[0x20 + row] = 0xFF;
+  }
+  chr
+}
+
+fn init_ppu() -> NesPPU {
+  init_ppu_with_chr(test_chr())
+}
+
+fn init_ppu_with_chr(chr: Vec<u8>) -> NesPPU {
+  let mut ppu = NesPPU::new(chr, Mirroring::HORIZONTAL);
+  ppu.oam_data = [0xFF; 256]; // everything below the screen
+  ppu.palette_table[0x00] = BACKGROUND_COLOR;
+  ppu.palette_table[0x01] = TILE_COLOR;
+  ppu.palette_table[0x11] = SPRITE_COLOR;
+  ppu.palette_table[0x15] = OTHER_SPRITE_COLOR;
+  ppu.mask = MaskRegister::SHOW_SPRITES;
+  ppu
+}
+
+// both layers, including the leftmost 8 pixels
+fn show_all() -> MaskRegister {
+  MaskRegister::SHOW_SPRITES | MaskRegister::SHOW_BACKGROUND
+    | MaskRegister::LEFTMOST_8PXL_SPRITE | MaskRegister::LEFTMOST_8PXL_BACKGROUND
+}
+
+fn place_sprite(ppu: &mut NesPPU, index: usize, x: u8, y: u8, tile: u8, attributes: u8) {
+  ppu.oam_data[index * 4..index * 4 + 4].copy_from_slice(&[y, tile, attributes, x]);
+}
+
+fn rendered(ppu: &NesPPU) -> Frame {
+  let mut frame = Frame::new();
+  render(ppu, &mut frame);
+  frame
+}
+
+fn rgb(color: u8) -> (u8, u8, u8) {
+  SYSTEM_PALETTE[color as usize]
+}
+
+#[test]
+fn test_sprite_is_drawn_one_scanline_below_oam_y() {
+  let mut ppu = init_ppu();
+  place_sprite(&mut ppu, 0, 10, 20, 1, 0);
+
+  let frame = rendered(&ppu);
+
+  assert_eq!(rgb(SPRITE_COLOR), frame.get_pixel(10, 21));
+  assert_eq!(rgb(BACKGROUND_COLOR), frame.get_pixel(11, 21));
+  assert_eq!(rgb(BACKGROUND_COLOR), frame.get_pixel(10, 20));
+}
+
+#[test]
+fn test_sprite_flips() {
+  let mut ppu = init_ppu();
+  place_sprite(&mut ppu, 0, 10, 20, 1, 0b0100_0000);
+  place_sprite(&mut ppu, 1, 30, 20, 1, 0b1000_0000);
+  place_sprite(&mut ppu, 2, 50, 20, 1, 0b1100_0000);
+
+  let frame = rendered(&ppu);
+
+  assert_eq!(rgb(SPRITE_COLOR), frame.get_pixel(17, 21));
+  assert_eq!(rgb(SPRITE_COLOR), frame.get_pixel(30, 28));
+  assert_eq!(rgb(SPRITE_COLOR), frame.get_pixel(57, 28));
+  assert_eq!(rgb(BACKGROUND_COLOR), frame.get_pixel(10, 21));
+}
+
+#[test]
+fn test_sprite_behind_opaque_background() {
+  let mut ppu = init_ppu();
+  ppu.mask = show_all();
+  ppu.vram[0] = 2; // opaque tile in the top left corner of the first nametable
+  place_sprite(&mut ppu, 0, 0, 0, 2, 0b0010_0000);
+  place_sprite(&mut ppu, 1, 8, 0, 2, 0b0010_0000);
+
+  let frame = rendered(&ppu);
+
+  assert_eq!(rgb(TILE_COLOR), frame.get_pixel(0, 1));
+  assert_eq!(rgb(SPRITE_COLOR), frame.get_pixel(8, 1));
+}
+
+#[test]
+fn test_lower_oam_index_wins_even_when_behind_background() {
+  let mut ppu = init_ppu();
+  ppu.mask = show_all();
+  ppu.vram[0] = 2;
+  place_sprite(&mut ppu, 0, 0, 0, 2, 0b0010_0000);
+  place_sprite(&mut ppu, 1, 0, 0, 2, 0b0000_0001);
+  place_sprite(&mut ppu, 2, 16, 0, 2, 0b0000_0001);
+  place_sprite(&mut ppu, 3, 16, 0, 2, 0);
+
+  let frame = rendered(&ppu);
+
+  assert_eq!(rgb(TILE_COLOR), frame.get_pixel(0, 1));
+  assert_eq!(rgb(OTHER_SPRITE_COLOR), frame.get_pixel(16, 1));
+}
+
+#[test]
+fn test_8x16_sprites_use_bank_bit_and_two_tiles() {
+  // tiles $1002/$1003 in the second bank: bottom half gets the single pixel tile
+  let mut chr = test_chr();
+  chr[0x1030] = 0b1000_0000;
+  let mut ppu = init_ppu_with_chr(chr);
+  ppu.ctrl.insert(crate::ppu::ControlRegister::SPRITE_SIZE);
+  place_sprite(&mut ppu, 0, 10, 20, 0x03, 0);
+  place_sprite(&mut ppu, 1, 30, 20, 0x03, 0b1000_0000);
+
+  let frame = rendered(&ppu);
+
+  assert_eq!(rgb(SPRITE_COLOR), frame.get_pixel(10, 29));
+  assert_eq!(rgb(BACKGROUND_COLOR), frame.get_pixel(10, 21));
+  // vertical flip mirrors over all 16 rows
+  assert_eq!(rgb(SPRITE_COLOR), frame.get_pixel(30, 28));
+}
+
+#[test]
+fn test_only_8_sprites_per_scanline() {
+  let mut ppu = init_ppu();
+  for i in 0..9 {
+    place_sprite(&mut ppu, i, (i * 10) as u8, 20, 1, 0);
+  }
+
+  let (sprites, overflow) = sprites_on_scanline(&ppu, 21);
+  let frame = rendered(&ppu);
+
+  assert_eq!((0..8).collect::<Vec<usize>>(), sprites);
+  assert!(overflow);
+  assert_eq!(rgb(SPRITE_COLOR), frame.get_pixel(70, 21));
+  assert_eq!(rgb(BACKGROUND_COLOR), frame.get_pixel(80, 21));
+}
+
+#[test]
+fn test_background_scrolls_into_the_neighbouring_nametable() {
+  let mut ppu = init_ppu_with_chr(test_chr());
+  ppu.mask = MaskRegister::SHOW_BACKGROUND | MaskRegister::LEFTMOST_8PXL_BACKGROUND;
+  ppu.vram[0x400] = 2; // first tile of the second nametable (mirrored to the first one here)
+  ppu.vram[0x000] = 2;
+  ppu.scanline_scroll = [LineScroll { v: 31, fine_x: 4 }; 240];
+
+  let frame = rendered(&ppu);
+
+  assert_eq!(rgb(BACKGROUND_COLOR), frame.get_pixel(3, 0));
+  assert_eq!(rgb(TILE_COLOR), frame.get_pixel(4, 0));
+  assert_eq!(rgb(TILE_COLOR), frame.get_pixel(11, 7));
+  assert_eq!(rgb(BACKGROUND_COLOR), frame.get_pixel(12, 0));
+}
+
+#[test]
+fn test_each_scanline_uses_its_own_scroll() {
+  let mut ppu = init_ppu_with_chr(test_chr());
+  ppu.mask = MaskRegister::SHOW_BACKGROUND | MaskRegister::LEFTMOST_8PXL_BACKGROUND;
+  ppu.vram[0] = 2;
+  // status bar on top, the playfield below is scrolled one tile to the right
+  for y in 100..240 {
+    ppu.scanline_scroll[y] = LineScroll { v: 0x0001 | ((y as u16 / 8) << 5) | ((y as u16 % 8) << 12), fine_x: 0 };
+  }
+  ppu.vram[(100 / 8) * 32 + 1] = 2;
+
+  let frame = rendered(&ppu);
+
+  assert_eq!(rgb(TILE_COLOR), frame.get_pixel(0, 0));
+  assert_eq!(rgb(TILE_COLOR), frame.get_pixel(0, 100));
+  assert_eq!(rgb(BACKGROUND_COLOR), frame.get_pixel(8, 100));
+}
+
+#[test]
+fn test_left_column_clipping() {
+  let mut ppu = init_ppu();
+  for column in 0..2 {
+    ppu.vram[column] = 2;
+  }
+  place_sprite(&mut ppu, 1, 0, 10, 2, 0);
+  ppu.mask = MaskRegister::SHOW_SPRITES | MaskRegister::SHOW_BACKGROUND;
+
+  let frame = rendered(&ppu);
+
+  // both layers hidden in the first 8 pixels, the backdrop shows
+  assert_eq!(rgb(BACKGROUND_COLOR), frame.get_pixel(0, 1));
+  assert_eq!(rgb(TILE_COLOR), frame.get_pixel(8, 1));
+  assert_eq!(rgb(BACKGROUND_COLOR), frame.get_pixel(7, 11));
+
+  ppu.mask.insert(MaskRegister::LEFTMOST_8PXL_SPRITE);
+  let frame = rendered(&ppu);
+  assert_eq!(rgb(SPRITE_COLOR), frame.get_pixel(7, 11));
+  assert_eq!(rgb(BACKGROUND_COLOR), frame.get_pixel(0, 1));
+}
+
+#[test]
+fn test_disabled_layers_are_not_drawn() {
+  let mut ppu = init_ppu();
+  ppu.vram[1] = 2;
+  place_sprite(&mut ppu, 0, 40, 10, 2, 0);
+
+  ppu.mask = MaskRegister::SHOW_SPRITES;
+  let frame = rendered(&ppu);
+  assert_eq!(rgb(BACKGROUND_COLOR), frame.get_pixel(8, 1));
+  assert_eq!(rgb(SPRITE_COLOR), frame.get_pixel(40, 11));
+
+  ppu.mask = MaskRegister::SHOW_BACKGROUND;
+  let frame = rendered(&ppu);
+  assert_eq!(rgb(TILE_COLOR), frame.get_pixel(8, 1));
+  assert_eq!(rgb(BACKGROUND_COLOR), frame.get_pixel(40, 11));
+}
+
+#[test]
+fn test_rendering_off_shows_the_palette_entry_v_points_at() {
+  let mut ppu = init_ppu();
+  ppu.mask = MaskRegister::empty();
+  ppu.scanline_scroll[5] = LineScroll { v: 0x3F11, fine_x: 0 };
+
+  let frame = rendered(&ppu);
+
+  assert_eq!(rgb(SPRITE_COLOR), frame.get_pixel(100, 5));
+  assert_eq!(rgb(BACKGROUND_COLOR), frame.get_pixel(100, 6));
+}
@@ -0,0 +1,42 @@
+// brings the mixer output from one value per cpu cycle (~1.79 MHz) down to the sample rate
+// of the sound card. Every output sample is the average of the cycles it covers: a box
+// filter, far from band-limited synthesis, but it keeps the aliasing of plain
+// point sampling (the pulse edges) out of the audible range mostly.
+pub struct Resampler {
+  cycles_per_sample: f64,
+  timer: f64,
+  sum: f64,
+  count: u32,
+}
+
+impl Resampler {
+  pub fn new(input_rate: f64, output_rate: u32) -> Self {
+    Resampler { cycles_per_sample: input_rate / output_rate as f64, timer: 0.0, sum: 0.0, count: 0 }
+  }
+
+  // one input value, an output sample once enough input is together
+  pub fn push(&mut self, input: f32) -> Option<f32> {
+    self.sum += input as f64;
+    self.count += 1;
+    self.timer += 1.0;
+    if self.timer < self.cycles_per_sample {
+      return None;
+    }
+    self.timer -= self.cycles_per_sample;
+    let sample = (self.sum / self.count as f64) as f32;
+    self.sum = 0.0;
+    self.count = 0;
+    Some(sample)
+  }
+
+  // position inside the current output sample, for save states
+  pub fn timer(&self) -> f64 {
+    self.timer
+  }
+
+  pub fn set_timer(&mut self, timer: f64) {
+    self.timer = timer;
+    self.sum = 0.0;
+    self.count = 0;
+  }
+}
@@ -0,0 +1,31 @@
+use crate::apu::CPU_FREQUENCY;
+use crate::resampler::Resampler;
+
+#[test]
+fn test_one_second_of_cycles_gives_the_sample_rate() {
+  let mut resampler = Resampler::new(CPU_FREQUENCY, 44_100);
+
+  let samples = (0..CPU_FREQUENCY as usize).filter_map(|_| resampler.push(0.5)).count();
+
+  assert!((44_099..=44_100).contains(&samples), "{}", samples);
+}
+
+#[test]
+fn test_output_is_the_average_of_the_covered_input() {
+  let mut resampler = Resampler::new(4.0, 1);
+
+  assert_eq!(None, resampler.push(1.0));
+  assert_eq!(None, resampler.push(0.0));
+  assert_eq!(None, resampler.push(1.0));
+  assert_eq!(Some(0.5), resampler.push(0.0));
+}
+
+#[test]
+fn test_tone_above_nyquist_averages_out() {
+  // alternating every cycle, point sampling would return only 0.0 or 1.0
+  let mut resampler = Resampler::new(CPU_FREQUENCY, 44_100);
+
+  let samples: Vec<f32> = (0..10_000).filter_map(|i| resampler.push((i % 2) as f32)).collect();
+
+  assert!(samples.iter().all(|&s| (0.45..=0.55).contains(&s)));
+}
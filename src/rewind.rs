@@ -0,0 +1,76 @@
+use crate::cpu::MyCPU;
+use crate::delta::DeltaHistory;
+
+pub const DEFAULT_INTERVAL_FRAMES: u32 = 5;
+pub const DEFAULT_CAPACITY_SECONDS: u32 = 30;
+const FRAMES_PER_SECOND: f32 = 60.0;
+
+// save state every few frames, kept delta encoded: consecutive states mostly differ
+// in ram and a few registers, so a 30s buffer stays far below the size of full states
+pub struct Rewind {
+  interval_frames: u32,
+  max_states: usize,
+  frames_since_state: u32,
+  states: DeltaHistory,
+}
+
+impl Rewind {
+  pub fn new(interval_frames: u32, capacity_seconds: u32) -> Self {
+    let interval_frames = interval_frames.max(1);
+    Rewind {
+      interval_frames,
+      max_states: ((capacity_seconds as f32 * FRAMES_PER_SECOND) as usize / interval_frames as usize).max(1),
+      frames_since_state: 0,
+      states: DeltaHistory::new(),
+    }
+  }
+
+  pub fn len(&self) -> usize {
+    self.states.len()
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.states.is_empty()
+  }
+
+  pub fn memory_usage(&self) -> usize {
+    self.states.memory_usage()
+  }
+
+  // call once per emulated frame
+  pub fn on_frame(&mut self, cpu: &MyCPU) {
+    if self.frames_since_state > 0 {
+      self.frames_since_state = (self.frames_since_state + 1) % self.interval_frames;
+      return;
+    }
+    if self.states.len() == self.max_states {
+      self.states.pop_front();
+    }
+    self.states.push(cpu.save_state());
+    self.frames_since_state = 1 % self.interval_frames;
+  }
+
+  // restores the state taken about `seconds` ago (or the oldest one), newer states are dropped
+  pub fn rewind(&mut self, cpu: &mut MyCPU, seconds: f32) -> bool {
+    if self.states.is_empty() {
+      return false;
+    }
+    let frames = (seconds.max(0.0) * FRAMES_PER_SECOND) as usize;
+    let states_back = frames.div_ceil(self.interval_frames as usize);
+    let keep = self.states.len().saturating_sub(states_back).max(1);
+    while self.states.len() > keep {
+      self.states.pop_back();
+    }
+
+    let state = self.states.get(keep - 1).expect("newest rewind state");
+    cpu.load_state(&state).expect("rewind state of the running rom");
+    self.frames_since_state = 1 % self.interval_frames;
+    true
+  }
+}
+
+impl Default for Rewind {
+  fn default() -> Self {
+    Rewind::new(DEFAULT_INTERVAL_FRAMES, DEFAULT_CAPACITY_SECONDS)
+  }
+}
@@ -0,0 +1,62 @@
+use crate::bus::Bus;
+use crate::cartridge_tests::create_test_rom;
+use crate::cpu::MyCPU;
+use crate::rewind::Rewind;
+
+fn init_cpu() -> MyCPU {
+  MyCPU::new(Bus::new(create_test_rom()).unwrap())
+}
+
+// register a holds the frame number
+fn run_frames(rewind: &mut Rewind, cpu: &mut MyCPU, frames: std::ops::Range<u8>) {
+  for frame in frames {
+    cpu.register_a = frame;
+    rewind.on_frame(cpu);
+  }
+}
+
+#[test]
+fn test_rewind_restores_state_from_seconds_ago() {
+  let mut cpu = init_cpu();
+  let mut rewind = Rewind::new(5, 10);
+  run_frames(&mut rewind, &mut cpu, 0..121);
+  assert_eq!(25, rewind.len()); // frames 0, 5, ..., 120
+
+  assert!(rewind.rewind(&mut cpu, 1.0));
+  assert_eq!(60, cpu.register_a);
+  assert_eq!(13, rewind.len());
+
+  // continues recording from the restored state
+  run_frames(&mut rewind, &mut cpu, 61..66);
+  assert_eq!(14, rewind.len());
+  assert!(rewind.rewind(&mut cpu, 0.0));
+  assert_eq!(65, cpu.register_a);
+}
+
+#[test]
+fn test_rewind_is_limited_by_capacity() {
+  let mut cpu = init_cpu();
+  let mut rewind = Rewind::new(1, 1);
+  run_frames(&mut rewind, &mut cpu, 0..200);
+  assert_eq!(60, rewind.len());
+
+  assert!(rewind.rewind(&mut cpu, 100.0));
+  assert_eq!(140, cpu.register_a);
+  assert_eq!(1, rewind.len());
+}
+
+#[test]
+fn test_rewind_states_are_delta_encoded() {
+  let mut cpu = init_cpu();
+  let mut rewind = Rewind::new(1, 1);
+  run_frames(&mut rewind, &mut cpu, 0..60);
+
+  let full_states = 60 * cpu.save_state().len();
+  assert!(rewind.memory_usage() < full_states / 10, "{} bytes", rewind.memory_usage());
+}
+
+#[test]
+fn test_nothing_to_rewind() {
+  let mut cpu = init_cpu();
+  assert!(!Rewind::default().rewind(&mut cpu, 1.0));
+}
@@ -0,0 +1,38 @@
+// palette indices to rgba bytes, once per frame for front-ends uploading rgba textures,
+// so at fast-forward speeds it runs thousands of times a second. With avx2 the colors
+// are gathered 8 at a time, everything else takes the scalar loop.
+// `table` holds the colors as little endian words (Palette::rgba_table)
+pub fn indexed_to_rgba(table: &[u32; 512], indices: &[u16], out: &mut [u8]) {
+  assert_eq!(indices.len() * 4, out.len(), "rgba output doesn't match {} pixels", indices.len());
+  #[cfg(target_arch = "x86_64")]
+  if is_x86_feature_detected!("avx2") {
+    // safe: avx2 is there, the lengths are checked above
+    unsafe { avx2::indexed_to_rgba(table, indices, out) };
+    return;
+  }
+  indexed_to_rgba_scalar(table, indices, out);
+}
+
+pub fn indexed_to_rgba_scalar(table: &[u32; 512], indices: &[u16], out: &mut [u8]) {
+  for (rgba, &index) in out.chunks_exact_mut(4).zip(indices) {
+    rgba.copy_from_slice(&table[(index & 0x1FF) as usize].to_le_bytes());
+  }
+}
+
+#[cfg(target_arch = "x86_64")]
+mod avx2 {
+  use std::arch::x86_64::*;
+
+  #[target_feature(enable = "avx2")]
+  pub unsafe fn indexed_to_rgba(table: &[u32; 512], indices: &[u16], out: &mut [u8]) {
+    let mask = _mm256_set1_epi32(0x1FF);
+    let blocks = indices.len() / 8;
+    for block in 0..blocks {
+      let packed = _mm_loadu_si128(indices.as_ptr().add(block * 8) as *const __m128i);
+      let offsets = _mm256_and_si256(_mm256_cvtepu16_epi32(packed), mask);
+      let colors = _mm256_i32gather_epi32::<4>(table.as_ptr() as *const i32, offsets);
+      _mm256_storeu_si256(out.as_mut_ptr().add(block * 32) as *mut __m256i, colors);
+    }
+    super::indexed_to_rgba_scalar(table, &indices[blocks * 8..], &mut out[blocks * 32..]);
+  }
+}
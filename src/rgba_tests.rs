@@ -0,0 +1,26 @@
+use crate::palette::Palette;
+use crate::rgba::{indexed_to_rgba, indexed_to_rgba_scalar};
+
+#[test]
+fn test_every_index_matches_the_palette() {
+  let palette = Palette::default();
+  // an odd count leaves a tail for the scalar loop, the upper bits are ignored
+  let indices: Vec<u16> = (0..515).map(|i| i as u16 | 0xFE00).collect();
+  let mut out = vec![0; indices.len() * 4];
+
+  indexed_to_rgba(palette.rgba_table(), &indices, &mut out);
+
+  for (rgba, &index) in out.chunks_exact(4).zip(&indices) {
+    let (r, g, b) = palette.rgb(index);
+    assert_eq!(&[r, g, b, 0xFF], rgba);
+  }
+  let mut scalar = vec![0; out.len()];
+  indexed_to_rgba_scalar(palette.rgba_table(), &indices, &mut scalar);
+  assert_eq!(scalar, out);
+}
+
+#[test]
+#[should_panic(expected = "rgba output doesn't match 2 pixels")]
+fn test_output_size_is_checked() {
+  indexed_to_rgba(Palette::default().rgba_table(), &[0, 1], &mut [0; 4]);
+}
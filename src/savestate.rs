@@ -0,0 +1,128 @@
+// binary save state format: magic, version, then every component writes its fields in a fixed order.
+// components look at reader.version() to stay compatible with states written by older versions.
+
+pub const STATE_MAGIC: [u8; 4] = *b"NESS";
+pub const STATE_VERSION: u16 = 1;
+
+pub trait Stateful {
+  fn save_state(&self, w: &mut StateWriter);
+  fn load_state(&mut self, r: &mut StateReader) -> Result<(), String>;
+}
+
+#[derive(Default)]
+pub struct StateWriter {
+  bytes: Vec<u8>,
+}
+
+impl StateWriter {
+  pub fn new() -> Self {
+    let mut w = StateWriter::default();
+    w.bytes.extend_from_slice(&STATE_MAGIC);
+    w.write_u16(STATE_VERSION);
+    w
+  }
+
+  pub fn into_bytes(self) -> Vec<u8> {
+    self.bytes
+  }
+
+  pub fn write_u8(&mut self, value: u8) {
+    self.bytes.push(value);
+  }
+
+  pub fn write_bool(&mut self, value: bool) {
+    self.write_u8(value as u8);
+  }
+
+  pub fn write_u16(&mut self, value: u16) {
+    self.bytes.extend_from_slice(&value.to_le_bytes());
+  }
+
+  pub fn write_u64(&mut self, value: u64) {
+    self.bytes.extend_from_slice(&value.to_le_bytes());
+  }
+
+  // length prefixed
+  pub fn write_bytes(&mut self, data: &[u8]) {
+    self.write_u64(data.len() as u64);
+    self.bytes.extend_from_slice(data);
+  }
+}
+
+pub struct StateReader<'a> {
+  bytes: &'a [u8],
+  pos: usize,
+  version: u16,
+}
+
+impl<'a> StateReader<'a> {
+  pub fn new(bytes: &'a [u8]) -> Result<Self, String> {
+    if bytes.len() < 6 || bytes[0..4] != STATE_MAGIC {
+      return Err("not a save state".to_string());
+    }
+    let mut r = StateReader { bytes, pos: 4, version: 0 };
+    r.version = r.read_u16()?;
+    if r.version > STATE_VERSION {
+      return Err(format!("save state version {} is newer than supported version {}", r.version, STATE_VERSION));
+    }
+    Ok(r)
+  }
+
+  pub fn version(&self) -> u16 {
+    self.version
+  }
+
+  fn take(&mut self, len: usize) -> Result<&'a [u8], String> {
+    if self.bytes.len() - self.pos < len {
+      return Err(format!("save state truncated at byte {}", self.pos));
+    }
+    let data = &self.bytes[self.pos..self.pos + len];
+    self.pos += len;
+    Ok(data)
+  }
+
+  pub fn read_u8(&mut self) -> Result<u8, String> {
+    Ok(self.take(1)?[0])
+  }
+
+  pub fn read_bool(&mut self) -> Result<bool, String> {
+    Ok(self.read_u8()? != 0)
+  }
+
+  pub fn read_u16(&mut self) -> Result<u16, String> {
+    let data = self.take(2)?;
+    Ok(u16::from_le_bytes([data[0], data[1]]))
+  }
+
+  pub fn read_u64(&mut self) -> Result<u64, String> {
+    let mut data = [0; 8];
+    data.copy_from_slice(self.take(8)?);
+    Ok(u64::from_le_bytes(data))
+  }
+
+  pub fn read_bytes(&mut self) -> Result<Vec<u8>, String> {
+    let len = self.read_u64()? as usize;
+    Ok(self.take(len)?.to_vec())
+  }
+
+  // for fixed size buffers, the stored length has to match
+  pub fn read_into(&mut self, target: &mut [u8]) -> Result<(), String> {
+    copy_exact(target, &self.read_bytes()?)
+  }
+
+  pub fn finish(&self) -> Result<(), String> {
+    if self.pos != self.bytes.len() {
+      return Err(format!("{} unexpected bytes at the end of the save state", self.bytes.len() - self.pos));
+    }
+    Ok(())
+  }
+}
+
+// a fixed size buffer from a state, the length has to match
+pub fn copy_exact<T: Copy>(target: &mut [T], data: &[T]) -> Result<(), String> {
+  if data.len() != target.len() {
+    return Err(format!("save state has {} values where {} are expected", data.len(), target.len()));
+  }
+  target.copy_from_slice(data);
+  Ok(())
+}
@@ -0,0 +1,162 @@
+use crate::bus::Bus;
+use crate::asm::assemble;
+use crate::cartridge_tests::{create_test_rom, create_test_rom_with_program, create_test_rom_with_vectors};
+use crate::cpu::{MyCPU, MyMem, RunLimits, StopCondition};
+use crate::frame::Frame;
+use crate::nes::Nes;
+use crate::savestate::{StateReader, StateWriter, STATE_VERSION};
+
+fn init_cpu() -> MyCPU {
+  let mut cpu = MyCPU::new(Bus::new(create_test_rom()).unwrap());
+  cpu.program_counter = 0x0600;
+  cpu
+}
+
+#[test]
+fn test_state_round_trip_restores_machine() {
+  let mut cpu = init_cpu();
+  // LDA #$42, STA $10, STA $6000, STA $2001 (ppu mask), STA $4003 (pulse 1), LDX #$07
+  cpu.load_and_run(vec![0xA9, 0x42, 0x85, 0x10, 0x8D, 0x00, 0x60, 0x8D, 0x01, 0x20, 0x8D, 0x03, 0x40, 0xA2, 0x07]);
+  cpu.bus.mem_write(0x8000, 0); // cnrom bank register
+  let state = cpu.save_state();
+
+  let mut other = init_cpu();
+  other.load_state(&state).unwrap();
+
+  assert_eq!(0x42, other.register_a);
+  assert_eq!(0x07, other.register_x);
+  assert_eq!(cpu.program_counter, other.program_counter);
+  assert_eq!(cpu.cycles, other.cycles);
+  assert_eq!(0x42, other.mem_read(0x10));
+  assert_eq!(0x42, other.mem_read(0x6000));
+  assert_eq!(cpu.bus.ppu.mask, other.bus.ppu.mask);
+  assert_eq!(state, other.save_state());
+}
+
+// nmi on, rendering on, a counter in ram and apu writes, the nmi handler counts frames
+const RUNNING_PROGRAM: &str = "
+        LDA #$80
+        STA $2000
+        LDA #$1E
+        STA $2001
+  loop: INC $10
+        LDA $10
+        STA $4002
+        JMP loop
+  nmi:  INC $11
+        RTI";
+
+fn running_machine() -> MyCPU {
+  let program = assemble(RUNNING_PROGRAM, 0x8000).unwrap();
+  let nmi = 0x8000 + program.len() as u16 - 3;
+  let mut cpu = MyCPU::new(Bus::new(create_test_rom_with_vectors(&program, nmi, 0x0000)).unwrap());
+  cpu.stop_condition = StopCondition::Never;
+  cpu.reset();
+  cpu
+}
+
+#[test]
+fn test_restored_machine_runs_on_identically() {
+  let mut cpu = running_machine();
+  cpu.limits = RunLimits { instructions: Some(50_000), time: None };
+  cpu.run();
+  let state = cpu.save_state();
+
+  let mut other = running_machine();
+  other.load_state(&state).unwrap();
+  other.limits = cpu.limits;
+  cpu.run();
+  other.run();
+
+  assert!(cpu.mem_read(0x11) > 0, "no frame completed");
+  assert_eq!(cpu.save_state(), other.save_state());
+  assert_eq!(cpu.bus.ppu.frame().data, other.bus.ppu.frame().data);
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn test_machine_state_survives_serde() {
+  let mut cpu = running_machine();
+  cpu.limits = RunLimits { instructions: Some(50_000), time: None };
+  cpu.run();
+  let state = cpu.machine_state();
+
+  let json = serde_json::to_string(&state).unwrap();
+  let restored: crate::cpu::MachineState = serde_json::from_str(&json).unwrap();
+  assert!(state == restored);
+
+  // covers exactly what the binary save state holds
+  let mut other = running_machine();
+  other.set_machine_state(restored).unwrap();
+  assert_eq!(cpu.save_state(), other.save_state());
+
+  let mut other_mapper = state;
+  other_mapper.bus.mapper = crate::mapper::MapperState::Axrom { chr_ram: vec![], prg_bank: 0, mirroring: crate::cartridge::Mirroring::VERTICAL };
+  assert!(other.set_machine_state(other_mapper).is_err());
+}
+
+// sets the backdrop color to $16, then loops
+fn backdrop_nes() -> Nes {
+  let program = [
+    0xA9, 0x3F, 0x8D, 0x06, 0x20, // LDA #$3F, STA $2006
+    0xA9, 0x00, 0x8D, 0x06, 0x20, // LDA #$00, STA $2006
+    0xA9, 0x16, 0x8D, 0x07, 0x20, // LDA #$16, STA $2007
+    0xA9, 0x00, 0x8D, 0x06, 0x20, // LDA #$00, STA $2006
+    0x8D, 0x06, 0x20,             // STA $2006
+    0xE8, 0x4C, 0x17, 0x80,       // loop: INX, JMP loop
+  ];
+  Nes::new(create_test_rom_with_program(&program)).unwrap()
+}
+
+#[test]
+fn test_state_saved_mid_frame_completes_the_same_frame() {
+  let mut nes = backdrop_nes();
+  nes.run_for_frames(2);
+  nes.run_for_cycles(15_000);
+  let state = nes.save_state();
+
+  // never drew a line, the lines above the save point have to come from the state
+  let mut other = backdrop_nes();
+  other.load_state(&state).unwrap();
+  nes.run_for_frames(1);
+  other.run_for_frames(1);
+
+  assert!(nes.frame().data != Frame::new().data);
+  assert!(nes.frame() == other.frame());
+}
+
+#[test]
+fn test_invalid_states_are_rejected() {
+  let mut cpu = init_cpu();
+  let state = cpu.save_state();
+
+  assert_eq!(Err("not a save state".to_string()), cpu.load_state(b"NOPE\x01\x00"));
+  assert!(cpu.load_state(&state[..state.len() - 1]).unwrap_err().starts_with("save state truncated"));
+
+  let mut extended = state.clone();
+  extended.push(0);
+  assert!(cpu.load_state(&extended).is_err());
+
+  let mut newer = state;
+  newer[4..6].copy_from_slice(&(STATE_VERSION + 1).to_le_bytes());
+  assert!(cpu.load_state(&newer).unwrap_err().contains("newer"));
+}
+
+#[test]
+fn test_writer_and_reader_agree() {
+  let mut w = StateWriter::new();
+  w.write_bool(true);
+  w.write_u16(0x1234);
+  w.write_u64(u64::MAX);
+  w.write_bytes(&[1, 2, 3]);
+  let bytes = w.into_bytes();
+
+  let mut r = StateReader::new(&bytes).unwrap();
+  assert_eq!(STATE_VERSION, r.version());
+  assert_eq!(Ok(true), r.read_bool());
+  assert_eq!(Ok(0x1234), r.read_u16());
+  assert_eq!(Ok(u64::MAX), r.read_u64());
+  let mut target = [0; 2];
+  assert!(r.read_into(&mut target).is_err());
+  assert_eq!(Ok(()), r.finish());
+}
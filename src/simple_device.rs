@@ -0,0 +1,53 @@
+use rand::{Rng, SeedableRng};
+use rand::rngs::StdRng;
+
+// the i/o of the easy6502 demos (snake and friends), mapped into ram:
+// every read of $FE returns a new random byte, $FF holds the ascii code of the last key
+// and $0200-$05FF is a 32x32 screen with one color index per pixel
+pub const RANDOM_ADDR: u16 = 0x00FE;
+pub const LAST_KEY_ADDR: u16 = 0x00FF;
+pub const SCREEN_START: u16 = 0x0200;
+pub const SCREEN_SIZE: usize = 32;
+
+pub struct SimpleDevice {
+  rng: StdRng,
+}
+
+impl SimpleDevice {
+  pub fn new(seed: u64) -> Self {
+    SimpleDevice { rng: StdRng::seed_from_u64(seed) }
+  }
+
+  pub fn random(&mut self) -> u8 {
+    self.rng.gen()
+  }
+}
+
+// converts the screen memory to rgb24, true if anything changed
+pub fn render_screen(ram: &[u8], frame: &mut [u8; SCREEN_SIZE * SCREEN_SIZE * 3]) -> bool {
+  let start = SCREEN_START as usize;
+  let screen = &ram[start..start + SCREEN_SIZE * SCREEN_SIZE];
+  let mut update = false;
+  for (pixel, &color_idx) in frame.chunks_exact_mut(3).zip(screen) {
+    let (r, g, b) = color(color_idx);
+    if pixel != [r, g, b] {
+      pixel.copy_from_slice(&[r, g, b]);
+      update = true;
+    }
+  }
+  update
+}
+
+pub fn color(byte: u8) -> (u8, u8, u8) {
+  match byte & 0x0F {
+    0 => (0, 0, 0),
+    1 => (255, 255, 255),
+    2 | 9 => (128, 128, 128),
+    3 | 10 => (255, 0, 0),
+    4 | 11 => (0, 255, 0),
+    5 | 12 => (0, 0, 255),
+    6 | 13 => (255, 0, 255),
+    7 | 14 => (255, 255, 0),
+    _ => (0, 255, 255),
+  }
+}
@@ -0,0 +1,57 @@
+use crate::bus::Bus;
+use crate::cartridge_tests::create_test_rom;
+use crate::cpu::MyMem;
+use crate::simple_device::{render_screen, SCREEN_SIZE};
+
+fn init_bus() -> Bus {
+  let mut bus = Bus::new(create_test_rom()).unwrap();
+  bus.enable_simple_device(42);
+  bus
+}
+
+#[test]
+fn test_random_byte_changes_on_every_read() {
+  let mut bus = init_bus();
+
+  let values: Vec<u8> = (0..8).map(|_| bus.mem_read(0xFE)).collect();
+
+  assert!(values.windows(2).any(|w| w[0] != w[1]));
+  // the same seed replays the same numbers
+  let mut other = init_bus();
+  assert_eq!(values, (0..8).map(|_| other.mem_read(0xFE)).collect::<Vec<u8>>());
+}
+
+#[test]
+fn test_fe_is_plain_ram_without_the_device() {
+  let mut bus = Bus::new(create_test_rom()).unwrap();
+
+  bus.mem_write(0xFE, 0x12);
+
+  assert_eq!(0x12, bus.mem_read(0xFE));
+  assert_eq!(0x12, bus.mem_read(0xFE));
+}
+
+#[test]
+fn test_last_key_can_be_cleared_by_the_program() {
+  let mut bus = init_bus();
+
+  bus.press_key(0x77);
+  assert_eq!(0x77, bus.mem_read(0xFF));
+
+  bus.mem_write(0xFF, 0x00);
+  assert_eq!(0x00, bus.mem_read(0xFF));
+}
+
+#[test]
+fn test_screen_memory_is_rendered_as_32x32_pixels() {
+  let mut bus = init_bus();
+  let mut frame = [0; SCREEN_SIZE * SCREEN_SIZE * 3];
+  bus.mem_write(0x0200, 0x01); // top left: white
+  bus.mem_write(0x05FF, 0x03); // bottom right: red
+
+  assert!(render_screen(bus.ram(), &mut frame));
+
+  assert_eq!([255, 255, 255], frame[..3]);
+  assert_eq!([255, 0, 0], frame[frame.len() - 3..]);
+  assert!(!render_screen(bus.ram(), &mut frame));
+}
@@ -0,0 +1,95 @@
+use std::ops::ControlFlow;
+use sdl2::event::Event;
+use sdl2::EventPump;
+use sdl2::keyboard::Keycode;
+use sdl2::pixels::PixelFormatEnum;
+use crate::bus::Bus;
+use crate::cartridge::Rom;
+use crate::cpu::MyCPU;
+use crate::error::EmuError;
+use crate::simple_device::{self, SCREEN_SIZE};
+use crate::stats::StatsCollector;
+
+// the 6502 snake game: 32x32 screen at $0200-$05FF, random number at $FE, last key at $FF
+pub fn run(rom: Rom) -> Result<(), EmuError> {
+  // init sdl2
+  let sdl_context = sdl2::init().unwrap();
+  let video_subsystem = sdl_context.video().unwrap();
+  let window = video_subsystem
+    .window("Snake game", (32.0 * 10.0) as u32, (32.0 * 10.0) as u32)
+    .position_centered()
+    .build().unwrap();
+
+  let mut canvas = window.into_canvas().present_vsync().build().unwrap();
+  let mut event_pump = sdl_context.event_pump().unwrap();
+  canvas.set_scale(10.0, 10.0).unwrap();
+
+  let creator = canvas.texture_creator();
+  let mut texture = creator
+    .create_texture_target(PixelFormatEnum::RGB24, 32, 32)
+    .unwrap();
+
+  let mut bus = Bus::new(rom)?;
+  bus.enable_simple_device(rand::random());
+  let mut cpu = MyCPU::new(bus);
+  cpu.reset();
+
+  let mut screen_state = [0; SCREEN_SIZE * SCREEN_SIZE * 3];
+  let mut stats = StatsCollector::new();
+
+  // run game cycle
+  cpu.run_with_callback(move |cpu| {
+    handle_user_input(cpu, &mut event_pump)?;
+
+    if simple_device::render_screen(cpu.bus.ram(), &mut screen_state) {
+      texture.update(None, &screen_state, SCREEN_SIZE * 3).unwrap();
+
+      canvas.copy(&texture, None, None).unwrap();
+
+      canvas.present();
+
+      stats.end_frame(cpu.cycles);
+      if stats.stats().frames.is_multiple_of(60) {
+        let title = format!("Snake game - {:.1} fps", stats.stats().fps);
+        canvas.window_mut().set_title(&title).unwrap();
+      }
+    }
+
+    ::std::thread::sleep(std::time::Duration::new(0, 40_000));
+    ControlFlow::Continue(())
+  });
+  Ok(())
+}
+
+fn handle_user_input(cpu: &mut MyCPU, event_pump: &mut EventPump) -> ControlFlow<()> {
+  for event in event_pump.poll_iter() {
+    match event {
+      Event::Quit { .. } | Event::KeyDown { keycode: Some(Keycode::Escape), ..} => {
+        println!("input quit");
+        return ControlFlow::Break(());
+      },
+      // where are the direction-values documented...?
+      Event::KeyDown { keycode: Some(Keycode::W), .. } => {
+        println!("input W");
+        cpu.bus.press_key(0x77);
+      },
+      Event::KeyDown { keycode: Some(Keycode::S), .. } => {
+        println!("input S");
+        cpu.bus.press_key(0x73);
+      },
+      Event::KeyDown { keycode: Some(Keycode::A), .. } => {
+        println!("input A");
+        cpu.bus.press_key(0x61);
+      },
+      Event::KeyDown { keycode: Some(Keycode::D), .. } => {
+        println!("input D");
+        cpu.bus.press_key(0x64);
+      },
+      _ => {
+        println!("input other");
+        /* do nothing */
+      }
+    }
+  }
+  ControlFlow::Continue(())
+}
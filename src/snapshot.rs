@@ -0,0 +1,47 @@
+use crate::cpu::CpuFlags;
+
+// everything needed to put the machine back into an earlier state (prg rom is immutable)
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Debug, Clone, PartialEq)]
+pub struct Snapshot {
+  pub register_a: u8,
+  pub register_x: u8,
+  pub register_y: u8,
+  pub status: CpuFlags,
+  pub program_counter: u16,
+  pub stack_pointer: u8,
+  pub cycles: usize,
+  pub ram: Vec<u8>,
+}
+
+const REGISTERS_LEN: usize = 15;
+
+impl Snapshot {
+  // fixed layout, so consecutive snapshots can be delta encoded
+  pub fn to_bytes(&self) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(REGISTERS_LEN + self.ram.len());
+    bytes.extend_from_slice(&[self.register_a, self.register_x, self.register_y, self.status.bits(), self.stack_pointer]);
+    bytes.extend_from_slice(&self.program_counter.to_le_bytes());
+    bytes.extend_from_slice(&(self.cycles as u64).to_le_bytes());
+    bytes.extend_from_slice(&self.ram);
+    bytes
+  }
+
+  pub fn from_bytes(bytes: &[u8]) -> Result<Snapshot, String> {
+    if bytes.len() < REGISTERS_LEN {
+      return Err(format!("snapshot too short: {} bytes", bytes.len()));
+    }
+    let mut cycles = [0; 8];
+    cycles.copy_from_slice(&bytes[7..15]);
+    Ok(Snapshot {
+      register_a: bytes[0],
+      register_x: bytes[1],
+      register_y: bytes[2],
+      status: CpuFlags::from_bits_truncate(bytes[3]),
+      stack_pointer: bytes[4],
+      program_counter: u16::from_le_bytes([bytes[5], bytes[6]]),
+      cycles: u64::from_le_bytes(cycles) as usize,
+      ram: bytes[REGISTERS_LEN..].to_vec(),
+    })
+  }
+}
@@ -0,0 +1,97 @@
+use std::time::Duration;
+
+// how fast the front-end runs the emulation, the emulation itself doesn't change
+#[derive(Debug, Clone, PartialEq)]
+pub struct EmulationSpeed {
+  multiplier: f64,
+  unlimited: bool,
+  paused: bool,
+  // frames to run while paused
+  advance: u32,
+  // fast-forward is usually silent, the ring buffer would drop most of it anyway
+  pub audio_when_fast: bool,
+}
+
+impl Default for EmulationSpeed {
+  fn default() -> Self {
+    EmulationSpeed { multiplier: 1.0, unlimited: false, paused: false, advance: 0, audio_when_fast: false }
+  }
+}
+
+impl EmulationSpeed {
+  pub fn new() -> Self {
+    EmulationSpeed::default()
+  }
+
+  // 2.0 = twice as fast, 0.5 = slow motion
+  pub fn set_multiplier(&mut self, multiplier: f64) {
+    assert!(multiplier > 0.0, "speed multiplier must be positive");
+    self.multiplier = multiplier;
+  }
+
+  pub fn multiplier(&self) -> f64 {
+    self.multiplier
+  }
+
+  // as fast as the host can, ignores the multiplier while set
+  pub fn set_unlimited(&mut self, unlimited: bool) {
+    self.unlimited = unlimited;
+  }
+
+  pub fn is_unlimited(&self) -> bool {
+    self.unlimited
+  }
+
+  pub fn pause(&mut self) {
+    self.paused = true;
+    self.advance = 0;
+  }
+
+  pub fn resume(&mut self) {
+    self.paused = false;
+    self.advance = 0;
+  }
+
+  pub fn toggle_pause(&mut self) {
+    if self.paused { self.resume() } else { self.pause() }
+  }
+
+  pub fn is_paused(&self) -> bool {
+    self.paused
+  }
+
+  // pauses after the next frame
+  pub fn advance_frame(&mut self) {
+    self.paused = true;
+    self.advance += 1;
+  }
+
+  // asked after every frame, false while paused without a pending frame advance
+  pub fn run_next_frame(&mut self) -> bool {
+    if !self.paused {
+      return true;
+    }
+    if self.advance > 0 {
+      self.advance -= 1;
+      return true;
+    }
+    false
+  }
+
+  // wall clock time a frame should take, None when unlimited
+  pub fn frame_time(&self, base: Duration) -> Option<Duration> {
+    if self.unlimited {
+      return None;
+    }
+    Some(base.div_f64(self.multiplier))
+  }
+
+  // slow motion and single frames would only stutter
+  pub fn plays_audio(&self) -> bool {
+    if self.paused || self.multiplier < 1.0 {
+      return false;
+    }
+    let fast = self.unlimited || self.multiplier > 1.0;
+    !fast || self.audio_when_fast
+  }
+}
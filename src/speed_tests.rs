@@ -0,0 +1,53 @@
+use std::time::Duration;
+use crate::speed::EmulationSpeed;
+
+const FRAME: Duration = Duration::from_millis(16);
+
+#[test]
+fn test_multiplier_scales_frame_time() {
+  let mut speed = EmulationSpeed::new();
+  assert_eq!(Some(FRAME), speed.frame_time(FRAME));
+
+  speed.set_multiplier(2.0);
+  assert_eq!(Some(Duration::from_millis(8)), speed.frame_time(FRAME));
+
+  speed.set_multiplier(0.5);
+  assert_eq!(Some(Duration::from_millis(32)), speed.frame_time(FRAME));
+}
+
+#[test]
+fn test_unlimited_skips_pacing_and_audio() {
+  let mut speed = EmulationSpeed::new();
+  speed.set_unlimited(true);
+
+  assert_eq!(None, speed.frame_time(FRAME));
+  assert!(!speed.plays_audio());
+
+  speed.audio_when_fast = true;
+  assert!(speed.plays_audio());
+
+  speed.set_unlimited(false);
+  assert_eq!(Some(FRAME), speed.frame_time(FRAME));
+}
+
+#[test]
+fn test_pause_and_frame_advance() {
+  let mut speed = EmulationSpeed::new();
+  assert!(speed.run_next_frame());
+
+  speed.pause();
+  assert!(!speed.run_next_frame());
+  assert!(!speed.plays_audio());
+
+  speed.advance_frame();
+  speed.advance_frame();
+  assert!(speed.run_next_frame());
+  assert!(speed.run_next_frame());
+  assert!(!speed.run_next_frame());
+  assert!(speed.is_paused());
+
+  speed.toggle_pause();
+  assert!(speed.run_next_frame());
+  assert!(speed.plays_audio());
+}
+
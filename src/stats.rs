@@ -0,0 +1,82 @@
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+const FPS_WINDOW: usize = 60;
+
+// ntsc ppu runs 3 dots per cpu cycle
+const PPU_DOTS_PER_CPU_CYCLE: usize = 3;
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct EmulatorStats {
+  pub frames: u64,
+  pub dropped_frames: u64,
+  pub cpu_cycles: usize,
+  pub cpu_cycles_per_frame: usize,
+  pub ppu_dots_per_frame: usize,
+  pub host_frame_time: Duration,
+  pub fps: f64,
+  // 0.0 = empty .. 1.0 = full, None without audio output
+  pub audio_buffer_fill: Option<f32>,
+}
+
+// updated by the front-end once per presented frame
+pub struct StatsCollector {
+  stats: EmulatorStats,
+  frame_started_at: Instant,
+  frame_start_cycles: usize,
+  frame_times: VecDeque<Duration>,
+}
+
+impl StatsCollector {
+  pub fn new() -> Self {
+    StatsCollector {
+      stats: EmulatorStats::default(),
+      frame_started_at: Instant::now(),
+      frame_start_cycles: 0,
+      frame_times: VecDeque::with_capacity(FPS_WINDOW),
+    }
+  }
+
+  pub fn stats(&self) -> &EmulatorStats {
+    &self.stats
+  }
+
+  pub fn end_frame(&mut self, cpu_cycles: usize) {
+    self.end_frame_at(cpu_cycles, Instant::now());
+  }
+
+  pub fn end_frame_at(&mut self, cpu_cycles: usize, now: Instant) {
+    let frame_time = now.saturating_duration_since(self.frame_started_at);
+    let frame_cycles = cpu_cycles.saturating_sub(self.frame_start_cycles);
+
+    self.stats.frames += 1;
+    self.stats.cpu_cycles = cpu_cycles;
+    self.stats.cpu_cycles_per_frame = frame_cycles;
+    self.stats.ppu_dots_per_frame = frame_cycles * PPU_DOTS_PER_CPU_CYCLE;
+    self.stats.host_frame_time = frame_time;
+
+    if self.frame_times.len() == FPS_WINDOW {
+      self.frame_times.pop_front();
+    }
+    self.frame_times.push_back(frame_time);
+    let total: Duration = self.frame_times.iter().sum();
+    self.stats.fps = if total.is_zero() { 0.0 } else { self.frame_times.len() as f64 / total.as_secs_f64() };
+
+    self.frame_started_at = now;
+    self.frame_start_cycles = cpu_cycles;
+  }
+
+  pub fn frame_dropped(&mut self) {
+    self.stats.dropped_frames += 1;
+  }
+
+  pub fn set_audio_buffer_fill(&mut self, fill: f32) {
+    self.stats.audio_buffer_fill = Some(fill.clamp(0.0, 1.0));
+  }
+}
+
+impl Default for StatsCollector {
+  fn default() -> Self {
+    StatsCollector::new()
+  }
+}
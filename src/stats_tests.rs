@@ -0,0 +1,43 @@
+use std::time::{Duration, Instant};
+use crate::stats::StatsCollector;
+
+#[test]
+fn test_frame_stats() {
+  let mut collector = StatsCollector::new();
+  let start = Instant::now();
+
+  collector.end_frame_at(29_780, start + Duration::from_millis(16));
+  collector.end_frame_at(59_561, start + Duration::from_millis(36));
+
+  let stats = collector.stats();
+  assert_eq!(2, stats.frames);
+  assert_eq!(29_781, stats.cpu_cycles_per_frame);
+  assert_eq!(3 * 29_781, stats.ppu_dots_per_frame);
+  assert_eq!(59_561, stats.cpu_cycles);
+  assert_eq!(Duration::from_millis(20), stats.host_frame_time);
+}
+
+#[test]
+fn test_fps_averages_recent_frames() {
+  let mut collector = StatsCollector::new();
+  let start = Instant::now();
+  collector.end_frame_at(0, start);
+
+  for i in 1..=10 {
+    collector.end_frame_at(0, start + Duration::from_millis(20 * i));
+  }
+
+  // first frame took ~0ms, ten frames took 20ms each
+  assert!((collector.stats().fps - 11.0 / 0.2).abs() < 1.0, "{}", collector.stats().fps);
+}
+
+#[test]
+fn test_dropped_frames_and_audio_fill() {
+  let mut collector = StatsCollector::new();
+
+  collector.frame_dropped();
+  collector.set_audio_buffer_fill(1.5);
+
+  assert_eq!(1, collector.stats().dropped_frames);
+  assert_eq!(Some(1.0), collector.stats().audio_buffer_fill);
+}
@@ -0,0 +1,134 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+use crate::cartridge::PRG_ROM_PAGE_SIZE;
+use crate::error::EmuError;
+
+// labels for cpu addresses, from cc65 debug files (ld65 --dbgfile) or mesen label files
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Symbols {
+  labels: BTreeMap<u16, String>,
+}
+
+impl Symbols {
+  pub fn new() -> Self {
+    Symbols::default()
+  }
+
+  // the first label of an address wins
+  pub fn add(&mut self, address: u16, name: &str) {
+    self.labels.entry(address).or_insert_with(|| name.to_string());
+  }
+
+  pub fn label(&self, address: u16) -> Option<&str> {
+    self.labels.get(&address).map(String::as_str)
+  }
+
+  // the closest label at or before `address`, e.g. the routine an instruction belongs to.
+  // only labels of the same 8KB region count, code in rom is not described relative to ram variables
+  pub fn containing(&self, address: u16) -> Option<(u16, &str)> {
+    self.labels.range(..=address).next_back()
+      .filter(|(&start, _)| start & 0xE000 == address & 0xE000)
+      .map(|(&start, label)| (start, label.as_str()))
+  }
+
+  // `label` or `label+offset`
+  pub fn describe(&self, address: u16) -> Option<String> {
+    self.containing(address).map(|(start, label)| match address - start {
+      0 => label.to_string(),
+      offset => format!("{}+{}", label, offset),
+    })
+  }
+
+  pub fn address(&self, name: &str) -> Option<u16> {
+    self.labels.iter().find(|(_, label)| label.as_str() == name).map(|(&address, _)| address)
+  }
+
+  pub fn len(&self) -> usize {
+    self.labels.len()
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.labels.is_empty()
+  }
+
+  // for the label callbacks of the disassembler and the call stack
+  pub fn lookup(&self) -> impl Fn(u16) -> Option<String> + '_ {
+    move |address| self.label(address).map(String::from)
+  }
+
+  // .dbg is read as cc65 debug info, everything else as mesen labels
+  pub fn load(path: &Path, prg_rom_size: usize) -> Result<Symbols, EmuError> {
+    let text = fs::read_to_string(path)?;
+    Ok(match path.extension().and_then(|e| e.to_str()) {
+      Some("dbg") => Symbols::from_dbg(&text),
+      _ => Symbols::from_mlb(&text, prg_rom_size),
+    })
+  }
+
+  // `sym id=0,name="reset",addrsize=absolute,...,val=0xC000,...,type=lab`, equates are skipped
+  pub fn from_dbg(text: &str) -> Symbols {
+    let mut symbols = Symbols::new();
+    for line in text.lines() {
+      let fields = match line.strip_prefix("sym") {
+        Some(fields) if fields.starts_with(char::is_whitespace) => fields.trim(),
+        _ => continue,
+      };
+      let mut name = None;
+      let mut value = None;
+      let mut label = false;
+      for field in fields.split(',') {
+        match field.split_once('=') {
+          Some(("name", v)) => name = Some(v.trim_matches('"')),
+          Some(("val", v)) => value = v.strip_prefix("0x").and_then(|hex| u16::from_str_radix(hex, 16).ok()),
+          Some(("type", v)) => label = v == "lab",
+          _ => {}
+        }
+      }
+      if let (Some(name), Some(value), true) = (name, value, label) {
+        symbols.add(value, name);
+      }
+    }
+    symbols
+  }
+
+  // `type:address[-end]:label[:comment]` with mesen 1 (P, R, S, W, G) or mesen 2 (NesPrgRom, ...) types
+  pub fn from_mlb(text: &str, prg_rom_size: usize) -> Symbols {
+    let mut symbols = Symbols::new();
+    for line in text.lines() {
+      let mut parts = line.trim().splitn(4, ':');
+      let (kind, range, name) = match (parts.next(), parts.next(), parts.next()) {
+        (Some(kind), Some(range), Some(name)) if !name.is_empty() => (kind, range, name),
+        // comments without a label
+        _ => continue,
+      };
+      let start = range.split('-').next().and_then(|hex| u32::from_str_radix(hex, 16).ok());
+      let address = match (kind, start) {
+        ("P" | "NesPrgRom", Some(offset)) => prg_rom_address(offset as usize, prg_rom_size),
+        ("R" | "NesInternalRam", Some(offset)) if offset < 0x800 => Some(offset as u16),
+        ("S" | "W" | "NesSaveRam" | "NesWorkRam", Some(offset)) if offset < 0x2000 => Some(0x6000 + offset as u16),
+        ("G" | "NesMemory", Some(address)) if address <= 0xFFFF => Some(address as u16),
+        _ => None,
+      };
+      if let Some(address) = address {
+        symbols.add(address, name);
+      }
+    }
+    symbols
+  }
+}
+
+// nrom layout: 16KB mirrored to $C000, 32KB at $8000. for bigger roms the last 16KB are assumed
+// fixed at $C000 and the other banks switched in at $8000
+fn prg_rom_address(offset: usize, prg_rom_size: usize) -> Option<u16> {
+  if offset >= prg_rom_size {
+    return None;
+  }
+  let address = match prg_rom_size {
+    PRG_ROM_PAGE_SIZE => 0xC000 + offset,
+    size if size <= 2 * PRG_ROM_PAGE_SIZE => 0x8000 + offset,
+    size if offset >= size - PRG_ROM_PAGE_SIZE => 0xC000 + offset - (size - PRG_ROM_PAGE_SIZE),
+    _ => 0x8000 + offset % PRG_ROM_PAGE_SIZE,
+  };
+  Some(address as u16)
+}
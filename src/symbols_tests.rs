@@ -0,0 +1,98 @@
+use std::fs;
+use crate::bus::Bus;
+use crate::cartridge_tests::create_test_rom;
+use crate::cpu::MyCPU;
+use crate::symbols::Symbols;
+use crate::trace::{RingBufferSink, Tracer};
+
+const DBG: &str = "version\tmajor=2,minor=0
+file\tid=0,name=\"main.s\",size=100,mtime=0x5F000000,mod=0
+seg\tid=0,name=\"CODE\",start=0x00C000,size=0x0010,addrsize=absolute,type=ro,oname=\"game.nes\",ooffs=16
+sym\tid=0,name=\"reset\",addrsize=absolute,scope=0,def=1,ref=5,val=0xC000,seg=0,type=lab
+sym\tid=1,name=\"player_x\",addrsize=zeropage,scope=0,def=2,val=0x10,seg=1,type=lab
+sym\tid=2,name=\"SPEED\",addrsize=zeropage,scope=0,def=3,val=0x3,type=equ
+";
+
+#[test]
+fn test_cc65_debug_file() {
+  let symbols = Symbols::from_dbg(DBG);
+
+  assert_eq!(2, symbols.len());
+  assert_eq!(Some("reset"), symbols.label(0xC000));
+  assert_eq!(Some("player_x"), symbols.label(0x0010));
+  assert_eq!(None, symbols.label(0x0003));
+  assert_eq!(Some(0xC000), symbols.address("reset"));
+}
+
+#[test]
+fn test_mesen_labels() {
+  let mlb = "P:0000:reset\nP:3FFA-3FFB:nmi_vector:points to nmi\nR:0010:player_x\nW:0000:save_slot\nG:2000:PPUCTRL\nP:0010::just a comment\nNesInternalRam:0011:player_y\n";
+  let symbols = Symbols::from_mlb(mlb, 0x4000);
+
+  assert_eq!(Some("reset"), symbols.label(0xC000));
+  assert_eq!(Some("nmi_vector"), symbols.label(0xFFFA));
+  assert_eq!(Some("player_x"), symbols.label(0x0010));
+  assert_eq!(Some("player_y"), symbols.label(0x0011));
+  assert_eq!(Some("save_slot"), symbols.label(0x6000));
+  assert_eq!(Some("PPUCTRL"), symbols.label(0x2000));
+  assert_eq!(6, symbols.len());
+}
+
+#[test]
+fn test_mesen_prg_offsets_of_banked_roms() {
+  let symbols = Symbols::from_mlb("P:0000:first_bank\nP:1C000:fixed\nP:20000:outside", 0x20000);
+
+  assert_eq!(Some("first_bank"), symbols.label(0x8000));
+  assert_eq!(Some("fixed"), symbols.label(0xC000));
+  assert_eq!(2, symbols.len());
+}
+
+#[test]
+fn test_first_label_of_an_address_wins() {
+  let mut symbols = Symbols::new();
+  symbols.add(0x8000, "reset");
+  symbols.add(0x8000, "@loop");
+
+  assert_eq!(Some("reset"), symbols.label(0x8000));
+}
+
+#[test]
+fn test_load_picks_format_by_extension() {
+  let path = std::env::temp_dir().join("nes_emulator_symbols_test.dbg");
+  fs::write(&path, DBG).unwrap();
+
+  let symbols = Symbols::load(&path, 0x4000).unwrap();
+  fs::remove_file(&path).unwrap();
+
+  assert_eq!(Some("reset"), symbols.label(0xC000));
+}
+
+#[test]
+fn test_trace_and_crash_dump_use_labels() {
+  let sink = RingBufferSink::new(4);
+  let mut cpu = MyCPU::new(Bus::new(create_test_rom()).unwrap());
+  cpu.program_counter = 0x0600;
+  cpu.tracer = Tracer::new(sink.clone());
+  cpu.symbols.add(0x0600, "start");
+  cpu.symbols.add(0x0604, "helper");
+
+  // JSR helper, BRK, helper: INX, BRK
+  cpu.load_and_run(vec![0x20, 0x04, 0x06, 0x00, 0xE8, 0x00]);
+
+  let lines = sink.lines();
+  assert!(lines[0].ends_with(" ; start"), "{}", lines[0]);
+  assert!(lines[1].ends_with(" ; helper"), "{}", lines[1]);
+  assert!(cpu.crash_dump("test").contains("#0 helper called from $0600"));
+}
+
+#[test]
+fn test_containing_label_stays_in_its_region() {
+  let mut symbols = Symbols::new();
+  symbols.add(0x0010, "player_x");
+  symbols.add(0x8000, "main");
+
+  assert_eq!(Some("main+4".to_string()), symbols.describe(0x8004));
+  assert_eq!(Some("player_x".to_string()), symbols.describe(0x0010));
+  assert_eq!(None, symbols.describe(0x2000));
+  assert_eq!(None, symbols.containing(0xA000));
+}
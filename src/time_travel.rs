@@ -0,0 +1,156 @@
+use std::collections::VecDeque;
+use crate::call_stack::CallStack;
+use crate::cpu::{CpuBus, CpuState, MyCPU, StopCondition};
+use crate::delta::DeltaHistory;
+use crate::savestate::Stateful;
+
+pub const DEFAULT_SNAPSHOT_INTERVAL: u64 = 1_000;
+pub const DEFAULT_MAX_SNAPSHOTS: usize = 100;
+
+struct Checkpoint {
+  position: u64,
+  call_stack: CallStack,
+}
+
+// steps backwards by loading the closest earlier save state and replaying
+// instructions up to the wanted position - works because the machine is deterministic.
+// the states cover ppu, apu and mapper too and are kept delta encoded, so long histories stay small.
+pub struct TimeTravel {
+  interval: u64,
+  max_snapshots: usize,
+  checkpoints: VecDeque<Checkpoint>,
+  states: DeltaHistory,
+  position: u64,
+}
+
+impl TimeTravel {
+  pub fn new(interval: u64, max_snapshots: usize) -> Self {
+    TimeTravel {
+      interval: interval.max(1),
+      max_snapshots: max_snapshots.max(1),
+      checkpoints: VecDeque::new(),
+      states: DeltaHistory::new(),
+      position: 0,
+    }
+  }
+
+  // number of instructions executed through this recorder
+  pub fn position(&self) -> u64 {
+    self.position
+  }
+
+  // earliest position that can still be reached with step_back
+  pub fn oldest_position(&self) -> Option<u64> {
+    self.checkpoints.front().map(|c| c.position)
+  }
+
+  pub fn memory_usage(&self) -> usize {
+    self.states.memory_usage()
+  }
+
+  // as cpu.step, only executed instructions move the position (a breakpoint hit doesn't)
+  pub fn step<B: CpuBus + Stateful>(&mut self, cpu: &mut MyCPU<B>) -> bool {
+    // state might have been edited while paused - later checkpoints can't be trusted anymore
+    while self.checkpoints.back().is_some_and(|c| c.position > self.position) {
+      self.checkpoints.pop_back();
+      self.states.pop_back();
+    }
+
+    let checkpoint_missing = self.checkpoints.back().is_none_or(|c| c.position != self.position);
+    if self.position.is_multiple_of(self.interval) && checkpoint_missing {
+      if self.checkpoints.len() == self.max_snapshots {
+        self.checkpoints.pop_front();
+        self.states.pop_front();
+      }
+      self.checkpoints.push_back(Checkpoint {
+        position: self.position,
+        call_stack: cpu.call_stack.clone(),
+      });
+      self.states.push(cpu.save_state());
+    }
+
+    let stepped = cpu.step().is_some();
+    if stepped {
+      self.position += 1;
+    }
+    stepped
+  }
+
+  pub fn step_back<B: CpuBus + Stateful>(&mut self, cpu: &mut MyCPU<B>) -> bool {
+    if self.position == 0 {
+      return false;
+    }
+    self.seek(cpu, self.position - 1)
+  }
+
+  // moves to the latest earlier position where the breakpoint matches. false if there is none,
+  // the cpu is at the oldest recorded position then, or if a replay stopped short as in seek.
+  // Replays every position only once
+  pub fn run_back_until<B: CpuBus + Stateful, F>(&mut self, cpu: &mut MyCPU<B>, mut breakpoint: F) -> bool
+    where F: FnMut(&MyCPU<B>) -> bool
+  {
+    let end = self.position;
+    for idx in (0..self.checkpoints.len()).rev() {
+      if self.checkpoints[idx].position >= end {
+        continue;
+      }
+      let segment_end = self.checkpoints.get(idx + 1).map_or(end, |c| c.position.min(end));
+      self.restore(cpu, idx);
+      let mut found = None;
+      self.replay(cpu, segment_end, |cpu, position| if breakpoint(cpu) { found = Some(position) });
+      if let Some(position) = found {
+        return self.seek(cpu, position);
+      }
+      // stopped on the way, the earlier segments are out of reach as well
+      if self.position < segment_end {
+        return false;
+      }
+    }
+    if let Some(oldest) = self.oldest_position().filter(|&oldest| oldest < end) {
+      self.seek(cpu, oldest);
+    }
+    false
+  }
+
+  // false if there is no checkpoint before `target` or the replay stopped short of it,
+  // e.g. on a stop condition set while paused. The cpu is wherever the replay stopped then
+  pub fn seek<B: CpuBus + Stateful>(&mut self, cpu: &mut MyCPU<B>, target: u64) -> bool {
+    let idx = match self.checkpoints.iter().rposition(|c| c.position <= target) {
+      Some(idx) => idx,
+      None => return false,
+    };
+    self.restore(cpu, idx);
+    self.replay(cpu, target, |_, _| {});
+    self.position == target
+  }
+
+  fn restore<B: CpuBus + Stateful>(&mut self, cpu: &mut MyCPU<B>, idx: usize) {
+    let state = self.states.get(idx).expect("state for checkpoint");
+    cpu.load_state(&state).expect("state saved by this machine");
+    cpu.call_stack = self.checkpoints[idx].call_stack.clone();
+    self.position = self.checkpoints[idx].position;
+  }
+
+  // steps up to `target`, `visit` sees every position on the way. Breakpoint hits are stepped
+  // over, they didn't move the position while recording either
+  fn replay<B: CpuBus + Stateful, F>(&mut self, cpu: &mut MyCPU<B>, target: u64, mut visit: F)
+    where F: FnMut(&MyCPU<B>, u64)
+  {
+    while self.position < target {
+      visit(cpu, self.position);
+      if cpu.step().is_some() {
+        self.position += 1;
+      } else if cpu.state != CpuState::Running || cpu.stop_condition == StopCondition::ProgramCounter(cpu.program_counter) {
+        break;
+      }
+    }
+    // landing on a breakpoint doesn't stop the next step forward
+    cpu.breakpoints.resume_at(cpu.program_counter);
+  }
+}
+
+impl Default for TimeTravel {
+  fn default() -> Self {
+    TimeTravel::new(DEFAULT_SNAPSHOT_INTERVAL, DEFAULT_MAX_SNAPSHOTS)
+  }
+}
@@ -0,0 +1,136 @@
+use crate::bus::Bus;
+use crate::cartridge_tests::create_test_rom;
+use crate::cpu::{CpuState, MyCPU, MyMem, StopCondition};
+use crate::time_travel::TimeTravel;
+
+// $0600: INC $10, JMP $0600
+fn init_looping_cpu() -> MyCPU {
+  let mut cpu = MyCPU::new(Bus::new(create_test_rom()).unwrap());
+  cpu.load(vec![0xE6, 0x10, 0x4C, 0x00, 0x06]);
+  cpu.program_counter = 0x0600;
+  cpu
+}
+
+#[test]
+fn test_step_back_restores_previous_state() {
+  let mut cpu = init_looping_cpu();
+  let mut time_travel = TimeTravel::new(4, 10);
+  for _ in 0..9 {
+    time_travel.step(&mut cpu);
+  }
+  assert_eq!(5, cpu.mem_read(0x10));
+  assert_eq!(0x0602, cpu.program_counter);
+
+  assert!(time_travel.step_back(&mut cpu));
+
+  assert_eq!(8, time_travel.position());
+  assert_eq!(4, cpu.mem_read(0x10));
+  assert_eq!(0x0600, cpu.program_counter);
+}
+
+#[test]
+fn test_step_back_then_forward_is_deterministic() {
+  let mut cpu = init_looping_cpu();
+  let mut time_travel = TimeTravel::new(3, 10);
+  for _ in 0..7 {
+    time_travel.step(&mut cpu);
+  }
+  let expected = cpu.snapshot();
+
+  time_travel.step_back(&mut cpu);
+  time_travel.step_back(&mut cpu);
+  time_travel.step(&mut cpu);
+  time_travel.step(&mut cpu);
+
+  assert_eq!(expected, cpu.snapshot());
+}
+
+#[test]
+fn test_run_back_until_breakpoint() {
+  let mut cpu = init_looping_cpu();
+  let mut time_travel = TimeTravel::new(5, 10);
+  for _ in 0..20 {
+    time_travel.step(&mut cpu);
+  }
+
+  assert!(time_travel.run_back_until(&mut cpu, |cpu| cpu.bus.peek(0x10) == 2));
+
+  assert_eq!(2, cpu.mem_read(0x10));
+  assert_eq!(4, time_travel.position());
+}
+
+#[test]
+fn test_cannot_step_back_before_oldest_snapshot() {
+  let mut cpu = init_looping_cpu();
+  let mut time_travel = TimeTravel::new(2, 2);
+  for _ in 0..10 {
+    time_travel.step(&mut cpu);
+  }
+
+  assert_eq!(Some(6), time_travel.oldest_position());
+  assert!(!time_travel.run_back_until(&mut cpu, |_| false));
+  assert_eq!(6, time_travel.position());
+}
+
+#[test]
+fn test_seek_restores_ppu_and_apu_state() {
+  // $0600: INC $10, LDA $10, STA $2001, STA $4002, JMP $0600
+  let mut cpu = MyCPU::new(Bus::new(create_test_rom()).unwrap());
+  cpu.load(vec![0xE6, 0x10, 0xA5, 0x10, 0x8D, 0x01, 0x20, 0x8D, 0x02, 0x40, 0x4C, 0x00, 0x06]);
+  cpu.program_counter = 0x0600;
+  let mut time_travel = TimeTravel::new(4, 10);
+  for _ in 0..6 {
+    time_travel.step(&mut cpu);
+  }
+  let expected = cpu.save_state();
+  for _ in 0..7 {
+    time_travel.step(&mut cpu);
+  }
+
+  assert!(time_travel.seek(&mut cpu, 6));
+
+  assert_eq!(expected, cpu.save_state());
+}
+
+#[test]
+fn test_step_back_out_of_a_jam() {
+  let mut cpu = MyCPU::new(Bus::new(create_test_rom()).unwrap());
+  // INX x4, KIL
+  cpu.load(vec![0xE8, 0xE8, 0xE8, 0xE8, 0x02]);
+  cpu.program_counter = 0x0600;
+  let mut time_travel = TimeTravel::new(1000, 10);
+  while time_travel.step(&mut cpu) {}
+  assert_eq!(4, time_travel.position());
+  assert_eq!(CpuState::Jammed { code: 0x02, program_counter: 0x0604 }, cpu.state);
+
+  assert!(time_travel.step_back(&mut cpu));
+
+  assert_eq!(3, time_travel.position());
+  assert_eq!(3, cpu.register_x);
+  assert_eq!(CpuState::Running, cpu.state);
+  time_travel.step(&mut cpu);
+  assert!(!time_travel.step(&mut cpu));
+  assert!(matches!(cpu.state, CpuState::Jammed { .. }));
+}
+
+#[test]
+fn test_replay_stopped_short_is_no_success() {
+  let mut time_travel = TimeTravel::new(100, 10);
+  let mut cpu = init_looping_cpu();
+  for _ in 0..9 {
+    time_travel.step(&mut cpu);
+  }
+  let running = cpu.stop_condition;
+  // set while paused, the replay from position 0 stops at the JMP
+  cpu.stop_condition = StopCondition::ProgramCounter(0x0602);
+
+  assert!(!time_travel.step_back(&mut cpu));
+  assert_eq!(1, time_travel.position());
+
+  cpu.stop_condition = running;
+  assert!(time_travel.seek(&mut cpu, 9));
+  cpu.stop_condition = StopCondition::ProgramCounter(0x0602);
+  // $10 is 2 at position 4, beyond where the replay stops
+  assert!(!time_travel.run_back_until(&mut cpu, |cpu| cpu.bus.peek(0x10) == 2));
+  assert_eq!(1, time_travel.position());
+}
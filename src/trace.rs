@@ -0,0 +1,338 @@
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::ops::RangeInclusive;
+use std::rc::Rc;
+use crate::bus::{AccessKind, BusAccess};
+use crate::cartridge::PRG_ROM_PAGE_SIZE;
+use crate::control::Json;
+use crate::history::ExecutedInstruction;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum InstructionKind {
+  Branch,
+  Jump,
+  Load,
+  Store,
+  Stack,
+  Other,
+}
+
+impl InstructionKind {
+  pub fn of(mnemonic: &str) -> InstructionKind {
+    match mnemonic {
+      "BCC" | "BCS" | "BEQ" | "BMI" | "BNE" | "BPL" | "BVC" | "BVS" => InstructionKind::Branch,
+      "JMP" | "JSR" | "RTS" | "RTI" | "BRK" => InstructionKind::Jump,
+      "LDA" | "LDX" | "LDY" => InstructionKind::Load,
+      "STA" | "STX" | "STY" => InstructionKind::Store,
+      "PHA" | "PHP" | "PLA" | "PLP" | "TSX" | "TXS" => InstructionKind::Stack,
+      _ => InstructionKind::Other,
+    }
+  }
+}
+
+// restricts tracing - all conditions have to match, empty lists match everything
+#[derive(Default)]
+pub struct TraceFilter {
+  pub ranges: Vec<RangeInclusive<u16>>,
+  pub kinds: Vec<InstructionKind>,
+  // 16KB prg rom banks as counted in the ines header, code outside the rom never matches
+  pub banks: Vec<usize>,
+  // tracing starts when pc hits start_at and stops again at stop_at
+  pub start_at: Option<u16>,
+  pub stop_at: Option<u16>,
+  scope_active: bool,
+}
+
+impl TraceFilter {
+  pub fn new() -> Self {
+    TraceFilter::default()
+  }
+
+  pub fn with_range(mut self, range: RangeInclusive<u16>) -> Self {
+    self.ranges.push(range);
+    self
+  }
+
+  pub fn with_kind(mut self, kind: InstructionKind) -> Self {
+    self.kinds.push(kind);
+    self
+  }
+
+  pub fn with_bank(mut self, bank: usize) -> Self {
+    self.banks.push(bank);
+    self
+  }
+
+  pub fn between(mut self, start_at: u16, stop_at: u16) -> Self {
+    self.start_at = Some(start_at);
+    self.stop_at = Some(stop_at);
+    self
+  }
+
+  // prg_rom_offset of the instruction, if it runs from the cartridge rom
+  pub fn matches(&mut self, program_counter: u16, prg_rom_offset: Option<usize>, mnemonic: &str) -> bool {
+    if self.start_at == Some(program_counter) {
+      self.scope_active = true;
+    }
+    let in_scope = self.start_at.is_none() || self.scope_active;
+    if self.scope_active && self.stop_at == Some(program_counter) {
+      // the stop instruction itself is still traced
+      self.scope_active = false;
+    }
+
+    in_scope
+      && (self.ranges.is_empty() || self.ranges.iter().any(|r| r.contains(&program_counter)))
+      && (self.kinds.is_empty() || self.kinds.contains(&InstructionKind::of(mnemonic)))
+      && (self.banks.is_empty() || prg_rom_offset.is_some_and(|offset| self.banks.contains(&(offset / PRG_ROM_PAGE_SIZE))))
+  }
+}
+
+// writes to base_path, moving full files to base_path.1, base_path.2, ... (oldest gets dropped)
+pub struct RotatingFile {
+  base_path: String,
+  max_bytes: u64,
+  max_files: usize,
+  written: u64,
+  file: BufWriter<File>,
+}
+
+impl RotatingFile {
+  pub fn create(base_path: &str, max_bytes: u64, max_files: usize) -> io::Result<Self> {
+    Ok(RotatingFile {
+      base_path: base_path.to_string(),
+      max_bytes,
+      max_files: max_files.max(1),
+      written: 0,
+      file: BufWriter::new(File::create(base_path)?),
+    })
+  }
+
+  fn rotated_path(&self, idx: usize) -> String {
+    format!("{}.{}", self.base_path, idx)
+  }
+
+  fn rotate(&mut self) -> io::Result<()> {
+    self.file.flush()?;
+    let oldest = self.rotated_path(self.max_files - 1);
+    if fs::metadata(&oldest).is_ok() {
+      fs::remove_file(&oldest)?;
+    }
+    for idx in (1..self.max_files - 1).rev() {
+      let path = self.rotated_path(idx);
+      if fs::metadata(&path).is_ok() {
+        fs::rename(&path, self.rotated_path(idx + 1))?;
+      }
+    }
+    if self.max_files > 1 {
+      fs::rename(&self.base_path, self.rotated_path(1))?;
+    }
+    self.file = BufWriter::new(File::create(&self.base_path)?);
+    self.written = 0;
+    Ok(())
+  }
+}
+
+impl Write for RotatingFile {
+  fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+    if self.written > 0 && self.written + buf.len() as u64 > self.max_bytes {
+      self.rotate()?;
+    }
+    let written = self.file.write(buf)?;
+    self.written += written as u64;
+    Ok(written)
+  }
+
+  fn flush(&mut self) -> io::Result<()> {
+    self.file.flush()
+  }
+}
+
+pub struct TraceRecord {
+  pub instruction: ExecutedInstruction,
+  pub cycles: usize, // before the instruction
+  pub prg_rom_offset: Option<usize>,
+  pub effective_address: Option<u16>,
+  pub bus_accesses: Vec<BusAccess>,
+  // symbol of the instruction address
+  pub label: Option<String>,
+}
+
+impl TraceRecord {
+  pub fn format_text(&self) -> String {
+    let i = &self.instruction;
+    let next_bytes = match i.operands.as_slice() {
+      [lo] => format!("{:#04x}     ", lo),
+      [lo, hi] => format!("{:#04x} {:#04x}", lo, hi),
+      _ => "         ".to_string(),
+    };
+    let line = format!("opCode {} {:#04x} {}, pc={:#04x}, registers={:b}",
+                       i.mnemonic, i.code, next_bytes, i.program_counter.wrapping_add(1), i.status.bits());
+    match &self.label {
+      Some(label) => format!("{} ; {}", line, label),
+      None => line,
+    }
+  }
+
+  // one json object per line, numbers instead of hex strings to keep parsing trivial
+  pub fn format_json(&self) -> String {
+    let i = &self.instruction;
+    let operands: Vec<String> = i.operands.iter().map(|b| b.to_string()).collect();
+    let effective_address = self.effective_address.map_or("null".to_string(), |a| a.to_string());
+    let accesses: Vec<String> = self.bus_accesses.iter()
+      .map(|a| {
+        let kind = match a.kind {
+          AccessKind::Read => "read",
+          AccessKind::Write => "write",
+        };
+        format!("{{\"op\":\"{}\",\"addr\":{},\"value\":{}}}", kind, a.addr, a.value)
+      })
+      .collect();
+    // only labelled instructions get a label field
+    let label = self.label.as_ref().map_or(String::new(), |l| format!(",\"label\":{}", Json::String(l.clone())));
+    format!("{{\"pc\":{},\"opcode\":{},\"mnemonic\":\"{}\",\"operands\":[{}],\"a\":{},\"x\":{},\"y\":{},\"p\":{},\"sp\":{},\"cycles\":{},\"effective_address\":{},\"bus\":[{}]{}}}",
+            i.program_counter, i.code, i.mnemonic, operands.join(","),
+            i.register_a, i.register_x, i.register_y, i.status.bits(), i.stack_pointer,
+            self.cycles, effective_address, accesses.join(","), label)
+  }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TraceFormat {
+  Text,
+  JsonLines,
+}
+
+// where formatted trace lines go
+pub trait TraceSink {
+  fn write_line(&mut self, line: &str) -> io::Result<()>;
+
+  // records are only built for enabled sinks
+  fn is_enabled(&self) -> bool {
+    true
+  }
+}
+
+pub struct NoopSink;
+
+impl TraceSink for NoopSink {
+  fn write_line(&mut self, _line: &str) -> io::Result<()> {
+    Ok(())
+  }
+
+  fn is_enabled(&self) -> bool {
+    false
+  }
+}
+
+pub struct StdoutSink;
+
+impl TraceSink for StdoutSink {
+  fn write_line(&mut self, line: &str) -> io::Result<()> {
+    println!("{}", line);
+    Ok(())
+  }
+}
+
+pub struct FileSink {
+  writer: Box<dyn Write>,
+}
+
+impl FileSink {
+  pub fn create(path: &str) -> io::Result<Self> {
+    Ok(FileSink { writer: Box::new(BufWriter::new(File::create(path)?)) })
+  }
+
+  pub fn rotating(base_path: &str, max_bytes: u64, max_files: usize) -> io::Result<Self> {
+    Ok(FileSink { writer: Box::new(RotatingFile::create(base_path, max_bytes, max_files)?) })
+  }
+}
+
+impl TraceSink for FileSink {
+  fn write_line(&mut self, line: &str) -> io::Result<()> {
+    writeln!(self.writer, "{}", line)
+  }
+}
+
+// keeps the last lines in memory, clones share the same buffer
+#[derive(Clone)]
+pub struct RingBufferSink {
+  capacity: usize,
+  lines: Rc<RefCell<VecDeque<String>>>,
+}
+
+impl RingBufferSink {
+  pub fn new(capacity: usize) -> Self {
+    RingBufferSink { capacity, lines: Rc::new(RefCell::new(VecDeque::with_capacity(capacity))) }
+  }
+
+  // oldest first
+  pub fn lines(&self) -> Vec<String> {
+    self.lines.borrow().iter().cloned().collect()
+  }
+}
+
+impl TraceSink for RingBufferSink {
+  fn write_line(&mut self, line: &str) -> io::Result<()> {
+    let mut lines = self.lines.borrow_mut();
+    if lines.len() == self.capacity {
+      lines.pop_front();
+    }
+    if self.capacity > 0 {
+      lines.push_back(line.to_string());
+    }
+    Ok(())
+  }
+}
+
+pub struct Tracer {
+  pub filter: TraceFilter,
+  pub sink: Box<dyn TraceSink>,
+  pub format: TraceFormat,
+}
+
+impl Tracer {
+  pub fn new(sink: impl TraceSink + 'static) -> Self {
+    Tracer {
+      filter: TraceFilter::new(),
+      sink: Box::new(sink),
+      format: TraceFormat::Text,
+    }
+  }
+
+  pub fn is_enabled(&self) -> bool {
+    self.sink.is_enabled()
+  }
+
+  pub fn with_format(mut self, format: TraceFormat) -> Self {
+    self.format = format;
+    self
+  }
+
+  pub fn wants_bus_accesses(&self) -> bool {
+    self.is_enabled() && self.format == TraceFormat::JsonLines
+  }
+
+  pub fn trace(&mut self, record: &TraceRecord) {
+    let instruction = &record.instruction;
+    if !self.is_enabled() || !self.filter.matches(instruction.program_counter, record.prg_rom_offset, instruction.mnemonic) {
+      return;
+    }
+    let line = match self.format {
+      TraceFormat::Text => record.format_text(),
+      TraceFormat::JsonLines => record.format_json(),
+    };
+    if let Err(e) = self.sink.write_line(&line) {
+      eprintln!("Disabling trace, writing failed: {}", e);
+      self.sink = Box::new(NoopSink);
+    }
+  }
+}
+
+// tracing is opt-in, it slows the emulation down a lot
+impl Default for Tracer {
+  fn default() -> Self {
+    Tracer::new(NoopSink)
+  }
+}
@@ -0,0 +1,193 @@
+use std::fs;
+use std::io::Write;
+use crate::bus::Bus;
+use crate::cartridge_tests::create_test_rom;
+use crate::cpu::{CpuFlags, MyCPU};
+use crate::history::ExecutedInstruction;
+use crate::trace::{FileSink, InstructionKind, RingBufferSink, RotatingFile, TraceFilter, TraceFormat, TraceRecord, TraceSink, Tracer};
+
+#[test]
+fn test_empty_filter_matches_everything() {
+  let mut filter = TraceFilter::new();
+
+  assert!(filter.matches(0x8000, None, "LDA"));
+  assert!(filter.matches(0x0000, None, "BNE"));
+}
+
+#[test]
+fn test_filter_by_address_range() {
+  let mut filter = TraceFilter::new().with_range(0x8000..=0x80FF);
+
+  assert!(filter.matches(0x8010, None, "LDA"));
+  assert!(!filter.matches(0x8100, None, "LDA"));
+}
+
+#[test]
+fn test_filter_by_instruction_kind() {
+  let mut filter = TraceFilter::new()
+    .with_kind(InstructionKind::Branch)
+    .with_kind(InstructionKind::Jump);
+
+  assert!(filter.matches(0x8000, None, "BEQ"));
+  assert!(filter.matches(0x8000, None, "JSR"));
+  assert!(!filter.matches(0x8000, None, "LDA"));
+}
+
+#[test]
+fn test_filter_by_prg_rom_bank() {
+  let mut filter = TraceFilter::new().with_bank(2);
+
+  assert!(filter.matches(0x8000, Some(0x8000), "LDA"));
+  assert!(filter.matches(0xBFFF, Some(0xBFFF), "LDA"));
+  assert!(!filter.matches(0x8000, Some(0x4000), "LDA"));
+  assert!(!filter.matches(0x0600, None, "LDA"));
+}
+
+#[test]
+fn test_filter_between_two_addresses() {
+  let mut filter = TraceFilter::new().between(0x8010, 0x8020);
+
+  assert!(!filter.matches(0x8000, None, "LDA"));
+  assert!(filter.matches(0x8010, None, "LDA"));
+  assert!(filter.matches(0x9000, None, "LDA"));
+  assert!(filter.matches(0x8020, None, "LDA"));
+  assert!(!filter.matches(0x8022, None, "LDA"));
+  assert!(filter.matches(0x8010, None, "LDA"));
+}
+
+#[test]
+fn test_rotating_file_keeps_max_files() {
+  let dir = std::env::temp_dir().join(format!("nes_trace_{}", std::process::id()));
+  fs::create_dir_all(&dir).unwrap();
+  let base = dir.join("trace.log");
+  let base = base.to_str().unwrap();
+
+  {
+    let mut file = RotatingFile::create(base, 10, 3).unwrap();
+    for line in ["line-0001", "line-0002", "line-0003", "line-0004"] {
+      writeln!(file, "{}", line).unwrap();
+    }
+    file.flush().unwrap();
+  }
+
+  assert_eq!("line-0004\n", fs::read_to_string(base).unwrap());
+  assert_eq!("line-0003\n", fs::read_to_string(format!("{}.1", base)).unwrap());
+  assert_eq!("line-0002\n", fs::read_to_string(format!("{}.2", base)).unwrap());
+  assert!(fs::metadata(format!("{}.3", base)).is_err());
+  fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_json_lines_trace_with_bus_accesses() {
+  let path = std::env::temp_dir().join(format!("nes_trace_json_{}.log", std::process::id()));
+  let path = path.to_str().unwrap();
+  let mut cpu = MyCPU::new(Bus::new(create_test_rom()).unwrap());
+  cpu.program_counter = 0x0600;
+  cpu.tracer = Tracer::new(FileSink::rotating(path, 1 << 20, 1).unwrap())
+    .with_format(TraceFormat::JsonLines);
+
+  // LDA #$42, STA $10
+  cpu.load_and_run(vec![0xA9, 0x42, 0x85, 0x10]);
+  cpu.tracer = Tracer::default();
+
+  let trace = fs::read_to_string(path).unwrap();
+  let lines: Vec<&str> = trace.lines().collect();
+  assert_eq!(3, lines.len());
+  assert_eq!("{\"pc\":1536,\"opcode\":169,\"mnemonic\":\"LDA\",\"operands\":[66],\"a\":0,\"x\":0,\"y\":0,\"p\":36,\"sp\":255,\"cycles\":0,\"effective_address\":1537,\"bus\":[{\"op\":\"read\",\"addr\":1537,\"value\":66}]}", lines[0]);
+  assert!(lines[1].contains("\"mnemonic\":\"STA\""));
+  assert!(lines[1].contains("\"a\":66"));
+  assert!(lines[1].contains("\"cycles\":2"));
+  assert!(lines[1].contains("{\"op\":\"write\",\"addr\":16,\"value\":66}"));
+  fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn test_text_format_keeps_classic_layout() {
+  let record = TraceRecord {
+    instruction: ExecutedInstruction {
+      program_counter: 0x0600,
+      code: 0xA9,
+      mnemonic: "LDA",
+      operands: vec![0x42],
+      register_a: 0,
+      register_x: 0,
+      register_y: 0,
+      stack_pointer: 0xFF,
+      status: CpuFlags::INTERRUPT_DISABLE,
+    },
+    cycles: 0,
+    prg_rom_offset: None,
+    effective_address: Some(0x0601),
+    bus_accesses: vec![],
+    label: None,
+  };
+
+  assert_eq!("opCode LDA 0xa9 0x42     , pc=0x601, registers=100", record.format_text());
+}
+
+#[test]
+fn test_json_labels_are_escaped() {
+  let record = TraceRecord {
+    instruction: ExecutedInstruction {
+      program_counter: 0x8000,
+      code: 0xEA,
+      mnemonic: "NOP",
+      operands: vec![],
+      register_a: 0,
+      register_x: 0,
+      register_y: 0,
+      stack_pointer: 0xFD,
+      status: CpuFlags::empty(),
+    },
+    cycles: 7,
+    prg_rom_offset: Some(0),
+    effective_address: None,
+    bus_accesses: vec![],
+    label: Some("say \"hi\"\\now".to_string()),
+  };
+
+  assert!(record.format_json().ends_with(r#","label":"say \"hi\"\\now"}"#), "{}", record.format_json());
+}
+
+#[test]
+fn test_ring_buffer_sink_keeps_last_lines() {
+  let sink = RingBufferSink::new(2);
+  let mut cpu = MyCPU::new(Bus::new(create_test_rom()).unwrap());
+  cpu.program_counter = 0x0600;
+  cpu.tracer = Tracer::new(sink.clone());
+
+  // LDA #$42, TAX, INX
+  cpu.load_and_run(vec![0xA9, 0x42, 0xAA, 0xE8]);
+
+  let lines = sink.lines();
+  assert_eq!(2, lines.len());
+  assert!(lines[0].contains("INX"));
+  assert!(lines[1].contains("BRK"));
+}
+
+#[test]
+fn test_tracing_is_disabled_by_default() {
+  let tracer = Tracer::default();
+
+  assert!(!tracer.is_enabled());
+  assert!(!tracer.wants_bus_accesses());
+}
+
+struct FailingSink;
+
+impl TraceSink for FailingSink {
+  fn write_line(&mut self, _line: &str) -> std::io::Result<()> {
+    Err(std::io::Error::other("disk full"))
+  }
+}
+
+#[test]
+fn test_failing_sink_disables_tracing() {
+  let mut cpu = MyCPU::new(Bus::new(create_test_rom()).unwrap());
+  cpu.program_counter = 0x0600;
+  cpu.tracer = Tracer::new(FailingSink);
+
+  cpu.load_and_run(vec![0xE8]);
+
+  assert!(!cpu.tracer.is_enabled());
+}
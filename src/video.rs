@@ -0,0 +1,64 @@
+use crate::frame::Frame;
+
+// nes pixels are a bit wider than tall on a tv
+pub const PIXEL_ASPECT_RATIO: f64 = 8.0 / 7.0;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ScaleMode {
+  // whole multiples only, every nes pixel gets the same size
+  Integer,
+  // as large as the window allows, letterboxed
+  Fit,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct VideoOptions {
+  pub scale: u32, // initial window size
+  pub mode: ScaleMode,
+  pub aspect_correct: bool,
+}
+
+impl Default for VideoOptions {
+  fn default() -> Self {
+    VideoOptions { scale: 3, mode: ScaleMode::Integer, aspect_correct: false }
+  }
+}
+
+// where the picture goes in the window, centered
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Viewport {
+  pub x: i32,
+  pub y: i32,
+  pub width: u32,
+  pub height: u32,
+}
+
+impl VideoOptions {
+  fn pixel_width(&self) -> f64 {
+    if self.aspect_correct { PIXEL_ASPECT_RATIO } else { 1.0 }
+  }
+
+  pub fn window_size(&self) -> (u32, u32) {
+    let scale = self.scale.max(1) as f64;
+    ((Frame::WIDTH as f64 * self.pixel_width() * scale).round() as u32, (Frame::HEIGHT as f64 * scale) as u32)
+  }
+
+  pub fn viewport(&self, window_width: u32, window_height: u32) -> Viewport {
+    let source_width = Frame::WIDTH as f64 * self.pixel_width();
+    let source_height = Frame::HEIGHT as f64;
+    let fit = (window_width as f64 / source_width).min(window_height as f64 / source_height);
+    let scale = match self.mode {
+      // smaller windows still get the picture, just not pixel perfect
+      ScaleMode::Integer if fit >= 1.0 => fit.floor(),
+      _ => fit,
+    };
+    let width = (source_width * scale).round() as u32;
+    let height = (source_height * scale).round() as u32;
+    Viewport {
+      x: (window_width as i32 - width as i32) / 2,
+      y: (window_height as i32 - height as i32) / 2,
+      width,
+      height,
+    }
+  }
+}
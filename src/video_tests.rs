@@ -0,0 +1,40 @@
+use crate::video::{ScaleMode, VideoOptions, Viewport};
+
+fn options(mode: ScaleMode, aspect_correct: bool) -> VideoOptions {
+  VideoOptions { scale: 2, mode, aspect_correct }
+}
+
+#[test]
+fn test_window_size() {
+  assert_eq!((512, 480), options(ScaleMode::Integer, false).window_size());
+  assert_eq!((585, 480), options(ScaleMode::Integer, true).window_size());
+}
+
+#[test]
+fn test_integer_scaling_is_centered() {
+  let viewport = options(ScaleMode::Integer, false).viewport(1000, 800);
+
+  // 3x fits, 4x would be 1024 wide
+  assert_eq!(Viewport { x: 116, y: 40, width: 768, height: 720 }, viewport);
+}
+
+#[test]
+fn test_fit_letterboxes() {
+  let viewport = options(ScaleMode::Fit, false).viewport(1000, 800);
+
+  assert_eq!(Viewport { x: 73, y: 0, width: 853, height: 800 }, viewport);
+}
+
+#[test]
+fn test_aspect_correction_widens_the_picture() {
+  let viewport = options(ScaleMode::Integer, true).viewport(1200, 720);
+
+  assert_eq!(Viewport { x: 161, y: 0, width: 878, height: 720 }, viewport);
+}
+
+#[test]
+fn test_window_smaller_than_one_times() {
+  let viewport = options(ScaleMode::Integer, false).viewport(128, 240);
+
+  assert_eq!(Viewport { x: 0, y: 60, width: 128, height: 120 }, viewport);
+}
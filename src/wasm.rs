@@ -0,0 +1,108 @@
+use crate::cartridge::Rom;
+use crate::error::EmuError;
+use crate::frame::FrameBuffer;
+use crate::input::Player;
+use crate::joypad::JoypadButton;
+use crate::nes::Nes;
+
+// browser front-end: the page (web/index.html) copies the rom into wasm memory,
+// calls tick_frame() from requestAnimationFrame and draws the rgba buffer of the ppu into a canvas.
+// No sdl, no clock: the page decides when the next frame is due.
+pub struct WebNes {
+  nes: Option<Nes>,
+  rom: Vec<u8>,
+  // shown until a rom is loaded
+  blank: FrameBuffer,
+}
+
+impl WebNes {
+  pub fn new() -> Self {
+    WebNes { nes: None, rom: Vec::new(), blank: FrameBuffer::new() }
+  }
+
+  pub fn nes(&self) -> Option<&Nes> {
+    self.nes.as_ref()
+  }
+
+  // space for the page to copy a rom of `len` bytes into
+  pub fn rom_buffer(&mut self, len: usize) -> &mut [u8] {
+    self.rom = vec![0; len];
+    &mut self.rom
+  }
+
+  pub fn load_rom(&mut self) -> Result<(), EmuError> {
+    let rom = Rom::new(&self.rom)?;
+    self.nes = Some(Nes::new(rom)?);
+    Ok(())
+  }
+
+  // runs one frame, false without a rom (or if the cpu stopped)
+  pub fn tick_frame(&mut self) -> bool {
+    let nes = match self.nes.as_mut() {
+      Some(nes) => nes,
+      None => return false,
+    };
+    let frames = nes.frame_count();
+    nes.run_for_frames(1);
+    nes.frame_count() > frames
+  }
+
+  // 256x240 rgba, the layout of canvas ImageData. It is the buffer of the ppu itself,
+  // the page takes the pixels from there after every tick_frame()
+  pub fn with_frame_buffer<R>(&self, f: impl FnOnce(&[u8]) -> R) -> R {
+    match self.nes.as_ref() {
+      Some(nes) => nes.with_frame(f),
+      None => f(&self.blank),
+    }
+  }
+
+  // bits in JoypadButton order: right, left, down, up, start, select, b, a
+  pub fn set_buttons(&mut self, buttons: u8) {
+    if let Some(nes) = self.nes.as_mut() {
+      nes.set_buttons(Player::One, JoypadButton::from_bits_truncate(buttons));
+    }
+  }
+}
+
+impl Default for WebNes {
+  fn default() -> Self {
+    WebNes::new()
+  }
+}
+
+// plain C exports, numbers and pointers into the wasm memory are all javascript needs
+#[cfg(target_arch = "wasm32")]
+mod exports {
+  use std::cell::RefCell;
+  use super::WebNes;
+
+  thread_local! {
+    static NES: RefCell<WebNes> = RefCell::new(WebNes::new());
+  }
+
+  #[no_mangle]
+  pub extern "C" fn rom_buffer_ptr(len: usize) -> *mut u8 {
+    NES.with(|nes| nes.borrow_mut().rom_buffer(len).as_mut_ptr())
+  }
+
+  // 0 on success
+  #[no_mangle]
+  pub extern "C" fn load_rom() -> i32 {
+    NES.with(|nes| if nes.borrow_mut().load_rom().is_ok() { 0 } else { -1 })
+  }
+
+  #[no_mangle]
+  pub extern "C" fn tick_frame() -> i32 {
+    NES.with(|nes| nes.borrow_mut().tick_frame() as i32)
+  }
+
+  #[no_mangle]
+  pub extern "C" fn frame_buffer_ptr() -> *const u8 {
+    NES.with(|nes| nes.borrow().with_frame_buffer(|rgba| rgba.as_ptr()))
+  }
+
+  #[no_mangle]
+  pub extern "C" fn set_buttons(buttons: u8) {
+    NES.with(|nes| nes.borrow_mut().set_buttons(buttons))
+  }
+}
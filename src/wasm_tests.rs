@@ -0,0 +1,56 @@
+use crate::cartridge_tests::test_rom_bytes_with_program;
+use crate::joypad::JoypadButton;
+use crate::palette::SYSTEM_PALETTE;
+use crate::wasm::WebNes;
+
+// sets the backdrop color to $16, then loops
+fn rom_bytes() -> Vec<u8> {
+  let program = [
+    0xA9, 0x3F, 0x8D, 0x06, 0x20, // LDA #$3F, STA $2006
+    0xA9, 0x00, 0x8D, 0x06, 0x20, // LDA #$00, STA $2006
+    0xA9, 0x16, 0x8D, 0x07, 0x20, // LDA #$16, STA $2007
+    // with rendering off the ppu shows the palette entry at the vram address, if it points there
+    0xA9, 0x00, 0x8D, 0x06, 0x20, // LDA #$00, STA $2006
+    0x8D, 0x06, 0x20,             // STA $2006
+    0x4C, 0x17, 0x80,             // loop: JMP loop
+  ];
+  test_rom_bytes_with_program(&program)
+}
+
+fn load(nes: &mut WebNes, rom: &[u8]) -> bool {
+  nes.rom_buffer(rom.len()).copy_from_slice(rom);
+  nes.load_rom().is_ok()
+}
+
+#[test]
+fn test_tick_frame_fills_rgba_buffer() {
+  let mut nes = WebNes::new();
+  assert!(load(&mut nes, &rom_bytes()));
+
+  assert!(nes.tick_frame());
+
+  let (r, g, b) = SYSTEM_PALETTE[0x16];
+  nes.with_frame_buffer(|rgba| {
+    assert_eq!(256 * 240 * 4, rgba.len());
+    assert_eq!(&[r, g, b, 0xFF], &rgba[..4]);
+  });
+}
+
+#[test]
+fn test_invalid_rom_is_rejected() {
+  let mut nes = WebNes::new();
+
+  assert!(!load(&mut nes, &[1, 2, 3]));
+  assert!(!nes.tick_frame());
+}
+
+#[test]
+fn test_set_buttons_reaches_controller_1() {
+  let mut nes = WebNes::new();
+  assert!(load(&mut nes, &rom_bytes()));
+
+  nes.set_buttons(0x81);
+
+  let buttons = nes.nes().unwrap().cpu.bus.joypad1.buttons();
+  assert_eq!(JoypadButton::RIGHT | JoypadButton::BUTTON_A, buttons);
+}
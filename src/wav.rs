@@ -0,0 +1,87 @@
+use std::fs::File;
+use std::io::{self, BufWriter, Seek, SeekFrom, Write};
+use std::path::Path;
+
+// records mono samples as 16 bit pcm .wav, the sizes in the header are filled in by finish()
+// http://soundfile.sapp.org/doc/WaveFormat/
+pub trait WavSink: Write + Seek {}
+
+impl<T: Write + Seek> WavSink for T {}
+
+const HEADER_SIZE: u32 = 44;
+
+pub struct WavRecorder {
+  sink: Box<dyn WavSink>,
+  sample_rate: u32,
+  samples: u64,
+  max_samples: Option<u64>,
+}
+
+impl WavRecorder {
+  pub fn new(sink: Box<dyn WavSink>, sample_rate: u32) -> io::Result<Self> {
+    let mut recorder = WavRecorder { sink, sample_rate, samples: 0, max_samples: None };
+    recorder.write_header()?;
+    Ok(recorder)
+  }
+
+  pub fn create(path: &Path, sample_rate: u32) -> io::Result<Self> {
+    WavRecorder::new(Box::new(BufWriter::new(File::create(path)?)), sample_rate)
+  }
+
+  // stops taking samples after this many seconds
+  pub fn with_duration(mut self, seconds: f64) -> Self {
+    self.max_samples = Some((seconds * self.sample_rate as f64) as u64);
+    self
+  }
+
+  pub fn samples(&self) -> u64 {
+    self.samples
+  }
+
+  pub fn is_full(&self) -> bool {
+    self.max_samples.is_some_and(|max| self.samples >= max)
+  }
+
+  // 0.0 - 1.0 from the mixer, anything outside is clipped
+  pub fn write_samples(&mut self, samples: &[f32]) -> io::Result<()> {
+    for &sample in samples {
+      if self.is_full() {
+        break;
+      }
+      let value = (sample.clamp(-1.0, 1.0) * i16::MAX as f32) as i16;
+      self.sink.write_all(&value.to_le_bytes())?;
+      self.samples += 1;
+    }
+    Ok(())
+  }
+
+  pub fn finish(mut self) -> io::Result<()> {
+    self.write_header()?;
+    self.sink.flush()
+  }
+
+  fn write_header(&mut self) -> io::Result<()> {
+    let data_size = (self.samples * 2) as u32;
+    let mut header = Vec::with_capacity(HEADER_SIZE as usize);
+    header.extend_from_slice(b"RIFF");
+    header.extend_from_slice(&(HEADER_SIZE - 8 + data_size).to_le_bytes());
+    header.extend_from_slice(b"WAVEfmt ");
+    header.extend_from_slice(&16u32.to_le_bytes());
+    header.extend_from_slice(&1u16.to_le_bytes()); // pcm
+    header.extend_from_slice(&1u16.to_le_bytes()); // mono
+    header.extend_from_slice(&self.sample_rate.to_le_bytes());
+    header.extend_from_slice(&(self.sample_rate * 2).to_le_bytes()); // bytes per second
+    header.extend_from_slice(&2u16.to_le_bytes()); // bytes per sample
+    header.extend_from_slice(&16u16.to_le_bytes());
+    header.extend_from_slice(b"data");
+    header.extend_from_slice(&data_size.to_le_bytes());
+
+    let position = self.sink.stream_position()?;
+    self.sink.seek(SeekFrom::Start(0))?;
+    self.sink.write_all(&header)?;
+    if position > HEADER_SIZE as u64 {
+      self.sink.seek(SeekFrom::Start(position))?;
+    }
+    Ok(())
+  }
+}
@@ -0,0 +1,92 @@
+use std::cell::RefCell;
+use std::io::{self, Cursor, Seek, SeekFrom, Write};
+use std::rc::Rc;
+use crate::apu::Apu;
+use crate::wav::WavRecorder;
+
+// keeps the bytes reachable after the recorder consumed the sink
+#[derive(Clone, Default)]
+struct SharedCursor(Rc<RefCell<Cursor<Vec<u8>>>>);
+
+impl Write for SharedCursor {
+  fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+    self.0.borrow_mut().write(buf)
+  }
+
+  fn flush(&mut self) -> io::Result<()> {
+    Ok(())
+  }
+}
+
+impl Seek for SharedCursor {
+  fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+    self.0.borrow_mut().seek(pos)
+  }
+}
+
+impl SharedCursor {
+  fn bytes(&self) -> Vec<u8> {
+    self.0.borrow().get_ref().clone()
+  }
+}
+
+fn u32_at(bytes: &[u8], pos: usize) -> u32 {
+  u32::from_le_bytes([bytes[pos], bytes[pos + 1], bytes[pos + 2], bytes[pos + 3]])
+}
+
+#[test]
+fn test_header_and_samples() {
+  let sink = SharedCursor::default();
+  let mut recorder = WavRecorder::new(Box::new(sink.clone()), 44_100).unwrap();
+
+  recorder.write_samples(&[0.0, 1.0, -2.0]).unwrap();
+  recorder.finish().unwrap();
+
+  let bytes = sink.bytes();
+  assert_eq!(44 + 6, bytes.len());
+  assert_eq!(b"RIFF", &bytes[..4]);
+  assert_eq!(36 + 6, u32_at(&bytes, 4));
+  assert_eq!(b"WAVEfmt ", &bytes[8..16]);
+  assert_eq!(44_100, u32_at(&bytes, 24));
+  assert_eq!(b"data", &bytes[36..40]);
+  assert_eq!(6, u32_at(&bytes, 40));
+  assert_eq!([0, 0, 0xFF, 0x7F, 0x01, 0x80], bytes[44..]);
+}
+
+#[test]
+fn test_duration_limits_the_samples() {
+  let sink = SharedCursor::default();
+  let mut recorder = WavRecorder::new(Box::new(sink.clone()), 100).unwrap().with_duration(0.05);
+
+  recorder.write_samples(&[0.5; 10]).unwrap();
+
+  assert_eq!(5, recorder.samples());
+  assert!(recorder.is_full());
+}
+
+#[test]
+fn test_apu_records_until_the_duration_is_reached() {
+  let sink = SharedCursor::default();
+  let mut apu = Apu::with_sample_rate(1000);
+  apu.start_recording(WavRecorder::new(Box::new(sink.clone()), 1000).unwrap().with_duration(0.01));
+
+  apu.tick(30_000); // 16 samples
+
+  assert!(!apu.is_recording());
+  assert!(apu.stop_recording().is_ok());
+  let bytes = sink.bytes();
+  assert_eq!(20, u32_at(&bytes, 40));
+  assert_eq!(44 + 20, bytes.len());
+}
+
+#[test]
+fn test_apu_stop_recording_finishes_the_file() {
+  let sink = SharedCursor::default();
+  let mut apu = Apu::with_sample_rate(1000);
+  apu.start_recording(WavRecorder::new(Box::new(sink.clone()), 1000).unwrap());
+
+  apu.tick(3580);
+  apu.stop_recording().unwrap();
+
+  assert_eq!(2 * 2, u32_at(&sink.bytes(), 40));
+}